@@ -2,8 +2,11 @@
 
 mod config;
 mod internal;
+mod persistence;
 
-use self::internal::{to_merkle_path, Key, MerkleCommitment, MerkleProof, PersistentMerkleNode};
+use self::internal::{to_merkle_path, Key, PersistentMerkleNode};
+pub use self::internal::{MerkleCommitment, MerkleProof};
+pub use self::persistence::{StakeTableProofs, StakeTableSnapshot, FORMAT_VERSION};
 use ark_std::{collections::HashMap, rand::SeedableRng, sync::Arc};
 use digest::crypto_common::rand_core::CryptoRngCore;
 use ethereum_types::{U256, U512};
@@ -26,8 +29,18 @@ pub struct StakeTable<K: Key> {
     height: usize,
 
     /// The mapping from public keys to their location in the Merkle tree.
-    #[serde(skip)]
+    /// Deregistered keys keep their location as long as a snapshot still holds them, so that
+    /// the snapshot can still be looked up and no other key takes it.
+    #[serde(with = "persistence::key_positions")]
     mapping: HashMap<K, usize>,
+
+    /// Locations of keys deregistered from the head, which are freed once no snapshot holds
+    /// them anymore.
+    vacated: Vec<usize>,
+
+    /// Locations no key holds, which new keys take before growing the table.
+    /// Every location below `mapping.len() + free.len()` is either mapped or free.
+    free: Vec<usize>,
 }
 
 impl<K: Key> StakeTableScheme for StakeTable<K> {
@@ -42,25 +55,68 @@ impl<K: Key> StakeTableScheme for StakeTable<K> {
         new_key: Self::Key,
         amount: Self::Amount,
     ) -> Result<(), StakeTableError> {
-        match self.mapping.get(&new_key) {
-            Some(_) => Err(StakeTableError::ExistingKey),
-            None => {
-                let pos = self.mapping.len();
-                self.head = self.head.register(
+        if self.contains_key(&new_key) {
+            return Err(StakeTableError::ExistingKey);
+        }
+        // A key registering again returns to its previous location if it still holds it, and
+        // other keys take a freed location if there is one.
+        let (pos, vacated, free) = match self.mapping.get(&new_key) {
+            Some(&pos) => (pos, true, false),
+            None => match self.free.last() {
+                Some(&pos) => (pos, false, true),
+                None => (self.mapping.len(), false, false),
+            },
+        };
+        if pos >= self.capacity() {
+            return Err(StakeTableError::TableFull);
+        }
+        self.head = self.head.register(
+            self.height,
+            &to_merkle_path(pos, self.height),
+            &new_key,
+            amount,
+        )?;
+        if vacated {
+            self.vacated.retain(|&vacated| vacated != pos);
+        }
+        if free {
+            self.free.pop();
+        }
+        self.mapping.insert(new_key, pos);
+        Ok(())
+    }
+
+    fn deregister(&mut self, existing_key: &Self::Key) -> Result<(), StakeTableError> {
+        match self.mapping.get(existing_key) {
+            Some(pos) => {
+                (self.head, _) = self.head.deregister(
                     self.height,
-                    &to_merkle_path(pos, self.height),
-                    &new_key,
-                    amount,
+                    &to_merkle_path(*pos, self.height),
+                    existing_key,
                 )?;
-                self.mapping.insert(new_key, pos);
+                self.vacated.push(*pos);
                 Ok(())
             }
+            None => Err(StakeTableError::KeyNotFound),
         }
     }
 
-    fn deregister(&mut self, _existing_key: &Self::Key) -> Result<(), StakeTableError> {
-        // TODO: (alex) work on this in a future PR
-        unimplemented!()
+    /// Deregister all of `existing_keys`, or none of them if any isn't registered.
+    fn batch_deregister<'a, I>(&mut self, existing_keys: I) -> Result<(), StakeTableError>
+    where
+        I: IntoIterator<Item = &'a <Self as StakeTableScheme>::Key>,
+        <Self as StakeTableScheme>::Key: 'a,
+    {
+        let mut head = Arc::clone(&self.head);
+        let mut vacated = Vec::new();
+        for key in existing_keys {
+            let pos = self.mapping.get(key).ok_or(StakeTableError::KeyNotFound)?;
+            (head, _) = head.deregister(self.height, &to_merkle_path(*pos, self.height), key)?;
+            vacated.push(*pos);
+        }
+        self.head = head;
+        self.vacated.extend(vacated);
+        Ok(())
     }
 
     fn commitment(&self, version: SnapshotVersion) -> Result<Self::Commitment, StakeTableError> {
@@ -83,7 +139,7 @@ impl<K: Key> StakeTableScheme for StakeTable<K> {
     }
 
     fn contains_key(&self, key: &Self::Key) -> bool {
-        self.mapping.contains_key(key)
+        self.simple_lookup(SnapshotVersion::Head, key).is_ok()
     }
 
     fn lookup(
@@ -171,9 +227,19 @@ impl<K: Key> StakeTable<K> {
             last_epoch_start: Arc::new(PersistentMerkleNode::Empty),
             height,
             mapping: HashMap::new(),
+            vacated: Vec::new(),
+            free: Vec::new(),
         }
     }
 
+    /// The number of locations in the underlying Merkle tree
+    fn capacity(&self) -> usize {
+        u32::try_from(self.height)
+            .ok()
+            .and_then(|height| config::TREE_BRANCH.checked_pow(height))
+            .unwrap_or(usize::MAX)
+    }
+
     // returns the root of stake table at `version`
     fn get_root(
         &self,
@@ -188,9 +254,28 @@ impl<K: Key> StakeTable<K> {
     }
 
     /// Update the stake table when the epoch number advances, should be manually called.
+    ///
+    /// Locations vacated by deregistered keys that no snapshot holds anymore are freed for new
+    /// keys.
     pub fn advance(&mut self) {
         self.last_epoch_start = self.epoch_start.clone();
         self.epoch_start = self.head.clone();
+
+        let roots = [&self.head, &self.epoch_start, &self.last_epoch_start];
+        let height = self.height;
+        let (freed, vacated): (Vec<usize>, Vec<usize>) = std::mem::take(&mut self.vacated)
+            .into_iter()
+            .partition(|&pos| {
+                let path = to_merkle_path(pos, height);
+                roots
+                    .iter()
+                    .all(|root| root.simple_lookup(height, &path).is_err())
+            });
+        self.vacated = vacated;
+        if !freed.is_empty() {
+            self.mapping.retain(|_, pos| !freed.contains(pos));
+            self.free.extend(freed);
+        }
     }
 
     /// Set the stake withheld by `key` to be `value`.
@@ -214,7 +299,7 @@ impl<K: Key> StakeTable<K> {
 
 #[cfg(test)]
 mod tests {
-    use super::StakeTable;
+    use super::{StakeTable, StakeTableSnapshot};
     use ark_std::{rand::SeedableRng, vec::Vec};
     use ethereum_types::U256;
     use hotshot_types::traits::stake_table::{SnapshotVersion, StakeTableError, StakeTableScheme};
@@ -328,4 +413,210 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_stake_table_deregister() -> Result<(), StakeTableError> {
+        let mut st = StakeTable::new(3);
+        let keys = (0..6).map(Key::from).collect::<Vec<_>>();
+        keys.iter()
+            .for_each(|&key| st.register(key, U256::from(100)).unwrap());
+        st.advance();
+        let old_comm = st.commitment(SnapshotVersion::EpochStart)?;
+
+        st.deregister(&keys[1])?;
+        assert!(!st.contains_key(&keys[1]));
+        assert!(st.deregister(&keys[1]).is_err());
+        assert_eq!(st.total_stake(SnapshotVersion::Head)?, U256::from(500));
+        assert_eq!(st.len(SnapshotVersion::Head)?, 5);
+        assert_eq!(
+            st.try_iter(SnapshotVersion::Head)?
+                .map(|(key, _)| key)
+                .collect::<Vec<_>>(),
+            [keys[0], keys[2], keys[3], keys[4], keys[5]]
+        );
+        assert!(st.lookup(SnapshotVersion::Head, &keys[1]).is_err());
+
+        // Older snapshots still hold the key, and their proofs still verify.
+        assert_eq!(st.commitment(SnapshotVersion::EpochStart)?, old_comm);
+        let proof = st.lookup(SnapshotVersion::EpochStart, &keys[1])?.1;
+        assert!(proof.verify(&old_comm).is_ok());
+
+        // A new snapshot no longer holds the key.
+        st.advance();
+        assert_ne!(st.commitment(SnapshotVersion::EpochStart)?, old_comm);
+        assert_eq!(
+            st.total_stake(SnapshotVersion::EpochStart)?,
+            U256::from(500)
+        );
+        assert!(st.lookup(SnapshotVersion::EpochStart, &keys[1]).is_err());
+
+        // Only the keys with stake left are sampled.
+        st.advance();
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(41u64);
+        for _ in 0..100 {
+            let (key, _) = st.sample(&mut rng).unwrap();
+            assert_ne!(key, &keys[1]);
+        }
+
+        // Deregistering a batch with an unknown key leaves the table unchanged.
+        assert!(st
+            .batch_deregister([&keys[2], &keys[1]].into_iter())
+            .is_err());
+        assert!(st.contains_key(&keys[2]));
+        st.batch_deregister([&keys[2], &keys[3]].into_iter())?;
+        assert_eq!(st.len(SnapshotVersion::Head)?, 3);
+
+        // A deregistered key can register again.
+        st.register(keys[1], U256::from(50))?;
+        assert_eq!(st.total_stake(SnapshotVersion::Head)?, U256::from(350));
+        assert!(st.register(Key::from(6), U256::from(100)).is_ok());
+        assert_eq!(st.len(SnapshotVersion::Head)?, 5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stake_table_reuses_vacated_locations() -> Result<(), StakeTableError> {
+        // Room for 3 keys only
+        let mut st = StakeTable::new(1);
+        let keys = (0..10).map(Key::from).collect::<Vec<_>>();
+        keys.iter()
+            .take(3)
+            .for_each(|&key| st.register(key, U256::from(100)).unwrap());
+        assert!(matches!(
+            st.register(keys[3], U256::from(100)),
+            Err(StakeTableError::TableFull)
+        ));
+
+        // A vacated location is only reused once no snapshot holds its key.
+        st.deregister(&keys[1])?;
+        st.advance();
+        assert!(st
+            .lookup(SnapshotVersion::LastEpochStart, &keys[1])
+            .is_err());
+        st.register(keys[3], U256::from(100))?;
+        assert!(st.lookup(SnapshotVersion::Head, &keys[3]).is_ok());
+        assert!(!st.contains_key(&keys[1]));
+        assert!(matches!(
+            st.register(keys[1], U256::from(100)),
+            Err(StakeTableError::TableFull)
+        ));
+
+        st.deregister(&keys[0])?;
+        st.advance();
+        assert!(matches!(
+            st.register(keys[4], U256::from(100)),
+            Err(StakeTableError::TableFull)
+        ));
+        assert!(st.lookup(SnapshotVersion::LastEpochStart, &keys[0]).is_ok());
+
+        // A key registering again while a snapshot still holds it returns to its location.
+        st.register(keys[0], U256::from(50))?;
+        st.deregister(&keys[0])?;
+        st.advance();
+        st.advance();
+
+        // Keys keep churning through the table well past its capacity.
+        for &key in &keys[4..] {
+            st.register(key, U256::from(100))?;
+            st.deregister(&key)?;
+            st.advance();
+            st.advance();
+        }
+        st.register(keys[0], U256::from(100))?;
+        assert_eq!(st.len(SnapshotVersion::Head)?, 3);
+        assert_eq!(st.total_stake(SnapshotVersion::Head)?, U256::from(300));
+        let proof = st.lookup(SnapshotVersion::Head, &keys[0])?.1;
+        assert!(proof.verify(&st.commitment(SnapshotVersion::Head)?).is_ok());
+
+        // Freed locations survive a round trip through bytes.
+        let mut loaded = StakeTable::<Key>::from_bytes(&st.to_bytes()?)?;
+        assert_eq!(loaded, st);
+        loaded.deregister(&keys[2])?;
+        loaded.advance();
+        loaded.advance();
+        loaded.register(keys[1], U256::from(100))?;
+        assert!(matches!(
+            loaded.register(keys[2], U256::from(100)),
+            Err(StakeTableError::TableFull)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stake_table_persistence() -> Result<(), StakeTableError> {
+        let mut st = StakeTable::new(3);
+        let keys = (0..8).map(Key::from).collect::<Vec<_>>();
+        keys.iter()
+            .take(5)
+            .for_each(|&key| st.register(key, U256::from(100)).unwrap());
+        st.advance();
+        st.register(keys[5], U256::from(100))?;
+        st.deregister(&keys[0])?;
+
+        // The whole table, with its snapshots, round-trips through bytes and disk.
+        let bytes = st.to_bytes()?;
+        assert_eq!(StakeTable::<Key>::from_bytes(&bytes)?, st);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stake_table");
+        st.save(&path)?;
+        let loaded = StakeTable::<Key>::load(&path)?;
+        assert_eq!(
+            loaded.commitment(SnapshotVersion::EpochStart)?,
+            st.commitment(SnapshotVersion::EpochStart)?
+        );
+        assert!(!loaded.contains_key(&keys[0]));
+
+        // Other format versions are rejected.
+        let mut other_version = bytes;
+        other_version[0] ^= 0xff;
+        assert!(matches!(
+            StakeTable::<Key>::from_bytes(&other_version),
+            Err(StakeTableError::UnsupportedFormatVersion)
+        ));
+
+        // A snapshot imports as a table of its own, which keeps growing from there.
+        let snapshot = st.export_snapshot(SnapshotVersion::EpochStart)?;
+        let snapshot = StakeTableSnapshot::from_bytes(&snapshot.to_bytes()?)?;
+        assert_eq!(
+            snapshot.commitment(),
+            st.commitment(SnapshotVersion::EpochStart)?
+        );
+        // Keys registered after the snapshot was taken aren't exported with it.
+        let mut without_head_keys = st.clone();
+        without_head_keys.mapping.remove(&keys[5]);
+        assert_eq!(
+            snapshot,
+            without_head_keys.export_snapshot(SnapshotVersion::EpochStart)?
+        );
+        let mut imported = StakeTable::from_snapshot(snapshot);
+        assert_eq!(imported.len(SnapshotVersion::Head)?, 5);
+        imported.register(keys[6], U256::from(100))?;
+        imported.register(keys[5], U256::from(100))?;
+        assert_eq!(imported.len(SnapshotVersion::Head)?, 7);
+        assert_eq!(
+            imported.simple_lookup(SnapshotVersion::Head, &keys[6])?,
+            U256::from(100)
+        );
+        assert_eq!(
+            imported.simple_lookup(SnapshotVersion::Head, &keys[4])?,
+            U256::from(100)
+        );
+
+        // Proofs for a subset of the keys verify against the commitment alone.
+        let trusted = st.commitment(SnapshotVersion::EpochStart)?;
+        let proofs = st.prove(SnapshotVersion::EpochStart, &keys[..2])?;
+        assert_eq!(
+            proofs.verify(&trusted)?,
+            [(&keys[0], &U256::from(100)), (&keys[1], &U256::from(100))]
+        );
+        assert!(proofs
+            .verify(&st.commitment(SnapshotVersion::Head)?)
+            .is_err());
+        // The deregistered key has no proof in the head.
+        assert!(st.prove(SnapshotVersion::Head, &keys[..2]).is_err());
+
+        Ok(())
+    }
 }
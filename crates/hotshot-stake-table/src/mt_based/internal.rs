@@ -73,13 +73,19 @@ pub(crate) enum PersistentMerkleNode<K: Key> {
 /// A compressed Merkle node for Merkle path
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum MerklePathEntry<K> {
+    /// A branch on the path
     Branch {
+        /// Position of the path among the children of the branch
         pos: usize,
+        /// Commitments of the other children of the branch
         #[serde(with = "canonical")]
         siblings: [FieldType; TREE_BRANCH - 1],
     },
+    /// The leaf at the end of the path
     Leaf {
+        /// Key of the leaf
         key: K,
+        /// Stake of the key
         value: U256,
     },
 }
@@ -96,14 +102,17 @@ pub struct MerkleProof<K> {
 }
 
 impl<K: Key> MerkleProof<K> {
+    /// Height of the tree the proof is in
     pub fn tree_height(&self) -> usize {
         self.path.len() - 1
     }
 
+    /// Location of the key in the tree
     pub fn index(&self) -> &usize {
         &self.index
     }
 
+    /// The key the proof is for
     pub fn get_key(&self) -> Option<&K> {
         match self.path.first() {
             Some(MerklePathEntry::Leaf { key, value: _ }) => Some(key),
@@ -111,6 +120,7 @@ impl<K: Key> MerkleProof<K> {
         }
     }
 
+    /// The stake of the key the proof is for
     pub fn get_value(&self) -> Option<&U256> {
         match self.path.first() {
             Some(MerklePathEntry::Leaf { key: _, value }) => Some(value),
@@ -118,6 +128,7 @@ impl<K: Key> MerkleProof<K> {
         }
     }
 
+    /// The key the proof is for and its stake
     pub fn get_key_value(&self) -> Option<(&K, &U256)> {
         match self.path.first() {
             Some(MerklePathEntry::Leaf { key, value }) => Some((key, value)),
@@ -125,6 +136,7 @@ impl<K: Key> MerkleProof<K> {
         }
     }
 
+    /// Compute the root of the tree the proof is in
     pub fn compute_root(&self) -> Result<FieldType, StakeTableError> {
         match self.path.first() {
             Some(MerklePathEntry::Leaf { key, value }) => {
@@ -152,6 +164,7 @@ impl<K: Key> MerkleProof<K> {
         }
     }
 
+    /// Verify the proof against a commitment to the tree
     pub fn verify(&self, comm: &MerkleCommitment) -> Result<(), StakeTableError> {
         if self.tree_height() != comm.tree_height() || !self.compute_root()?.eq(comm.digest()) {
             Err(StakeTableError::VerificationError)
@@ -174,18 +187,22 @@ pub struct MerkleCommitment {
 }
 
 impl MerkleCommitment {
+    /// Create a commitment to a tree
     pub fn new(comm: FieldType, height: usize, size: usize) -> Self {
         Self { comm, height, size }
     }
 
+    /// Digest of the tree
     pub fn digest(&self) -> &FieldType {
         &self.comm
     }
 
+    /// Height of the tree
     pub fn tree_height(&self) -> usize {
         self.height
     }
 
+    /// Number of keys in the tree
     pub fn size(&self) -> usize {
         self.size
     }
@@ -384,6 +401,60 @@ impl<K: Key> PersistentMerkleNode<K> {
         }
     }
 
+    /// Remove `key` from the Merkle tree, leaving an empty leaf in its place.
+    /// Return the stake it withheld
+    pub fn deregister(
+        &self,
+        height: usize,
+        path: &[usize],
+        key: &K,
+    ) -> Result<(Arc<Self>, U256), StakeTableError> {
+        match self {
+            PersistentMerkleNode::Empty => Err(StakeTableError::KeyNotFound),
+            PersistentMerkleNode::Branch {
+                comm: _,
+                children,
+                num_keys: _,
+                total_stakes: _,
+            } => {
+                let mut children = children.clone();
+                let value: U256;
+                (children[path[height - 1]], value) =
+                    children[path[height - 1]].deregister(height - 1, path, key)?;
+                let num_keys = children.iter().map(|child| child.num_keys()).sum();
+                if num_keys == 0 {
+                    return Ok((Arc::new(PersistentMerkleNode::Empty), value));
+                }
+                let total_stakes = children
+                    .iter()
+                    .map(|child| child.total_stakes())
+                    .fold(U256::zero(), |sum, val| sum + val);
+                let comm = Digest::evaluate(children.clone().map(|child| child.commitment()))
+                    .map_err(|_| StakeTableError::RescueError)?[0];
+                Ok((
+                    Arc::new(PersistentMerkleNode::Branch {
+                        comm,
+                        children,
+                        num_keys,
+                        total_stakes,
+                    }),
+                    value,
+                ))
+            }
+            PersistentMerkleNode::Leaf {
+                comm: _,
+                key: node_key,
+                value,
+            } => {
+                if key == node_key {
+                    Ok((Arc::new(PersistentMerkleNode::Empty), *value))
+                } else {
+                    Err(StakeTableError::MismatchedKey)
+                }
+            }
+        }
+    }
+
     /// Update the stake of the `key` with `(negative ? -1 : 1) * delta`.
     /// Return the updated stake
     pub fn update(
@@ -553,7 +624,11 @@ impl<K: Key> Iterator for IntoIter<K> {
 
         let visiting = (**self.unvisited.last()?).clone();
         match visiting {
-            PersistentMerkleNode::Empty => None,
+            PersistentMerkleNode::Empty => {
+                // Skip the holes left by deregistered keys.
+                self.unvisited.pop();
+                self.next()
+            }
             PersistentMerkleNode::Leaf {
                 comm: _,
                 key,
@@ -0,0 +1,265 @@
+//! Exporting and importing stake tables and their snapshots, and proofs of membership for light
+//! clients
+
+use super::{
+    internal::{to_merkle_path, Key, MerkleCommitment, MerkleProof, PersistentMerkleNode},
+    StakeTable,
+};
+use ark_std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    vec::Vec,
+};
+use ethereum_types::U256;
+use hotshot_types::traits::stake_table::{SnapshotVersion, StakeTableError, StakeTableScheme};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::path::Path;
+
+/// Version of the format stake tables and their snapshots are exported in. Bump it whenever the
+/// layout of [`StakeTable`] or [`StakeTableSnapshot`] changes.
+pub const FORMAT_VERSION: u16 = 2;
+
+/// A snapshot of a [`StakeTable`], which can be imported as a stake table of its own
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "K: Key")]
+pub struct StakeTableSnapshot<K: Key> {
+    /// Height of the underlying Merkle tree
+    height: usize,
+    /// Root of the snapshot
+    root: Arc<PersistentMerkleNode<K>>,
+    /// The mapping from public keys to their location in the Merkle tree
+    #[serde(with = "key_positions")]
+    mapping: HashMap<K, usize>,
+}
+
+impl<K: Key> StakeTableSnapshot<K> {
+    /// The commitment to the snapshot
+    pub fn commitment(&self) -> MerkleCommitment {
+        MerkleCommitment::new(self.root.commitment(), self.height, self.root.num_keys())
+    }
+
+    /// Serialize the snapshot, prefixed with the format version.
+    ///
+    /// # Errors
+    /// Returns [`StakeTableError::SerializationError`] if the snapshot can't be serialized.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, StakeTableError> {
+        encode(self)
+    }
+
+    /// Deserialize a snapshot serialized by [`Self::to_bytes`].
+    ///
+    /// # Errors
+    /// Returns [`StakeTableError::UnsupportedFormatVersion`] if the snapshot was serialized in
+    /// another format version, or [`StakeTableError::SerializationError`] if it is malformed.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, StakeTableError> {
+        decode(bytes)
+    }
+}
+
+/// Commitment to a snapshot of a stake table, with proofs of the stake of some of its keys.
+///
+/// Light clients verify it against a commitment to the snapshot they received out-of-band,
+/// without holding the stake table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StakeTableProofs<K> {
+    /// Commitment to the snapshot the proofs are against
+    pub commitment: MerkleCommitment,
+    /// Proofs of the stake of the keys
+    pub proofs: Vec<MerkleProof<K>>,
+}
+
+impl<K: Key> StakeTableProofs<K> {
+    /// Verify the proofs against `trusted`, returning the keys proven and their stake.
+    ///
+    /// # Errors
+    /// Returns [`StakeTableError::VerificationError`] if the proofs are against another
+    /// commitment or any of them doesn't verify, or [`StakeTableError::MalformedProof`] if a
+    /// proof doesn't hold a key.
+    pub fn verify(&self, trusted: &MerkleCommitment) -> Result<Vec<(&K, &U256)>, StakeTableError> {
+        if self.commitment != *trusted {
+            return Err(StakeTableError::VerificationError);
+        }
+        self.proofs
+            .iter()
+            .map(|proof| {
+                proof.verify(trusted)?;
+                proof.get_key_value().ok_or(StakeTableError::MalformedProof)
+            })
+            .collect()
+    }
+}
+
+impl<K: Key> StakeTable<K> {
+    /// Serialize the whole stake table, with all its snapshots, prefixed with the format
+    /// version.
+    ///
+    /// # Errors
+    /// Returns [`StakeTableError::SerializationError`] if the table can't be serialized.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, StakeTableError> {
+        encode(self)
+    }
+
+    /// Deserialize a stake table serialized by [`Self::to_bytes`].
+    ///
+    /// # Errors
+    /// Returns [`StakeTableError::UnsupportedFormatVersion`] if the table was serialized in
+    /// another format version, or [`StakeTableError::SerializationError`] if it is malformed.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, StakeTableError> {
+        decode(bytes)
+    }
+
+    /// Write the whole stake table to the file at `path`.
+    ///
+    /// # Errors
+    /// Returns [`StakeTableError::IoError`] if the file can't be written.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), StakeTableError> {
+        std::fs::write(path, self.to_bytes()?).map_err(|_| StakeTableError::IoError)
+    }
+
+    /// Read a stake table written by [`Self::save`] from the file at `path`.
+    ///
+    /// # Errors
+    /// Returns [`StakeTableError::IoError`] if the file can't be read, or any error of
+    /// [`Self::from_bytes`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, StakeTableError> {
+        Self::from_bytes(&std::fs::read(path).map_err(|_| StakeTableError::IoError)?)
+    }
+
+    /// Export the `version` snapshot of the stake table.
+    ///
+    /// Only the locations up to the last key in the snapshot are exported, so keys registered
+    /// after the snapshot was taken are left out, while the locations of keys deregistered
+    /// before it stay reserved.
+    ///
+    /// # Errors
+    /// Returns [`StakeTableError::SnapshotUnsupported`] if the `version` isn't kept.
+    pub fn export_snapshot(
+        &self,
+        version: SnapshotVersion,
+    ) -> Result<StakeTableSnapshot<K>, StakeTableError> {
+        let end = self
+            .try_iter(version)?
+            .filter_map(|(key, _)| self.mapping.get(&key).copied())
+            .max()
+            .map_or(0, |pos| pos + 1);
+        Ok(StakeTableSnapshot {
+            height: self.height,
+            root: self.get_root(version)?,
+            mapping: self
+                .mapping
+                .iter()
+                .filter(|(_, pos)| **pos < end)
+                .map(|(key, &pos)| (key.clone(), pos))
+                .collect(),
+        })
+    }
+
+    /// Create a stake table from an exported snapshot, with every version of it at the
+    /// snapshot.
+    ///
+    /// Since no version holds the keys deregistered before the snapshot was taken, their
+    /// locations are free for new keys.
+    pub fn from_snapshot(snapshot: StakeTableSnapshot<K>) -> Self {
+        let StakeTableSnapshot {
+            height,
+            root,
+            mut mapping,
+        } = snapshot;
+        let end = mapping.values().max().map_or(0, |pos| pos + 1);
+        mapping.retain(|_, pos| {
+            root.simple_lookup(height, &to_merkle_path(*pos, height))
+                .is_ok()
+        });
+        let mapped: HashSet<usize> = mapping.values().copied().collect();
+        let mut free: Vec<_> = (0..end).filter(|pos| !mapped.contains(pos)).collect();
+        // New keys take the lowest free location first.
+        free.reverse();
+        Self {
+            head: Arc::clone(&root),
+            epoch_start: Arc::clone(&root),
+            last_epoch_start: root,
+            height,
+            mapping,
+            vacated: Vec::new(),
+            free,
+        }
+    }
+
+    /// Prove the stake of `keys` in the `version` snapshot of the stake table.
+    ///
+    /// # Errors
+    /// Returns [`StakeTableError::SnapshotUnsupported`] if the `version` isn't kept, or
+    /// [`StakeTableError::KeyNotFound`] if any of `keys` isn't in the snapshot.
+    pub fn prove<'a>(
+        &self,
+        version: SnapshotVersion,
+        keys: impl IntoIterator<Item = &'a K>,
+    ) -> Result<StakeTableProofs<K>, StakeTableError>
+    where
+        K: 'a,
+    {
+        let commitment = self.commitment(version)?;
+        let proofs = keys
+            .into_iter()
+            .map(|key| Ok(self.lookup(version, key)?.1))
+            .collect::<Result<_, StakeTableError>>()?;
+        Ok(StakeTableProofs { commitment, proofs })
+    }
+}
+
+/// Serialize `value`, prefixed with [`FORMAT_VERSION`]
+fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, StakeTableError> {
+    let mut bytes = FORMAT_VERSION.to_le_bytes().to_vec();
+    bincode::serialize_into(&mut bytes, value).map_err(|_| StakeTableError::SerializationError)?;
+    Ok(bytes)
+}
+
+/// Deserialize a value serialized by [`encode`] in the current [`FORMAT_VERSION`]
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, StakeTableError> {
+    if bytes.len() < 2 {
+        return Err(StakeTableError::SerializationError);
+    }
+    let (version, body) = bytes.split_at(2);
+    if u16::from_le_bytes([version[0], version[1]]) != FORMAT_VERSION {
+        return Err(StakeTableError::UnsupportedFormatVersion);
+    }
+    bincode::deserialize(body).map_err(|_| StakeTableError::SerializationError)
+}
+
+/// (De)serialize the mapping from keys to their location in the Merkle tree, with the keys in
+/// their canonical serialization
+pub(crate) mod key_positions {
+    use super::super::internal::Key;
+    use ark_std::{collections::HashMap, vec::Vec};
+    use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+
+    /// Serialize the mapping as a list of serialized keys and their locations
+    pub fn serialize<K: Key, S: Serializer>(
+        mapping: &HashMap<K, usize>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        mapping
+            .iter()
+            .map(|(key, pos)| {
+                let mut bytes = Vec::new();
+                key.serialize_compressed(&mut bytes)
+                    .map_err(ser::Error::custom)?;
+                Ok((bytes, *pos))
+            })
+            .collect::<Result<Vec<_>, S::Error>>()?
+            .serialize(serializer)
+    }
+
+    /// Deserialize a mapping serialized by [`serialize`]
+    pub fn deserialize<'de, K: Key, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<K, usize>, D::Error> {
+        Vec::<(Vec<u8>, usize)>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(bytes, pos)| {
+                let key = K::deserialize_compressed(&bytes[..]).map_err(de::Error::custom)?;
+                Ok((key, pos))
+            })
+            .collect()
+    }
+}
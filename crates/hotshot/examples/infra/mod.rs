@@ -38,6 +38,7 @@ use hotshot_types::{
     },
     HotShotConfig,
 };
+use hotshot_web_server::auth::{AuthConfig, MembershipLeaders};
 use libp2p_identity::{
     ed25519::{self, SecretKey},
     Keypair,
@@ -129,6 +130,26 @@ pub fn load_config_from_file<TYPES: NodeType>(
     config
 }
 
+/// Who may submit to the web servers of the network configured in `config_file`: the nodes,
+/// whose keys are generated from its seed, with proposals from the leaders of `MEMBERSHIP`
+pub fn web_server_auth<TYPES: NodeType, MEMBERSHIP: Membership<TYPES>>(
+    config_file: String,
+) -> AuthConfig<TYPES::SignatureKey> {
+    let config = load_config_from_file::<TYPES>(config_file);
+    let stake_table = config.config.known_nodes_with_stake;
+    let election_config = config.config.election_config.unwrap_or_else(|| {
+        MEMBERSHIP::default_election_config(config.config.total_nodes.get() as u64)
+    });
+    let keys = stake_table
+        .iter()
+        .map(TYPES::SignatureKey::get_public_key)
+        .collect();
+    let membership = MEMBERSHIP::create_election(stake_table, election_config);
+    AuthConfig::new(keys).with_leaders(Arc::new(MembershipLeaders::<TYPES, MEMBERSHIP>::new(
+        membership,
+    )))
+}
+
 /// Runs the orchestrator
 pub async fn run_orchestrator<
     TYPES: NodeType,
@@ -523,7 +544,7 @@ where
         >,
     ) -> WebServerDARun<TYPES, NODE, MEMBERSHIP> {
         // Generate our own key
-        let (pub_key, _priv_key) =
+        let (pub_key, priv_key) =
             <<TYPES as NodeType>::SignatureKey as SignatureKey>::generated_from_seed_indexed(
                 config.seed,
                 config.node_index,
@@ -534,13 +555,15 @@ where
             host,
             port,
             wait_between_polls,
+            relays,
         }: WebServerConfig = config.clone().web_server_config.unwrap();
 
-        let underlying_quorum_network = WebServerNetwork::create(
-            &host.to_string(),
-            port,
+        let underlying_quorum_network = WebServerNetwork::create_with_relays(
+            &[format!("http://{host}:{port}")],
+            &relays,
             wait_between_polls,
             pub_key.clone(),
+            priv_key.clone(),
             false,
         );
 
@@ -555,23 +578,33 @@ where
             host,
             port,
             wait_between_polls,
+            relays,
         }: WebServerConfig = config.clone().da_web_server_config.unwrap();
+        let da_urls = [format!("http://{host}:{port}")];
 
         // Each node runs the DA network so that leaders have access to transactions and DA votes
         let da_network: WebCommChannel<TYPES, NODE, MEMBERSHIP> = WebCommChannel::new(
-            WebServerNetwork::create(
-                &host.to_string(),
-                port,
+            WebServerNetwork::create_with_relays(
+                &da_urls,
+                &relays,
                 wait_between_polls,
                 pub_key.clone(),
+                priv_key.clone(),
                 true,
             )
             .into(),
         );
 
         let vid_network: WebCommChannel<TYPES, NODE, MEMBERSHIP> = WebCommChannel::new(
-            WebServerNetwork::create(&host.to_string(), port, wait_between_polls, pub_key, true)
-                .into(),
+            WebServerNetwork::create_with_relays(
+                &da_urls,
+                &relays,
+                wait_between_polls,
+                pub_key,
+                priv_key,
+                true,
+            )
+            .into(),
         );
 
         WebServerDARun {
@@ -923,8 +956,14 @@ pub async fn main_entry_point<
         "Identifying with orchestrator using IP address {}",
         public_ip.to_string()
     );
+    // Our keys are only known once we have a node index, so our public IP is the stable token
+    // we can identify with by default
+    let session = args
+        .session
+        .clone()
+        .or_else(|| args.public_ip.map(|ip| ip.to_string()));
     let node_index: u16 = orchestrator_client
-        .identify_with_orchestrator(public_ip.to_string())
+        .identify_with_orchestrator(public_ip.to_string(), session.as_deref())
         .await;
     info!("Finished identifying; our node index is {node_index}");
     info!("Getting config from orchestrator");
@@ -45,7 +45,7 @@ async fn main() {
         args.port
     );
     let mut nodes = Vec::new();
-    for _ in 0..args.num_nodes {
+    for index in 0..args.num_nodes {
         let node = async_spawn(async move {
             infra::main_entry_point::<
                 DemoTypes,
@@ -60,6 +60,8 @@ async fn main() {
                 host: args.host.to_string(),
                 port: args.port,
                 public_ip: args.public_ip,
+                // The validators share a public IP, so each needs its own token
+                session: args.public_ip.map(|ip| format!("{ip}-{index}")),
             })
             .await
         });
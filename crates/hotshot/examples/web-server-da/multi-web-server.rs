@@ -1,15 +1,30 @@
+pub mod types;
+
 use std::sync::Arc;
 
 use async_compatibility_layer::{art::async_spawn, channel::oneshot};
 use clap::Parser;
 use hotshot::demo::DemoTypes;
+use hotshot_web_server::auth::AuthConfig;
 use tracing::error;
+use types::ThisMembership;
+
+use crate::infra::web_server_auth;
+
+#[path = "../infra/mod.rs"]
+pub mod infra;
 
 #[derive(Parser, Debug)]
 struct MultiWebServerArgs {
     cdn_port: u16,
     da_port: u16,
     view_sync_port: u16,
+    /// The run configuration of the network, whose nodes are the only ones allowed to submit
+    #[arg(long)]
+    config_file: Option<String>,
+    /// Let any key add itself to the stake table instead, e.g. for local testing
+    #[arg(long)]
+    open_registration: bool,
 }
 #[cfg_attr(
     async_executor_impl = "tokio",
@@ -18,6 +33,11 @@ struct MultiWebServerArgs {
 #[cfg_attr(async_executor_impl = "async-std", async_std::main)]
 async fn main() {
     let args = MultiWebServerArgs::parse();
+    let auth = match args.config_file {
+        _ if args.open_registration => AuthConfig::open(),
+        Some(config_file) => web_server_auth::<DemoTypes, ThisMembership>(config_file),
+        None => panic!("Pass the --config-file of the network, or --open-registration"),
+    };
     let (server_shutdown_sender_cdn, server_shutdown_cdn) = oneshot();
     let (server_shutdown_sender_da, server_shutdown_da) = oneshot();
     let (server_shutdown_sender_view_sync, server_shutdown_view_sync) = oneshot();
@@ -25,20 +45,22 @@ async fn main() {
     let _sender = Arc::new(server_shutdown_sender_da);
     let _sender = Arc::new(server_shutdown_sender_view_sync);
 
+    let cdn_auth = auth.clone();
     let cdn_server = async_spawn(async move {
         if let Err(e) = hotshot_web_server::run_web_server::<
             <DemoTypes as hotshot_types::traits::node_implementation::NodeType>::SignatureKey,
-        >(Some(server_shutdown_cdn), args.cdn_port)
+        >(Some(server_shutdown_cdn), args.cdn_port, cdn_auth)
         .await
         {
             error!("Problem starting cdn web server: {:?}", e);
         }
         error!("cdn");
     });
+    let da_auth = auth.clone();
     let da_server = async_spawn(async move {
         if let Err(e) = hotshot_web_server::run_web_server::<
             <DemoTypes as hotshot_types::traits::node_implementation::NodeType>::SignatureKey,
-        >(Some(server_shutdown_da), args.da_port)
+        >(Some(server_shutdown_da), args.da_port, da_auth)
         .await
         {
             error!("Problem starting da web server: {:?}", e);
@@ -48,7 +70,7 @@ async fn main() {
     let vs_server = async_spawn(async move {
         if let Err(e) = hotshot_web_server::run_web_server::<
             <DemoTypes as hotshot_types::traits::node_implementation::NodeType>::SignatureKey,
-        >(Some(server_shutdown_view_sync), args.view_sync_port)
+        >(Some(server_shutdown_view_sync), args.view_sync_port, auth)
         .await
         {
             error!("Problem starting view sync web server: {:?}", e);
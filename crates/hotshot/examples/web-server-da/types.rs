@@ -6,6 +6,7 @@ use hotshot::{
         implementations::{MemoryStorage, WebCommChannel},
     },
 };
+use hotshot_task_impls::mempool::DefaultMempool;
 use hotshot_types::{
     certificate::ViewSyncCertificate,
     data::{DAProposal, Leaf, QuorumProposal},
@@ -41,6 +42,7 @@ pub type ThisViewSyncVote = ViewSyncVote<DemoTypes>;
 
 impl NodeImplementation<DemoTypes> for NodeImpl {
     type Storage = MemoryStorage<DemoTypes, Self::Leaf>;
+    type Mempool = DefaultMempool<DemoTypes>;
     type Leaf = Leaf<DemoTypes>;
     type Exchanges = Exchanges<
         DemoTypes,
@@ -1,15 +1,33 @@
+pub mod types;
+
 use hotshot::demo::DemoTypes;
-use std::sync::Arc;
+use hotshot_web_server::{auth::AuthConfig, storage::StorageConfig};
+use std::{path::PathBuf, sync::Arc};
 
 use async_compatibility_layer::{
     channel::oneshot,
     logging::{setup_backtrace, setup_logging},
 };
 use clap::Parser;
+use types::ThisMembership;
+
+use crate::infra::web_server_auth;
+
+#[path = "../infra/mod.rs"]
+pub mod infra;
 
 #[derive(Parser, Debug)]
 struct WebServerArgs {
     port: u16,
+    /// The run configuration of the network, whose nodes are the only ones allowed to submit
+    #[arg(long)]
+    config_file: Option<String>,
+    /// Directory to keep posted messages in, so they are still served after a restart
+    #[arg(long)]
+    storage_path: Option<PathBuf>,
+    /// Let any key add itself to the stake table instead, e.g. for local testing
+    #[arg(long)]
+    open_registration: bool,
 }
 #[cfg_attr(
     async_executor_impl = "tokio",
@@ -20,10 +38,23 @@ async fn main() {
     setup_backtrace();
     setup_logging();
     let args = WebServerArgs::parse();
+    let auth = match args.config_file {
+        _ if args.open_registration => AuthConfig::open(),
+        Some(config_file) => web_server_auth::<DemoTypes, ThisMembership>(config_file),
+        None => panic!("Pass the --config-file of the network, or --open-registration"),
+    };
     let (server_shutdown_sender, server_shutdown) = oneshot();
     let _sender = Arc::new(server_shutdown_sender);
-    let _result = hotshot_web_server::run_web_server::<
+    let _result = hotshot_web_server::run_web_server_with_config::<
         <DemoTypes as hotshot_types::traits::node_implementation::NodeType>::SignatureKey,
-    >(Some(server_shutdown), args.port)
+    >(
+        Some(server_shutdown),
+        args.port,
+        auth,
+        StorageConfig {
+            path: args.storage_path,
+            ..StorageConfig::default()
+        },
+    )
     .await;
 }
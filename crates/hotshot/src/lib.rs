@@ -33,8 +33,8 @@ pub mod tasks;
 use crate::{
     certificate::QuorumCertificate,
     tasks::{
-        add_consensus_task, add_da_task, add_network_event_task, add_network_message_task,
-        add_transaction_task, add_view_sync_task,
+        add_catchup_task, add_consensus_task, add_da_task, add_network_event_task,
+        add_network_message_task, add_transaction_task, add_view_sync_task,
     },
     traits::{NodeImplementation, Storage},
     types::{Event, SystemContextHandle},
@@ -52,10 +52,14 @@ use hotshot_task::{
     event_stream::{ChannelStream, EventStream},
     task_launcher::TaskRunner,
 };
-use hotshot_task_impls::{events::HotShotEvent, network::NetworkTaskKind};
+use hotshot_task_impls::{
+    events::HotShotEvent,
+    mempool::Mempool,
+    network::{NetworkTaskKind, OutgoingMessageTransform},
+};
 use hotshot_types::{
     certificate::{TimeoutCertificate, VIDCertificate},
-    data::VidDisperse,
+    data::{VidDisperse, VidSetup},
     simple_certificate::QuorumCertificate2,
     traits::node_implementation::TimeoutEx,
 };
@@ -80,7 +84,7 @@ use hotshot_types::{
         },
         signature_key::SignatureKey,
         state::ConsensusTime,
-        storage::StoredView,
+        storage::{UndecidedState, ViewEntry},
         State,
     },
     vote::ViewSyncData,
@@ -129,6 +133,9 @@ pub struct SystemContextInner<TYPES: NodeType, I: NodeImplementation<TYPES>> {
     /// This `HotShot` instance's storage backend
     storage: I::Storage,
 
+    /// The SRS and parameters for the verifiable information dispersal of block payloads
+    vid: VidSetup,
+
     /// This `HotShot` instance's way to interact with the nodes needed to form a quorum and/or DA certificate.
     pub exchanges: Arc<I::Exchanges>,
 
@@ -152,6 +159,9 @@ pub struct SystemContextInner<TYPES: NodeType, I: NodeImplementation<TYPES>> {
     /// access to the internal event stream, in case we need to, say, shut something down
     internal_event_stream: ChannelStream<HotShotEvent<TYPES, I>>,
 
+    /// Rewrites every message this node sends, if set
+    outgoing_message_transform: RwLock<Option<Arc<dyn OutgoingMessageTransform<TYPES, I>>>>,
+
     /// uid for instrumentation
     id: u64,
 }
@@ -187,31 +197,43 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> SystemContext<TYPES, I> {
         let consensus_metrics = Arc::new(metrics);
         let anchored_leaf = initializer.inner;
 
+        let vid = VidSetup::new(&config.vid, config.total_nodes.get()).map_err(|e| {
+            HotShotError::Misc {
+                context: format!("Failed to set up VID: {e}"),
+            }
+        })?;
+
         // insert to storage
         storage
             .append(vec![anchored_leaf.clone().into()])
             .await
             .context(StorageSnafu)?;
 
-        // insert genesis (or latest block) to state map
+        // insert genesis (or latest block) to state map, followed by any undecided leaves we are
+        // reloading
         let mut state_map = BTreeMap::default();
-        state_map.insert(
-            anchored_leaf.get_view_number(),
-            View {
-                view_inner: ViewInner::Leaf {
-                    leaf: anchored_leaf.commit(),
-                },
-            },
-        );
-
         let mut saved_leaves = HashMap::new();
         let mut saved_block_payloads = BlockPayloadStore::default();
-        saved_leaves.insert(anchored_leaf.commit(), anchored_leaf.clone());
-        if let Some(payload) = anchored_leaf.get_block_payload() {
-            saved_block_payloads.insert(payload);
+        for leaf in std::iter::once(anchored_leaf.clone()).chain(initializer.undecided_leaves) {
+            state_map.insert(
+                leaf.get_view_number(),
+                View {
+                    view_inner: ViewInner::Leaf {
+                        leaf: leaf.commit(),
+                    },
+                },
+            );
+            if let Some(payload) = leaf.get_block_payload() {
+                saved_block_payloads.insert(payload);
+            }
+            saved_leaves.insert(leaf.commit(), leaf);
         }
 
-        let start_view = anchored_leaf.get_view_number();
+        let start_view = state_map
+            .keys()
+            .next_back()
+            .copied()
+            .unwrap_or_else(|| anchored_leaf.get_view_number());
 
         let consensus = Consensus {
             state_map,
@@ -219,10 +241,14 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> SystemContext<TYPES, I> {
             last_decided_view: anchored_leaf.get_view_number(),
             saved_leaves,
             saved_block_payloads,
-            // TODO this is incorrect
+            // TODO this is incorrect if we are not reloading
             // https://github.com/EspressoSystems/HotShot/issues/560
-            locked_view: anchored_leaf.get_view_number(),
-            high_qc: anchored_leaf.get_justify_qc(),
+            locked_view: initializer
+                .locked_view
+                .unwrap_or(anchored_leaf.get_view_number()),
+            high_qc: initializer
+                .high_qc
+                .unwrap_or_else(|| anchored_leaf.get_justify_qc()),
             metrics: consensus_metrics.clone(),
         };
         let consensus = Arc::new(RwLock::new(consensus));
@@ -235,23 +261,35 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> SystemContext<TYPES, I> {
             private_key,
             config,
             storage,
+            vid,
             exchanges: Arc::new(exchanges),
             event_sender: RwLock::default(),
             _metrics: consensus_metrics.clone(),
             internal_event_stream: ChannelStream::new(),
             output_event_stream: ChannelStream::new(),
+            outgoing_message_transform: RwLock::default(),
         });
 
         Ok(Self { inner })
     }
 
-    /// "Starts" consensus by sending a `QCFormed` event
+    /// Rewrite every message this node sends with `transform`, e.g. to make it misbehave in
+    /// tests. Only takes effect for tasks started by a later [`HotShotType::run_tasks`].
+    pub async fn set_outgoing_message_transform(
+        &self,
+        transform: Arc<dyn OutgoingMessageTransform<TYPES, I>>,
+    ) {
+        *self.inner.outgoing_message_transform.write().await = Some(transform);
+    }
+
+    /// "Starts" consensus by sending a `QCFormed` event for our high QC
+    ///
+    /// This is the genesis QC unless we were reloaded from storage.
     pub async fn start_consensus(&self) {
+        let high_qc = self.inner.consensus.read().await.high_qc.clone();
         self.inner
             .internal_event_stream
-            .publish(HotShotEvent::QCFormed(either::Left(
-                QuorumCertificate2::genesis(),
-            )))
+            .publish(HotShotEvent::QCFormed(either::Left(high_qc)))
             .await;
     }
 
@@ -417,6 +455,50 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> SystemContext<TYPES, I> {
         Ok((handle, internal_event_stream))
     }
 
+    /// Initializes a new hotshot from the state persisted in `storage`, so that a restarted node
+    /// resumes from its last decided view instead of from genesis.
+    ///
+    /// See [`HotShotInitializer::from_storage`] and [`SystemContext::init`].
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the persisted state could not be loaded from `storage`
+    #[allow(clippy::too_many_arguments)]
+    pub async fn init_from_storage(
+        public_key: TYPES::SignatureKey,
+        private_key: <TYPES::SignatureKey as SignatureKey>::PrivateKey,
+        node_id: u64,
+        config: HotShotConfig<
+            <TYPES::SignatureKey as SignatureKey>::StakeTableEntry,
+            TYPES::ElectionConfigType,
+        >,
+        storage: I::Storage,
+        exchanges: I::Exchanges,
+        metrics: ConsensusMetricsValue,
+    ) -> Result<
+        (
+            SystemContextHandle<TYPES, I>,
+            ChannelStream<HotShotEvent<TYPES, I>>,
+        ),
+        HotShotError<TYPES>,
+    >
+    where
+        SystemContext<TYPES, I>: HotShotType<TYPES, I>,
+    {
+        let initializer = HotShotInitializer::from_storage(&storage).await?;
+        Self::init(
+            public_key,
+            private_key,
+            node_id,
+            config,
+            storage,
+            exchanges,
+            initializer,
+            metrics,
+        )
+        .await
+    }
+
     /// Send a broadcast message.
     ///
     /// This is an alias for `hotshot.inner.networking.broadcast_message(msg.into())`.
@@ -700,6 +782,7 @@ where
         let committee_exchange = self.inner.exchanges.committee_exchange().clone();
         let view_sync_exchange = self.inner.exchanges.view_sync_exchange().clone();
         let vid_exchange = self.inner.exchanges.vid_exchange().clone();
+        let outgoing_message_transform = self.inner.outgoing_message_transform.read().await.clone();
 
         let handle = SystemContextHandle {
             registry,
@@ -713,24 +796,28 @@ where
             task_runner,
             internal_event_stream.clone(),
             quorum_exchange.clone(),
+            self.inner.config.clock.clone(),
         )
         .await;
         let task_runner = add_network_message_task(
             task_runner,
             internal_event_stream.clone(),
             committee_exchange.clone(),
+            self.inner.config.clock.clone(),
         )
         .await;
         let task_runner = add_network_message_task(
             task_runner,
             internal_event_stream.clone(),
             view_sync_exchange.clone(),
+            self.inner.config.clock.clone(),
         )
         .await;
         let task_runner = add_network_message_task(
             task_runner,
             internal_event_stream.clone(),
             vid_exchange.clone(),
+            self.inner.config.clock.clone(),
         )
         .await;
         let task_runner = add_network_event_task(
@@ -738,6 +825,7 @@ where
             internal_event_stream.clone(),
             quorum_exchange.clone(),
             NetworkTaskKind::Quorum,
+            outgoing_message_transform.clone(),
         )
         .await;
         let task_runner = add_network_event_task(
@@ -745,6 +833,7 @@ where
             internal_event_stream.clone(),
             committee_exchange.clone(),
             NetworkTaskKind::Committee,
+            outgoing_message_transform.clone(),
         )
         .await;
         let task_runner = add_network_event_task(
@@ -752,6 +841,7 @@ where
             internal_event_stream.clone(),
             view_sync_exchange.clone(),
             NetworkTaskKind::ViewSync,
+            outgoing_message_transform.clone(),
         )
         .await;
         let task_runner = add_network_event_task(
@@ -759,6 +849,7 @@ where
             internal_event_stream.clone(),
             vid_exchange.clone(),
             NetworkTaskKind::VID,
+            outgoing_message_transform.clone(),
         )
         .await;
         let task_runner = add_consensus_task(
@@ -782,11 +873,14 @@ where
             handle.clone(),
         )
         .await;
+        let mempool = I::Mempool::create(handle.hotshot.inner.config.mempool.clone());
         let task_runner = add_transaction_task(
             task_runner,
             internal_event_stream.clone(),
             quorum_exchange,
+            vid_exchange,
             handle.clone(),
+            mempool,
         )
         .await;
         let task_runner = add_view_sync_task::<TYPES, I>(
@@ -795,6 +889,12 @@ where
             handle.clone(),
         )
         .await;
+        let task_runner = add_catchup_task::<TYPES, I>(
+            task_runner,
+            internal_event_stream.clone(),
+            handle.clone(),
+        )
+        .await;
         async_spawn(async move {
             task_runner.launch().await;
             info!("Task runner exited!");
@@ -830,6 +930,14 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> ConsensusSharedApi<TYPES, I:
         self.inner.config.max_transactions
     }
 
+    fn max_block_size(&self) -> NonZeroUsize {
+        self.inner.config.max_block_size
+    }
+
+    fn vid(&self) -> &VidSetup {
+        &self.inner.vid
+    }
+
     fn min_transactions(&self) -> usize {
         self.inner.config.min_transactions
     }
@@ -859,18 +967,67 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> ConsensusSharedApi<TYPES, I:
         &self.inner.private_key
     }
 
-    async fn store_leaf(
+    async fn store_leaves(
+        &self,
+        leaves: Vec<I::Leaf>,
+    ) -> std::result::Result<(), hotshot_types::traits::storage::StorageError> {
+        self.store_consensus_state(leaves, None).await
+    }
+
+    async fn store_consensus_state(
         &self,
-        old_anchor_view: TYPES::Time,
-        leaf: I::Leaf,
+        leaves: Vec<I::Leaf>,
+        undecided: Option<UndecidedState<TYPES, I::Leaf>>,
     ) -> std::result::Result<(), hotshot_types::traits::storage::StorageError> {
-        let view_to_insert = StoredView::from(leaf);
         let storage = &self.inner.storage;
-        storage.append_single_view(view_to_insert).await?;
-        storage.cleanup_storage_up_to_view(old_anchor_view).await?;
+        let newest_view = leaves.iter().map(LeafType::get_view_number).max();
+        if !leaves.is_empty() {
+            storage
+                .append(leaves.into_iter().map(ViewEntry::from).collect())
+                .await?;
+        }
+        // Keep only the decided views within the retention, if the history is bounded.
+        if let (Some(newest_view), Some(retention)) =
+            (newest_view, self.inner.config.decided_retention_views)
+        {
+            if *newest_view > retention {
+                storage
+                    .cleanup_storage_up_to_view(TYPES::Time::new(*newest_view - retention))
+                    .await?;
+            }
+        }
+        if let Some(undecided) = undecided {
+            storage.update_undecided_state(undecided).await?;
+        }
         storage.commit().await?;
         Ok(())
     }
+
+    async fn get_decided_leaf(
+        &self,
+        commitment: Commitment<I::Leaf>,
+    ) -> std::result::Result<Option<I::Leaf>, hotshot_types::traits::storage::StorageError> {
+        self.inner
+            .storage
+            .get_leaf_by_commitment(commitment)
+            .await?
+            .map(I::Leaf::from_stored_view)
+            .transpose()
+    }
+
+    async fn get_decided_leaves(
+        &self,
+        from: TYPES::Time,
+        to: TYPES::Time,
+    ) -> std::result::Result<Vec<I::Leaf>, hotshot_types::traits::storage::StorageError> {
+        self.inner
+            .storage
+            .get_views_in_range(from, to)
+            .await?
+            .into_iter()
+            .map(I::Leaf::from_stored_view)
+            .collect()
+    }
 }
 
 #[async_trait]
@@ -1012,6 +1169,15 @@ impl<
 pub struct HotShotInitializer<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>> {
     /// the leaf specified initialization
     inner: LEAF,
+
+    /// Leaves after `inner` that had not been decided yet, in ascending view order
+    undecided_leaves: Vec<LEAF>,
+
+    /// The high QC to resume from, if reloading
+    high_qc: Option<QuorumCertificate2<TYPES, LEAF>>,
+
+    /// The locked view to resume from, if reloading
+    locked_view: Option<TYPES::Time>,
 }
 
 impl<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>> HotShotInitializer<TYPES, LEAF> {
@@ -1025,11 +1191,55 @@ impl<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>> HotShotInitializer<TYPES
 
         Ok(Self {
             inner: LEAF::new(time, justify_qc, genesis_payload, state),
+            undecided_leaves: Vec::new(),
+            high_qc: None,
+            locked_view: None,
         })
     }
 
     /// reload previous state based on most recent leaf
     pub fn from_reload(anchor_leaf: LEAF) -> Self {
-        Self { inner: anchor_leaf }
+        Self {
+            inner: anchor_leaf,
+            undecided_leaves: Vec::new(),
+            high_qc: None,
+            locked_view: None,
+        }
+    }
+
+    /// reload previous state from `storage`
+    ///
+    /// The anchor leaf is the latest anchored view in `storage`. If `storage` also holds an
+    /// [`UndecidedState`] newer than the anchor, the undecided leaves, high QC and locked view are
+    /// restored from it as well.
+    ///
+    /// # Errors
+    /// If `storage` has no anchored view or fails to load
+    pub async fn from_storage<STORAGE: Storage<TYPES, LEAF>>(
+        storage: &STORAGE,
+    ) -> Result<Self, HotShotError<TYPES>> {
+        let anchor_leaf =
+            LEAF::from_stored_view(storage.get_anchored_view().await.context(StorageSnafu)?)
+                .context(StorageSnafu)?;
+        let mut initializer = Self::from_reload(anchor_leaf);
+
+        let Some(undecided) = storage.get_undecided_state().await.context(StorageSnafu)? else {
+            return Ok(initializer);
+        };
+        let anchor_view = initializer.inner.get_view_number();
+        initializer.undecided_leaves = undecided
+            .leaves
+            .into_iter()
+            .filter(|view| view.view_number > anchor_view)
+            .map(LEAF::from_stored_view)
+            .collect::<Result<_, _>>()
+            .context(StorageSnafu)?;
+        if undecided.high_qc.view_number >= anchor_view {
+            initializer.high_qc = Some(undecided.high_qc);
+        }
+        if undecided.locked_view >= anchor_view {
+            initializer.locked_view = Some(undecided.locked_view);
+        }
+        Ok(initializer)
     }
 }
@@ -3,7 +3,7 @@
 use crate::{
     async_spawn, types::SystemContextHandle, DACertificate, HotShotConsensusApi, QuorumCertificate,
 };
-use async_compatibility_layer::art::async_sleep;
+use async_compatibility_layer::async_primitives::subscribable_rwlock::SubscribableRwLock;
 use commit::{Commitment, CommitmentBounds, Committable};
 use futures::FutureExt;
 use hotshot_task::{
@@ -15,12 +15,14 @@ use hotshot_task::{
     GeneratedStream, Merge,
 };
 use hotshot_task_impls::{
+    catchup::{CatchupTaskState, CatchupTaskTypes},
     consensus::{consensus_event_filter, ConsensusTaskState, ConsensusTaskTypes},
     da::{DATaskState, DATaskTypes},
     events::HotShotEvent,
+    mempool::Mempool,
     network::{
         NetworkEventTaskState, NetworkEventTaskTypes, NetworkMessageTaskState,
-        NetworkMessageTaskTypes, NetworkTaskKind,
+        NetworkMessageTaskTypes, NetworkTaskKind, OutgoingMessageTransform,
     },
     transactions::{TransactionTaskState, TransactionsTaskTypes},
     vid::{VIDTaskState, VIDTaskTypes},
@@ -29,6 +31,7 @@ use hotshot_task_impls::{
 use hotshot_types::{
     block_impl::{VIDBlockPayload, VIDTransaction},
     certificate::{TimeoutCertificate, VIDCertificate, ViewSyncCertificate},
+    clock::Clock,
     data::{Leaf, ProposalType, QuorumProposal},
     event::Event,
     message::{Message, Messages, SequencingMessage},
@@ -44,7 +47,7 @@ use hotshot_types::{
     vote::{ViewSyncData, VoteType},
 };
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     marker::PhantomData,
     sync::Arc,
     time::Duration,
@@ -80,14 +83,17 @@ pub async fn add_network_message_task<
     task_runner: TaskRunner,
     event_stream: ChannelStream<HotShotEvent<TYPES, I>>,
     exchange: EXCHANGE,
+    clock: Clock,
 ) -> TaskRunner
 // This bound is required so that we can call the `recv_msgs` function of `CommunicationChannel`.
 where
     EXCHANGE::Networking: CommunicationChannel<TYPES, Message<TYPES, I>, MEMBERSHIP>,
 {
     let channel = exchange.network().clone();
+    let broadcast_clock = clock.clone();
     let broadcast_stream = GeneratedStream::<Messages<TYPES, I>>::new(Arc::new(move || {
         let network = channel.clone();
+        let clock = broadcast_clock.clone();
         let closure = async move {
             loop {
                 let msgs = Messages(
@@ -97,7 +103,7 @@ where
                         .expect("Failed to receive broadcast messages"),
                 );
                 if msgs.0.is_empty() {
-                    async_sleep(Duration::from_millis(100)).await;
+                    clock.sleep(Duration::from_millis(100)).await;
                 } else {
                     break msgs;
                 }
@@ -108,6 +114,7 @@ where
     let channel = exchange.network().clone();
     let direct_stream = GeneratedStream::<Messages<TYPES, I>>::new(Arc::new(move || {
         let network = channel.clone();
+        let clock = clock.clone();
         let closure = async move {
             loop {
                 let msgs = Messages(
@@ -117,7 +124,7 @@ where
                         .expect("Failed to receive direct messages"),
                 );
                 if msgs.0.is_empty() {
-                    async_sleep(Duration::from_millis(100)).await;
+                    clock.sleep(Duration::from_millis(100)).await;
                 } else {
                     break msgs;
                 }
@@ -187,6 +194,7 @@ pub async fn add_network_event_task<
     event_stream: ChannelStream<HotShotEvent<TYPES, I>>,
     exchange: EXCHANGE,
     task_kind: NetworkTaskKind,
+    transform: Option<Arc<dyn OutgoingMessageTransform<TYPES, I>>>,
 ) -> TaskRunner
 // This bound is required so that we can call the `recv_msgs` function of `CommunicationChannel`.
 where
@@ -204,6 +212,7 @@ where
         event_stream: event_stream.clone(),
         view: TYPES::Time::genesis(),
         phantom: PhantomData,
+        transform,
     };
     let registry = task_runner.registry.clone();
     let network_event_handler = HandleEvent(Arc::new(
@@ -274,6 +283,8 @@ where
     >,
 {
     let consensus = handle.hotshot.get_consensus();
+    // This is genesis unless we were reloaded from storage
+    let start_view = consensus.read().await.cur_view;
     let c_api: HotShotConsensusApi<TYPES, I> = HotShotConsensusApi {
         inner: handle.hotshot.inner.clone(),
     };
@@ -283,7 +294,8 @@ where
         registry: registry.clone(),
         consensus,
         timeout: handle.hotshot.inner.config.next_view_timeout,
-        cur_view: TYPES::Time::new(0),
+        clock: handle.hotshot.inner.config.clock.clone(),
+        cur_view: start_view,
         payload_commitment: Some(VIDBlockPayload::genesis().commit()),
         quorum_exchange: c_api.inner.exchanges.quorum_exchange().clone().into(),
         timeout_exchange: c_api.inner.exchanges.timeout_exchange().clone().into(),
@@ -299,6 +311,7 @@ where
         current_proposal: None,
         id: handle.hotshot.inner.id,
         qc: None,
+        stored_undecided_views: None,
     };
     consensus_state
         .quorum_exchange
@@ -308,7 +321,7 @@ where
     consensus_state
         .quorum_exchange
         .network()
-        .inject_consensus_info(ConsensusIntentEvent::PollForProposal(1))
+        .inject_consensus_info(ConsensusIntentEvent::PollForProposal(*start_view + 1))
         .await;
     let filter = FilterEvent(Arc::new(consensus_event_filter));
     let consensus_name = "Consensus Task";
@@ -378,6 +391,10 @@ where
         cur_view: TYPES::Time::new(0),
         vid_exchange: vid_exchange.into(),
         vote_collector: None,
+        vid_shares: BTreeMap::new(),
+        share_retention: handle.hotshot.inner.config.vid.share_retention_views,
+        reconstruct_decided: handle.hotshot.inner.config.vid.reconstruct_decided_payloads,
+        reconstructions: HashMap::new(),
         event_stream: event_stream.clone(),
         id: handle.hotshot.inner.id,
     };
@@ -477,17 +494,20 @@ where
     task_runner.add_task(da_task_id, da_name.to_string(), da_task)
 }
 
-/// add the Transaction Handling task
+/// add the Transaction Handling task, keeping undecided transactions in `mempool`
 /// # Panics
 /// Is unable to panic. This section here is just to satisfy clippy
 pub async fn add_transaction_task<
     TYPES: NodeType<Transaction = VIDTransaction, BlockPayload = VIDBlockPayload>,
     I: NodeImplementation<TYPES, Leaf = Leaf<TYPES>, ConsensusMessage = SequencingMessage<TYPES, I>>,
+    M: Mempool<TYPES>,
 >(
     task_runner: TaskRunner,
     event_stream: ChannelStream<HotShotEvent<TYPES, I>>,
     quorum_exchange: QuorumEx<TYPES, I>,
+    vid_exchange: VIDEx<TYPES, I>,
     handle: SystemContextHandle<TYPES, I>,
+    mempool: M,
 ) -> TaskRunner
 where
     QuorumEx<TYPES, I>: ConsensusExchange<
@@ -495,6 +515,12 @@ where
         Message<TYPES, I>,
         Certificate = QuorumCertificate<TYPES, Commitment<I::Leaf>>,
     >,
+    VIDEx<TYPES, I>: ConsensusExchange<
+        TYPES,
+        Message<TYPES, I>,
+        Certificate = VIDCertificate<TYPES>,
+        Commitment = Commitment<TYPES::BlockPayload>,
+    >,
 {
     // build the transactions task
     let c_api: HotShotConsensusApi<TYPES, I> = HotShotConsensusApi {
@@ -504,16 +530,19 @@ where
     let transactions_state = TransactionTaskState {
         registry: registry.clone(),
         api: c_api.clone(),
+        clock: handle.hotshot.inner.config.clock.clone(),
         consensus: handle.hotshot.get_consensus(),
-        transactions: Arc::default(),
+        transactions: Arc::new(SubscribableRwLock::new(mempool)),
         seen_transactions: HashSet::new(),
         cur_view: TYPES::Time::new(0),
         quorum_exchange: quorum_exchange.into(),
+        vid_exchange: vid_exchange.into(),
         event_stream: event_stream.clone(),
         id: handle.hotshot.inner.id,
     };
     let transactions_event_handler = HandleEvent(Arc::new(
-        move |event, mut state: TransactionTaskState<TYPES, I, HotShotConsensusApi<TYPES, I>>| {
+        move |event,
+              mut state: TransactionTaskState<TYPES, I, HotShotConsensusApi<TYPES, I>, M>| {
             async move {
                 let completion_status = state.handle_event(event).await;
                 (completion_status, state)
@@ -523,11 +552,11 @@ where
     ));
     let transactions_name = "Transactions Task";
     let transactions_event_filter = FilterEvent(Arc::new(
-        TransactionTaskState::<TYPES, I, HotShotConsensusApi<TYPES, I>>::filter,
+        TransactionTaskState::<TYPES, I, HotShotConsensusApi<TYPES, I>, M>::filter,
     ));
 
     let transactions_task_builder = TaskBuilder::<
-        TransactionsTaskTypes<TYPES, I, HotShotConsensusApi<TYPES, I>>,
+        TransactionsTaskTypes<TYPES, I, HotShotConsensusApi<TYPES, I>, M>,
     >::new(transactions_name.to_string())
     .register_event_stream(event_stream.clone(), transactions_event_filter)
     .await
@@ -541,6 +570,67 @@ where
     let da_task = TransactionsTaskTypes::build(transactions_task_builder).launch();
     task_runner.add_task(da_task_id, transactions_name.to_string(), da_task)
 }
+/// add the catchup task
+/// # Panics
+/// Is unable to panic. This section here is just to satisfy clippy
+pub async fn add_catchup_task<
+    TYPES: NodeType,
+    I: NodeImplementation<TYPES, Leaf = Leaf<TYPES>, ConsensusMessage = SequencingMessage<TYPES, I>>,
+>(
+    task_runner: TaskRunner,
+    event_stream: ChannelStream<HotShotEvent<TYPES, I>>,
+    handle: SystemContextHandle<TYPES, I>,
+) -> TaskRunner {
+    let c_api: HotShotConsensusApi<TYPES, I> = HotShotConsensusApi {
+        inner: handle.hotshot.inner.clone(),
+    };
+    let registry = task_runner.registry.clone();
+    let catchup_state = CatchupTaskState {
+        api: c_api,
+        cur_view: TYPES::Time::new(0),
+        consensus: handle.hotshot.get_consensus(),
+        outstanding: HashMap::new(),
+        outstanding_ranges: HashMap::new(),
+        quorum_membership: handle
+            .hotshot
+            .inner
+            .exchanges
+            .quorum_exchange()
+            .membership()
+            .clone(),
+        event_stream: event_stream.clone(),
+        id: handle.hotshot.inner.id,
+    };
+    let catchup_event_handler = HandleEvent(Arc::new(
+        move |event, mut state: CatchupTaskState<TYPES, I, HotShotConsensusApi<TYPES, I>>| {
+            async move {
+                let completion_status = state.handle_event(event).await;
+                (completion_status, state)
+            }
+            .boxed()
+        },
+    ));
+    let catchup_name = "Catchup Task";
+    let catchup_event_filter = FilterEvent(Arc::new(
+        CatchupTaskState::<TYPES, I, HotShotConsensusApi<TYPES, I>>::filter,
+    ));
+
+    let catchup_task_builder = TaskBuilder::<
+        CatchupTaskTypes<TYPES, I, HotShotConsensusApi<TYPES, I>>,
+    >::new(catchup_name.to_string())
+    .register_event_stream(event_stream.clone(), catchup_event_filter)
+    .await
+    .register_registry(&mut registry.clone())
+    .await
+    .register_state(catchup_state)
+    .register_event_handler(catchup_event_handler);
+    // impossible for unwrap to fail
+    // we *just* registered
+    let catchup_task_id = catchup_task_builder.get_task_id().unwrap();
+    let catchup_task = CatchupTaskTypes::build(catchup_task_builder).launch();
+    task_runner.add_task(catchup_task_id, catchup_name.to_string(), catchup_task)
+}
+
 /// add the view sync task
 /// # Panics
 /// Is unable to panic. This section here is just to satisfy clippy
@@ -576,6 +666,7 @@ where
         replica_task_map: HashMap::default(),
         relay_task_map: HashMap::default(),
         view_sync_timeout: Duration::new(5, 0),
+        clock: handle.hotshot.inner.config.clock.clone(),
         id: handle.hotshot.inner.id,
         last_garbage_collected_view: TYPES::Time::new(0),
     };
@@ -19,7 +19,7 @@ pub mod implementations {
             web_server_network::{WebCommChannel, WebServerNetwork},
             NetworkingMetricsValue,
         },
-        storage::memory_storage::MemoryStorage, // atomic_storage::AtomicStorage,
+        storage::{atomic_storage::AtomicStorage, memory_storage::MemoryStorage},
     };
 }
 
@@ -2,3 +2,9 @@
 
 /// static (round robin) committee election
 pub mod static_committee;
+
+/// committee electing leaders in proportion to their stake
+pub mod stake_weighted;
+
+/// committee whose members come from the stake table snapshot at the start of each epoch
+pub mod epoch_committee;
@@ -0,0 +1,566 @@
+//! A [`Membership`] whose committees change at epoch boundaries
+
+use super::stake_weighted::{stake_fits_in_u64, stake_to_u64, StakeVoteToken};
+use ethereum_types::U256;
+use hotshot_signature_key::bn254::BLSPubKey;
+use hotshot_stake_table::mt_based::StakeTable;
+use hotshot_types::{
+    data::LeafType,
+    traits::{
+        election::{Checked, ElectionConfig, ElectionError, Membership, StakeMap},
+        epoch::{EpochError, EpochMembership, EpochNumber, EpochSchedule, StakeTableChange},
+        node_implementation::NodeType,
+        signature_key::SignatureKey,
+        stake_table::{SnapshotVersion, StakeTableScheme},
+    },
+};
+use jf_primitives::signatures::bls_over_bn254::VerKey;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    num::NonZeroU64,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
+use tracing::{debug, error};
+
+/// Height of the Merkle tree underlying the [`StakeTable`], leaving room for validators to join
+const STAKE_TABLE_HEIGHT: usize = 10;
+
+/// Number of views in an epoch, unless configured otherwise
+const DEFAULT_EPOCH_LENGTH: u64 = 100;
+
+/// Entry of the stake table of a committee
+type Entry = <BLSPubKey as SignatureKey>::StakeTableEntry;
+
+/// The changes scheduled for each epoch, with the view each was scheduled in, in the order they
+/// were scheduled
+type Changes = BTreeMap<EpochNumber, Vec<(u64, StakeTableChange<BLSPubKey>)>>;
+
+/// The committee from an epoch on, until the next epoch with changes scheduled
+#[derive(Debug)]
+struct Committee {
+    /// Stake table with the changes up to the epoch applied, snapshotted at its start
+    stake_table: StakeTable<VerKey>,
+    /// The members of the committee
+    members: Vec<Entry>,
+    /// The stake of each member of the committee
+    stakes: StakeMap<BLSPubKey>,
+}
+
+/// The changes scheduled on an [`EpochCommittee`] and the committees they produce
+#[derive(Debug)]
+struct EpochState {
+    /// The public keys of the nodes that have been in the stake table
+    keys: HashMap<VerKey, BLSPubKey>,
+    /// The changes scheduled so far
+    changes: Changes,
+    /// The committee of epoch 0 and of every epoch with changes scheduled. The epochs in between
+    /// have the committee of the last of these before them.
+    committees: BTreeMap<EpochNumber, Committee>,
+    /// The epoch of the view the node is in
+    current_epoch: EpochNumber,
+    /// File the changes are persisted to, if any
+    path: Option<PathBuf>,
+}
+
+impl EpochState {
+    /// Apply a change to the head of `stake_table`
+    fn apply(
+        &mut self,
+        stake_table: &mut StakeTable<VerKey>,
+        change: &StakeTableChange<BLSPubKey>,
+    ) {
+        let result = match change {
+            StakeTableChange::Join { key, stake } => {
+                let stake_key = key.get_stake_table_entry(0).stake_key;
+                self.keys.insert(stake_key, *key);
+                stake_table.register(stake_key, *stake)
+            }
+            StakeTableChange::Leave { key } => {
+                let stake_key = key.get_stake_table_entry(0).stake_key;
+                stake_table.deregister(&stake_key)
+            }
+            StakeTableChange::UpdateStake { key, stake } => {
+                let stake_key = key.get_stake_table_entry(0).stake_key;
+                stake_table.set_value(&stake_key, *stake).map(|_| ())
+            }
+        };
+        if let Err(e) = result {
+            error!("Ignoring stake table change {:?}: {}", change, e);
+        }
+    }
+
+    /// The committee of the snapshot of `stake_table` at the start of `epoch`, after the changes
+    /// scheduled for it.
+    ///
+    /// If nobody is left with stake, the committee of the previous epoch carries over, as a
+    /// committee without members could neither lead nor vote. So it does if the total stake of
+    /// the committee doesn't fit in the `u64` votes are counted in.
+    fn committee_from(
+        &self,
+        epoch: EpochNumber,
+        stake_table: StakeTable<VerKey>,
+        committee_size: usize,
+    ) -> Committee {
+        let members: Vec<Entry> = stake_table
+            .try_iter(SnapshotVersion::EpochStart)
+            .expect("the stake table keeps epoch start snapshots")
+            .filter(|(_, stake)| !stake.is_zero())
+            .take(committee_size)
+            .map(|(stake_key, stake)| self.keys[&stake_key].get_stake_table_entry(stake))
+            .collect();
+        let total = members.iter().fold(U256::zero(), |total, entry| {
+            total.saturating_add(entry.stake_amount)
+        });
+        let members = match self.committees.range(..epoch).next_back() {
+            Some((_, previous)) if members.is_empty() => {
+                error!(
+                    "The committee of epoch {} would be empty, keeping the previous one",
+                    *epoch
+                );
+                previous.members.clone()
+            }
+            Some((_, previous)) if !stake_fits_in_u64(total) => {
+                error!(
+                    "The total stake of the committee of epoch {} doesn't fit in a u64, keeping the previous one",
+                    *epoch
+                );
+                previous.members.clone()
+            }
+            _ => members,
+        };
+        debug!("Derived the committee of epoch {}", *epoch);
+        Committee {
+            stake_table,
+            stakes: StakeMap::new(&members),
+            members,
+        }
+    }
+
+    /// Derive the committees again from `from` on, after the changes scheduled for `from`
+    /// changed
+    fn derive_from(&mut self, from: EpochNumber, committee_size: usize) {
+        self.committees.split_off(&from);
+        let epochs: Vec<_> = self
+            .changes
+            .range(from..)
+            .map(|(epoch, _)| *epoch)
+            .collect();
+        for epoch in epochs {
+            let mut stake_table = self
+                .committees
+                .values()
+                .next_back()
+                .expect("the committee of epoch 0 is derived on creation")
+                .stake_table
+                .clone();
+            for (_, change) in self.changes[&epoch].clone() {
+                self.apply(&mut stake_table, &change);
+            }
+            stake_table.advance();
+            let committee = self.committee_from(epoch, stake_table, committee_size);
+            self.committees.insert(epoch, committee);
+        }
+    }
+
+    /// The committee of `epoch`
+    fn committee(&self, epoch: EpochNumber) -> &Committee {
+        self.committees
+            .range(..=epoch)
+            .next_back()
+            .map(|(_, committee)| committee)
+            .expect("the committee of epoch 0 is derived on creation")
+    }
+
+    /// Write the changes scheduled so far to the file they are persisted to, if any
+    fn persist(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let bytes = bincode::serialize(&self.changes).map_err(|e| e.to_string())?;
+        // Write to a temporary file first, so that a crash can't leave a partial schedule
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, bytes).map_err(|e| e.to_string())?;
+        std::fs::rename(&tmp, path).map_err(|e| e.to_string())
+    }
+}
+
+/// Committee whose members and their stake come from the stake table snapshot taken at the start
+/// of each epoch.
+///
+/// Validators join, leave or change their stake through [`EpochMembership::schedule_change`],
+/// taking effect from the epoch the change is scheduled for. A change must be scheduled before
+/// the [deadline](EpochSchedule::change_deadline) of its epoch, which only depends on the view it
+/// is scheduled in, so every node accepts the same changes. The committee of any epoch, however
+/// far ahead, is derived from the changes up to it. Leaders rotate through the committee of the
+/// epoch, and votes are weighted by stake.
+///
+/// The changes can be persisted to a file, so that a restarted node gets them back. Clones share
+/// the changes, so a change scheduled on one applies to all of them.
+#[derive(Clone, Debug)]
+pub struct EpochCommittee<T, LEAF: LeafType<NodeType = T>> {
+    /// The nodes the stake table started with
+    initial_nodes_with_stake: Vec<Entry>,
+    /// Maximum number of nodes on the committee of an epoch
+    committee_size: usize,
+    /// How views are grouped into epochs
+    schedule: EpochSchedule,
+    /// The changes scheduled and the committees they produce
+    state: Arc<RwLock<EpochState>>,
+    /// Node type phantom
+    _type_phantom: PhantomData<T>,
+    /// Leaf phantom
+    _leaf_phantom: PhantomData<LEAF>,
+}
+
+impl<T, LEAF: LeafType<NodeType = T>> EpochCommittee<T, LEAF> {
+    /// Create a committee of the first `committee_size` nodes with stake, starting from
+    /// `nodes_with_stake` in epoch 0.
+    ///
+    /// # Panics
+    /// If a node is listed twice, if no node has any stake, or if the total stake of the
+    /// committee of epoch 0 doesn't fit in a `u64`.
+    #[must_use]
+    pub fn new(
+        nodes_with_stake: Vec<Entry>,
+        committee_size: usize,
+        schedule: EpochSchedule,
+    ) -> Self {
+        let mut stake_table = StakeTable::new(STAKE_TABLE_HEIGHT);
+        let mut keys = HashMap::new();
+        for entry in &nodes_with_stake {
+            stake_table
+                .register(entry.stake_key, entry.stake_amount)
+                .expect("nodes in the stake table must be unique");
+            keys.insert(entry.stake_key, BLSPubKey::get_public_key(entry));
+        }
+        stake_table.advance();
+        let mut state = EpochState {
+            keys,
+            changes: BTreeMap::new(),
+            committees: BTreeMap::new(),
+            current_epoch: EpochNumber::default(),
+            path: None,
+        };
+        let committee = state.committee_from(EpochNumber::default(), stake_table, committee_size);
+        assert!(
+            !committee.members.is_empty(),
+            "the committee of epoch 0 must not be empty"
+        );
+        assert!(
+            stake_fits_in_u64(committee.stakes.total()),
+            "the total stake of the committee of epoch 0 must fit in a u64"
+        );
+        state.committees.insert(EpochNumber::default(), committee);
+        Self {
+            initial_nodes_with_stake: nodes_with_stake,
+            committee_size,
+            schedule,
+            state: Arc::new(RwLock::new(state)),
+            _type_phantom: PhantomData,
+            _leaf_phantom: PhantomData,
+        }
+    }
+
+    /// Like [`Self::new`], but with the changes persisted to the file at `path`, starting from
+    /// the changes already in it, if it exists.
+    ///
+    /// # Panics
+    /// Like [`Self::new`], or if the file exists but can't be read as changes.
+    #[must_use]
+    pub fn with_persisted_changes(
+        nodes_with_stake: Vec<Entry>,
+        committee_size: usize,
+        schedule: EpochSchedule,
+        path: PathBuf,
+    ) -> Self {
+        let committee = Self::new(nodes_with_stake, committee_size, schedule);
+        {
+            let mut state = committee.state.write().unwrap();
+            if path.exists() {
+                let bytes = std::fs::read(&path).expect("the persisted changes must be readable");
+                state.changes = bincode::deserialize::<Changes>(&bytes)
+                    .expect("the persisted changes must be valid");
+                state.derive_from(EpochNumber::default().next(), committee_size);
+            }
+            state.path = Some(path);
+        }
+        committee
+    }
+
+    /// Read from the committee of the epoch of `view`
+    ///
+    /// # Panics
+    /// If the lock on the state is poisoned.
+    fn with_committee_at<R>(&self, view: u64, f: impl FnOnce(&Committee) -> R) -> R {
+        f(self
+            .state
+            .read()
+            .unwrap()
+            .committee(self.schedule.epoch(view)))
+    }
+
+    /// The committee of the epoch of `view`
+    fn committee_at(&self, view: u64) -> Vec<Entry> {
+        self.with_committee_at(view, |committee| committee.members.clone())
+    }
+
+    /// The stake of `key` on the committee of the epoch of `view`, if it is a member
+    fn stake_at(&self, view: u64, key: &BLSPubKey) -> Option<U256> {
+        self.with_committee_at(view, |committee| committee.stakes.get(key))
+    }
+
+    /// Total stake of the committee of the epoch of `view`
+    fn total_stake_at(&self, view: u64) -> U256 {
+        self.with_committee_at(view, |committee| committee.stakes.total())
+    }
+
+    /// The stake of `key` on the committee of the epoch of `view` as a vote count, if it is a
+    /// member with stake
+    fn vote_count_at(&self, view: u64, key: &BLSPubKey) -> Option<NonZeroU64> {
+        self.stake_at(view, key)
+            .and_then(|stake| NonZeroU64::new(stake_to_u64(stake)))
+    }
+}
+
+// The state is derived from the initial nodes and the changes scheduled since, so it isn't
+// compared.
+impl<T, LEAF: LeafType<NodeType = T>> PartialEq for EpochCommittee<T, LEAF> {
+    fn eq(&self, other: &Self) -> bool {
+        self.initial_nodes_with_stake == other.initial_nodes_with_stake
+            && self.committee_size == other.committee_size
+            && self.schedule == other.schedule
+    }
+}
+
+impl<T, LEAF: LeafType<NodeType = T>> Eq for EpochCommittee<T, LEAF> {}
+
+impl<T, LEAF: LeafType<NodeType = T>> Hash for EpochCommittee<T, LEAF> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.initial_nodes_with_stake.hash(state);
+        self.committee_size.hash(state);
+        self.schedule.hash(state);
+    }
+}
+
+/// configuration for an epoch committee
+#[derive(Clone, Serialize, Deserialize, core::fmt::Debug)]
+pub struct EpochElectionConfig {
+    /// Maximum number of nodes on the committee
+    num_nodes: u64,
+    /// Number of views in an epoch
+    epoch_length: NonZeroU64,
+    /// File the scheduled changes are persisted to, if any
+    #[serde(default)]
+    schedule_path: Option<PathBuf>,
+}
+
+impl EpochElectionConfig {
+    /// Configure committees of at most `num_nodes` nodes, changing every `epoch_length` views
+    #[must_use]
+    pub fn new(num_nodes: u64, epoch_length: NonZeroU64) -> Self {
+        Self {
+            num_nodes,
+            epoch_length,
+            schedule_path: None,
+        }
+    }
+
+    /// Persist the scheduled changes to the file at `path`, and start from the changes already
+    /// in it
+    #[must_use]
+    pub fn with_schedule_path(mut self, path: PathBuf) -> Self {
+        self.schedule_path = Some(path);
+        self
+    }
+}
+
+impl Default for EpochElectionConfig {
+    fn default() -> Self {
+        Self {
+            num_nodes: 0,
+            epoch_length: NonZeroU64::new(DEFAULT_EPOCH_LENGTH).unwrap(),
+            schedule_path: None,
+        }
+    }
+}
+
+impl ElectionConfig for EpochElectionConfig {}
+
+impl<TYPES, LEAF: LeafType<NodeType = TYPES>> Membership<TYPES> for EpochCommittee<TYPES, LEAF>
+where
+    TYPES: NodeType<
+        SignatureKey = BLSPubKey,
+        VoteTokenType = StakeVoteToken<BLSPubKey>,
+        ElectionConfigType = EpochElectionConfig,
+    >,
+{
+    fn get_committee_qc_stake_table(&self, view_number: TYPES::Time) -> Vec<Entry> {
+        self.committee_at(*view_number)
+    }
+
+    /// Index the committee of the epoch of the view with the view number
+    fn get_leader(&self, view_number: TYPES::Time) -> BLSPubKey {
+        let committee = self.committee_at(*view_number);
+        let index = (*view_number % committee.len() as u64) as usize;
+        BLSPubKey::get_public_key(&committee[index])
+    }
+
+    /// Sign the view number, with the stake of the voter in the epoch of the view as the vote
+    /// count
+    fn make_vote_token(
+        &self,
+        view_number: TYPES::Time,
+        private_key: &<BLSPubKey as SignatureKey>::PrivateKey,
+    ) -> std::result::Result<Option<StakeVoteToken<BLSPubKey>>, ElectionError> {
+        let pub_key = BLSPubKey::from_private(private_key);
+        let Some(stake) = self.vote_count_at(*view_number, &pub_key) else {
+            return Ok(None);
+        };
+        let mut message: Vec<u8> = vec![];
+        message.extend(view_number.to_le_bytes());
+        message.extend_from_slice(&[0u8; 32 - 8]);
+        let signature = BLSPubKey::sign(private_key, &message);
+        Ok(Some(StakeVoteToken {
+            signature,
+            pub_key,
+            stake,
+        }))
+    }
+
+    fn validate_vote_token(
+        &self,
+        view_number: TYPES::Time,
+        pub_key: BLSPubKey,
+        token: Checked<TYPES::VoteTokenType>,
+    ) -> Result<Checked<TYPES::VoteTokenType>, ElectionError> {
+        match token {
+            Checked::Valid(t) | Checked::Unchecked(t) => {
+                if t.pub_key == pub_key
+                    && self.vote_count_at(*view_number, &pub_key) == Some(t.stake)
+                {
+                    Ok(Checked::Valid(t))
+                } else {
+                    Ok(Checked::Inval(t))
+                }
+            }
+            Checked::Inval(t) => Ok(Checked::Inval(t)),
+        }
+    }
+
+    fn default_election_config(num_nodes: u64) -> TYPES::ElectionConfigType {
+        EpochElectionConfig {
+            num_nodes,
+            ..EpochElectionConfig::default()
+        }
+    }
+
+    fn create_election(keys_qc: Vec<Entry>, config: TYPES::ElectionConfigType) -> Self {
+        debug!(
+            "Election Membership Size: {}, Epoch Length: {}",
+            config.num_nodes, config.epoch_length
+        );
+        let committee_size = config.num_nodes.try_into().unwrap();
+        let schedule = EpochSchedule::new(config.epoch_length);
+        match config.schedule_path {
+            Some(path) => Self::with_persisted_changes(keys_qc, committee_size, schedule, path),
+            None => Self::new(keys_qc, committee_size, schedule),
+        }
+    }
+
+    fn total_nodes(&self, view_number: TYPES::Time) -> usize {
+        self.committee_at(*view_number).len()
+    }
+
+    fn get_stake(&self, view_number: TYPES::Time, key: &BLSPubKey) -> Option<U256> {
+        self.stake_at(*view_number, key)
+    }
+
+    fn total_stake(&self, view_number: TYPES::Time) -> U256 {
+        self.total_stake_at(*view_number)
+    }
+
+    /// More than two thirds of the stake of the committee of the epoch of the view
+    fn success_threshold(&self, view_number: TYPES::Time) -> NonZeroU64 {
+        let two_thirds = u128::from(stake_to_u64(self.total_stake_at(*view_number))) * 2 / 3;
+        NonZeroU64::new(
+            u64::try_from(two_thirds)
+                .unwrap_or(u64::MAX)
+                .saturating_add(1),
+        )
+        .unwrap()
+    }
+
+    /// More than one third of the stake of the committee of the epoch of the view
+    fn failure_threshold(&self, view_number: TYPES::Time) -> NonZeroU64 {
+        NonZeroU64::new(stake_to_u64(self.total_stake_at(*view_number)) / 3 + 1).unwrap()
+    }
+
+    fn get_committee(&self, view_number: TYPES::Time) -> BTreeSet<BLSPubKey> {
+        self.committee_at(*view_number)
+            .iter()
+            .map(BLSPubKey::get_public_key)
+            .collect()
+    }
+
+    /// Track the epoch the node is in. The committees don't depend on it.
+    ///
+    /// # Panics
+    /// If the lock on the state is poisoned.
+    fn enter_view(&self, view_number: TYPES::Time) {
+        let epoch = self.schedule.epoch(*view_number);
+        let mut state = self.state.write().unwrap();
+        if state.current_epoch < epoch {
+            debug!("Entering epoch {}", *epoch);
+            state.current_epoch = epoch;
+        }
+    }
+}
+
+impl<TYPES, LEAF: LeafType<NodeType = TYPES>> EpochMembership<TYPES> for EpochCommittee<TYPES, LEAF>
+where
+    TYPES: NodeType<
+        SignatureKey = BLSPubKey,
+        VoteTokenType = StakeVoteToken<BLSPubKey>,
+        ElectionConfigType = EpochElectionConfig,
+    >,
+{
+    fn epoch_schedule(&self) -> EpochSchedule {
+        self.schedule
+    }
+
+    /// # Panics
+    /// If the lock on the state is poisoned.
+    fn current_epoch(&self) -> EpochNumber {
+        self.state.read().unwrap().current_epoch
+    }
+
+    /// If the changes are persisted and writing them fails, the change is still scheduled, so
+    /// that the committees don't depend on the file system, and the failure is logged.
+    ///
+    /// # Panics
+    /// If the lock on the state is poisoned.
+    fn schedule_change(
+        &self,
+        view: TYPES::Time,
+        epoch: EpochNumber,
+        change: StakeTableChange<BLSPubKey>,
+    ) -> Result<(), EpochError> {
+        self.check_change(view, epoch)?;
+        let mut state = self.state.write().unwrap();
+        let changes = state.changes.entry(epoch).or_default();
+        let scheduled = (*view, change);
+        if changes.contains(&scheduled) {
+            return Ok(());
+        }
+        changes.push(scheduled);
+        if let Err(e) = state.persist() {
+            error!("Could not persist the stake table changes: {}", e);
+        }
+        state.derive_from(epoch, self.committee_size);
+        Ok(())
+    }
+}
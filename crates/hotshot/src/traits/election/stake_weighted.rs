@@ -0,0 +1,305 @@
+//! A [`Membership`] that elects leaders in proportion to their stake
+
+use commit::{Commitment, Committable, RawCommitmentBuilder};
+use ethereum_types::U256;
+use hotshot_signature_key::bn254::BLSPubKey;
+use hotshot_stake_table::mt_based::StakeTable;
+use hotshot_types::{
+    data::LeafType,
+    traits::{
+        election::{Checked, ElectionConfig, ElectionError, Membership, StakeMap, VoteToken},
+        node_implementation::NodeType,
+        signature_key::{EncodedSignature, SignatureKey},
+        stake_table::StakeTableScheme,
+    },
+};
+use jf_primitives::signatures::bls_over_bn254::VerKey;
+use rand_chacha::{rand_core::SeedableRng, ChaCha20Rng};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeSet, HashMap},
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    num::NonZeroU64,
+};
+use tracing::debug;
+
+/// Branching factor of the Merkle tree underlying [`StakeTable`]
+const STAKE_TABLE_BRANCH: usize = 3;
+
+/// Committee electing the leader of each view by sampling the stake table, so that a node leads
+/// in proportion to its stake.
+///
+/// The sample for a view is drawn from a random number generator seeded with the view number, so
+/// every node agrees on the leader without communicating. Vote tokens carry the stake of the voter,
+/// and the thresholds are fractions of the total stake of the committee.
+#[derive(Clone, Debug)]
+pub struct StakeWeightedCommittee<T, LEAF: LeafType<NodeType = T>> {
+    /// All the nodes participating and their stake
+    nodes_with_stake: Vec<<BLSPubKey as SignatureKey>::StakeTableEntry>,
+    /// The nodes on the committee and their stake
+    committee_nodes_with_stake: Vec<<BLSPubKey as SignatureKey>::StakeTableEntry>,
+    /// The stake of each node on the committee
+    committee_stakes: StakeMap<BLSPubKey>,
+    /// Stake table of all the nodes participating, which leaders are sampled from
+    stake_table: StakeTable<VerKey>,
+    /// The public keys of the nodes in the stake table
+    keys: HashMap<VerKey, BLSPubKey>,
+    /// Node type phantom
+    _type_phantom: PhantomData<T>,
+    /// Leaf phantom
+    _leaf_phantom: PhantomData<LEAF>,
+}
+
+impl<T, LEAF: LeafType<NodeType = T>> StakeWeightedCommittee<T, LEAF> {
+    /// Create a committee of the first `committee_size` of `nodes_with_stake`, with leaders
+    /// sampled from all of `nodes_with_stake`.
+    ///
+    /// # Panics
+    /// If a node is listed twice, or if the total stake of the committee doesn't fit in a `u64`.
+    #[must_use]
+    pub fn new(
+        nodes_with_stake: Vec<<BLSPubKey as SignatureKey>::StakeTableEntry>,
+        committee_size: usize,
+    ) -> Self {
+        let mut height = 1;
+        while STAKE_TABLE_BRANCH.pow(height) < nodes_with_stake.len() {
+            height += 1;
+        }
+        let mut stake_table = StakeTable::new(height as usize);
+        let mut keys = HashMap::new();
+        for entry in &nodes_with_stake {
+            stake_table
+                .register(entry.stake_key, entry.stake_amount)
+                .expect("nodes in the stake table must be unique");
+            keys.insert(entry.stake_key, BLSPubKey::get_public_key(entry));
+        }
+        // Leaders are sampled from the `LastEpochStart` snapshot. The stake table never changes, so
+        // make every snapshot hold all the nodes.
+        stake_table.advance();
+        stake_table.advance();
+
+        let mut committee_nodes_with_stake = nodes_with_stake.clone();
+        committee_nodes_with_stake.truncate(committee_size);
+        let committee_stakes = StakeMap::new(&committee_nodes_with_stake);
+        assert!(
+            stake_fits_in_u64(committee_stakes.total()),
+            "the total stake of the committee must fit in a u64"
+        );
+        Self {
+            nodes_with_stake,
+            committee_stakes,
+            committee_nodes_with_stake,
+            stake_table,
+            keys,
+            _type_phantom: PhantomData,
+            _leaf_phantom: PhantomData,
+        }
+    }
+
+    /// The stake of `key` on the committee, if it is a member
+    fn committee_stake(&self, key: &BLSPubKey) -> Option<NonZeroU64> {
+        self.committee_stakes
+            .get(key)
+            .and_then(|stake| NonZeroU64::new(stake_to_u64(stake)))
+    }
+
+    /// Total stake of the committee
+    fn total_committee_stake(&self) -> u64 {
+        stake_to_u64(self.committee_stakes.total())
+    }
+}
+
+// The stakes, the stake table and the keys are derived from the nodes, so they don't need
+// comparing.
+impl<T, LEAF: LeafType<NodeType = T>> PartialEq for StakeWeightedCommittee<T, LEAF> {
+    fn eq(&self, other: &Self) -> bool {
+        self.nodes_with_stake == other.nodes_with_stake
+            && self.committee_nodes_with_stake == other.committee_nodes_with_stake
+    }
+}
+
+impl<T, LEAF: LeafType<NodeType = T>> Eq for StakeWeightedCommittee<T, LEAF> {}
+
+impl<T, LEAF: LeafType<NodeType = T>> Hash for StakeWeightedCommittee<T, LEAF> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.nodes_with_stake.hash(state);
+        self.committee_nodes_with_stake.hash(state);
+    }
+}
+
+/// Whether a stake amount fits in the `u64` that vote tokens count with.
+///
+/// The accumulators add up vote counts in a `u64`, so memberships only admit committees whose
+/// total stake fits in one.
+pub(super) fn stake_fits_in_u64(stake: U256) -> bool {
+    stake <= U256::from(u64::MAX)
+}
+
+/// Convert a stake amount of a member of a committee to the `u64` that vote tokens count with
+///
+/// # Panics
+/// If the stake doesn't fit in a `u64`, which [`stake_fits_in_u64`] rules out for the stake of
+/// every committee.
+pub(super) fn stake_to_u64(stake: U256) -> u64 {
+    assert!(stake_fits_in_u64(stake), "stake must fit in a u64");
+    stake.low_u64()
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+#[serde(bound(deserialize = ""))]
+/// Vote token for a stake-weighted committee, worth the stake of the voter
+pub struct StakeVoteToken<K: SignatureKey> {
+    /// signature
+    pub(super) signature: EncodedSignature,
+    /// public key
+    pub(super) pub_key: K,
+    /// stake of the voter
+    pub(super) stake: NonZeroU64,
+}
+
+impl<PUBKEY: SignatureKey> VoteToken for StakeVoteToken<PUBKEY> {
+    fn vote_count(&self) -> NonZeroU64 {
+        self.stake
+    }
+}
+
+impl<PUBKEY: SignatureKey> Committable for StakeVoteToken<PUBKEY> {
+    fn commit(&self) -> Commitment<Self> {
+        RawCommitmentBuilder::new("StakeVoteToken")
+            .var_size_field("signature", &self.signature.0)
+            .var_size_field("pub_key", &self.pub_key.to_bytes().0)
+            .u64_field("stake", self.stake.get())
+            .finalize()
+    }
+
+    fn tag() -> String {
+        "STAKE_VOTE_TOKEN".to_string()
+    }
+}
+
+/// configuration for a stake-weighted committee
+#[derive(Default, Clone, Serialize, Deserialize, core::fmt::Debug)]
+pub struct StakeWeightedElectionConfig {
+    /// Number of nodes on the committee
+    num_nodes: u64,
+}
+
+impl ElectionConfig for StakeWeightedElectionConfig {}
+
+impl<TYPES, LEAF: LeafType<NodeType = TYPES>> Membership<TYPES>
+    for StakeWeightedCommittee<TYPES, LEAF>
+where
+    TYPES: NodeType<
+        SignatureKey = BLSPubKey,
+        VoteTokenType = StakeVoteToken<BLSPubKey>,
+        ElectionConfigType = StakeWeightedElectionConfig,
+    >,
+{
+    fn get_committee_qc_stake_table(
+        &self,
+        _view_number: TYPES::Time,
+    ) -> Vec<<BLSPubKey as SignatureKey>::StakeTableEntry> {
+        self.committee_nodes_with_stake.clone()
+    }
+
+    /// Sample the stake table with the view number as the seed
+    ///
+    /// # Panics
+    /// If no node has any stake.
+    fn get_leader(&self, view_number: TYPES::Time) -> BLSPubKey {
+        let mut rng = ChaCha20Rng::seed_from_u64(*view_number);
+        let (stake_key, _) = self
+            .stake_table
+            .sample(&mut rng)
+            .expect("stake table must not be empty");
+        self.keys[stake_key]
+    }
+
+    /// Sign the view number, with the stake of the voter as the vote count
+    fn make_vote_token(
+        &self,
+        view_number: TYPES::Time,
+        private_key: &<BLSPubKey as SignatureKey>::PrivateKey,
+    ) -> std::result::Result<Option<StakeVoteToken<BLSPubKey>>, ElectionError> {
+        let pub_key = BLSPubKey::from_private(private_key);
+        let Some(stake) = self.committee_stake(&pub_key) else {
+            return Ok(None);
+        };
+        let mut message: Vec<u8> = vec![];
+        message.extend(view_number.to_le_bytes());
+        message.extend_from_slice(&[0u8; 32 - 8]);
+        let signature = BLSPubKey::sign(private_key, &message);
+        Ok(Some(StakeVoteToken {
+            signature,
+            pub_key,
+            stake,
+        }))
+    }
+
+    fn validate_vote_token(
+        &self,
+        _view_number: TYPES::Time,
+        pub_key: BLSPubKey,
+        token: Checked<TYPES::VoteTokenType>,
+    ) -> Result<Checked<TYPES::VoteTokenType>, ElectionError> {
+        match token {
+            Checked::Valid(t) | Checked::Unchecked(t) => {
+                // The token must be worth exactly the stake the voter has on the committee.
+                if t.pub_key == pub_key && self.committee_stake(&pub_key) == Some(t.stake) {
+                    Ok(Checked::Valid(t))
+                } else {
+                    Ok(Checked::Inval(t))
+                }
+            }
+            Checked::Inval(t) => Ok(Checked::Inval(t)),
+        }
+    }
+
+    fn default_election_config(num_nodes: u64) -> TYPES::ElectionConfigType {
+        StakeWeightedElectionConfig { num_nodes }
+    }
+
+    fn create_election(
+        keys_qc: Vec<<BLSPubKey as SignatureKey>::StakeTableEntry>,
+        config: TYPES::ElectionConfigType,
+    ) -> Self {
+        debug!("Election Membership Size: {}", config.num_nodes);
+        Self::new(keys_qc, config.num_nodes.try_into().unwrap())
+    }
+
+    fn total_nodes(&self, _view_number: TYPES::Time) -> usize {
+        self.committee_nodes_with_stake.len()
+    }
+
+    fn get_stake(&self, _view_number: TYPES::Time, key: &BLSPubKey) -> Option<U256> {
+        self.committee_stakes.get(key)
+    }
+
+    fn total_stake(&self, _view_number: TYPES::Time) -> U256 {
+        self.committee_stakes.total()
+    }
+
+    /// More than two thirds of the stake of the committee
+    fn success_threshold(&self, _view_number: TYPES::Time) -> NonZeroU64 {
+        let two_thirds = u128::from(self.total_committee_stake()) * 2 / 3;
+        NonZeroU64::new(
+            u64::try_from(two_thirds)
+                .unwrap_or(u64::MAX)
+                .saturating_add(1),
+        )
+        .unwrap()
+    }
+
+    /// More than one third of the stake of the committee
+    fn failure_threshold(&self, _view_number: TYPES::Time) -> NonZeroU64 {
+        NonZeroU64::new(self.total_committee_stake() / 3 + 1).unwrap()
+    }
+
+    fn get_committee(&self, _view_number: TYPES::Time) -> BTreeSet<BLSPubKey> {
+        self.committee_nodes_with_stake
+            .iter()
+            .map(BLSPubKey::get_public_key)
+            .collect()
+    }
+}
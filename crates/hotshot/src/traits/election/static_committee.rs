@@ -1,11 +1,12 @@
 // use ark_bls12_381::Parameters as Param381;
 use commit::{Commitment, Committable, RawCommitmentBuilder};
 use espresso_systems_common::hotshot::tag;
+use ethereum_types::U256;
 use hotshot_signature_key::bn254::BLSPubKey;
 use hotshot_types::{
     data::LeafType,
     traits::{
-        election::{Checked, ElectionConfig, ElectionError, Membership, VoteToken},
+        election::{Checked, ElectionConfig, ElectionError, Membership, StakeMap, VoteToken},
         node_implementation::NodeType,
         signature_key::{EncodedSignature, SignatureKey},
     },
@@ -23,6 +24,8 @@ pub struct GeneralStaticCommittee<T, LEAF: LeafType<NodeType = T>, PUBKEY: Signa
     nodes_with_stake: Vec<PUBKEY::StakeTableEntry>,
     /// The nodes on the static committee and their stake
     committee_nodes_with_stake: Vec<PUBKEY::StakeTableEntry>,
+    /// The stake of each node on the static committee
+    committee_stakes: StakeMap<PUBKEY>,
     /// Node type phantom
     _type_phantom: PhantomData<T>,
     /// Leaf phantom
@@ -40,6 +43,7 @@ impl<T, LEAF: LeafType<NodeType = T>, PUBKEY: SignatureKey>
     pub fn new(_nodes: &[PUBKEY], nodes_with_stake: Vec<PUBKEY::StakeTableEntry>) -> Self {
         Self {
             nodes_with_stake: nodes_with_stake.clone(),
+            committee_stakes: StakeMap::new(&nodes_with_stake),
             committee_nodes_with_stake: nodes_with_stake,
             _type_phantom: PhantomData,
             _leaf_phantom: PhantomData,
@@ -95,7 +99,10 @@ where
     >,
 {
     /// Clone the public key and corresponding stake table for current elected committee
-    fn get_committee_qc_stake_table(&self) -> Vec<PUBKEY::StakeTableEntry> {
+    fn get_committee_qc_stake_table(
+        &self,
+        _view_number: TYPES::Time,
+    ) -> Vec<PUBKEY::StakeTableEntry> {
         self.committee_nodes_with_stake.clone()
     }
 
@@ -127,6 +134,7 @@ where
 
     fn validate_vote_token(
         &self,
+        _view_number: TYPES::Time,
         pub_key: PUBKEY,
         token: Checked<TYPES::VoteTokenType>,
     ) -> Result<Checked<TYPES::VoteTokenType>, ElectionError> {
@@ -156,21 +164,30 @@ where
         committee_nodes_with_stake.truncate(config.num_nodes.try_into().unwrap());
         Self {
             nodes_with_stake: keys_qc,
+            committee_stakes: StakeMap::new(&committee_nodes_with_stake),
             committee_nodes_with_stake,
             _type_phantom: PhantomData,
             _leaf_phantom: PhantomData,
         }
     }
 
-    fn total_nodes(&self) -> usize {
+    fn total_nodes(&self, _view_number: TYPES::Time) -> usize {
         self.committee_nodes_with_stake.len()
     }
 
-    fn success_threshold(&self) -> NonZeroU64 {
+    fn get_stake(&self, _view_number: TYPES::Time, key: &PUBKEY) -> Option<U256> {
+        self.committee_stakes.get(key)
+    }
+
+    fn total_stake(&self, _view_number: TYPES::Time) -> U256 {
+        self.committee_stakes.total()
+    }
+
+    fn success_threshold(&self, _view_number: TYPES::Time) -> NonZeroU64 {
         NonZeroU64::new(((self.committee_nodes_with_stake.len() as u64 * 2) / 3) + 1).unwrap()
     }
 
-    fn failure_threshold(&self) -> NonZeroU64 {
+    fn failure_threshold(&self, _view_number: TYPES::Time) -> NonZeroU64 {
         NonZeroU64::new(((self.committee_nodes_with_stake.len() as u64) / 3) + 1).unwrap()
     }
 
@@ -16,6 +16,7 @@ use dashmap::DashMap;
 use futures::StreamExt;
 use hotshot_task::{boxed_sync, BoxSyncFuture};
 use hotshot_types::{
+    clock::VirtualClock,
     message::{Message, MessageKind},
     traits::{
         election::Membership,
@@ -31,13 +32,15 @@ use hotshot_utils::bincode::bincode_opts;
 use rand::Rng;
 use snafu::ResultExt;
 use std::{
-    collections::BTreeSet,
+    collections::{hash_map::DefaultHasher, BTreeSet},
     fmt::Debug,
+    hash::{Hash, Hasher},
     marker::PhantomData,
     sync::{
         atomic::{AtomicUsize, Ordering},
         Arc,
     },
+    time::Duration,
 };
 use tracing::{debug, error, info, info_span, instrument, trace, warn, Instrument};
 
@@ -52,8 +55,15 @@ pub struct MasterMap<M: NetworkMsg, K: SignatureKey> {
     map: DashMap<K, MemoryNetwork<M, K>>,
     /// The id of this `MemoryNetwork` cluster
     id: u64,
+    /// Seed of the latencies of the messages between the `MemoryNetwork`s, and the virtual clock
+    /// they are delivered by, if this is a simulation
+    #[debug(skip)]
+    simulation: Option<(u64, Arc<VirtualClock>)>,
 }
 
+/// Upper bound of the latency of a message in a seeded `MasterMap`, in nanoseconds
+const MAX_SIMULATED_LATENCY_NANOS: u64 = 10_000_000;
+
 impl<M: NetworkMsg, K: SignatureKey> MasterMap<M, K> {
     /// Create a new, empty, `MasterMap`
     #[must_use]
@@ -61,6 +71,19 @@ impl<M: NetworkMsg, K: SignatureKey> MasterMap<M, K> {
         Arc::new(MasterMap {
             map: DashMap::new(),
             id: rand::thread_rng().gen(),
+            simulation: None,
+        })
+    }
+
+    /// Create a new, empty, `MasterMap` whose `MemoryNetwork`s deliver each message after a
+    /// latency on `clock`. The latency is derived from `seed`, the sender, the recipient and the
+    /// message, so it doesn't depend on when the message is sent or how deliveries are batched.
+    #[must_use]
+    pub fn new_seeded(seed: u64, clock: Arc<VirtualClock>) -> Arc<MasterMap<M, K>> {
+        Arc::new(MasterMap {
+            map: DashMap::new(),
+            id: seed,
+            simulation: Some((seed, clock)),
         })
     }
 }
@@ -86,6 +109,8 @@ struct MemoryNetworkInner<M: NetworkMsg, K: SignatureKey> {
     direct_output: Mutex<Receiver<M>>,
     /// The master map
     master_map: Arc<MasterMap<M, K>>,
+    /// Our own key, which other nodes send to us by
+    pub_key: K,
 
     /// Count of messages that are in-flight (send but not processed yet)
     in_flight_message_count: AtomicUsize,
@@ -202,6 +227,7 @@ impl<M: NetworkMsg, K: SignatureKey> MemoryNetwork<M, K> {
                 broadcast_output: Mutex::new(broadcast_output),
                 direct_output: Mutex::new(direct_output),
                 master_map: master_map.clone(),
+                pub_key: pub_key.clone(),
                 in_flight_message_count,
                 metrics,
                 reliability_config,
@@ -213,6 +239,23 @@ impl<M: NetworkMsg, K: SignatureKey> MemoryNetwork<M, K> {
         mn
     }
 
+    /// The virtual clock to deliver `message` to `recipient` by, and the latency to deliver it
+    /// after, if the master map is seeded
+    fn simulated_latency(
+        &self,
+        recipient: &K,
+        message: &[u8],
+    ) -> Option<(Arc<VirtualClock>, Duration)> {
+        let (seed, clock) = self.inner.master_map.simulation.as_ref()?;
+        let mut hasher = DefaultHasher::new();
+        seed.hash(&mut hasher);
+        self.inner.pub_key.to_bytes().hash(&mut hasher);
+        recipient.to_bytes().hash(&mut hasher);
+        message.hash(&mut hasher);
+        let latency = Duration::from_nanos(hasher.finish() % MAX_SIMULATED_LATENCY_NANOS);
+        Some((Arc::clone(clock), latency))
+    }
+
     /// Send a [`Vec<u8>`] message to the inner `broadcast_input`
     async fn broadcast_input(&self, message: Vec<u8>) -> Result<(), SendError<Vec<u8>>> {
         self.inner
@@ -242,6 +285,17 @@ impl<M: NetworkMsg, K: SignatureKey> MemoryNetwork<M, K> {
     }
 }
 
+impl<M: NetworkMsg, K: SignatureKey> MemoryNetwork<M, K> {
+    /// Generates the networks of test nodes, all in `master`
+    fn generator_with_master(master: Arc<MasterMap<M, K>>) -> Box<dyn Fn(u64) -> Self + 'static> {
+        Box::new(move |node_id| {
+            let privkey = K::generated_from_seed_indexed([0u8; 32], node_id).1;
+            let pubkey = K::from_private(&privkey);
+            MemoryNetwork::new(pubkey, NetworkingMetricsValue::new(), master.clone(), None)
+        })
+    }
+}
+
 impl<TYPES: NodeType, I: NodeImplementation<TYPES>>
     TestableNetworkingImplementation<TYPES, Message<TYPES, I>>
     for MemoryNetwork<Message<TYPES, I>, TYPES::SignatureKey>
@@ -253,12 +307,20 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>>
         _da_committee_size: usize,
         _is_da: bool,
     ) -> Box<dyn Fn(u64) -> Self + 'static> {
-        let master: Arc<_> = MasterMap::new();
-        Box::new(move |node_id| {
-            let privkey = TYPES::SignatureKey::generated_from_seed_indexed([0u8; 32], node_id).1;
-            let pubkey = TYPES::SignatureKey::from_private(&privkey);
-            MemoryNetwork::new(pubkey, NetworkingMetricsValue::new(), master.clone(), None)
-        })
+        Self::generator_with_master(MasterMap::new())
+    }
+
+    fn seeded_generator(
+        _expected_node_count: usize,
+        _num_bootstrap: usize,
+        network_id: usize,
+        _da_committee_size: usize,
+        _is_da: bool,
+        seed: u64,
+        clock: Arc<VirtualClock>,
+    ) -> Box<dyn Fn(u64) -> Self + 'static> {
+        // Networks with different ids shouldn't delay alike
+        Self::generator_with_master(MasterMap::new_seeded(seed ^ network_id as u64, clock))
     }
 
     fn in_flight_message_count(&self) -> Option<usize> {
@@ -302,18 +364,25 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> ConnectedNetwork<M, K> for Memory
             .serialize(&message)
             .context(FailedToSerializeSnafu)?;
         trace!("Message bincoded, sending");
-        for node in &self.inner.master_map.map {
-            // TODO delay/drop etc here
-            let (key, node) = node.pair();
-            if !recipients.contains(key) {
+        // Send in the order of the recipients, so that a seeded simulation sends alike every time
+        for key in &recipients {
+            let Some(node) = self
+                .inner
+                .master_map
+                .map
+                .get(key)
+                .map(|node| node.value().clone())
+            else {
                 continue;
-            }
+            };
             trace!(?key, "Sending message to node");
             if let Some(r) = &self.inner.reliability_config {
                 let config = r.read().await;
                 {
                     let node2 = node.clone();
-                    let fut = config.chaos_send_msg(
+                    let fut = config.chaos_send_link_msg(
+                        &self.inner.pub_key.to_bytes(),
+                        &key.to_bytes(),
                         vec.clone(),
                         Arc::new(move |msg: Vec<u8>| {
                             let node3 = (node2).clone();
@@ -326,6 +395,12 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> ConnectedNetwork<M, K> for Memory
                     );
                     async_spawn(fut);
                 }
+            } else if let Some((clock, latency)) = self.simulated_latency(key, &vec) {
+                let vec = vec.clone();
+                async_spawn(async move {
+                    clock.sleep(latency).await;
+                    let _res = node.broadcast_input(vec).await;
+                });
             } else {
                 let res = node.broadcast_input(vec.clone()).await;
                 match res {
@@ -356,12 +431,14 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> ConnectedNetwork<M, K> for Memory
             if let Some(r) = &self.inner.reliability_config {
                 let config = r.read().await;
                 {
-                    let fut = config.chaos_send_msg(
+                    let fut = config.chaos_send_link_msg(
+                        &self.inner.pub_key.to_bytes(),
+                        &recipient.to_bytes(),
                         vec.clone(),
                         Arc::new(move |msg: Vec<u8>| {
                             let node2 = node.clone();
                             boxed_sync(async move {
-                                let _res = node2.broadcast_input(msg).await;
+                                let _res = node2.direct_input(msg).await;
                                 // NOTE we're dropping metrics here but this is only for testing
                                 // purposes. I think that should be okay
                             })
@@ -370,6 +447,12 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> ConnectedNetwork<M, K> for Memory
                     async_spawn(fut);
                 }
                 Ok(())
+            } else if let Some((clock, latency)) = self.simulated_latency(&recipient, &vec) {
+                async_spawn(async move {
+                    clock.sleep(latency).await;
+                    let _res = node.direct_input(vec).await;
+                });
+                Ok(())
             } else {
                 let res = node.direct_input(vec).await;
                 match res {
@@ -495,6 +578,30 @@ where
         Box::new(move |node_id| Self(generator(node_id).into(), PhantomData))
     }
 
+    fn seeded_generator(
+        expected_node_count: usize,
+        num_bootstrap: usize,
+        network_id: usize,
+        da_committee_size: usize,
+        is_da: bool,
+        seed: u64,
+        clock: Arc<VirtualClock>,
+    ) -> Box<dyn Fn(u64) -> Self + 'static> {
+        let generator = <MemoryNetwork<
+            Message<TYPES, I>,
+            TYPES::SignatureKey,
+        > as TestableNetworkingImplementation<_, _>>::seeded_generator(
+            expected_node_count,
+            num_bootstrap,
+            network_id,
+            da_committee_size,
+            is_da,
+            seed,
+            clock,
+        );
+        Box::new(move |node_id| Self(generator(node_id).into(), PhantomData))
+    }
+
     fn in_flight_message_count(&self) -> Option<usize> {
         Some(self.0.inner.in_flight_message_count.load(Ordering::Relaxed))
     }
@@ -11,6 +11,7 @@ use async_compatibility_layer::{
 };
 use async_lock::RwLock;
 use async_trait::async_trait;
+use futures::future::join_all;
 use hotshot_task::{boxed_sync, BoxSyncFuture};
 use hotshot_types::{
     message::{Message, MessagePurpose},
@@ -25,7 +26,12 @@ use hotshot_types::{
         signature_key::SignatureKey,
     },
 };
-use hotshot_web_server::{self, config};
+use hotshot_web_server::{
+    self,
+    auth::{AuthConfig, LeaderSchedule, MembershipLeaders, Nonces, SignedSubmission},
+    config::{self, MessageClass, RelayUrls},
+    PostedTransaction,
+};
 use rand::random;
 use serde::{Deserialize, Serialize};
 
@@ -34,10 +40,10 @@ use std::{
     collections::{hash_map::Entry, BTreeSet, HashMap},
     marker::PhantomData,
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicUsize, Ordering},
         Arc,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 use surf_disco::error::ClientError;
 use tracing::{debug, error, info};
@@ -65,6 +71,10 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, MEMBERSHIP: Membership<TYPES
 }
 
 /// The web server network state
+///
+/// The web server has no routes for catchup, so sending a catchup message fails with
+/// [`WebServerNetworkError::UnsupportedPurpose`]. Nodes that need catchup must also be connected
+/// by a network that delivers direct messages.
 #[derive(Clone, Debug)]
 pub struct WebServerNetwork<M: NetworkMsg, KEY: SignatureKey, TYPES: NodeType> {
     /// The inner, core state of the web server network
@@ -73,21 +83,186 @@ pub struct WebServerNetwork<M: NetworkMsg, KEY: SignatureKey, TYPES: NodeType> {
     server_shutdown_signal: Option<Arc<OneShotSender<()>>>,
 }
 
-impl<M: NetworkMsg, KEY: SignatureKey, TYPES: NodeType> WebServerNetwork<M, KEY, TYPES> {
-    /// Post a message to the web server and return the result
+impl<M: NetworkMsg + ViewMessage<TYPES>, KEY: SignatureKey, TYPES: NodeType>
+    WebServerNetwork<M, KEY, TYPES>
+{
+    /// Post a message to every web server of its class. Succeeds if any of them accepted it.
     async fn post_message_to_web_server(&self, message: SendMsg<M>) -> Result<(), NetworkError> {
-        let result: Result<(), ClientError> = self
+        if !self.inner.registered.load(Ordering::Relaxed) {
+            self.register_with_web_server().await;
+        }
+        let serialization_error = |_e: bincode::Error| NetworkError::WebServer {
+            source: WebServerNetworkError::ClientError,
+        };
+        let mut payload =
+            bincode::serialize(&message.get_message()).map_err(serialization_error)?;
+        // Transactions are posted with their commitment, which they can be looked up by
+        if let Some(commitment) = message
+            .message
+            .as_ref()
+            .and_then(|message| message.transaction_commitment())
+        {
+            payload = bincode::serialize(&PostedTransaction {
+                commitment: commitment.to_string(),
+                transaction: payload,
+            })
+            .map_err(serialization_error)?;
+        }
+        let submission = self
             .inner
-            .client
-            .post(&message.get_endpoint())
-            .body_binary(&message.get_message())
-            .unwrap()
-            .send()
-            .await;
+            .sign(&message.route, message.view_number, payload);
+        let endpoint = message.get_endpoint();
+        let results: Vec<Result<(), ClientError>> = join_all(
+            self.inner
+                .relay(message.class)
+                .clients
+                .iter()
+                .map(|client| {
+                    client
+                        .post(&endpoint)
+                        .body_binary(&submission)
+                        .unwrap()
+                        .send()
+                }),
+        )
+        .await;
         // error!("POST message error for endpoint {} is {:?}", &message.get_endpoint(), result.clone());
-        result.map_err(|_e| NetworkError::WebServer {
-            source: WebServerNetworkError::ClientError,
-        })
+        if results.iter().any(Result::is_ok) {
+            Ok(())
+        } else {
+            Err(NetworkError::WebServer {
+                source: WebServerNetworkError::ClientError,
+            })
+        }
+    }
+
+    /// Add our key to the stake table of every web server we post to, which only accept
+    /// submissions from keys in them. Succeeds for web servers that already have our key. Web
+    /// servers that can't be reached are tried again on the next post.
+    async fn register_with_web_server(&self) {
+        let route = config::post_staketable_route();
+        // Each web server is sent a submission of its own, since several classes of messages
+        // can share a web server, which would reject the same submission twice as a replay
+        let results: Vec<Result<(), ClientError>> = join_all(
+            self.inner
+                .relays
+                .values()
+                .flat_map(|relay| relay.clients.iter())
+                .map(|client| {
+                    let submission =
+                        self.inner
+                            .sign("poststaketable", None, self.inner.own_key.to_bytes().0);
+                    client.post(&route).body_binary(&submission).unwrap().send()
+                }),
+        )
+        .await;
+        let mut registered = true;
+        for e in results.into_iter().filter_map(Result::err) {
+            error!("Failed to add our key to the web server stake table: {e:?}");
+            registered = false;
+        }
+        self.inner.registered.store(registered, Ordering::Relaxed);
+    }
+}
+
+/// The web servers for a class of messages. Messages are posted to all of them, and read from
+/// one of them at a time, moving on to the next when it fails. Reads keep [`Cursors`] for each
+/// web server, since they don't hold the same messages at the same indices.
+#[derive(Debug)]
+struct Relay {
+    /// Clients of the web servers, in the order to fail over in
+    clients: Vec<surf_disco::Client<ClientError>>,
+    /// Index in `clients` of the web server to read from
+    active: AtomicUsize,
+}
+
+impl Relay {
+    /// Connect to the web servers at `urls`, of which there must be at least one
+    /// # Panics
+    /// if a url is malformed
+    fn new(urls: &[String]) -> Self {
+        assert!(!urls.is_empty(), "Relay without web servers");
+        let clients = urls
+            .iter()
+            .map(|url| {
+                let base_url = url.parse();
+                if base_url.is_err() {
+                    error!("Web server url {:?} is malformed", url);
+                }
+                // TODO ED Wait for healthcheck
+                surf_disco::Client::<ClientError>::new(base_url.unwrap())
+            })
+            .collect();
+        Self {
+            clients,
+            active: AtomicUsize::new(0),
+        }
+    }
+
+    /// Index in `clients` of the web server to read from
+    fn active_index(&self) -> usize {
+        self.active.load(Ordering::Relaxed) % self.clients.len()
+    }
+
+    /// Read from the web server after `server`, after a read from `server` failed. Does nothing
+    /// if another read already failed over from `server`.
+    ///
+    /// The next web server was posted the same messages, but may have missed some, e.g. while it
+    /// was down, or ordered concurrent posts differently, so reading from it continues from its
+    /// own cursor. It may return messages already read from `server`, which consensus ignores
+    /// as duplicates.
+    fn fail_over(&self, server: usize) {
+        let next = (server + 1) % self.clients.len();
+        let _ = self
+            .active
+            .compare_exchange(server, next, Ordering::Relaxed, Ordering::Relaxed);
+    }
+}
+
+/// Where reading a sequence of messages, e.g. the votes for a view or the transactions, is up to
+/// on each web server of a relay
+#[derive(Clone, Debug, Default)]
+struct Cursors(HashMap<usize, u64>);
+
+impl Cursors {
+    /// Index of the next message to read from web server `server`
+    fn get(&self, server: usize) -> u64 {
+        self.0.get(&server).copied().unwrap_or_default()
+    }
+
+    /// The cursor of web server `server`
+    fn at(&mut self, server: usize) -> &mut u64 {
+        self.0.entry(server).or_default()
+    }
+}
+
+/// The class of messages with `purpose`, for a DA server if `is_da`, or `None` if messages with
+/// `purpose` never go to the web server
+fn message_class(purpose: MessagePurpose, is_da: bool) -> Option<MessageClass> {
+    Some(match purpose {
+        MessagePurpose::Proposal | MessagePurpose::CurrentProposal | MessagePurpose::Vote
+            if is_da =>
+        {
+            MessageClass::Da
+        }
+        MessagePurpose::Proposal | MessagePurpose::CurrentProposal => MessageClass::Proposal,
+        MessagePurpose::Vote => MessageClass::Vote,
+        MessagePurpose::DAC => MessageClass::Da,
+        MessagePurpose::ViewSyncProposal | MessagePurpose::ViewSyncVote => MessageClass::ViewSync,
+        MessagePurpose::VidDisperse | MessagePurpose::VidVote | MessagePurpose::VidCert => {
+            MessageClass::Vid
+        }
+        MessagePurpose::Data => MessageClass::Transaction,
+        MessagePurpose::Internal | MessagePurpose::Catchup => return None,
+    })
+}
+
+/// The private key to sign submissions to the web server with
+struct SigningKey<KEY: SignatureKey>(KEY::PrivateKey);
+
+impl<KEY: SignatureKey> std::fmt::Debug for SigningKey<KEY> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SigningKey(..)")
     }
 }
 
@@ -97,7 +272,13 @@ struct Inner<M: NetworkMsg, KEY: SignatureKey, TYPES: NodeType> {
     /// Phantom data for generic types
     phantom: PhantomData<(KEY, TYPES::ElectionConfigType)>,
     /// Our own key
-    _own_key: TYPES::SignatureKey,
+    own_key: TYPES::SignatureKey,
+    /// The private key of `own_key`
+    private_key: SigningKey<TYPES::SignatureKey>,
+    /// Our key is in the stake table of the web server
+    registered: AtomicBool,
+    /// Nonces of our submissions to the web server
+    nonces: Nonces,
     /// Queue for broadcasted messages
     broadcast_poll_queue: Arc<RwLock<Vec<RecvMsg<M>>>>,
     /// Queue for direct messages
@@ -106,15 +287,15 @@ struct Inner<M: NetworkMsg, KEY: SignatureKey, TYPES: NodeType> {
     running: AtomicBool,
     /// The web server connection is ready
     connected: AtomicBool,
-    /// The connectioni to the web server
-    client: surf_disco::Client<ClientError>,
+    /// The web servers for each class of messages
+    relays: HashMap<MessageClass, Relay>,
     /// The duration to wait between poll attempts
     wait_between_polls: Duration,
     /// Whether we are connecting to a DA server
     is_da: bool,
 
-    /// The last tx_index we saw from the web server
-    tx_index: Arc<RwLock<u64>>,
+    /// The last tx_index we saw from each transaction web server
+    tx_indices: Arc<RwLock<Cursors>>,
 
     /// Task map for quorum proposals.
     proposal_task_map:
@@ -147,6 +328,24 @@ struct Inner<M: NetworkMsg, KEY: SignatureKey, TYPES: NodeType> {
 
 impl<M: NetworkMsg, KEY: SignatureKey, TYPES: NodeType> Inner<M, KEY, TYPES> {
     #![allow(clippy::too_many_lines)]
+    /// Sign a submission of `payload` to the web server route named `route` for `view_number`
+    /// with our key
+    fn sign(&self, route: &str, view_number: Option<u64>, payload: Vec<u8>) -> SignedSubmission {
+        SignedSubmission::new(
+            &self.own_key,
+            &self.private_key.0,
+            route,
+            view_number,
+            self.nonces.next(),
+            payload,
+        )
+    }
+
+    /// The web servers for `class`
+    fn relay(&self, class: MessageClass) -> &Relay {
+        &self.relays[&class]
+    }
+
     /// Pull a web server.
     async fn poll_web_server(
         &self,
@@ -154,21 +353,33 @@ impl<M: NetworkMsg, KEY: SignatureKey, TYPES: NodeType> Inner<M, KEY, TYPES> {
         message_purpose: MessagePurpose,
         view_number: u64,
     ) -> Result<(), NetworkError> {
-        let mut vote_index = 0;
-        let mut tx_index = 0;
+        let mut vote_indices = Cursors::default();
+        let mut tx_indices = Cursors::default();
+        let Some(class) = message_class(message_purpose, self.is_da) else {
+            return Err(NetworkError::WebServer {
+                source: WebServerNetworkError::UnsupportedPurpose,
+            });
+        };
+        let relay = self.relay(class);
 
         if message_purpose == MessagePurpose::Data {
-            tx_index = *self.tx_index.read().await;
-            debug!("Previous tx index was {}", tx_index);
+            tx_indices = self.tx_indices.read().await.clone();
+            debug!("Previous tx indices were {:?}", tx_indices);
         };
 
         while self.running.load(Ordering::Relaxed) {
+            let server = relay.active_index();
+            let vote_index = vote_indices.get(server);
             let endpoint = match message_purpose {
                 MessagePurpose::Proposal => config::get_proposal_route(view_number),
                 MessagePurpose::CurrentProposal => config::get_recent_proposal_route(),
                 MessagePurpose::Vote => config::get_vote_route(view_number, vote_index),
-                MessagePurpose::Data => config::get_transactions_route(tx_index),
-                MessagePurpose::Internal => unimplemented!(),
+                MessagePurpose::Data => config::get_transactions_route(tx_indices.get(server)),
+                MessagePurpose::Internal | MessagePurpose::Catchup => {
+                    return Err(NetworkError::WebServer {
+                        source: WebServerNetworkError::UnsupportedPurpose,
+                    })
+                }
                 MessagePurpose::ViewSyncProposal => {
                     config::get_view_sync_proposal_route(view_number, vote_index)
                 }
@@ -180,31 +391,37 @@ impl<M: NetworkMsg, KEY: SignatureKey, TYPES: NodeType> Inner<M, KEY, TYPES> {
                 MessagePurpose::VidVote => config::get_vid_vote_route(view_number, vote_index), // like `Vote`
                 MessagePurpose::VidCert => config::get_vid_certificate_route(view_number), // like `DAC`
             };
+            // Have the web server hold the request until there is something to return, except
+            // for the most recent proposal, which is there as soon as there is any proposal
+            let endpoint = if message_purpose == MessagePurpose::CurrentProposal {
+                endpoint
+            } else {
+                config::long_poll_route(&endpoint, config::LONG_POLL_WAIT)
+            };
+            let poll_started = Instant::now();
 
             if message_purpose == MessagePurpose::Data {
-                let possible_message = self.get_txs_from_web_server(endpoint).await;
+                let possible_message = self.get_txs_from_web_server(relay, server, endpoint).await;
                 match possible_message {
                     Ok(Some((index, deserialized_messages))) => {
                         let mut broadcast_poll_queue = self.broadcast_poll_queue.write().await;
-                        if index > tx_index + 1 {
-                            debug!("missed txns from {} to {}", tx_index + 1, index - 1);
-                            tx_index = index - 1;
+                        let tx_index = tx_indices.at(server);
+                        if index > *tx_index + 1 {
+                            debug!("missed txns from {} to {}", *tx_index + 1, index - 1);
+                            *tx_index = index - 1;
                         }
                         for tx in &deserialized_messages {
-                            tx_index += 1;
+                            *tx_index += 1;
                             broadcast_poll_queue.push(tx.clone());
                         }
                         debug!("tx index is {}", tx_index);
                     }
-                    Ok(None) => {
-                        async_sleep(self.wait_between_polls).await;
-                    }
-                    Err(_e) => {
-                        async_sleep(self.wait_between_polls).await;
-                    }
+                    Ok(None) | Err(_) => self.wait_after_poll(poll_started).await,
                 }
             } else {
-                let possible_message = self.get_message_from_web_server(endpoint).await;
+                let possible_message = self
+                    .get_message_from_web_server(relay, server, endpoint)
+                    .await;
 
                 match possible_message {
                     Ok(Some(deserialized_messages)) => {
@@ -247,7 +464,7 @@ impl<M: NetworkMsg, KEY: SignatureKey, TYPES: NodeType> Inner<M, KEY, TYPES> {
                                 // );
                                 let mut direct_poll_queue = self.direct_poll_queue.write().await;
                                 for vote in &deserialized_messages {
-                                    vote_index += 1;
+                                    *vote_indices.at(server) += 1;
                                     direct_poll_queue.push(vote.clone());
                                 }
                             }
@@ -255,7 +472,7 @@ impl<M: NetworkMsg, KEY: SignatureKey, TYPES: NodeType> Inner<M, KEY, TYPES> {
                                 // TODO copy-pasted from `MessagePurpose::Vote` https://github.com/EspressoSystems/HotShot/issues/1690
                                 let mut direct_poll_queue = self.direct_poll_queue.write().await;
                                 for vote in &deserialized_messages {
-                                    vote_index += 1;
+                                    *vote_indices.at(server) += 1;
                                     direct_poll_queue.push(vote.clone());
                                 }
                             }
@@ -318,7 +535,7 @@ impl<M: NetworkMsg, KEY: SignatureKey, TYPES: NodeType> Inner<M, KEY, TYPES> {
                                 // );
                                 let mut direct_poll_queue = self.direct_poll_queue.write().await;
                                 for vote in &deserialized_messages {
-                                    vote_index += 1;
+                                    *vote_indices.at(server) += 1;
                                     direct_poll_queue.push(vote.clone());
                                 }
                             }
@@ -334,7 +551,7 @@ impl<M: NetworkMsg, KEY: SignatureKey, TYPES: NodeType> Inner<M, KEY, TYPES> {
                                 // TODO ED Special case this for view sync
                                 // TODO ED Need to add vote indexing to web server for view sync certs
                                 for cert in &deserialized_messages {
-                                    vote_index += 1;
+                                    *vote_indices.at(server) += 1;
                                     broadcast_poll_queue.push(cert.clone());
                                 }
                             }
@@ -342,15 +559,13 @@ impl<M: NetworkMsg, KEY: SignatureKey, TYPES: NodeType> Inner<M, KEY, TYPES> {
                             MessagePurpose::Internal => {
                                 error!("Received internal message in web server network");
                             }
+
+                            MessagePurpose::Catchup => {
+                                error!("Received catchup message in web server network");
+                            }
                         }
                     }
-                    Ok(None) => {
-                        async_sleep(self.wait_between_polls).await;
-                    }
-                    Err(_e) => {
-                        // error!("error is {:?}", _e);
-                        async_sleep(self.wait_between_polls).await;
-                    }
+                    Ok(None) | Err(_) => self.wait_after_poll(poll_started).await,
                 }
             }
             let maybe_event = receiver.try_recv();
@@ -373,8 +588,8 @@ impl<M: NetworkMsg, KEY: SignatureKey, TYPES: NodeType> Inner<M, KEY, TYPES> {
                         ConsensusIntentEvent::CancelPollForTransactions(event_view) => {
                             // Write the most recent tx index so we can pick up where we left off later
 
-                            let mut lock = self.tx_index.write().await;
-                            *lock = tx_index;
+                            let mut lock = self.tx_indices.write().await;
+                            *lock = tx_indices.clone();
 
                             if view_number == event_view {
                                 debug!("Shutting down polling task for view {}", event_view);
@@ -396,17 +611,31 @@ impl<M: NetworkMsg, KEY: SignatureKey, TYPES: NodeType> Inner<M, KEY, TYPES> {
         Err(NetworkError::ShutDown)
     }
 
-    /// Fetches transactions from web server
+    /// Wait before polling again after a poll started at `poll_started` found nothing. There is
+    /// no need to if the web server already waited, but a request that failed right away, e.g.
+    /// because the web server is down, shouldn't be retried right away either.
+    async fn wait_after_poll(&self, poll_started: Instant) {
+        if poll_started.elapsed() < config::LONG_POLL_WAIT {
+            async_sleep(self.wait_between_polls).await;
+        }
+    }
+
+    /// Fetches transactions from web server `server` of `relay`
     async fn get_txs_from_web_server(
         &self,
+        relay: &Relay,
+        server: usize,
         endpoint: String,
     ) -> Result<Option<(u64, Vec<RecvMsg<M>>)>, NetworkError> {
         let result: Result<Option<(u64, Vec<Vec<u8>>)>, ClientError> =
-            self.client.get(&endpoint).send().await;
+            relay.clients[server].get(&endpoint).send().await;
         match result {
-            Err(_error) => Err(NetworkError::WebServer {
-                source: WebServerNetworkError::ClientError,
-            }),
+            Err(_error) => {
+                relay.fail_over(server);
+                Err(NetworkError::WebServer {
+                    source: WebServerNetworkError::ClientError,
+                })
+            }
             Ok(Some((index, messages))) => {
                 let mut deserialized_messages = Vec::new();
                 for message in &messages {
@@ -422,18 +651,23 @@ impl<M: NetworkMsg, KEY: SignatureKey, TYPES: NodeType> Inner<M, KEY, TYPES> {
         }
     }
 
-    /// Sends a GET request to the webserver for some specified endpoint
+    /// Sends a GET request to web server `server` of `relay` for some specified endpoint
     /// Returns a vec of deserialized, received messages or an error
     async fn get_message_from_web_server(
         &self,
+        relay: &Relay,
+        server: usize,
         endpoint: String,
     ) -> Result<Option<Vec<RecvMsg<M>>>, NetworkError> {
         let result: Result<Option<Vec<Vec<u8>>>, ClientError> =
-            self.client.get(&endpoint).send().await;
+            relay.clients[server].get(&endpoint).send().await;
         match result {
-            Err(_error) => Err(NetworkError::WebServer {
-                source: WebServerNetworkError::ClientError,
-            }),
+            Err(_error) => {
+                relay.fail_over(server);
+                Err(NetworkError::WebServer {
+                    source: WebServerNetworkError::ClientError,
+                })
+            }
             Ok(Some(messages)) => {
                 let mut deserialized_messages = Vec::new();
                 for message in &messages {
@@ -458,6 +692,12 @@ pub struct SendMsg<M: NetworkMsg> {
     message: Option<M>,
     /// The endpoint to send the message to
     endpoint: String,
+    /// The name of the web server route of `endpoint`, which the submission is signed for
+    route: String,
+    /// The view the submission is signed for, if the route takes one
+    view_number: Option<u64>,
+    /// The class of the message, which decides the web servers to send it to
+    class: MessageClass,
 }
 
 /// A message being received from the web server
@@ -507,7 +747,8 @@ impl<
         TYPES: NodeType + 'static,
     > WebServerNetwork<M, K, TYPES>
 {
-    /// Creates a new instance of the `WebServerNetwork`
+    /// Creates a new instance of the `WebServerNetwork`, signing its submissions with
+    /// `private_key`, the private key of `key`
     /// # Panics
     /// if the web server url is malformed
     pub fn create(
@@ -515,18 +756,42 @@ impl<
         port: u16,
         wait_between_polls: Duration,
         key: TYPES::SignatureKey,
+        private_key: <TYPES::SignatureKey as SignatureKey>::PrivateKey,
         is_da_server: bool,
     ) -> Self {
-        let base_url_string = format!("http://{host}:{port}");
-        info!("Connecting to web server at {base_url_string:?} is da: {is_da_server}");
-
-        let base_url = base_url_string.parse();
-        if base_url.is_err() {
-            error!("Web server url {:?} is malformed", base_url_string);
-        }
+        Self::create_with_relays(
+            &[format!("http://{host}:{port}")],
+            &RelayUrls::default(),
+            wait_between_polls,
+            key,
+            private_key,
+            is_da_server,
+        )
+    }
 
-        // TODO ED Wait for healthcheck
-        let client = surf_disco::Client::<ClientError>::new(base_url.unwrap());
+    /// Creates a new instance of the `WebServerNetwork` that sends each class of messages to
+    /// its web servers in `relays`, and classes without any to the web servers at `urls`
+    /// # Panics
+    /// if `urls` is empty, or a web server url is malformed
+    pub fn create_with_relays(
+        urls: &[String],
+        relays: &RelayUrls,
+        wait_between_polls: Duration,
+        key: TYPES::SignatureKey,
+        private_key: <TYPES::SignatureKey as SignatureKey>::PrivateKey,
+        is_da_server: bool,
+    ) -> Self {
+        let relays = MessageClass::ALL
+            .into_iter()
+            .map(|class| {
+                let class_urls = match relays.get(class) {
+                    [] => urls,
+                    class_urls => class_urls,
+                };
+                info!("Connecting to {class:?} web servers at {class_urls:?} da: {is_da_server}");
+                (class, Relay::new(class_urls))
+            })
+            .collect();
 
         let inner = Arc::new(Inner {
             phantom: PhantomData,
@@ -534,11 +799,14 @@ impl<
             direct_poll_queue: Arc::default(),
             running: AtomicBool::new(true),
             connected: AtomicBool::new(false),
-            client,
+            relays,
             wait_between_polls,
-            _own_key: key,
+            own_key: key,
+            private_key: SigningKey(private_key),
+            registered: AtomicBool::new(false),
+            nonces: Nonces::default(),
             is_da: is_da_server,
-            tx_index: Arc::default(),
+            tx_indices: Arc::default(),
             proposal_task_map: Arc::default(),
             vote_task_map: Arc::default(),
             vid_vote_task_map: Arc::default(),
@@ -560,30 +828,67 @@ impl<
 
     /// Parses a message to find the appropriate endpoint
     /// Returns a `SendMsg` containing the endpoint
-    fn parse_post_message(message: M) -> Result<SendMsg<M>, WebServerNetworkError> {
+    ///
+    /// # Errors
+    /// Returns [`WebServerNetworkError::UnsupportedPurpose`] for internal and catchup messages,
+    /// which the web server has no routes for
+    fn parse_post_message(&self, message: M) -> Result<SendMsg<M>, WebServerNetworkError> {
         let view_number: TYPES::Time = message.get_view_number();
 
-        let endpoint = match &message.purpose() {
-            MessagePurpose::Proposal => config::post_proposal_route(*view_number),
-            MessagePurpose::Vote => config::post_vote_route(*view_number),
-            MessagePurpose::Data => config::post_transactions_route(),
-            MessagePurpose::Internal | MessagePurpose::CurrentProposal => {
-                return Err(WebServerNetworkError::EndpointError)
-            }
-            MessagePurpose::ViewSyncProposal => {
-                // error!("Posting view sync proposal route is: {}", config::post_view_sync_proposal_route(*view_number));
-                config::post_view_sync_proposal_route(*view_number)
+        let view = Some(*view_number);
+        let (endpoint, route, view_number) = match &message.purpose() {
+            MessagePurpose::Proposal => (
+                config::post_proposal_route(*view_number),
+                "postproposal",
+                view,
+            ),
+            MessagePurpose::Vote => (config::post_vote_route(*view_number), "postvote", view),
+            MessagePurpose::Data => (config::post_transactions_route(), "posttransaction", None),
+            // Nodes only ever fetch the most recent proposal
+            MessagePurpose::CurrentProposal => return Err(WebServerNetworkError::EndpointError),
+            MessagePurpose::Internal | MessagePurpose::Catchup => {
+                return Err(WebServerNetworkError::UnsupportedPurpose)
             }
-            MessagePurpose::ViewSyncVote => config::post_view_sync_vote_route(*view_number),
-            MessagePurpose::DAC => config::post_da_certificate_route(*view_number),
-            MessagePurpose::VidVote => config::post_vid_vote_route(*view_number),
-            MessagePurpose::VidDisperse => config::post_vid_disperse_route(*view_number),
-            MessagePurpose::VidCert => config::post_vid_certificate_route(*view_number),
+            MessagePurpose::ViewSyncProposal => (
+                config::post_view_sync_proposal_route(*view_number),
+                "postviewsyncproposal",
+                view,
+            ),
+            MessagePurpose::ViewSyncVote => (
+                config::post_view_sync_vote_route(*view_number),
+                "postviewsyncvote",
+                view,
+            ),
+            MessagePurpose::DAC => (
+                config::post_da_certificate_route(*view_number),
+                "postcertificate",
+                view,
+            ),
+            MessagePurpose::VidVote => (
+                config::post_vid_vote_route(*view_number),
+                "postvidvote",
+                view,
+            ),
+            MessagePurpose::VidDisperse => (
+                config::post_vid_disperse_route(*view_number),
+                "postviddisperse",
+                view,
+            ),
+            MessagePurpose::VidCert => (
+                config::post_vid_certificate_route(*view_number),
+                "postvidcertificate",
+                view,
+            ),
         };
 
+        let class = message_class(message.purpose(), self.inner.is_da)
+            .ok_or(WebServerNetworkError::UnsupportedPurpose)?;
         let network_msg: SendMsg<M> = SendMsg {
             message: Some(message),
             endpoint,
+            route: route.to_string(),
+            view_number,
+            class,
         };
         Ok(network_msg)
     }
@@ -724,7 +1029,7 @@ impl<
         message: M,
         _recipients: BTreeSet<K>,
     ) -> Result<(), NetworkError> {
-        let network_msg = Self::parse_post_message(message);
+        let network_msg = self.parse_post_message(message);
         match network_msg {
             Ok(network_msg) => self.post_message_to_web_server(network_msg).await,
             Err(network_msg) => Err(NetworkError::WebServer {
@@ -736,7 +1041,7 @@ impl<
     /// Sends a direct message to a specific node
     /// blocking
     async fn direct_message(&self, message: M, _recipient: K) -> Result<(), NetworkError> {
-        let network_msg = Self::parse_post_message(message);
+        let network_msg = self.parse_post_message(message);
         match network_msg {
             Ok(network_msg) => {
                 // error!("network msg is {:?}", network_msg.clone());
@@ -1205,49 +1510,63 @@ impl<
 }
 
 impl<TYPES: NodeType, I: NodeImplementation<TYPES>>
-    TestableNetworkingImplementation<TYPES, Message<TYPES, I>>
-    for WebServerNetwork<Message<TYPES, I>, TYPES::SignatureKey, TYPES>
+    WebServerNetwork<Message<TYPES, I>, TYPES::SignatureKey, TYPES>
 {
-    fn generator(
+    /// Launch a web server for a test, only accepting submissions from the nodes, and
+    /// proposals from the leaders in `leaders` if any, and generate the networks of the nodes
+    fn test_generator(
         expected_node_count: usize,
-        _num_bootstrap: usize,
-        _network_id: usize,
-        _da_committee_size: usize,
         is_da: bool,
+        leaders: Option<Arc<dyn LeaderSchedule<TYPES::SignatureKey>>>,
     ) -> Box<dyn Fn(u64) -> Self + 'static> {
         let (server_shutdown_sender, server_shutdown) = oneshot();
         let sender = Arc::new(server_shutdown_sender);
         // TODO ED Restrict this to be an open port using portpicker
         let port = random::<u16>();
         info!("Launching web server on port {port}");
-        // Start web server
+        let known_nodes = (0..expected_node_count as u64)
+            .map(|id| TYPES::SignatureKey::generated_from_seed_indexed([0u8; 32], id))
+            .collect::<Vec<_>>();
+
+        let mut auth = AuthConfig::new(known_nodes.iter().map(|(key, _)| key.clone()).collect());
+        auth.leaders = leaders;
         async_spawn(hotshot_web_server::run_web_server::<TYPES::SignatureKey>(
             Some(server_shutdown),
             port,
+            auth,
         ));
 
-        let known_nodes = (0..expected_node_count as u64)
-            .map(|id| {
-                TYPES::SignatureKey::from_private(
-                    &TYPES::SignatureKey::generated_from_seed_indexed([0u8; 32], id).1,
-                )
-            })
-            .collect::<Vec<_>>();
-
         // Start each node's web server client
         Box::new(move |id| {
             let sender = Arc::clone(&sender);
+            let (key, private_key) = known_nodes[id as usize].clone();
             let mut network = WebServerNetwork::create(
                 "0.0.0.0",
                 port,
                 Duration::from_millis(100),
-                known_nodes[id as usize].clone(),
+                key,
+                private_key,
                 is_da,
             );
             network.server_shutdown_signal = Some(sender);
             network
         })
     }
+}
+
+impl<TYPES: NodeType, I: NodeImplementation<TYPES>>
+    TestableNetworkingImplementation<TYPES, Message<TYPES, I>>
+    for WebServerNetwork<Message<TYPES, I>, TYPES::SignatureKey, TYPES>
+{
+    fn generator(
+        expected_node_count: usize,
+        _num_bootstrap: usize,
+        _network_id: usize,
+        _da_committee_size: usize,
+        is_da: bool,
+    ) -> Box<dyn Fn(u64) -> Self + 'static> {
+        Self::test_generator(expected_node_count, is_da, None)
+    }
 
     fn in_flight_message_count(&self) -> Option<usize> {
         None
@@ -1260,21 +1579,29 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, MEMBERSHIP: Membership<TYPES
 {
     fn generator(
         expected_node_count: usize,
-        num_bootstrap: usize,
-        network_id: usize,
-        da_committee_size: usize,
+        _num_bootstrap: usize,
+        _network_id: usize,
+        _da_committee_size: usize,
         is_da: bool,
     ) -> Box<dyn Fn(u64) -> Self + 'static> {
-        let generator = <WebServerNetwork<
-            Message<TYPES, I>,
-            TYPES::SignatureKey,
-            TYPES,
-        > as TestableNetworkingImplementation<_, _>>::generator(
+        // The web server checks proposals against the leaders of the same membership as the nodes
+        let stake_table = (0..expected_node_count as u64)
+            .map(|id| {
+                TYPES::SignatureKey::generated_from_seed_indexed([0u8; 32], id)
+                    .0
+                    .get_stake_table_entry(1u64)
+            })
+            .collect();
+        let membership = MEMBERSHIP::create_election(
+            stake_table,
+            MEMBERSHIP::default_election_config(expected_node_count as u64),
+        );
+        let generator = WebServerNetwork::test_generator(
             expected_node_count,
-            num_bootstrap,
-            network_id,
-            da_committee_size,
             is_da,
+            Some(Arc::new(MembershipLeaders::<TYPES, MEMBERSHIP>::new(
+                membership,
+            ))),
         );
         Box::new(move |node_id| Self(generator(node_id).into(), PhantomData))
     }
@@ -1299,3 +1626,36 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, MEMBERSHIP: Membership<TYPES
         Box::new(move |network| WebCommChannel::new(network))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Each web server of a relay is read from its own cursor, across fail overs
+    #[test]
+    fn test_fail_over_keeps_cursor_per_web_server() {
+        let relay = Relay::new(&[
+            "http://localhost:1".to_string(),
+            "http://localhost:2".to_string(),
+        ]);
+        let mut vote_indices = Cursors::default();
+
+        let server = relay.active_index();
+        assert_eq!(server, 0);
+        *vote_indices.at(server) += 3;
+        relay.fail_over(server);
+        let server = relay.active_index();
+        assert_eq!(server, 1);
+        assert_eq!(vote_indices.get(server), 0);
+        *vote_indices.at(server) += 1;
+
+        // A read that started on the first web server fails after another already failed over
+        relay.fail_over(0);
+        assert_eq!(relay.active_index(), 1);
+
+        relay.fail_over(1);
+        assert_eq!(relay.active_index(), 0);
+        assert_eq!(vote_indices.get(0), 3);
+        assert_eq!(vote_indices.get(1), 1);
+    }
+}
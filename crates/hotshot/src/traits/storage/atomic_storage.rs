@@ -1,82 +1,79 @@
 //! On-disk storage of node state. Based on [`atomic_store`](https://github.com/EspressoSystems/atomicstore).
 
-mod dual_key_value_store;
-mod hash_map_store;
+mod rolling_store;
+mod view_store;
 
-use self::{dual_key_value_store::DualKeyValueStore, hash_map_store::HashMapStore};
-use crate::{data::Leaf, traits::StateContents, QuorumCertificate};
-use async_std::sync::Mutex;
+use self::{rolling_store::RollingStore, view_store::ViewStore};
+use async_lock::Mutex;
 use async_trait::async_trait;
 use atomic_store::{AtomicStore, AtomicStoreLoader};
 use commit::Commitment;
 use hotshot_types::{
-    traits::storage::{
-        AtomicStoreSnafu, Storage, StorageError, StorageResult, StorageState, StorageUpdater,
-        TestableStorage,
+    data::LeafType,
+    traits::{
+        node_implementation::NodeType,
+        storage::{
+            Result, Storage, StorageError, StorageState, StoredView, TestableStorage,
+            UndecidedState, ViewEntry,
+        },
     },
 };
-use serde::{de::DeserializeOwned, Serialize};
-use snafu::ResultExt;
 use std::{path::Path, sync::Arc};
 use tempfile::{tempdir, TempDir};
 use tracing::{instrument, trace};
 
 /// Inner state of an atomic storage
-struct AtomicStorageInner<STATE>
-where
-    STATE: DeserializeOwned + Serialize + StateContents,
-{
+struct AtomicStorageInner<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>> {
     /// Temporary directory storage might live in
     /// (we want to delete the temporary directory when storage is droppped)
     _temp_dir: Option<TempDir>,
     /// The atomic store loader
     atomic_store: Mutex<AtomicStore>,
 
-    /// The Blocks stored by this [`AtomicStorage`]
-    blocks: HashMapStore<Commitment<STATE::BlockPayload>, STATE::BlockPayload>,
-
-    /// The [`QuorumCertificate`]s stored by this [`AtomicStorage`]
-    qcs: DualKeyValueStore<QuorumCertificate<STATE>>,
-
-    /// The [`Leaf`s stored by this [`AtomicStorage`]
-    ///
-    /// In order to maintain the struct constraints, this list must be append only. Once a QC is
-    /// inserted, it index _must not_ change
-    leaves: DualKeyValueStore<Leaf<STATE>>,
+    /// The views that have been stored or have failed
+    views: ViewStore<TYPES, LEAF>,
 
-    /// The store of states
-    states: HashMapStore<Commitment<Leaf<STATE>>, STATE>,
+    /// The latest undecided consensus state
+    undecided: RollingStore<Option<UndecidedState<TYPES, LEAF>>>,
 }
 
 /// Persistent [`Storage`] implementation, based upon [`atomic_store`].
+///
+/// Changes made through [`Storage::append`] and [`Storage::cleanup_storage_up_to_view`] are only
+/// kept in memory until [`Storage::commit`] is called. A commit writes every store and then
+/// commits a new version of the underlying [`AtomicStore`], so a crash at any point leaves the
+/// storage at the last fully committed version.
 #[derive(Clone)]
-pub struct AtomicStorage<STATE>
-where
-    STATE: DeserializeOwned + Serialize + StateContents,
-{
+pub struct AtomicStorage<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>> {
     /// Inner state of the atomic storage
-    inner: Arc<AtomicStorageInner<STATE>>,
+    inner: Arc<AtomicStorageInner<TYPES, LEAF>>,
 }
 
-impl<S: StateContents + 'static> TestableStorage<S> for AtomicStorage<S> {
-    fn construct_tmp_storage() -> StorageResult<Self> {
-        let tempdir = tempdir().map_err(|e| StorageError::InconsistencyError {
-            description: e.to_string(),
-        })?;
-        let loader = AtomicStoreLoader::create(tempdir.path(), "hotshot").map_err(|e| {
-            StorageError::InconsistencyError {
-                description: e.to_string(),
-            }
-        })?;
-        Self::init_from_loader(loader, Some(tempdir))
-            .map_err(|e| StorageError::AtomicStore { source: e })
+/// Convert an [`atomic_store`] error into a [`StorageError`]
+fn persistence_error(e: impl std::fmt::Display) -> StorageError {
+    StorageError::Persistence {
+        description: e.to_string(),
     }
 }
 
-impl<STATE> AtomicStorage<STATE>
-where
-    STATE: StateContents,
+#[async_trait]
+impl<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>> TestableStorage<TYPES, LEAF>
+    for AtomicStorage<TYPES, LEAF>
 {
+    fn construct_tmp_storage() -> Result<Self> {
+        let tempdir = tempdir().map_err(persistence_error)?;
+        let loader =
+            AtomicStoreLoader::create(tempdir.path(), "hotshot").map_err(persistence_error)?;
+        Self::init_from_loader(loader, Some(tempdir)).map_err(persistence_error)
+    }
+
+    async fn get_full_state(&self) -> StorageState<TYPES, LEAF> {
+        let (stored, failed) = self.inner.views.load_all().await;
+        StorageState { stored, failed }
+    }
+}
+
+impl<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>> AtomicStorage<TYPES, LEAF> {
     /// Creates an atomic storage at a given path. If files exist, will back up existing directory before creating.
     ///
     /// # Errors
@@ -85,7 +82,6 @@ where
     /// - [`atomic_store::AtomicStoreLoader`]
     /// - [`atomic_store::AtomicStore`]
     /// - [`atomic_store::RollingLog`]
-    /// - [`atomic_store::AppendLog`]
     pub fn create(path: &Path) -> atomic_store::Result<Self> {
         let loader = AtomicStoreLoader::create(path, "hotshot")?;
         Self::init_from_loader(loader, None)
@@ -99,7 +95,6 @@ where
     /// - [`atomic_store::AtomicStoreLoader`]
     /// - [`atomic_store::AtomicStore`]
     /// - [`atomic_store::RollingLog`]
-    /// - [`atomic_store::AppendLog`]
     pub fn open(path: &Path) -> atomic_store::Result<Self> {
         let loader = AtomicStoreLoader::load(path, "hotshot")?;
         Self::init_from_loader(loader, None)
@@ -112,15 +107,12 @@ where
     /// Returns the underlying errors that the following types can throw:
     /// - [`atomic_store::AtomicStore`]
     /// - [`atomic_store::RollingLog`]
-    /// - [`atomic_store::AppendLog`]
     pub fn init_from_loader(
         mut loader: AtomicStoreLoader,
         dir: Option<TempDir>,
     ) -> atomic_store::Result<Self> {
-        let blocks = HashMapStore::load(&mut loader, "hotshot_blocks")?;
-        let qcs = DualKeyValueStore::open(&mut loader, "hotshot_qcs")?;
-        let leaves = DualKeyValueStore::open(&mut loader, "hotshot_leaves")?;
-        let states = HashMapStore::load(&mut loader, "hotshot_states")?;
+        let views = ViewStore::load(&mut loader, "hotshot_views")?;
+        let undecided = RollingStore::load(&mut loader, "hotshot_undecided_state")?;
 
         let atomic_store = AtomicStore::open(loader)?;
 
@@ -128,134 +120,116 @@ where
             inner: Arc::new(AtomicStorageInner {
                 _temp_dir: dir,
                 atomic_store: Mutex::new(atomic_store),
-                blocks,
-                qcs,
-                leaves,
-                states,
+                views,
+                undecided,
             }),
         })
     }
 }
 
 #[async_trait]
-impl<STATE: StateContents> Storage<STATE> for AtomicStorage<STATE> {
-    #[instrument(name = "AtomicStorage::get_block", skip_all)]
-    async fn get_block(
-        &self,
-        hash: &Commitment<STATE::BlockPayload>,
-    ) -> StorageResult<Option<STATE::BlockPayload>> {
-        Ok(self.inner.blocks.get(hash).await)
+impl<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>> Storage<TYPES, LEAF>
+    for AtomicStorage<TYPES, LEAF>
+{
+    #[instrument(name = "AtomicStorage::append", skip_all)]
+    async fn append(&self, views: Vec<ViewEntry<TYPES, LEAF>>) -> Result {
+        for view in views {
+            match view {
+                ViewEntry::Failed(num) => {
+                    trace!(?num, "Inserting failed view");
+                    self.inner.views.insert_failed(num).await;
+                }
+                ViewEntry::Success(view) => {
+                    trace!(view_number = ?view.view_number, "Inserting view");
+                    self.inner.views.insert(view).await;
+                }
+            }
+        }
+        Ok(())
     }
 
-    #[instrument(name = "AtomicStorage::get_qc", skip_all)]
-    async fn get_qc(
-        &self,
-        hash: &Commitment<STATE::BlockPayload>,
-    ) -> StorageResult<Option<QuorumCertificate<STATE>>> {
-        Ok(self.inner.qcs.load_by_key_1_ref(hash).await)
+    #[instrument(name = "AtomicStorage::cleanup_storage_up_to_view", skip_all)]
+    async fn cleanup_storage_up_to_view(&self, view: TYPES::Time) -> Result<usize> {
+        Ok(self.inner.views.cleanup(view).await)
     }
 
-    #[instrument(name = "AtomicStorage::get_newest_qc", skip_all)]
-    async fn get_newest_qc(&self) -> StorageResult<Option<QuorumCertificate<STATE>>> {
-        Ok(self.inner.qcs.load_latest(|qc| qc.view_number()).await)
+    #[instrument(name = "AtomicStorage::get_anchored_view", skip_all)]
+    async fn get_anchored_view(&self) -> Result<StoredView<TYPES, LEAF>> {
+        self.inner
+            .views
+            .latest()
+            .await
+            .ok_or(StorageError::NoGenesisView)
     }
 
-    #[instrument(name = "AtomicStorage::get_qc_for_view", skip_all)]
-    async fn get_qc_for_view(
-        &self,
-        view: TYPES::Time,
-    ) -> StorageResult<Option<QuorumCertificate<STATE>>> {
-        Ok(self.inner.qcs.load_by_key_2(view).await)
+    #[instrument(name = "AtomicStorage::update_undecided_state", skip_all)]
+    async fn update_undecided_state(&self, state: UndecidedState<TYPES, LEAF>) -> Result {
+        self.inner
+            .undecided
+            .modify(|undecided| *undecided = Some(state))
+            .await;
+        Ok(())
     }
 
-    #[instrument(name = "AtomicStorage::get_leaf", skip_all)]
-    async fn get_leaf(&self, hash: &Commitment<Leaf<STATE>>) -> StorageResult<Option<Leaf<STATE>>> {
-        Ok(self.inner.leaves.load_by_key_1_ref(hash).await)
+    #[instrument(name = "AtomicStorage::get_undecided_state", skip_all)]
+    async fn get_undecided_state(&self) -> Result<Option<UndecidedState<TYPES, LEAF>>> {
+        Ok(self.inner.undecided.load_all().await)
     }
 
-    #[instrument(name = "AtomicStorage::get_leaf_by_block", skip_all)]
-    async fn get_leaf_by_block(
-        &self,
-        hash: &Commitment<STATE::BlockPayload>,
-    ) -> StorageResult<Option<Leaf<STATE>>> {
-        Ok(self.inner.leaves.load_by_key_2_ref(hash).await)
+    #[instrument(name = "AtomicStorage::get_view", skip_all)]
+    async fn get_view(&self, view: TYPES::Time) -> Result<Option<StoredView<TYPES, LEAF>>> {
+        Ok(self.inner.views.get(&view).await)
     }
 
-    #[instrument(name = "AtomicStorage::get_state", skip_all)]
-    async fn get_state(&self, hash: &Commitment<Leaf<STATE>>) -> StorageResult<Option<STATE>> {
-        Ok(self.inner.states.get(hash).await)
+    #[instrument(name = "AtomicStorage::get_leaf_by_commitment", skip_all)]
+    async fn get_leaf_by_commitment(
+        &self,
+        commitment: Commitment<LEAF>,
+    ) -> Result<Option<StoredView<TYPES, LEAF>>> {
+        Ok(self.inner.views.get_by_leaf(&commitment).await)
     }
 
-    async fn get_internal_state(&self) -> StorageState<STATE> {
-        let mut blocks: Vec<(Commitment<STATE::BlockPayload>, STATE::BBlockPayloadlock)> =
-            self.inner.blocks.load_all().await.into_iter().collect();
-
-        blocks.sort_by_key(|(hash, _)| *hash);
-        let blocks = blocks.into_iter().map(|(_, block)| block).collect();
-
-        let mut leafs: Vec<Leaf<STATE>> = self.inner.leaves.load_all().await;
-        leafs.sort_by_cached_key(Leaf::hash);
-
-        let mut quorum_certificates = self.inner.qcs.load_all().await;
-        quorum_certificates.sort_by_key(|qc| qc.view_number());
-
-        let mut states: Vec<(Commitment<Leaf<STATE>>, STATE)> =
-            self.inner.states.load_all().await.into_iter().collect();
-        states.sort_by_key(|(hash, _)| *hash);
-        let states = states.into_iter().map(|(_, state)| state).collect();
+    #[instrument(name = "AtomicStorage::get_by_block_height", skip_all)]
+    async fn get_by_block_height(&self, height: u64) -> Result<Option<StoredView<TYPES, LEAF>>> {
+        Ok(self.inner.views.get_by_height(height).await)
+    }
 
-        StorageState {
-            blocks,
-            quorum_certificates,
-            leafs,
-            states,
+    #[instrument(name = "AtomicStorage::get_views_in_range", skip_all)]
+    async fn get_views_in_range(
+        &self,
+        from: TYPES::Time,
+        to: TYPES::Time,
+    ) -> Result<Vec<StoredView<TYPES, LEAF>>> {
+        if from > to {
+            return Ok(Vec::new());
         }
+        Ok(self.inner.views.range(from, to).await)
     }
-}
 
-/// Implementation of [`StorageUpdater`] for the [`AtomicStorage`]
-struct AtomicStorageUpdater<'a, S: StateContents> {
-    /// A reference to the internals of the [`AtomicStorage`]
-    inner: &'a AtomicStorageInner<S>,
-}
+    #[instrument(name = "AtomicStorage::get_view_by_transaction", skip_all)]
+    async fn get_view_by_transaction(
+        &self,
+        commitment: Commitment<TYPES::Transaction>,
+    ) -> Result<Option<StoredView<TYPES, LEAF>>> {
+        Ok(self.inner.views.get_by_transaction(&commitment).await)
+    }
 
-#[async_trait]
-impl<'a, STATE: StateContents + 'static> StorageUpdater<'a, STATE>
-    for AtomicStorageUpdater<'a, STATE>
-{
-    #[instrument(name = "AtomicStorage::get_block", skip_all)]
-    async fn insert_block(
-        &mut self,
-        hash: Commitment<STATE::BlockPayload>,
-        block: STATE::BlockPayload,
-    ) -> StorageResult {
-        trace!(?block, "inserting block");
+    #[instrument(name = "AtomicStorage::commit", skip_all)]
+    async fn commit(&self) -> Result {
+        // Hold the store lock for the whole commit so that concurrent commits can't interleave
+        // the versions of the individual logs.
+        let mut atomic_store = self.inner.atomic_store.lock().await;
         self.inner
-            .blocks
-            .insert(hash, block)
+            .views
+            .commit_version()
             .await
-            .context(AtomicStoreSnafu)?;
-        Ok(())
-    }
-
-    #[instrument(name = "AtomicStorage::insert_leaf", skip_all)]
-    async fn insert_leaf(&mut self, leaf: Leaf<STATE>) -> StorageResult {
-        self.inner.leaves.insert(leaf).await
-    }
-
-    #[instrument(name = "AtomicStorage::insert_qc", skip_all)]
-    async fn insert_qc(&mut self, qc: QuorumCertificate<STATE>) -> StorageResult {
-        self.inner.qcs.insert(qc).await
-    }
-
-    #[instrument(name = "AtomicStorage::insert_state", skip_all)]
-    async fn insert_state(&mut self, state: STATE, hash: Commitment<Leaf<STATE>>) -> StorageResult {
-        trace!(?hash, "Inserting state");
+            .map_err(persistence_error)?;
         self.inner
-            .states
-            .insert(hash, state)
+            .undecided
+            .commit_version()
             .await
-            .context(AtomicStoreSnafu)?;
+            .map_err(persistence_error)?;
+        atomic_store.commit_version().map_err(persistence_error)?;
         Ok(())
     }
 }
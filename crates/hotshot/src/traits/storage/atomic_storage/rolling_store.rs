@@ -0,0 +1,75 @@
+//! A store based on [`RollingLog`]
+
+use async_lock::RwLock;
+use atomic_store::{load_store::BincodeLoadStore, AtomicStoreLoader, RollingLog};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// A store that keeps a single value in memory and persists the latest version of it in a
+/// [`RollingLog`].
+pub struct RollingStore<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    /// Inner value
+    inner: RwLock<Inner<T>>,
+}
+
+/// The inner value of the [`RollingStore`]
+struct Inner<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    /// The underlying atomic_store store
+    store: RollingLog<BincodeLoadStore<T>>,
+    /// Data currently loaded in the store
+    data: T,
+}
+
+impl<T> RollingStore<T>
+where
+    T: Serialize + DeserializeOwned + Clone + Default,
+{
+    /// Load a `RollingStore` with the given loader and name.
+    ///
+    /// If nothing has been committed under this name yet, the store starts out with
+    /// `T::default()`.
+    ///
+    /// # Errors
+    ///
+    /// Returns any errors that [`RollingLog`]'s `load` returns.
+    pub fn load(loader: &mut AtomicStoreLoader, name: &str) -> atomic_store::Result<Self> {
+        let store = RollingLog::load(loader, BincodeLoadStore::default(), name, 1024)?;
+        let data = store.load_latest().unwrap_or_default();
+        Ok(Self {
+            inner: RwLock::new(Inner { store, data }),
+        })
+    }
+
+    /// Modify the data currently loaded in this store. The change won't be persisted until
+    /// `commit_version` is called.
+    pub async fn modify<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let mut lock = self.inner.write().await;
+        f(&mut lock.data)
+    }
+
+    /// Returns all data stored in this [`RollingStore`].
+    pub async fn load_all(&self) -> T {
+        self.inner.read().await.data.clone()
+    }
+
+    /// Write the data currently loaded in this store to the underlying log and commit it.
+    ///
+    /// The commit only becomes durable once the owning [`AtomicStore`](atomic_store::AtomicStore)
+    /// commits its version as well; until then a crash reverts to the previous version.
+    ///
+    /// # Errors
+    ///
+    /// Returns any errors that [`RollingLog`]'s `store_resource` and `commit_version` returns.
+    pub async fn commit_version(&self) -> atomic_store::Result<()> {
+        let mut lock = self.inner.write().await;
+        let Inner { store, data } = &mut *lock;
+        store.store_resource(data)?;
+        store.commit_version()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,312 @@
+//! A store of views based on [`RollingLog`]
+
+use async_lock::RwLock;
+use atomic_store::{load_store::BincodeLoadStore, AtomicStoreLoader, RollingLog};
+use commit::Commitment;
+use hotshot_types::{
+    data::LeafType,
+    traits::{node_implementation::NodeType, storage::StoredView, BlockPayload},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// A change to a [`ViewStore`], as written to its log
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound(deserialize = ""))]
+enum ViewLogEntry<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>> {
+    /// A view was stored, replacing any view stored with the same number
+    Stored(StoredView<TYPES, LEAF>),
+    /// A view failed
+    Failed(TYPES::Time),
+    /// Every view before this one was removed
+    Cleanup(TYPES::Time),
+}
+
+/// The views of a [`ViewStore`] at the time of its last compaction
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound(deserialize = ""))]
+struct ViewSnapshot<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>> {
+    /// The views that have been stored
+    stored: BTreeMap<TYPES::Time, StoredView<TYPES, LEAF>>,
+    /// The views that have failed
+    failed: BTreeSet<TYPES::Time>,
+}
+
+/// A store of succeeded and failed views.
+///
+/// The store is persisted as a snapshot of its views plus the changes made since that snapshot,
+/// each in a [`RollingLog`] that only retains its latest entry. Committing writes the changes
+/// made since the snapshot, unless a cleanup happened since the last commit, in which case the
+/// remaining views are written as a new snapshot and the changes are reset. The size on disk is
+/// therefore bounded by the views that haven't been cleaned up yet. The views are kept in memory,
+/// indexed by the keys [`Storage`] looks them up by, and rebuilt when the store is loaded.
+///
+/// [`Storage`]: hotshot_types::traits::storage::Storage
+pub struct ViewStore<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>> {
+    /// Inner value
+    inner: RwLock<Inner<TYPES, LEAF>>,
+}
+
+/// The inner value of the [`ViewStore`]
+struct Inner<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>> {
+    /// The log of snapshots taken on compaction
+    snapshots: RollingLog<BincodeLoadStore<ViewSnapshot<TYPES, LEAF>>>,
+    /// The log of changes made since the latest snapshot
+    changes: RollingLog<BincodeLoadStore<Vec<ViewLogEntry<TYPES, LEAF>>>>,
+    /// Changes made since the latest snapshot, including the ones not written to the log yet
+    since_snapshot: Vec<ViewLogEntry<TYPES, LEAF>>,
+    /// Whether a cleanup happened since the last commit, so the next commit compacts the log
+    compact: bool,
+    /// The views that have been stored
+    stored: BTreeMap<TYPES::Time, StoredView<TYPES, LEAF>>,
+    /// The views that have failed
+    failed: BTreeSet<TYPES::Time>,
+    /// Leaf commitment to the view of that leaf
+    by_leaf: HashMap<Commitment<LEAF>, TYPES::Time>,
+    /// Block height to the view of that block
+    by_height: HashMap<u64, TYPES::Time>,
+    /// Transaction commitment to the views whose block payloads contain it
+    by_transaction: HashMap<Commitment<TYPES::Transaction>, BTreeSet<TYPES::Time>>,
+}
+
+impl<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>> Inner<TYPES, LEAF> {
+    /// Apply `entry` to the views in memory, returning the number of views it removed
+    fn apply(&mut self, entry: ViewLogEntry<TYPES, LEAF>) -> usize {
+        match entry {
+            ViewLogEntry::Stored(view) => {
+                let view_number = view.view_number;
+                if let Some(old) = self.stored.remove(&view_number) {
+                    self.unindex(&old);
+                }
+                if let Ok(leaf) = view.leaf_commitment() {
+                    self.by_leaf.insert(leaf, view_number);
+                }
+                self.by_height.insert(view.block_height(), view_number);
+                if let Some(payload) = &view.block_payload {
+                    for txn in payload.transaction_commitments() {
+                        self.by_transaction
+                            .entry(txn)
+                            .or_default()
+                            .insert(view_number);
+                    }
+                }
+                self.stored.insert(view_number, view);
+                0
+            }
+            ViewLogEntry::Failed(view_number) => {
+                self.failed.insert(view_number);
+                0
+            }
+            ViewLogEntry::Cleanup(view_number) => {
+                // .split_off will return everything after the given key, including the key.
+                let stored_after = self.stored.split_off(&view_number);
+                let old_stored = std::mem::replace(&mut self.stored, stored_after);
+                for view in old_stored.values() {
+                    self.unindex(view);
+                }
+                let failed_after = self.failed.split_off(&view_number);
+                let old_failed = std::mem::replace(&mut self.failed, failed_after);
+                old_stored.len() + old_failed.len()
+            }
+        }
+    }
+
+    /// Remove the index entries that point at `view`
+    fn unindex(&mut self, view: &StoredView<TYPES, LEAF>) {
+        let view_number = view.view_number;
+        if let Ok(leaf) = view.leaf_commitment() {
+            if self.by_leaf.get(&leaf) == Some(&view_number) {
+                self.by_leaf.remove(&leaf);
+            }
+        }
+        if self.by_height.get(&view.block_height()) == Some(&view_number) {
+            self.by_height.remove(&view.block_height());
+        }
+        if let Some(payload) = &view.block_payload {
+            for txn in payload.transaction_commitments() {
+                if let Some(views) = self.by_transaction.get_mut(&txn) {
+                    views.remove(&view_number);
+                    if views.is_empty() {
+                        self.by_transaction.remove(&txn);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The view with the given number, if it is stored
+    fn view(&self, view_number: Option<&TYPES::Time>) -> Option<StoredView<TYPES, LEAF>> {
+        view_number.and_then(|view_number| self.stored.get(view_number).cloned())
+    }
+}
+
+impl<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>> ViewStore<TYPES, LEAF> {
+    /// Load a `ViewStore` with the given loader and name, replaying the changes made since its
+    /// latest snapshot.
+    ///
+    /// # Errors
+    ///
+    /// Returns any errors that [`RollingLog`]'s `load` returns.
+    pub fn load(loader: &mut AtomicStoreLoader, name: &str) -> atomic_store::Result<Self> {
+        let mut snapshots = RollingLog::load(
+            loader,
+            BincodeLoadStore::default(),
+            &format!("{name}_snapshot"),
+            1024,
+        )?;
+        snapshots.set_retained_entries(1);
+        let mut changes = RollingLog::load(
+            loader,
+            BincodeLoadStore::default(),
+            &format!("{name}_changes"),
+            1024,
+        )?;
+        changes.set_retained_entries(1);
+
+        let snapshot = snapshots.load_latest().ok();
+        let since_snapshot: Vec<_> = changes.load_latest().unwrap_or_default();
+        let mut inner = Inner {
+            snapshots,
+            changes,
+            since_snapshot: Vec::new(),
+            compact: false,
+            stored: BTreeMap::new(),
+            failed: BTreeSet::new(),
+            by_leaf: HashMap::new(),
+            by_height: HashMap::new(),
+            by_transaction: HashMap::new(),
+        };
+        if let Some(ViewSnapshot { stored, failed }) = snapshot {
+            for view in stored.into_values() {
+                inner.apply(ViewLogEntry::Stored(view));
+            }
+            inner.failed = failed;
+        }
+        for entry in &since_snapshot {
+            inner.apply(entry.clone());
+        }
+        inner.since_snapshot = since_snapshot;
+        Ok(Self {
+            inner: RwLock::new(inner),
+        })
+    }
+
+    /// Apply `entry` and queue it to be written on the next `commit_version`
+    async fn push(&self, entry: ViewLogEntry<TYPES, LEAF>) -> usize {
+        let mut lock = self.inner.write().await;
+        let removed = lock.apply(entry.clone());
+        if matches!(entry, ViewLogEntry::Cleanup(_)) {
+            lock.compact = true;
+        }
+        lock.since_snapshot.push(entry);
+        removed
+    }
+
+    /// Store `view`, replacing any view stored with the same number.
+    pub async fn insert(&self, view: StoredView<TYPES, LEAF>) {
+        self.push(ViewLogEntry::Stored(view)).await;
+    }
+
+    /// Mark `view_number` as failed.
+    pub async fn insert_failed(&self, view_number: TYPES::Time) {
+        self.push(ViewLogEntry::Failed(view_number)).await;
+    }
+
+    /// Remove every succeeded and failed view before `view_number`, returning how many were
+    /// removed.
+    pub async fn cleanup(&self, view_number: TYPES::Time) -> usize {
+        self.push(ViewLogEntry::Cleanup(view_number)).await
+    }
+
+    /// The view with the given number.
+    pub async fn get(&self, view_number: &TYPES::Time) -> Option<StoredView<TYPES, LEAF>> {
+        self.inner.read().await.stored.get(view_number).cloned()
+    }
+
+    /// The view with the highest number.
+    pub async fn latest(&self) -> Option<StoredView<TYPES, LEAF>> {
+        self.inner.read().await.stored.values().next_back().cloned()
+    }
+
+    /// The view of the leaf with the given commitment.
+    pub async fn get_by_leaf(&self, leaf: &Commitment<LEAF>) -> Option<StoredView<TYPES, LEAF>> {
+        let read = self.inner.read().await;
+        read.view(read.by_leaf.get(leaf))
+    }
+
+    /// The view of the block at the given height.
+    pub async fn get_by_height(&self, height: u64) -> Option<StoredView<TYPES, LEAF>> {
+        let read = self.inner.read().await;
+        read.view(read.by_height.get(&height))
+    }
+
+    /// The first view whose block payload contains the transaction with the given commitment.
+    pub async fn get_by_transaction(
+        &self,
+        txn: &Commitment<TYPES::Transaction>,
+    ) -> Option<StoredView<TYPES, LEAF>> {
+        let read = self.inner.read().await;
+        read.view(read.by_transaction.get(txn).and_then(BTreeSet::first))
+    }
+
+    /// The views numbered from `from` to `to`, both included, in ascending order.
+    pub async fn range(&self, from: TYPES::Time, to: TYPES::Time) -> Vec<StoredView<TYPES, LEAF>> {
+        self.inner
+            .read()
+            .await
+            .stored
+            .range(from..=to)
+            .map(|(_, view)| view.clone())
+            .collect()
+    }
+
+    /// Returns all succeeded and failed views in this [`ViewStore`].
+    pub async fn load_all(
+        &self,
+    ) -> (
+        BTreeMap<TYPES::Time, StoredView<TYPES, LEAF>>,
+        BTreeSet<TYPES::Time>,
+    ) {
+        let read = self.inner.read().await;
+        (read.stored.clone(), read.failed.clone())
+    }
+
+    /// Write the changes made since the latest snapshot to the underlying logs and commit them,
+    /// taking a new snapshot instead if a cleanup happened since the last commit.
+    ///
+    /// The commit only becomes durable once the owning [`AtomicStore`](atomic_store::AtomicStore)
+    /// commits its version as well; until then a crash reverts to the previous version.
+    ///
+    /// # Errors
+    ///
+    /// Returns any errors that [`RollingLog`]'s `store_resource`, `commit_version` and
+    /// `prune_file_entries` return.
+    pub async fn commit_version(&self) -> atomic_store::Result<()> {
+        let mut lock = self.inner.write().await;
+        let Inner {
+            snapshots,
+            changes,
+            since_snapshot,
+            compact,
+            stored,
+            failed,
+            ..
+        } = &mut *lock;
+        if *compact {
+            snapshots.store_resource(&ViewSnapshot {
+                stored: stored.clone(),
+                failed: failed.clone(),
+            })?;
+            since_snapshot.clear();
+        } else {
+            snapshots.skip_version()?;
+        }
+        changes.store_resource(since_snapshot)?;
+        snapshots.commit_version()?;
+        changes.commit_version()?;
+        snapshots.prune_file_entries()?;
+        changes.prune_file_entries()?;
+        *compact = false;
+        Ok(())
+    }
+}
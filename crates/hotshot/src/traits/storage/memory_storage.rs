@@ -3,12 +3,14 @@
 //! This module provides a non-persisting, dummy adapter for the [`Storage`] trait
 use async_lock::RwLock;
 use async_trait::async_trait;
+use commit::Commitment;
 use hotshot_types::{
     data::LeafType,
     traits::{
         node_implementation::NodeType,
         storage::{
-            Result, Storage, StorageError, StorageState, StoredView, TestableStorage, ViewEntry,
+            Result, Storage, StorageError, StorageState, StoredView, TestableStorage,
+            UndecidedState, ViewEntry,
         },
     },
 };
@@ -23,6 +25,8 @@ struct MemoryStorageInternal<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>>
     stored: BTreeMap<TYPES::Time, StoredView<TYPES, LEAF>>,
     /// The views that have failed
     failed: BTreeSet<TYPES::Time>,
+    /// The latest undecided consensus state
+    undecided: Option<UndecidedState<TYPES, LEAF>>,
 }
 
 /// In memory, ephemeral, storage for a [`HotShot`](crate::HotShot) instance
@@ -39,6 +43,7 @@ impl<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>> MemoryStorage<TYPES, LEA
         let inner = MemoryStorageInternal {
             stored: BTreeMap::new(),
             failed: BTreeSet::new(),
+            undecided: None,
         };
         Self {
             inner: Arc::new(RwLock::new(inner)),
@@ -107,6 +112,68 @@ impl<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>> Storage<TYPES, LEAF>
         Ok(last.clone())
     }
 
+    async fn update_undecided_state(&self, state: UndecidedState<TYPES, LEAF>) -> Result {
+        self.inner.write().await.undecided = Some(state);
+        Ok(())
+    }
+
+    async fn get_undecided_state(&self) -> Result<Option<UndecidedState<TYPES, LEAF>>> {
+        Ok(self.inner.read().await.undecided.clone())
+    }
+
+    async fn get_view(&self, view: TYPES::Time) -> Result<Option<StoredView<TYPES, LEAF>>> {
+        Ok(self.inner.read().await.stored.get(&view).cloned())
+    }
+
+    async fn get_leaf_by_commitment(
+        &self,
+        commitment: Commitment<LEAF>,
+    ) -> Result<Option<StoredView<TYPES, LEAF>>> {
+        let inner = self.inner.read().await;
+        Ok(inner
+            .stored
+            .values()
+            .find(|view| view.leaf_commitment().is_ok_and(|leaf| leaf == commitment))
+            .cloned())
+    }
+
+    async fn get_by_block_height(&self, height: u64) -> Result<Option<StoredView<TYPES, LEAF>>> {
+        let inner = self.inner.read().await;
+        Ok(inner
+            .stored
+            .values()
+            .find(|view| view.block_height() == height)
+            .cloned())
+    }
+
+    async fn get_views_in_range(
+        &self,
+        from: TYPES::Time,
+        to: TYPES::Time,
+    ) -> Result<Vec<StoredView<TYPES, LEAF>>> {
+        if from > to {
+            return Ok(Vec::new());
+        }
+        let inner = self.inner.read().await;
+        Ok(inner
+            .stored
+            .range(from..=to)
+            .map(|(_, view)| view.clone())
+            .collect())
+    }
+
+    async fn get_view_by_transaction(
+        &self,
+        commitment: Commitment<TYPES::Transaction>,
+    ) -> Result<Option<StoredView<TYPES, LEAF>>> {
+        let inner = self.inner.read().await;
+        Ok(inner
+            .stored
+            .values()
+            .find(|view| view.contains_transaction(&commitment))
+            .cloned())
+    }
+
     async fn commit(&self) -> Result {
         Ok(()) // do nothing
     }
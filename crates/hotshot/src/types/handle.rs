@@ -4,7 +4,7 @@ use crate::QuorumCertificate2;
 use crate::{traits::NodeImplementation, types::Event, SystemContext};
 use async_compatibility_layer::channel::UnboundedStream;
 use async_lock::RwLock;
-use commit::Committable;
+use commit::{Commitment, Committable};
 use futures::Stream;
 use hotshot_task::{
     boxed_sync,
@@ -20,10 +20,14 @@ use hotshot_types::{
     data::LeafType,
     error::HotShotError,
     event::EventType,
-    message::MessageKind,
+    message::{Message, MessageKind},
     traits::{
         election::{ConsensusExchange, QuorumExchangeType},
-        node_implementation::{ExchangesType, NodeType},
+        epoch::{EpochError, EpochMembership, EpochNumber, StakeTableChange},
+        node_implementation::{
+            CommitteeMembership, ExchangesType, NodeType, QuorumMembership, TimeoutEx,
+            VIDMembership, ViewSyncEx,
+        },
         state::ConsensusTime,
         storage::Storage,
     },
@@ -31,8 +35,6 @@ use hotshot_types::{
 use std::sync::Arc;
 use tracing::error;
 
-#[cfg(feature = "hotshot-testing")]
-use commit::Commitment;
 #[cfg(feature = "hotshot-testing")]
 use hotshot_types::traits::signature_key::EncodedSignature;
 
@@ -190,7 +192,13 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES> + 'static> SystemContextHandl
         let _anchor = self.storage();
         if let Ok(anchor_leaf) = self.storage().get_anchored_view().await {
             if anchor_leaf.view_number == TYPES::Time::genesis() {
-                let leaf: I::Leaf = I::Leaf::from_stored_view(anchor_leaf);
+                let leaf: I::Leaf = match I::Leaf::from_stored_view(anchor_leaf) {
+                    Ok(leaf) => leaf,
+                    Err(e) => {
+                        error!("Could not rebuild the genesis leaf from storage: {:?}", e);
+                        return;
+                    }
+                };
                 let mut qc = QuorumCertificate2::<TYPES, I::Leaf>::genesis();
                 qc.data = QuorumData {
                     leaf_commit: leaf.commit(),
@@ -267,6 +275,40 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES> + 'static> SystemContextHandl
         &self.storage
     }
 
+    /// Ask `peer` for the decided leaves with a view number in `from..=to`, for example to fill
+    /// in history that is missing from [`storage`](Self::storage).
+    ///
+    /// The peer sends back the leaves along with the decided leaf after them. A leaf is only
+    /// taken as decided if it is an ancestor, by parent commitment, of a leaf known to be
+    /// decided: the decided leaf after the range in [`storage`](Self::storage), the last decided
+    /// leaf, or a leaf decided by a 3-chain in the response. The leaves that verify are appended
+    /// to the storage, and the others dropped.
+    pub async fn request_decided_leaves(
+        &self,
+        from: TYPES::Time,
+        to: TYPES::Time,
+        peer: TYPES::SignatureKey,
+    ) {
+        self.internal_event_stream
+            .publish(HotShotEvent::DecidedLeavesMissing(from, to, peer))
+            .await;
+    }
+
+    /// Reconstruct the block payload dispersed in `view` from the shares of its VID storage
+    /// nodes, for example on a node outside the DA committee that only has the block header.
+    ///
+    /// The payload is only accepted if it matches `payload_commitment`. If it belongs to a
+    /// decided leaf, the leaf is filled in with the payload and stored again.
+    pub async fn request_block_payload(
+        &self,
+        view: TYPES::Time,
+        payload_commitment: Commitment<TYPES::BlockPayload>,
+    ) {
+        self.internal_event_stream
+            .publish(HotShotEvent::VidPayloadMissing(view, payload_commitment))
+            .await;
+    }
+
     /// Get the underlying consensus state for this [`SystemContext`]
     pub fn get_consensus(&self) -> Arc<RwLock<Consensus<TYPES, I::Leaf>>> {
         self.hotshot.get_consensus()
@@ -390,3 +432,58 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES> + 'static> SystemContextHandl
         UnboundedReceiver::len(&*receiver)
     }
 }
+
+impl<TYPES: NodeType, I: NodeImplementation<TYPES> + 'static> SystemContextHandle<TYPES, I>
+where
+    QuorumMembership<TYPES, I>: EpochMembership<TYPES>,
+    <TimeoutEx<TYPES, I> as ConsensusExchange<TYPES, Message<TYPES, I>>>::Membership:
+        EpochMembership<TYPES>,
+    CommitteeMembership<TYPES, I>: EpochMembership<TYPES>,
+    VIDMembership<TYPES, I>: EpochMembership<TYPES>,
+    <ViewSyncEx<TYPES, I> as ConsensusExchange<TYPES, Message<TYPES, I>>>::Membership:
+        EpochMembership<TYPES>,
+{
+    /// Schedule a change to the stake table in `view`, such as a validator joining or leaving,
+    /// taking effect from the start of `epoch` on every committee of this node.
+    ///
+    /// Every committee is checked before the change is scheduled on any of them, so they either
+    /// all take the change or none does.
+    ///
+    /// # Errors
+    /// Returns [`EpochError::TooLate`] if `view` is past the deadline for changes to `epoch`.
+    pub fn schedule_stake_table_change(
+        &self,
+        view: TYPES::Time,
+        epoch: EpochNumber,
+        change: StakeTableChange<TYPES::SignatureKey>,
+    ) -> Result<(), EpochError> {
+        let exchanges = &self.hotshot.inner.exchanges;
+        let quorum = exchanges.quorum_exchange().membership();
+        let timeout = exchanges.timeout_exchange().membership();
+        let committee = exchanges.committee_exchange().membership();
+        let vid = exchanges.vid_exchange().membership();
+        let view_sync = exchanges.view_sync_exchange().membership();
+        quorum.check_change(view, epoch)?;
+        timeout.check_change(view, epoch)?;
+        committee.check_change(view, epoch)?;
+        vid.check_change(view, epoch)?;
+        view_sync.check_change(view, epoch)?;
+
+        quorum.schedule_change(view, epoch, change.clone())?;
+        timeout.schedule_change(view, epoch, change.clone())?;
+        committee.schedule_change(view, epoch, change.clone())?;
+        vid.schedule_change(view, epoch, change.clone())?;
+        view_sync.schedule_change(view, epoch, change)
+    }
+
+    /// The epoch this node is in
+    #[must_use]
+    pub fn get_current_epoch(&self) -> EpochNumber {
+        self.hotshot
+            .inner
+            .exchanges
+            .quorum_exchange()
+            .membership()
+            .current_epoch()
+    }
+}
@@ -1,6 +1,6 @@
 use std::{net::IpAddr, time::Duration};
 
-use crate::config::NetworkConfig;
+use crate::{config::NetworkConfig, OrchestratorStatus};
 use async_compatibility_layer::art::async_sleep;
 use clap::Parser;
 use futures::{Future, FutureExt};
@@ -29,6 +29,12 @@ pub struct ValidatorArgs {
     /// This node's public IP address, for libp2p
     /// If no IP address is passed in, it will default to 127.0.0.1
     pub public_ip: Option<IpAddr>,
+    /// A token identifying this node to the orchestrator, e.g. its public key. A node restarted
+    /// with the same token gets its original node index back.
+    /// If no token is passed in, `public_ip` is used if passed in, as it is stable across restarts
+    /// of the node; otherwise the node gets a new node index each time it identifies
+    #[arg(long)]
+    pub session: Option<String>,
 }
 
 impl OrchestratorClient {
@@ -41,16 +47,18 @@ impl OrchestratorClient {
         OrchestratorClient { client }
     }
 
-    /// Sends an identify message to the server
-    /// Returns this validator's node_index in the network
-    pub async fn identify_with_orchestrator(&self, identity: String) -> u16 {
-        let identity = identity.as_str();
+    /// Sends an identify message to the server, identifying this validator by `session`
+    /// Returns this validator's node_index in the network, the same one each time for the same
+    /// `session`
+    pub async fn identify_with_orchestrator(&self, identity: String, session: Option<&str>) -> u16 {
+        let route = match session {
+            Some(session) => format!("api/identity/{identity}/session/{session}"),
+            None => format!("api/identity/{identity}"),
+        };
+        let route = route.as_str();
         let f = |client: Client<ClientError>| {
             async move {
-                let node_index: Result<u16, ClientError> = client
-                    .post(&format!("api/identity/{identity}"))
-                    .send()
-                    .await;
+                let node_index: Result<u16, ClientError> = client.post(route).send().await;
                 node_index
             }
             .boxed()
@@ -115,6 +123,14 @@ impl OrchestratorClient {
             .await
     }
 
+    /// Returns which nodes have identified with the orchestrator and which are ready to start
+    pub async fn get_status_from_orchestrator(&self) -> OrchestratorStatus {
+        let f = |client: Client<ClientError>| {
+            async move { client.get("api/status").send().await }.boxed()
+        };
+        self.wait_for_fn_from_orchestrator(f).await
+    }
+
     /// Generic function that waits for the orchestrator to return a non-error
     /// Returns whatever type the given function returns
     async fn wait_for_fn_from_orchestrator<F, Fut, GEN>(&self, f: F) -> GEN
@@ -1,4 +1,8 @@
-use hotshot_types::{ExecutionType, HotShotConfig};
+use hotshot_types::{
+    clock::Clock, ExecutionType, HotShotConfig, MempoolConfig, VidConfig,
+    DEFAULT_DECIDED_RETENTION_VIEWS,
+};
+use hotshot_web_server::config::RelayUrls;
 use std::{
     marker::PhantomData,
     net::{IpAddr, Ipv4Addr, SocketAddr},
@@ -48,6 +52,9 @@ pub struct WebServerConfig {
     pub host: IpAddr,
     pub port: u16,
     pub wait_between_polls: Duration,
+    /// Web servers for classes of messages that shouldn't go to `host` and `port`
+    #[serde(default)]
+    pub relays: RelayUrls,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
@@ -176,6 +183,9 @@ pub struct HotShotConfigFile {
     pub committee_nodes: usize,
     /// Maximum transactions per block
     pub max_transactions: NonZeroUsize,
+    /// Maximum total serialized size of the transactions in a block, in bytes
+    #[serde(default = "default_max_block_size")]
+    pub max_block_size: NonZeroUsize,
     /// Minimum transactions per block
     pub min_transactions: usize,
     /// Base duration for next-view timeout, in milliseconds
@@ -192,6 +202,16 @@ pub struct HotShotConfigFile {
     pub propose_min_round_time: Duration,
     /// The maximum amount of time a leader can wait to start a round
     pub propose_max_round_time: Duration,
+    /// Limits on the transactions a node holds while waiting for them to be included in a block
+    #[serde(default)]
+    pub mempool: MempoolConfig,
+    /// Parameters of the verifiable information dispersal of block payloads
+    #[serde(default)]
+    pub vid: VidConfig,
+    /// Number of views before the newest decided view whose leaves are kept in the storage, or
+    /// `None` to keep the full decided history
+    #[serde(default = "default_decided_retention_views")]
+    pub decided_retention_views: Option<u64>,
 }
 
 impl<ENTRY, E> From<HotShotConfigFile> for HotShotConfig<ENTRY, E> {
@@ -200,6 +220,7 @@ impl<ENTRY, E> From<HotShotConfigFile> for HotShotConfig<ENTRY, E> {
             execution_type: ExecutionType::Continuous,
             total_nodes: val.total_nodes,
             max_transactions: val.max_transactions,
+            max_block_size: val.max_block_size,
             min_transactions: val.min_transactions,
             known_nodes_with_stake: Vec::new(),
             da_committee_size: val.committee_nodes,
@@ -211,6 +232,10 @@ impl<ENTRY, E> From<HotShotConfigFile> for HotShotConfig<ENTRY, E> {
             propose_min_round_time: val.propose_min_round_time,
             propose_max_round_time: val.propose_max_round_time,
             election_config: None,
+            mempool: val.mempool,
+            vid: val.vid,
+            decided_retention_views: val.decided_retention_views,
+            clock: Clock::default(),
         }
     }
 }
@@ -225,11 +250,19 @@ fn default_transactions_per_round() -> usize {
 fn default_padding() -> usize {
     100
 }
+fn default_max_block_size() -> NonZeroUsize {
+    NonZeroUsize::new(5 * 1024 * 1024).unwrap()
+}
+#[allow(clippy::unnecessary_wraps)]
+fn default_decided_retention_views() -> Option<u64> {
+    Some(DEFAULT_DECIDED_RETENTION_VIEWS)
+}
 fn default_config() -> HotShotConfigFile {
     HotShotConfigFile {
         total_nodes: NonZeroUsize::new(10).unwrap(),
         committee_nodes: 5,
         max_transactions: NonZeroUsize::new(100).unwrap(),
+        max_block_size: default_max_block_size(),
         min_transactions: 1,
         next_view_timeout: 10000,
         timeout_ratio: (11, 10),
@@ -238,6 +271,9 @@ fn default_config() -> HotShotConfigFile {
         propose_min_round_time: Duration::from_secs(0),
         propose_max_round_time: Duration::from_secs(10),
         num_bootstrap: 5,
+        mempool: MempoolConfig::default(),
+        vid: VidConfig::default(),
+        decided_retention_views: default_decided_retention_views(),
     }
 }
 
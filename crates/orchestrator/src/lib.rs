@@ -3,7 +3,9 @@ pub mod config;
 
 use async_lock::RwLock;
 use hotshot_types::traits::{election::ElectionConfig, signature_key::SignatureKey};
+use serde::{Deserialize, Serialize};
 use std::{
+    collections::{BTreeSet, HashMap},
     io,
     io::ErrorKind,
     net::{IpAddr, SocketAddr},
@@ -18,6 +20,7 @@ use tide_disco::{
 };
 
 use futures::FutureExt;
+use tracing::info;
 
 use crate::config::NetworkConfig;
 
@@ -35,17 +38,34 @@ pub fn libp2p_generate_indexed_identity(seed: [u8; 32], index: u64) -> Keypair {
     <EdKeypair as From<SecretKey>>::from(sk_bytes).into()
 }
 
+/// Which nodes have identified with the orchestrator and which are ready to start
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrchestratorStatus {
+    /// The number of nodes in the network
+    pub total_nodes: usize,
+    /// Indices handed out to nodes that identified
+    pub registered: Vec<u16>,
+    /// Indices of nodes that posted they are ready to start
+    pub ready: Vec<u16>,
+    /// Indices of nodes that have not posted they are ready to start, whether or not a node has
+    /// identified with them
+    pub missing: Vec<u16>,
+}
+
 #[derive(Default, Clone)]
 struct OrchestratorState<KEY, ENTRY, ELECTION> {
     /// Tracks the latest node index we have generated a configuration for
     latest_index: u16,
+    /// The node index handed out for each session token, so a node identifying again with its
+    /// token gets its original index back
+    sessions: HashMap<String, u16>,
     /// The network configuration
     config: NetworkConfig<KEY, ENTRY, ELECTION>,
     /// Whether nodes should start their HotShot instances
     /// Will be set to true once all nodes post they are ready to start
     start: bool,
-    /// The total nodes that have posted they are ready to start
-    pub nodes_connected: u64,
+    /// The nodes that have posted they are ready to start
+    pub nodes_ready: BTreeSet<u16>,
     /// connection to the web server
     client: Option<surf_disco::Client<ClientError>>,
 }
@@ -61,23 +81,33 @@ impl<KEY: SignatureKey + 'static, ELECTION: ElectionConfig + 'static>
         }
         OrchestratorState {
             latest_index: 0,
+            sessions: HashMap::new(),
             config: network_config,
             start: false,
-            nodes_connected: 0,
+            nodes_ready: BTreeSet::new(),
             client: web_client,
         }
     }
 }
 
 pub trait OrchestratorApi<KEY, ENTRY, ELECTION> {
-    fn post_identity(&mut self, identity: IpAddr) -> Result<u16, ServerError>;
+    /// Hand out a node index to the node at `identity`. A node identifying with a `session`
+    /// token it identified with before gets the same index again.
+    fn post_identity(
+        &mut self,
+        identity: IpAddr,
+        session: Option<String>,
+    ) -> Result<u16, ServerError>;
     fn post_getconfig(
         &mut self,
         node_index: u16,
     ) -> Result<NetworkConfig<KEY, ENTRY, ELECTION>, ServerError>;
     fn get_start(&self) -> Result<bool, ServerError>;
-    fn post_ready(&mut self) -> Result<(), ServerError>;
+    /// Record that the node with `node_index` is ready to start. Posting twice has no effect.
+    fn post_ready(&mut self, node_index: u16) -> Result<(), ServerError>;
     fn post_run_results(&mut self) -> Result<(), ServerError>;
+    /// Which nodes have identified and which are ready to start
+    fn get_status(&self) -> Result<OrchestratorStatus, ServerError>;
 }
 
 impl<KEY, ELECTION> OrchestratorApi<KEY, KEY::StakeTableEntry, ELECTION>
@@ -86,17 +116,30 @@ where
     KEY: serde::Serialize + Clone + SignatureKey,
     ELECTION: serde::Serialize + Clone + Send,
 {
-    fn post_identity(&mut self, identity: IpAddr) -> Result<u16, ServerError> {
-        let node_index = self.latest_index;
-        self.latest_index += 1;
+    fn post_identity(
+        &mut self,
+        identity: IpAddr,
+        session: Option<String>,
+    ) -> Result<u16, ServerError> {
+        // A node re-joining, e.g. after a crash, is already set up under its original index
+        if let Some(node_index) = session
+            .as_ref()
+            .and_then(|session| self.sessions.get(session))
+        {
+            return Ok(*node_index);
+        }
 
-        // TODO https://github.com/EspressoSystems/HotShot/issues/850
+        let node_index = self.latest_index;
         if usize::from(node_index) >= self.config.config.total_nodes.get() {
             return Err(ServerError {
                 status: tide_disco::StatusCode::BadRequest,
                 message: "Network has reached capacity".to_string(),
             });
         }
+        self.latest_index += 1;
+        if let Some(session) = session {
+            self.sessions.insert(session, node_index);
+        }
 
         //add new node's key to stake table
         if self.config.web_server_config.clone().is_some() {
@@ -164,20 +207,17 @@ where
         Ok(self.start)
     }
 
-    // Assumes nodes do not post 'ready' twice
-    // TODO ED Add a map to verify which nodes have posted they're ready
-    fn post_ready(&mut self) -> Result<(), ServerError> {
-        self.nodes_connected += 1;
-        println!("Nodes connected: {}", self.nodes_connected);
-        if self.nodes_connected
-            >= self
-                .config
-                .config
-                .known_nodes_with_stake
-                .len()
-                .try_into()
-                .unwrap()
-        {
+    fn post_ready(&mut self, node_index: u16) -> Result<(), ServerError> {
+        if node_index >= self.latest_index {
+            return Err(ServerError {
+                status: tide_disco::StatusCode::BadRequest,
+                message: format!("No node has identified with index {node_index}"),
+            });
+        }
+        if self.nodes_ready.insert(node_index) {
+            info!("Nodes ready: {}", self.nodes_ready.len());
+        }
+        if self.nodes_ready.len() >= self.config.config.known_nodes_with_stake.len() {
             self.start = true;
         }
         Ok(())
@@ -186,6 +226,19 @@ where
     fn post_run_results(&mut self) -> Result<(), ServerError> {
         Ok(())
     }
+
+    fn get_status(&self) -> Result<OrchestratorStatus, ServerError> {
+        let total_nodes = self.config.config.total_nodes.get();
+        let all_indices = (0..total_nodes).filter_map(|index| u16::try_from(index).ok());
+        Ok(OrchestratorStatus {
+            total_nodes,
+            registered: (0..self.latest_index).collect(),
+            ready: self.nodes_ready.iter().copied().collect(),
+            missing: all_indices
+                .filter(|index| !self.nodes_ready.contains(index))
+                .collect(),
+        })
+    }
 }
 
 /// Sets up all API routes
@@ -212,7 +265,8 @@ where
                     message: "Identity is not a properly formed IP address".to_string(),
                 });
             }
-            state.post_identity(identity.unwrap())
+            let session = req.opt_string_param("session")?;
+            state.post_identity(identity.unwrap(), session)
         }
         .boxed()
     })?
@@ -223,14 +277,28 @@ where
         }
         .boxed()
     })?
-    .post("postready", |_req, state| {
-        async move { state.post_ready() }.boxed()
+    .post("postready", |req, state| {
+        async move {
+            let node_index: u64 = req.body_json().map_err(|_e| ServerError {
+                status: tide_disco::StatusCode::BadRequest,
+                message: "Body is not a node index".to_string(),
+            })?;
+            let node_index = u16::try_from(node_index).map_err(|_e| ServerError {
+                status: tide_disco::StatusCode::BadRequest,
+                message: format!("Node index {node_index} is out of range"),
+            })?;
+            state.post_ready(node_index)
+        }
+        .boxed()
     })?
     .get("getstart", |_req, state| {
         async move { state.get_start() }.boxed()
     })?
     .post("postresults", |_req, state| {
         async move { state.post_run_results() }.boxed()
+    })?
+    .get("getstatus", |_req, state| {
+        async move { state.get_status() }.boxed()
     })?;
     Ok(api)
 }
@@ -256,3 +324,111 @@ where
     tracing::error!("lisening on {:?}:{:?}", host, port);
     app.serve(format!("http://{host}:{port}")).await
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use hotshot_signature_key::bn254::BLSPubKey;
+    use std::net::Ipv4Addr;
+
+    /// An election config for the orchestrator to hand out
+    #[derive(Clone, Debug, Default, Serialize, Deserialize)]
+    struct TestElectionConfig;
+
+    impl ElectionConfig for TestElectionConfig {}
+
+    /// The state of an orchestrator for the default network config, with `known_nodes` staked
+    fn state(
+        known_nodes: u64,
+    ) -> OrchestratorState<
+        BLSPubKey,
+        <BLSPubKey as SignatureKey>::StakeTableEntry,
+        TestElectionConfig,
+    > {
+        let mut config = NetworkConfig::default();
+        config.config.known_nodes_with_stake = (0..known_nodes)
+            .map(|index| {
+                BLSPubKey::generated_from_seed_indexed([0u8; 32], index)
+                    .0
+                    .get_stake_table_entry(1)
+            })
+            .collect();
+        OrchestratorState::new(config)
+    }
+
+    fn localhost() -> IpAddr {
+        IpAddr::V4(Ipv4Addr::LOCALHOST)
+    }
+
+    #[test]
+    fn test_identify_again_with_session() {
+        let mut state = state(3);
+        let first = state
+            .post_identity(localhost(), Some("a".to_string()))
+            .unwrap();
+        let second = state
+            .post_identity(localhost(), Some("b".to_string()))
+            .unwrap();
+        assert_ne!(first, second);
+
+        // The same session gets its index back, without using up a new one
+        assert_eq!(
+            state
+                .post_identity(localhost(), Some("a".to_string()))
+                .unwrap(),
+            first
+        );
+        assert_eq!(state.get_status().unwrap().registered, vec![0, 1]);
+
+        // Nodes without a session always get a new index
+        let third = state.post_identity(localhost(), None).unwrap();
+        let fourth = state.post_identity(localhost(), None).unwrap();
+        assert_ne!(third, fourth);
+        assert_eq!(state.get_status().unwrap().registered, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_identify_past_capacity() {
+        let mut state = state(3);
+        let total_nodes = state.config.config.total_nodes.get();
+        for index in 0..total_nodes {
+            state
+                .post_identity(localhost(), Some(index.to_string()))
+                .unwrap();
+        }
+        assert!(state
+            .post_identity(localhost(), Some("new".to_string()))
+            .is_err());
+        // A node re-joining still gets its index back at capacity
+        assert_eq!(
+            state
+                .post_identity(localhost(), Some("0".to_string()))
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_post_ready() {
+        let mut state = state(2);
+        let first = state.post_identity(localhost(), None).unwrap();
+        let second = state.post_identity(localhost(), None).unwrap();
+
+        // Only nodes that identified can post they are ready
+        assert!(state.post_ready(second + 1).is_err());
+        assert!(state.get_start().is_err());
+
+        // Posting twice doesn't count the node twice
+        state.post_ready(first).unwrap();
+        state.post_ready(first).unwrap();
+        assert!(state.get_start().is_err());
+        let status = state.get_status().unwrap();
+        assert_eq!(status.ready, vec![first]);
+        assert!(status.missing.contains(&second));
+        assert!(!status.missing.contains(&first));
+
+        state.post_ready(second).unwrap();
+        assert!(state.get_start().unwrap());
+        assert_eq!(state.get_status().unwrap().ready, vec![first, second]);
+    }
+}
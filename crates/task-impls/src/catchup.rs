@@ -0,0 +1,456 @@
+use crate::events::HotShotEvent;
+use async_lock::RwLock;
+use commit::{Commitment, Committable};
+use hotshot_task::{
+    event_stream::{ChannelStream, EventStream},
+    task::{HotShotTaskCompleted, TS},
+    task_impls::HSTWithEvent,
+};
+use hotshot_types::{
+    consensus::{Consensus, View},
+    data::{Leaf, LeafType},
+    message::{CatchupRequest, CatchupResponse, SequencingMessage},
+    traits::{
+        consensus_api::ConsensusApi,
+        node_implementation::{NodeImplementation, NodeType, QuorumMembership},
+        state::ConsensusTime,
+    },
+    utils::ViewInner,
+    vote2::{Certificate2, HasViewNumber},
+};
+use snafu::Snafu;
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+use tracing::{debug, error, instrument, warn};
+
+/// Maximum number of leaves sent in a single catchup response. A requester that needs more asks
+/// again, starting from the oldest leaf it received.
+pub const MAX_CATCHUP_LEAVES: usize = 100;
+
+/// Number of views after which a request for leaves that hasn't been answered is given up on
+pub const CATCHUP_REQUEST_TIMEOUT_VIEWS: u64 = 10;
+
+#[derive(Snafu, Debug)]
+/// Error type for the catchup task
+pub struct CatchupTaskError {}
+
+/// Tracks state of the catchup task, which fetches leaves this node missed from its peers and
+/// serves the leaves it has to peers that fell behind
+pub struct CatchupTaskState<
+    TYPES: NodeType,
+    I: NodeImplementation<TYPES, Leaf = Leaf<TYPES>, ConsensusMessage = SequencingMessage<TYPES, I>>,
+    A: ConsensusApi<TYPES, Leaf<TYPES>, I> + 'static,
+> {
+    /// The state's api
+    pub api: A,
+
+    /// View number this view is executing in.
+    pub cur_view: TYPES::Time,
+
+    /// Reference to consensus. Fetched leaves are inserted here.
+    pub consensus: Arc<RwLock<Consensus<TYPES, Leaf<TYPES>>>>,
+
+    /// Leaves we have asked a peer for, and the view in which we asked.
+    ///
+    /// Each of them is certified by a QC we have already validated, which is what lets us trust
+    /// a response containing them. Requests are given up on after
+    /// [`CATCHUP_REQUEST_TIMEOUT_VIEWS`] views.
+    pub outstanding: HashMap<Commitment<Leaf<TYPES>>, TYPES::Time>,
+
+    /// Ranges of decided leaves we have asked a peer for, as `(from, to)`, and the view in which
+    /// we asked. Given up on like [`outstanding`](Self::outstanding).
+    pub outstanding_ranges: HashMap<(TYPES::Time, TYPES::Time), TYPES::Time>,
+
+    /// Membership of the quorum, which the QCs in responses are validated against
+    pub quorum_membership: QuorumMembership<TYPES, I>,
+
+    /// Global events stream to publish events
+    pub event_stream: ChannelStream<HotShotEvent<TYPES, I>>,
+
+    /// This state's ID
+    pub id: u64,
+}
+
+impl<
+        TYPES: NodeType,
+        I: NodeImplementation<
+            TYPES,
+            Leaf = Leaf<TYPES>,
+            ConsensusMessage = SequencingMessage<TYPES, I>,
+        >,
+        A: ConsensusApi<TYPES, Leaf<TYPES>, I> + 'static,
+    > CatchupTaskState<TYPES, I, A>
+{
+    /// main task event handler
+    #[instrument(skip_all, fields(id = self.id, view = *self.cur_view), name = "Catchup Task", level = "error")]
+    pub async fn handle_event(
+        &mut self,
+        event: HotShotEvent<TYPES, I>,
+    ) -> Option<HotShotTaskCompleted> {
+        match event {
+            HotShotEvent::LeafMissing(commitment, peer) => {
+                if self
+                    .outstanding
+                    .get(&commitment)
+                    .is_some_and(|requested| *requested >= self.cur_view)
+                {
+                    // Already asked for it in this view, give the peer a chance to answer.
+                    return None;
+                }
+                let stop_before = self.consensus.read().await.last_decided_view;
+                self.request_ancestors(commitment, stop_before, peer).await;
+            }
+            HotShotEvent::DecidedLeavesMissing(from, to, peer) => {
+                debug!(
+                    "Requesting decided leaves {:?}..={:?} from {:?}",
+                    from, to, peer
+                );
+                self.outstanding_ranges.insert((from, to), self.cur_view);
+                self.event_stream
+                    .publish(HotShotEvent::CatchupRequestSend(
+                        CatchupRequest::ViewRange { from, to },
+                        self.api.public_key().clone(),
+                        peer,
+                    ))
+                    .await;
+            }
+            HotShotEvent::CatchupRequestRecv(request, sender) => {
+                let leaves = match &request {
+                    CatchupRequest::Ancestors {
+                        commitment,
+                        stop_before,
+                    } => self.find_ancestors(*commitment, *stop_before).await,
+                    CatchupRequest::ViewRange { from, to } => self.find_range(*from, *to).await,
+                };
+                debug!(
+                    "Answering catchup request from {:?} with {} leaves",
+                    sender,
+                    leaves.len()
+                );
+                self.event_stream
+                    .publish(HotShotEvent::CatchupResponseSend(
+                        CatchupResponse { request, leaves },
+                        self.api.public_key().clone(),
+                        sender,
+                    ))
+                    .await;
+            }
+            HotShotEvent::CatchupResponseRecv(response, sender) => {
+                self.handle_response(response, sender).await;
+            }
+            HotShotEvent::ViewChange(view) => {
+                if view > self.cur_view {
+                    self.cur_view = view;
+                    self.outstanding
+                        .retain(|_, requested| **requested + CATCHUP_REQUEST_TIMEOUT_VIEWS > *view);
+                    self.outstanding_ranges
+                        .retain(|_, requested| **requested + CATCHUP_REQUEST_TIMEOUT_VIEWS > *view);
+                }
+            }
+            HotShotEvent::Shutdown => return Some(HotShotTaskCompleted::ShutDown),
+            _ => {}
+        }
+        None
+    }
+
+    /// Ask `peer` for the leaf with `commitment` and its ancestors after `stop_before`.
+    async fn request_ancestors(
+        &mut self,
+        commitment: Commitment<Leaf<TYPES>>,
+        stop_before: TYPES::Time,
+        peer: TYPES::SignatureKey,
+    ) {
+        debug!("Requesting leaf {:?} from {:?}", commitment, peer);
+        self.outstanding.insert(commitment, self.cur_view);
+        self.event_stream
+            .publish(HotShotEvent::CatchupRequestSend(
+                CatchupRequest::Ancestors {
+                    commitment,
+                    stop_before,
+                },
+                self.api.public_key().clone(),
+                peer,
+            ))
+            .await;
+    }
+
+    /// Look up a leaf, first among the undecided and recently decided leaves in [`Consensus`],
+    /// then in the storage. The block payload is filled in if we have it.
+    async fn find_leaf(&self, commitment: Commitment<Leaf<TYPES>>) -> Option<Leaf<TYPES>> {
+        {
+            let consensus = self.consensus.read().await;
+            if let Some(leaf) = consensus.saved_leaves.get(&commitment) {
+                let mut leaf = leaf.clone();
+                if let Some(payload) = consensus
+                    .saved_block_payloads
+                    .get(leaf.get_payload_commitment())
+                {
+                    if let Err(e) = leaf.fill_block_payload(payload.clone()) {
+                        error!("Saved block payload and commitment don't match: {:?}", e);
+                    }
+                }
+                return Some(leaf);
+            }
+        }
+        match self.api.get_decided_leaf(commitment).await {
+            Ok(leaf) => leaf,
+            Err(e) => {
+                error!("Could not load decided leaf for catchup: {:?}", e);
+                None
+            }
+        }
+    }
+
+    /// Collect the leaf with `commitment` and its ancestors, newest first, stopping before the
+    /// leaf of view `stop_before` or at the first leaf we don't have.
+    async fn find_ancestors(
+        &self,
+        commitment: Commitment<Leaf<TYPES>>,
+        stop_before: TYPES::Time,
+    ) -> Vec<Leaf<TYPES>> {
+        let mut leaves = Vec::new();
+        let mut next = commitment;
+        while leaves.len() < MAX_CATCHUP_LEAVES {
+            let Some(leaf) = self.find_leaf(next).await else {
+                break;
+            };
+            if leaf.get_view_number() <= stop_before {
+                break;
+            }
+            next = leaf.get_parent_commitment();
+            leaves.push(leaf);
+        }
+        leaves
+    }
+
+    /// Collect the decided leaves with a view number in `from..=to`, newest first, up to
+    /// [`MAX_CATCHUP_LEAVES`] of them starting from `from`.
+    ///
+    /// The decided leaf after the last one collected is sent along if we have it, as its justify
+    /// QC is what certifies that leaf to the requester.
+    async fn find_range(&self, from: TYPES::Time, to: TYPES::Time) -> Vec<Leaf<TYPES>> {
+        let lookahead = u64::try_from(MAX_CATCHUP_LEAVES).unwrap_or(u64::MAX);
+        let until = TYPES::Time::new(to.saturating_add(lookahead));
+        match self.api.get_decided_leaves(from, until).await {
+            Ok(mut leaves) => {
+                let in_range = leaves
+                    .iter()
+                    .take_while(|leaf| leaf.get_view_number() <= to)
+                    .count()
+                    .min(MAX_CATCHUP_LEAVES);
+                leaves.truncate(in_range + 1);
+                leaves.reverse();
+                leaves
+            }
+            Err(e) => {
+                error!("Could not load decided leaves for catchup: {:?}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Verify the leaves in a catchup response and add them to [`Consensus`], or to the storage
+    /// if they have already been decided. Responses to requests we didn't send, or have given up
+    /// on, are dropped.
+    ///
+    /// An undecided leaf is only accepted if its commitment is certified by a QC we trust: one
+    /// we requested the leaf for, one held by a leaf we already have, the justify QC of a leaf
+    /// accepted earlier in the same response, or a justify QC in the response that is signed by
+    /// the quorum.
+    ///
+    /// Being certified doesn't make a leaf decided, as the certified leaf may be on a fork that
+    /// was abandoned. A leaf at or before our last decided view is only accepted if it is an
+    /// ancestor, by parent commitment, of a leaf we know to be decided: our last decided leaf,
+    /// the stored decided leaf after a requested range, or a leaf decided by a 3-chain in the
+    /// response, i.e. a leaf whose child is from the very next view and carries a QC on the
+    /// child from the view after that, signed by the quorum.
+    ///
+    /// Leaves are checked newest first so that a chain of ancestors verifies link by link.
+    /// Leaves outside a requested range only carry QCs and are never added.
+    async fn handle_response(
+        &mut self,
+        response: CatchupResponse<TYPES, Leaf<TYPES>>,
+        sender: TYPES::SignatureKey,
+    ) {
+        let range = match response.request {
+            CatchupRequest::ViewRange { from, to } => {
+                if self.outstanding_ranges.remove(&(from, to)).is_none() {
+                    warn!("Dropping unsolicited catchup response from {:?}", sender);
+                    return;
+                }
+                Some((from, to))
+            }
+            CatchupRequest::Ancestors { commitment, .. } => {
+                if !self.outstanding.contains_key(&commitment) {
+                    warn!("Dropping unsolicited catchup response from {:?}", sender);
+                    return;
+                }
+                None
+            }
+        };
+        let mut leaves = response.leaves;
+        leaves.sort_by_key(|leaf| Reverse(leaf.get_view_number()));
+
+        // The stored decided leaf after a range links the range to our decided history.
+        let mut decided: HashSet<_> = HashSet::new();
+        if let Some((_, to)) = range {
+            let lookahead = u64::try_from(MAX_CATCHUP_LEAVES).unwrap_or(u64::MAX);
+            let after = self
+                .api
+                .get_decided_leaves(
+                    TYPES::Time::new(to.saturating_add(1)),
+                    TYPES::Time::new(to.saturating_add(lookahead)),
+                )
+                .await;
+            match after {
+                Ok(after) => decided.extend(after.first().map(Committable::commit)),
+                Err(e) => error!("Could not load decided leaves for catchup: {:?}", e),
+            }
+        }
+
+        // Leaves certified by a quorum-signed QC whose leaf is from the very next view, which is
+        // how the consensus task extends a chain.
+        let certified_by_next: HashSet<_> = leaves
+            .iter()
+            .filter(|leaf| {
+                !leaf.justify_qc.is_genesis
+                    && leaf.justify_qc.get_view_number() + 1 == leaf.get_view_number()
+                    && leaf.justify_qc.is_valid_cert(&self.quorum_membership)
+            })
+            .map(|leaf| leaf.justify_qc.get_data().leaf_commit)
+            .collect();
+
+        let mut consensus = self.consensus.write().await;
+        decided.insert(consensus.get_decided_leaf().commit());
+        let mut trusted: HashSet<_> = consensus
+            .saved_leaves
+            .values()
+            .map(LeafType::get_parent_commitment)
+            .chain(self.outstanding.keys().copied())
+            .collect();
+        trusted.insert(consensus.high_qc.get_data().leaf_commit);
+
+        let mut decided_leaves = Vec::new();
+        let mut oldest_leaf = None;
+        for mut leaf in leaves {
+            let commitment = leaf.commit();
+            let view = leaf.get_view_number();
+            let is_decided = decided.contains(&commitment);
+            if is_decided {
+                decided.insert(leaf.get_parent_commitment());
+            } else if certified_by_next.contains(&commitment)
+                && leaf.justify_qc.get_view_number() + 1 == view
+                && leaf.justify_qc.get_data().leaf_commit == leaf.get_parent_commitment()
+            {
+                // The parent, this leaf and the leaf certifying it form a 3-chain of consecutive
+                // views, which decides the parent.
+                decided.insert(leaf.get_parent_commitment());
+            }
+            let is_trusted = if view > consensus.last_decided_view {
+                trusted.contains(&commitment)
+            } else {
+                is_decided
+            };
+            if is_trusted {
+                trusted.insert(leaf.get_parent_commitment());
+            } else if !leaf.justify_qc.is_genesis
+                && leaf.justify_qc.is_valid_cert(&self.quorum_membership)
+            {
+                // The quorum signed the QC, so it certifies the parent even though this leaf
+                // can't be verified itself.
+                trusted.insert(leaf.justify_qc.get_data().leaf_commit);
+            }
+            if range.is_some_and(|(from, to)| view < from || view > to) {
+                continue;
+            }
+            if !is_trusted {
+                warn!(
+                    "Dropping unverified leaf for view {:?} from catchup response of {:?}",
+                    view, sender
+                );
+                continue;
+            }
+            self.outstanding.remove(&commitment);
+
+            // The payload isn't part of the leaf commitment, so check it against the header.
+            if let Some(payload) = leaf.block_payload.take() {
+                if let Err(e) = leaf.fill_block_payload(payload) {
+                    warn!("Dropping block payload from catchup response: {:?}", e);
+                }
+            }
+
+            if view > consensus.last_decided_view {
+                if let Some(payload) = leaf.get_block_payload() {
+                    consensus.saved_block_payloads.insert(payload);
+                }
+                consensus.state_map.insert(
+                    view,
+                    View {
+                        view_inner: ViewInner::Leaf { leaf: commitment },
+                    },
+                );
+                consensus.saved_leaves.insert(commitment, leaf.clone());
+            } else {
+                decided_leaves.push(leaf.clone());
+            }
+            oldest_leaf = Some(leaf);
+        }
+        let last_decided_view = consensus.last_decided_view;
+        // If the chain still has a gap after the oldest leaf we got, keep walking back.
+        let next_missing = oldest_leaf.and_then(|leaf| {
+            let parent = leaf.get_parent_commitment();
+            (!leaf.justify_qc.is_genesis
+                && leaf.justify_qc.get_view_number() > last_decided_view
+                && !consensus.saved_leaves.contains_key(&parent))
+            .then_some(parent)
+        });
+        drop(consensus);
+
+        if !decided_leaves.is_empty() {
+            if let Err(e) = self.api.store_leaves(decided_leaves).await {
+                error!("Could not store decided leaves from catchup: {:?}", e);
+            }
+        }
+        if let Some(parent) = next_missing {
+            self.request_ancestors(parent, last_decided_view, sender)
+                .await;
+        }
+    }
+
+    /// Filter the catchup events.
+    pub fn filter(event: &HotShotEvent<TYPES, I>) -> bool {
+        matches!(
+            event,
+            HotShotEvent::LeafMissing(_, _)
+                | HotShotEvent::DecidedLeavesMissing(_, _, _)
+                | HotShotEvent::CatchupRequestRecv(_, _)
+                | HotShotEvent::CatchupResponseRecv(_, _)
+                | HotShotEvent::ViewChange(_)
+                | HotShotEvent::Shutdown
+        )
+    }
+}
+
+/// task state implementation for Catchup Task
+impl<
+        TYPES: NodeType,
+        I: NodeImplementation<
+            TYPES,
+            Leaf = Leaf<TYPES>,
+            ConsensusMessage = SequencingMessage<TYPES, I>,
+        >,
+        A: ConsensusApi<TYPES, Leaf<TYPES>, I> + 'static,
+    > TS for CatchupTaskState<TYPES, I, A>
+{
+}
+
+/// Type alias for Catchup Task Types
+pub type CatchupTaskTypes<TYPES, I, A> = HSTWithEvent<
+    CatchupTaskError,
+    HotShotEvent<TYPES, I>,
+    ChannelStream<HotShotEvent<TYPES, I>>,
+    CatchupTaskState<TYPES, I, A>,
+>;
@@ -1,5 +1,5 @@
 use crate::events::HotShotEvent;
-use async_compatibility_layer::art::{async_sleep, async_spawn};
+use async_compatibility_layer::art::async_spawn;
 use async_lock::{RwLock, RwLockUpgradableReadGuard};
 #[cfg(async_executor_impl = "async-std")]
 use async_std::task::JoinHandle;
@@ -18,6 +18,7 @@ use hotshot_task::{
 use hotshot_types::{
     block_impl::{VIDBlockPayload, VIDTransaction},
     certificate::{DACertificate, QuorumCertificate, TimeoutCertificate, VIDCertificate},
+    clock::Clock,
     consensus::{Consensus, View},
     data::{Leaf, LeafType, ProposalType, QuorumProposal},
     event::{Event, EventType},
@@ -27,7 +28,10 @@ use hotshot_types::{
     traits::{
         block_contents::BlockHeader,
         consensus_api::ConsensusApi,
-        election::{ConsensusExchange, QuorumExchangeType, SignedCertificate, TimeoutExchangeType},
+        election::{
+            ConsensusExchange, Membership, QuorumExchangeType, SignedCertificate,
+            TimeoutExchangeType,
+        },
         network::{CommunicationChannel, ConsensusIntentEvent},
         node_implementation::{
             CommitteeEx, NodeImplementation, NodeType, QuorumEx, QuorumMembership, TimeoutEx,
@@ -91,6 +95,8 @@ pub struct ConsensusTaskState<
     pub consensus: Arc<RwLock<Consensus<TYPES, Leaf<TYPES>>>>,
     /// View timeout from config.
     pub timeout: u64,
+    /// Clock to time views with
+    pub clock: Clock,
     /// View number this view is executing in.
     pub cur_view: TYPES::Time,
 
@@ -144,6 +150,10 @@ pub struct ConsensusTaskState<
 
     /// The most Recent QC we've formed from votes, if we've formed it.
     pub qc: Option<QuorumCertificate<TYPES, Commitment<I::Leaf>>>,
+
+    /// The newest undecided view, high QC view and locked view of the undecided state that was
+    /// last persisted, so an unchanged state is not written again.
+    pub stored_undecided_views: Option<(TYPES::Time, TYPES::Time, TYPES::Time)>,
 }
 
 /// State for the vote collection task.  This handles the building of a QC from a votes received
@@ -593,6 +603,8 @@ where
                 self.da_certs.remove(&v);
             }
             self.cur_view = new_view;
+            self.quorum_exchange.membership().enter_view(new_view);
+            self.timeout_exchange.membership().enter_view(new_view);
 
             // Poll the future leader for lookahead
             let lookahead_view = new_view + LOOK_AHEAD;
@@ -633,11 +645,12 @@ where
             let timeout = self.timeout;
             self.timeout_task = async_spawn({
                 let stream = self.event_stream.clone();
+                let clock = self.clock.clone();
                 // Nuance: We timeout on the view + 1 here because that means that we have
                 // not seen evidence to transition to this new view
                 let view_number = self.cur_view + 1;
                 async move {
-                    async_sleep(Duration::from_millis(timeout)).await;
+                    clock.sleep(Duration::from_millis(timeout)).await;
                     stream
                         .publish(HotShotEvent::Timeout(TYPES::Time::new(*view_number)))
                         .await;
@@ -758,6 +771,15 @@ where
                         },
                     );
                     consensus.saved_leaves.insert(leaf.commit(), leaf.clone());
+                    drop(consensus);
+
+                    // The parent is certified by a valid QC, so the leader must have it.
+                    self.event_stream
+                        .publish(HotShotEvent::LeafMissing(
+                            justify_qc.get_data().leaf_commit,
+                            sender,
+                        ))
+                        .await;
 
                     return;
                 };
@@ -912,20 +934,23 @@ where
                 if new_commit_reached {
                     consensus.locked_view = new_locked_view;
                 }
+                let mut decided_leaves = Vec::new();
+                let mut decide_sent = None;
                 #[allow(clippy::cast_precision_loss)]
                 if new_decide_reached {
                     debug!("about to publish decide");
+                    decided_leaves = leaf_views.clone();
                     self.event_stream
                         .publish(HotShotEvent::LeafDecided(leaf_views.clone()))
                         .await;
-                    let decide_sent = self.output_event_stream.publish(Event {
+                    decide_sent = Some(self.output_event_stream.publish(Event {
                         view_number: consensus.last_decided_view,
                         event: EventType::Decide {
                             leaf_chain: Arc::new(leaf_views),
                             qc: Arc::new(new_decide_qc.unwrap()),
                             block_size: Some(included_txns_set.len().try_into().unwrap()),
                         },
-                    });
+                    }));
                     let old_anchor_view = consensus.last_decided_view;
                     consensus
                         .collect_garbage(old_anchor_view, new_anchor_view)
@@ -942,17 +967,25 @@ where
                         .metrics
                         .number_of_views_per_decide_event
                         .add_point(cur_number_of_views_per_decide_event as f64);
-
-                    // We're only storing the last QC. We could store more but we're realistically only going to retrieve the last one.
-                    if let Err(e) = self.api.store_leaf(old_anchor_view, leaf).await {
-                        error!("Could not insert new anchor into the storage API: {:?}", e);
-                    }
-
                     debug!("Sending Decide for view {:?}", consensus.last_decided_view);
                     debug!("Decided txns len {:?}", included_txns_set.len());
-                    decide_sent.await;
                 }
 
+                // Snapshot what we haven't decided yet so we can pick up from here after a restart,
+                // unless it is the same as what we persisted last time.
+                let undecided_views = (
+                    consensus
+                        .state_map
+                        .keys()
+                        .next_back()
+                        .copied()
+                        .unwrap_or(view),
+                    consensus.high_qc.view_number,
+                    consensus.locked_view,
+                );
+                let undecided = (self.stored_undecided_views != Some(undecided_views))
+                    .then(|| consensus.undecided_state());
+
                 let new_view = self.current_proposal.clone().unwrap().view_number + 1;
                 // In future we can use the mempool model where we fetch the proposal if we don't have it, instead of having to wait for it here
                 // This is for the case where we form a QC but have not yet seen the previous proposal ourselves
@@ -963,6 +996,28 @@ where
                 let qc = consensus.high_qc.clone();
 
                 drop(consensus);
+
+                // Store the whole decided chain, not just the new anchor, so the storage can be
+                // queried for the decided history. The decided leaves and the undecided state are
+                // written in one commit, outside of the consensus lock.
+                if !decided_leaves.is_empty() || undecided.is_some() {
+                    let stores_undecided = undecided.is_some();
+                    match self
+                        .api
+                        .store_consensus_state(decided_leaves, undecided)
+                        .await
+                    {
+                        Ok(()) if stores_undecided => {
+                            self.stored_undecided_views = Some(undecided_views);
+                        }
+                        Ok(()) => {}
+                        Err(e) => error!("Could not persist consensus state: {:?}", e),
+                    }
+                }
+                if let Some(decide_sent) = decide_sent {
+                    decide_sent.await;
+                }
+
                 if should_propose {
                     debug!(
                         "Attempting to publish proposal after voting; now in view: {}",
@@ -1011,7 +1066,7 @@ where
                 let new_accumulator = VoteAccumulator2 {
                     vote_outcomes: HashMap::new(),
                     sig_lists: Vec::new(),
-                    signers: bitvec![0; self.quorum_exchange.total_nodes()],
+                    signers: bitvec![0; self.quorum_exchange.total_nodes(vote.get_view_number())],
                     phantom: PhantomData,
                 };
 
@@ -1022,9 +1077,11 @@ where
                 // https://github.com/EspressoSystems/HotShot/issues/1797
                 let timeout_accumulator = TimeoutVoteAccumulator {
                     da_vote_outcomes: HashMap::new(),
-                    success_threshold: self.timeout_exchange.success_threshold(),
+                    success_threshold: self
+                        .timeout_exchange
+                        .success_threshold(vote.get_view_number()),
                     sig_lists: Vec::new(),
-                    signers: bitvec![0; self.timeout_exchange.total_nodes()],
+                    signers: bitvec![0; self.timeout_exchange.total_nodes(vote.get_view_number())],
                     phantom: PhantomData,
                 };
 
@@ -1097,10 +1154,10 @@ where
                 let new_accumulator = TimeoutVoteAccumulator {
                     da_vote_outcomes: HashMap::new(),
 
-                    success_threshold: self.timeout_exchange.success_threshold(),
+                    success_threshold: self.timeout_exchange.success_threshold(vote.get_view()),
 
                     sig_lists: Vec::new(),
-                    signers: bitvec![0; self.timeout_exchange.total_nodes()],
+                    signers: bitvec![0; self.timeout_exchange.total_nodes(vote.get_view())],
                     phantom: PhantomData,
                 };
 
@@ -1113,7 +1170,7 @@ where
                 let quorum_accumulator = VoteAccumulator2 {
                     vote_outcomes: HashMap::new(),
                     sig_lists: Vec::new(),
-                    signers: bitvec![0; self.quorum_exchange.total_nodes()],
+                    signers: bitvec![0; self.quorum_exchange.total_nodes(vote.get_view())],
                     phantom: PhantomData,
                 };
 
@@ -319,9 +319,9 @@ where
 
                 let new_accumulator = DAVoteAccumulator {
                     da_vote_outcomes: HashMap::new(),
-                    success_threshold: self.committee_exchange.success_threshold(),
+                    success_threshold: self.committee_exchange.success_threshold(view),
                     sig_lists: Vec::new(),
-                    signers: bitvec![0; self.committee_exchange.total_nodes()],
+                    signers: bitvec![0; self.committee_exchange.total_nodes(view)],
                     phantom: PhantomData,
                 };
 
@@ -374,6 +374,7 @@ where
                     error!("View changed by more than 1 going to view {:?}", view);
                 }
                 self.cur_view = view;
+                self.committee_exchange.membership().enter_view(view);
 
                 // Inject view info into network
                 let is_da = self
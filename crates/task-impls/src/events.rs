@@ -5,7 +5,7 @@ use either::Either;
 use hotshot_types::{
     certificate::{DACertificate, TimeoutCertificate, VIDCertificate},
     data::{DAProposal, VidDisperse},
-    message::Proposal,
+    message::{CatchupRequest, CatchupResponse, Proposal, VidShareRequest, VidShareResponse},
     simple_certificate::QuorumCertificate2,
     simple_vote::QuorumVote,
     traits::node_implementation::{
@@ -64,8 +64,8 @@ pub enum HotShotEvent<TYPES: NodeType, I: NodeImplementation<TYPES>> {
     ViewSyncTrigger(TYPES::Time),
     /// A consensus view has timed out; emitted by a replica in the consensus task; received by the view sync task; internal event only
     Timeout(TYPES::Time),
-    /// Receive transactions from the network
-    TransactionsRecv(Vec<TYPES::Transaction>),
+    /// Receive transactions from the network, each with the node it came from
+    TransactionsRecv(Vec<(TYPES::Transaction, TYPES::SignatureKey)>),
     /// Send transactions to the network
     TransactionSend(TYPES::Transaction, TYPES::SignatureKey),
     /// Event to send block payload commitment from DA leader to the quorum; internal event only
@@ -98,4 +98,55 @@ pub enum HotShotEvent<TYPES: NodeType, I: NodeImplementation<TYPES>> {
     ///
     /// Like [`DACRecv`]
     VidCertRecv(VIDCertificate<TYPES>),
+    /// A leaf certified by a valid QC is missing; emitted by the consensus task; handled by the catchup task, which fetches it from the given peer
+    LeafMissing(Commitment<I::Leaf>, TYPES::SignatureKey),
+    /// The decided leaves with a view number in the given range are missing from the storage;
+    /// emitted by the handle; handled by the catchup task, which fetches them from the given peer
+    DecidedLeavesMissing(TYPES::Time, TYPES::Time, TYPES::SignatureKey),
+    /// Send a catchup request directly to a peer; emitted by the catchup task
+    ///
+    /// The first key is the sender, the second the recipient.
+    CatchupRequestSend(
+        CatchupRequest<TYPES, I::Leaf>,
+        TYPES::SignatureKey,
+        TYPES::SignatureKey,
+    ),
+    /// A catchup request has been received from the network; handled by the catchup task
+    CatchupRequestRecv(CatchupRequest<TYPES, I::Leaf>, TYPES::SignatureKey),
+    /// Send a catchup response directly to a peer; emitted by the catchup task
+    ///
+    /// The first key is the sender, the second the recipient.
+    CatchupResponseSend(
+        CatchupResponse<TYPES, I::Leaf>,
+        TYPES::SignatureKey,
+        TYPES::SignatureKey,
+    ),
+    /// A catchup response has been received from the network; handled by the catchup task
+    CatchupResponseRecv(CatchupResponse<TYPES, I::Leaf>, TYPES::SignatureKey),
+    /// The block payload dispersed in the given view is needed but we don't have it; emitted by the
+    /// system handle; handled by the VID task, which reconstructs it from VID shares
+    VidPayloadMissing(TYPES::Time, Commitment<TYPES::BlockPayload>),
+    /// The block payload dispersed in the given view has been reconstructed from VID shares and
+    /// verified against its commitment; emitted by the VID task
+    VidPayloadRecovered(TYPES::BlockPayload, TYPES::Time),
+    /// Ask a VID storage node for its share of a block payload; emitted by the VID task
+    ///
+    /// The first key is the sender, the second the recipient.
+    VidShareRequestSend(
+        VidShareRequest<TYPES>,
+        TYPES::SignatureKey,
+        TYPES::SignatureKey,
+    ),
+    /// A VID share request has been received from the network; handled by the VID task
+    VidShareRequestRecv(VidShareRequest<TYPES>, TYPES::SignatureKey),
+    /// Send a VID share directly to the node that requested it; emitted by the VID task
+    ///
+    /// The first key is the sender, the second the recipient.
+    VidShareResponseSend(
+        VidShareResponse<TYPES>,
+        TYPES::SignatureKey,
+        TYPES::SignatureKey,
+    ),
+    /// A VID share has been received from the network; handled by the VID task
+    VidShareResponseRecv(VidShareResponse<TYPES>, TYPES::SignatureKey),
 }
@@ -17,9 +17,15 @@ pub mod consensus;
 /// The task which implements the main parts of data availability.
 pub mod da;
 
+/// The task which fetches missing leaves from peers and serves them to peers that fell behind
+pub mod catchup;
+
 /// The task which implements all transaction handling
 pub mod transactions;
 
+/// Defines the mempool the transaction task keeps undecided transactions in
+pub mod mempool;
+
 /// Defines the events passed between tasks
 pub mod events;
 
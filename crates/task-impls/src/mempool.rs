@@ -0,0 +1,313 @@
+use bincode::config::Options;
+use commit::{Commitment, Committable};
+use hotshot_types::{traits::node_implementation::NodeType, EvictionPolicy, MempoolConfig};
+use hotshot_utils::bincode::bincode_opts;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Debug,
+    sync::Arc,
+    time::Instant,
+};
+
+pub use hotshot_types::traits::mempool::{Mempool, MempoolError};
+
+/// A comparator ranking transactions, e.g. by fee. Transactions comparing as
+/// [`Ordering::Less`] are included in blocks first.
+pub type TransactionComparator<T> = Arc<dyn Fn(&T, &T) -> Ordering + Send + Sync>;
+
+/// The order in which a [`DefaultMempool`] hands out transactions
+#[derive(Clone)]
+pub enum MempoolOrdering<T> {
+    /// In the order they arrived
+    Fifo,
+    /// By a user-supplied comparator, with ties broken by arrival order
+    Priority(TransactionComparator<T>),
+}
+
+impl<T> Debug for MempoolOrdering<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Fifo => write!(f, "Fifo"),
+            Self::Priority(_) => write!(f, "Priority"),
+        }
+    }
+}
+
+/// A transaction held by a [`DefaultMempool`]
+#[derive(Clone, Debug)]
+struct MempoolEntry<TYPES: NodeType> {
+    /// The transaction itself
+    transaction: TYPES::Transaction,
+    /// The node we received the transaction from
+    sender: TYPES::SignatureKey,
+    /// Serialized size of the transaction
+    size: u64,
+    /// Position of the transaction in arrival order
+    sequence: u64,
+    /// When the transaction was received
+    received: Instant,
+}
+
+/// [`Mempool`] enforcing the limits of a [`MempoolConfig`]
+#[derive(Clone, Debug)]
+pub struct DefaultMempool<TYPES: NodeType> {
+    /// The limits to enforce
+    config: MempoolConfig,
+    /// The order in which transactions go into blocks
+    ordering: MempoolOrdering<TYPES::Transaction>,
+    /// The transactions held, by commitment
+    entries: HashMap<Commitment<TYPES::Transaction>, MempoolEntry<TYPES>>,
+    /// The transactions held, in arrival order
+    arrivals: BTreeMap<u64, Commitment<TYPES::Transaction>>,
+    /// Number of transactions held per sender
+    per_sender: HashMap<TYPES::SignatureKey, usize>,
+    /// Total serialized size of the transactions held
+    total_bytes: u64,
+    /// Sequence number of the next transaction to arrive
+    next_sequence: u64,
+}
+
+impl<TYPES: NodeType> DefaultMempool<TYPES> {
+    /// Create a mempool handing out transactions in arrival order
+    #[must_use]
+    pub fn new(config: MempoolConfig) -> Self {
+        Self::with_ordering(config, MempoolOrdering::Fifo)
+    }
+
+    /// Create a mempool handing out transactions in the given order
+    #[must_use]
+    pub fn with_ordering(
+        config: MempoolConfig,
+        ordering: MempoolOrdering<TYPES::Transaction>,
+    ) -> Self {
+        Self {
+            config,
+            ordering,
+            entries: HashMap::new(),
+            arrivals: BTreeMap::new(),
+            per_sender: HashMap::new(),
+            total_bytes: 0,
+            next_sequence: 0,
+        }
+    }
+
+    /// Compare two held transactions by the order in which they go into blocks
+    fn compare(&self, a: &MempoolEntry<TYPES>, b: &MempoolEntry<TYPES>) -> Ordering {
+        match &self.ordering {
+            MempoolOrdering::Fifo => a.sequence.cmp(&b.sequence),
+            MempoolOrdering::Priority(comparator) => {
+                comparator(&a.transaction, &b.transaction).then_with(|| a.sequence.cmp(&b.sequence))
+            }
+        }
+    }
+
+    /// The held transactions, optionally only those from `sender`, in the order the eviction
+    /// policy evicts them to make room for `new`
+    fn eviction_order(
+        &self,
+        new: &MempoolEntry<TYPES>,
+        sender: Option<&TYPES::SignatureKey>,
+    ) -> Vec<&MempoolEntry<TYPES>> {
+        let from_sender =
+            |entry: &&MempoolEntry<TYPES>| sender.map_or(true, |sender| entry.sender == *sender);
+        match self.config.eviction_policy {
+            EvictionPolicy::RejectNew => Vec::new(),
+            EvictionPolicy::EvictOldest => self
+                .arrivals
+                .values()
+                .filter_map(|commitment| self.entries.get(commitment))
+                .filter(from_sender)
+                .collect(),
+            EvictionPolicy::EvictLowestPriority => {
+                let mut entries: Vec<_> = self.entries.values().filter(from_sender).collect();
+                entries.sort_by(|a, b| self.compare(b, a));
+                // Never evict a transaction that would go into a block before the new one.
+                entries
+                    .into_iter()
+                    .take_while(|entry| self.compare(new, entry) == Ordering::Less)
+                    .collect()
+            }
+        }
+    }
+
+    /// Pick the transactions to evict so that `new` fits
+    ///
+    /// # Errors
+    /// Returns a [`MempoolError`] if the eviction policy doesn't make enough room for `new`.
+    fn victims(
+        &self,
+        new: &MempoolEntry<TYPES>,
+    ) -> Result<Vec<Commitment<TYPES::Transaction>>, MempoolError> {
+        let mut victims = Vec::new();
+
+        // Make room among the sender's own transactions first, so that a single sender can't
+        // push out everyone else's.
+        let sent = self
+            .per_sender
+            .get(&new.sender)
+            .copied()
+            .unwrap_or_default();
+        if sent >= self.config.max_transactions_per_sender {
+            let excess = sent + 1 - self.config.max_transactions_per_sender;
+            victims.extend(
+                self.eviction_order(new, Some(&new.sender))
+                    .into_iter()
+                    .take(excess),
+            );
+            if victims.len() < excess {
+                return Err(MempoolError::SenderLimit);
+            }
+        }
+
+        let mut count = self.entries.len() - victims.len();
+        let mut bytes = self.total_bytes - victims.iter().map(|entry| entry.size).sum::<u64>();
+        let mut candidates = self.eviction_order(new, None).into_iter();
+        while count >= self.config.max_transactions || bytes + new.size > self.config.max_bytes {
+            let victim = candidates.next().ok_or(MempoolError::Full)?;
+            if victims
+                .iter()
+                .any(|chosen| chosen.sequence == victim.sequence)
+            {
+                continue;
+            }
+            count -= 1;
+            bytes -= victim.size;
+            victims.push(victim);
+        }
+        Ok(victims
+            .into_iter()
+            .map(|entry| entry.transaction.commit())
+            .collect())
+    }
+}
+
+impl<TYPES: NodeType> Mempool<TYPES> for DefaultMempool<TYPES> {
+    fn create(config: MempoolConfig) -> Self {
+        Self::new(config)
+    }
+
+    fn insert(
+        &mut self,
+        transaction: TYPES::Transaction,
+        sender: TYPES::SignatureKey,
+        now: Instant,
+    ) -> Result<(), MempoolError> {
+        let commitment = transaction.commit();
+        if self.entries.contains_key(&commitment) {
+            return Err(MempoolError::Duplicate);
+        }
+        let size = bincode_opts()
+            .serialized_size(&transaction)
+            .unwrap_or_default();
+        if size > self.config.max_bytes {
+            return Err(MempoolError::TooLarge { size });
+        }
+        // Keep arrival times monotonic, so that the expired transactions are always the oldest
+        // arrivals.
+        let received = self
+            .arrivals
+            .last_key_value()
+            .and_then(|(_, last)| self.entries.get(last))
+            .map_or(now, |last| last.received.max(now));
+        let entry = MempoolEntry {
+            transaction,
+            sender,
+            size,
+            sequence: self.next_sequence,
+            received,
+        };
+
+        for victim in &self.victims(&entry)? {
+            self.remove(victim);
+        }
+
+        self.next_sequence += 1;
+        self.total_bytes += size;
+        *self.per_sender.entry(entry.sender.clone()).or_default() += 1;
+        self.arrivals.insert(entry.sequence, commitment);
+        self.entries.insert(commitment, entry);
+        Ok(())
+    }
+
+    fn remove(
+        &mut self,
+        commitment: &Commitment<TYPES::Transaction>,
+    ) -> Option<TYPES::Transaction> {
+        let entry = self.entries.remove(commitment)?;
+        self.arrivals.remove(&entry.sequence);
+        self.total_bytes -= entry.size;
+        if let Some(count) = self.per_sender.get_mut(&entry.sender) {
+            *count -= 1;
+            if *count == 0 {
+                self.per_sender.remove(&entry.sender);
+            }
+        }
+        Some(entry.transaction)
+    }
+
+    fn contains(&self, commitment: &Commitment<TYPES::Transaction>) -> bool {
+        self.entries.contains_key(commitment)
+    }
+
+    fn remove_expired(&mut self, now: Instant) -> usize {
+        let ttl = self.config.transaction_ttl;
+        // Arrival times are monotonic, so the expired transactions are the oldest arrivals and we
+        // can stop at the first one that hasn't expired.
+        let mut expired = 0;
+        while let Some((_, commitment)) = self.arrivals.first_key_value() {
+            let commitment = *commitment;
+            let received = self.entries.get(&commitment).map(|entry| entry.received);
+            if received.is_some_and(|received| now.saturating_duration_since(received) < ttl) {
+                break;
+            }
+            self.arrivals.pop_first();
+            if self.remove(&commitment).is_some() {
+                expired += 1;
+            }
+        }
+        expired
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn size_in_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+
+    fn batch(
+        &self,
+        max_count: usize,
+        max_bytes: u64,
+        exclude: &HashSet<Commitment<TYPES::Transaction>>,
+    ) -> Vec<TYPES::Transaction> {
+        let entries: Vec<_> = match self.ordering {
+            MempoolOrdering::Fifo => self
+                .arrivals
+                .values()
+                .filter_map(|commitment| self.entries.get(commitment))
+                .collect(),
+            MempoolOrdering::Priority(_) => {
+                let mut entries: Vec<_> = self.entries.values().collect();
+                entries.sort_by(|a, b| self.compare(a, b));
+                entries
+            }
+        };
+        let mut remaining_bytes = max_bytes;
+        entries
+            .into_iter()
+            .filter(|entry| !exclude.contains(&entry.transaction.commit()))
+            .filter(|entry| {
+                let fits = entry.size <= remaining_bytes;
+                if fits {
+                    remaining_bytes -= entry.size;
+                }
+                fits
+            })
+            .take(max_count)
+            .map(|entry| entry.transaction.clone())
+            .collect()
+    }
+}
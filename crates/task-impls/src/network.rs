@@ -9,8 +9,8 @@ use hotshot_task::{
 use hotshot_types::{
     data::Leaf,
     message::{
-        CommitteeConsensusMessage, GeneralConsensusMessage, Message, MessageKind, Messages,
-        SequencingMessage,
+        CatchupMessage, CommitteeConsensusMessage, GeneralConsensusMessage, Message, MessageKind,
+        Messages, SequencingMessage,
     },
     traits::{
         election::Membership,
@@ -126,9 +126,26 @@ impl<
                 }
                 MessageKind::Data(message) => match message {
                     hotshot_types::message::DataMessage::SubmitTransaction(transaction, _) => {
-                        transactions.push(transaction);
+                        transactions.push((transaction, sender));
                     }
                 },
+                MessageKind::Catchup(message) => {
+                    let event = match message {
+                        CatchupMessage::Request(request) => {
+                            HotShotEvent::CatchupRequestRecv(request, sender)
+                        }
+                        CatchupMessage::Response(response) => {
+                            HotShotEvent::CatchupResponseRecv(response, sender)
+                        }
+                        CatchupMessage::VidShareRequest(request) => {
+                            HotShotEvent::VidShareRequestRecv(request, sender)
+                        }
+                        CatchupMessage::VidShareResponse(response) => {
+                            HotShotEvent::VidShareResponseRecv(response, sender)
+                        }
+                    };
+                    self.event_stream.publish(event).await;
+                }
                 MessageKind::_Unreachable(_) => unimplemented!(),
             };
         }
@@ -140,6 +157,19 @@ impl<
     }
 }
 
+/// Rewrites the messages a node sends, e.g. to make it misbehave in tests
+pub trait OutgoingMessageTransform<TYPES: NodeType, I: NodeImplementation<TYPES>>:
+    Send + Sync
+{
+    /// The messages to send in place of `message`, each with its recipient, or `None` to
+    /// broadcast it
+    fn transform(
+        &self,
+        message: Message<TYPES, I>,
+        recipient: Option<TYPES::SignatureKey>,
+    ) -> Vec<(Message<TYPES, I>, Option<TYPES::SignatureKey>)>;
+}
+
 /// network event task state
 pub struct NetworkEventTaskState<
     TYPES: NodeType,
@@ -155,6 +185,8 @@ pub struct NetworkEventTaskState<
     pub view: TYPES::Time,
     /// phantom data
     pub phantom: PhantomData<MEMBERSHIP>,
+    /// rewrites every message before it is sent, if set
+    pub transform: Option<Arc<dyn OutgoingMessageTransform<TYPES, I>>>,
     // TODO ED Need to add exchange so we can get the recipient key and our own key?
 }
 
@@ -290,6 +322,30 @@ impl<
                 TransmitType::Direct,
                 Some(membership.get_leader(vote.get_view() + 1)),
             ),
+            HotShotEvent::CatchupRequestSend(request, sender, recipient) => (
+                sender,
+                MessageKind::<TYPES, I>::Catchup(CatchupMessage::Request(request)),
+                TransmitType::Direct,
+                Some(recipient),
+            ),
+            HotShotEvent::CatchupResponseSend(response, sender, recipient) => (
+                sender,
+                MessageKind::<TYPES, I>::Catchup(CatchupMessage::Response(response)),
+                TransmitType::Direct,
+                Some(recipient),
+            ),
+            HotShotEvent::VidShareRequestSend(request, sender, recipient) => (
+                sender,
+                MessageKind::<TYPES, I>::Catchup(CatchupMessage::VidShareRequest(request)),
+                TransmitType::Direct,
+                Some(recipient),
+            ),
+            HotShotEvent::VidShareResponseSend(response, sender, recipient) => (
+                sender,
+                MessageKind::<TYPES, I>::Catchup(CatchupMessage::VidShareResponse(response)),
+                TransmitType::Direct,
+                Some(recipient),
+            ),
             HotShotEvent::ViewChange(view) => {
                 self.view = view;
                 return None;
@@ -309,18 +365,25 @@ impl<
             kind: message_kind,
             _phantom: PhantomData,
         };
-        let transmit_result = match transmit_type {
-            TransmitType::Direct => {
-                self.channel
-                    .direct_message(message, recipient.unwrap())
-                    .await
-            }
-            TransmitType::Broadcast => self.channel.broadcast_message(message, membership).await,
+        let recipient = match transmit_type {
+            TransmitType::Direct => Some(recipient.unwrap()),
+            TransmitType::Broadcast => None,
+        };
+        let messages = match &self.transform {
+            Some(transform) => transform.transform(message, recipient),
+            None => vec![(message, recipient)],
         };
 
-        match transmit_result {
-            Ok(()) => {}
-            Err(e) => error!("Failed to send message from network task: {:?}", e),
+        for (message, recipient) in messages {
+            let transmit_result = match recipient {
+                Some(recipient) => self.channel.direct_message(message, recipient).await,
+                None => self.channel.broadcast_message(message, membership).await,
+            };
+
+            match transmit_result {
+                Ok(()) => {}
+                Err(e) => error!("Failed to send message from network task: {:?}", e),
+            }
         }
 
         None
@@ -346,6 +409,10 @@ impl<
                 | HotShotEvent::DACSend(_, _)
                 | HotShotEvent::ViewChange(_)
                 | HotShotEvent::TimeoutVoteSend(_)
+                | HotShotEvent::CatchupRequestSend(_, _, _)
+                | HotShotEvent::CatchupResponseSend(_, _, _)
+                | HotShotEvent::VidShareRequestSend(_, _, _)
+                | HotShotEvent::VidShareResponseSend(_, _, _)
         )
     }
 
@@ -1,10 +1,8 @@
-use crate::events::HotShotEvent;
-use async_compatibility_layer::{
-    art::async_timeout,
-    async_primitives::subscribable_rwlock::{ReadView, SubscribableRwLock},
+use crate::{events::HotShotEvent, mempool::Mempool};
+use async_compatibility_layer::async_primitives::subscribable_rwlock::{
+    ReadView, SubscribableRwLock,
 };
 use async_lock::RwLock;
-use bincode::config::Options;
 use commit::{Commitment, Committable};
 use hotshot_task::{
     event_stream::{ChannelStream, EventStream},
@@ -14,29 +12,22 @@ use hotshot_task::{
 };
 use hotshot_types::{
     block_impl::{VIDBlockPayload, VIDTransaction},
-    certificate::QuorumCertificate,
+    certificate::{QuorumCertificate, VIDCertificate},
+    clock::Clock,
     consensus::Consensus,
-    data::{Leaf, VidDisperse, VidScheme, VidSchemeTrait},
+    data::{Leaf, LeafType, VidDisperse, VidSchemeTrait},
     message::{Message, Proposal, SequencingMessage},
     traits::{
         consensus_api::ConsensusApi,
-        election::{ConsensusExchange, QuorumExchangeType},
-        node_implementation::{NodeImplementation, NodeType, QuorumEx},
+        election::{ConsensusExchange, Membership, QuorumExchangeType},
+        node_implementation::{NodeImplementation, NodeType, QuorumEx, VIDEx},
         BlockPayload,
     },
 };
-use hotshot_utils::bincode::bincode_opts;
 use snafu::Snafu;
-use std::{
-    collections::{HashMap, HashSet},
-    sync::Arc,
-    time::Instant,
-};
+use std::{collections::HashSet, sync::Arc};
 use tracing::{debug, error, instrument, warn};
 
-/// A type alias for `HashMap<Commitment<T>, T>`
-type CommitmentMap<T> = HashMap<Commitment<T>, T>;
-
 #[derive(Snafu, Debug)]
 /// Error type for consensus tasks
 pub struct ConsensusTaskError {}
@@ -46,15 +37,24 @@ pub struct TransactionTaskState<
     TYPES: NodeType,
     I: NodeImplementation<TYPES, Leaf = Leaf<TYPES>, ConsensusMessage = SequencingMessage<TYPES, I>>,
     A: ConsensusApi<TYPES, Leaf<TYPES>, I> + 'static,
+    M: Mempool<TYPES>,
 > where
     QuorumEx<TYPES, I>: ConsensusExchange<
         TYPES,
         Message<TYPES, I>,
         Certificate = QuorumCertificate<TYPES, Commitment<I::Leaf>>,
     >,
+    VIDEx<TYPES, I>: ConsensusExchange<
+        TYPES,
+        Message<TYPES, I>,
+        Certificate = VIDCertificate<TYPES>,
+        Commitment = Commitment<TYPES::BlockPayload>,
+    >,
 {
     /// The state's api
     pub api: A,
+    /// Clock to time transactions and block proposals with
+    pub clock: Clock,
     /// Global registry task for the state
     pub registry: GlobalRegistry,
 
@@ -64,8 +64,8 @@ pub struct TransactionTaskState<
     /// Reference to consensus. Leader will require a read lock on this.
     pub consensus: Arc<RwLock<Consensus<TYPES, Leaf<TYPES>>>>,
 
-    /// A list of undecided transactions
-    pub transactions: Arc<SubscribableRwLock<CommitmentMap<TYPES::Transaction>>>,
+    /// The undecided transactions
+    pub transactions: Arc<SubscribableRwLock<M>>,
 
     /// A list of transactions we've seen decided, but didn't receive
     pub seen_transactions: HashSet<Commitment<TYPES::Transaction>>,
@@ -73,6 +73,9 @@ pub struct TransactionTaskState<
     /// the committee exchange
     pub quorum_exchange: Arc<QuorumEx<TYPES, I>>,
 
+    /// the VID exchange, whose committee stores the shares of the blocks we propose
+    pub vid_exchange: Arc<VIDEx<TYPES, I>>,
+
     /// Global events stream to publish events
     pub event_stream: ChannelStream<HotShotEvent<TYPES, I>>,
 
@@ -91,13 +94,20 @@ impl<
             ConsensusMessage = SequencingMessage<TYPES, I>,
         >,
         A: ConsensusApi<TYPES, Leaf<TYPES>, I> + 'static,
-    > TransactionTaskState<TYPES, I, A>
+        M: Mempool<TYPES>,
+    > TransactionTaskState<TYPES, I, A, M>
 where
     QuorumEx<TYPES, I>: ConsensusExchange<
         TYPES,
         Message<TYPES, I>,
         Certificate = QuorumCertificate<TYPES, Commitment<I::Leaf>>,
     >,
+    VIDEx<TYPES, I>: ConsensusExchange<
+        TYPES,
+        Message<TYPES, I>,
+        Certificate = VIDCertificate<TYPES>,
+        Commitment = Commitment<TYPES::BlockPayload>,
+    >,
 {
     /// main task event handler
     #[instrument(skip_all, fields(id = self.id, view = *self.cur_view), name = "Transaction Handling Task", level = "error")]
@@ -108,35 +118,30 @@ where
     ) -> Option<HotShotTaskCompleted> {
         match event {
             HotShotEvent::TransactionsRecv(transactions) => {
-                let consensus = self.consensus.read().await;
+                let now = self.clock.now();
                 self.transactions
                     .modify(|txns| {
-                        for transaction in transactions {
-                            let size = bincode_opts().serialized_size(&transaction).unwrap_or(0);
-
-                            // If we didn't already know about this transaction, update our mempool metrics.
-                            if !self.seen_transactions.remove(&transaction.commit())
-                                && txns.insert(transaction.commit(), transaction).is_none()
-                            {
-                                consensus.metrics.outstanding_transactions.update(1);
-                                consensus
-                                    .metrics
-                                    .outstanding_transactions_memory_size
-                                    .update(i64::try_from(size).unwrap_or_else(|e| {
-                                        warn!("Conversion failed: {e}. Using the max value.");
-                                        i64::MAX
-                                    }));
+                        let expired = txns.remove_expired(now);
+                        if expired > 0 {
+                            debug!("Dropped {} expired transactions", expired);
+                        }
+                        for (transaction, sender) in transactions {
+                            // Skip transactions we already saw decided.
+                            if self.seen_transactions.remove(&transaction.commit()) {
+                                continue;
+                            }
+                            if let Err(e) = txns.insert(transaction, sender, now) {
+                                debug!("Transaction not admitted to the mempool: {}", e);
                             }
                         }
                     })
                     .await;
+                self.update_mempool_metrics().await;
 
                 return None;
             }
             HotShotEvent::LeafDecided(leaf_chain) => {
                 let mut included_txns = HashSet::new();
-                let mut included_txn_size = 0;
-                let mut included_txn_count = 0;
                 for leaf in leaf_chain {
                     if let Some(payload) = leaf.block_payload {
                         for txn in payload.transaction_commitments() {
@@ -144,41 +149,16 @@ where
                         }
                     }
                 }
-                let consensus = self.consensus.read().await;
-                let txns = self.transactions.cloned().await;
-
-                let _ = included_txns.iter().map(|hash| {
-                    if !txns.contains_key(hash) {
-                        self.seen_transactions.insert(*hash);
-                    }
-                });
-                drop(txns);
                 self.transactions
                     .modify(|txns| {
-                        *txns = txns
-                            .drain()
-                            .filter(|(txn_hash, txn)| {
-                                if included_txns.contains(txn_hash) {
-                                    included_txn_count += 1;
-                                    included_txn_size +=
-                                        bincode_opts().serialized_size(txn).unwrap_or_default();
-                                    false
-                                } else {
-                                    true
-                                }
-                            })
-                            .collect();
+                        for hash in included_txns {
+                            if txns.remove(&hash).is_none() {
+                                self.seen_transactions.insert(hash);
+                            }
+                        }
                     })
                     .await;
-
-                consensus
-                    .metrics
-                    .outstanding_transactions
-                    .update(-included_txn_count);
-                consensus
-                    .metrics
-                    .outstanding_transactions_memory_size
-                    .update(-(i64::try_from(included_txn_size).unwrap_or(i64::MAX)));
+                self.update_mempool_metrics().await;
                 return None;
             }
             HotShotEvent::ViewChange(view) => {
@@ -231,27 +211,29 @@ where
 
                 // TODO move all VID stuff to a new VID task
                 // details here: https://github.com/EspressoSystems/HotShot/issues/1817#issuecomment-1747143528
-                let num_storage_nodes = 8;
+                // Every node of the VID committee stores a share.
+                let num_storage_nodes =
+                    self.vid_exchange.membership().get_committee(view + 1).len();
                 debug!("Prepare VID shares for {} storage nodes", num_storage_nodes);
 
-                // TODO Secure SRS for VID
-                // https://github.com/EspressoSystems/HotShot/issues/1686
-                let srs = hotshot_types::data::test_srs(num_storage_nodes);
-
-                // TODO proper source for VID erasure code rate
-                // https://github.com/EspressoSystems/HotShot/issues/1734
-                let num_chunks = 8;
-
-                let vid = VidScheme::new(num_chunks, num_storage_nodes, &srs).unwrap();
-
-                // TODO Wasteful flattening of tx bytes to accommodate VID API
-                // https://github.com/EspressoSystems/jellyfish/issues/375
-                let mut txns_flatten = Vec::new();
-                for txn in &txns {
-                    txns_flatten.extend(txn.0.clone());
-                }
+                let vid = match self.api.vid().scheme(num_storage_nodes) {
+                    Ok(vid) => vid,
+                    Err(e) => {
+                        error!("Failed to set up VID: {}", e);
+                        return None;
+                    }
+                };
 
-                let vid_disperse = vid.disperse(&txns_flatten).unwrap();
+                // Keep the boundaries between transactions, so that the block payload can be
+                // rebuilt from the bytes recovered from VID shares.
+                let vid_disperse = match vid.disperse(&VIDBlockPayload::encode_transactions(&txns))
+                {
+                    Ok(vid_disperse) => vid_disperse,
+                    Err(e) => {
+                        error!("Failed to disperse block payload: {:?}", e);
+                        return None;
+                    }
+                };
                 let block = VIDBlockPayload {
                     transactions: txns,
                     payload_commitment: vid_disperse.commit,
@@ -303,48 +285,96 @@ impl<
             ConsensusMessage = SequencingMessage<TYPES, I>,
         >,
         A: ConsensusApi<TYPES, Leaf<TYPES>, I> + 'static,
-    > TransactionTaskState<TYPES, I, A>
+        M: Mempool<TYPES>,
+    > TransactionTaskState<TYPES, I, A, M>
 where
     QuorumEx<TYPES, I>: ConsensusExchange<
         TYPES,
         Message<TYPES, I>,
         Certificate = QuorumCertificate<TYPES, Commitment<I::Leaf>>,
     >,
+    VIDEx<TYPES, I>: ConsensusExchange<
+        TYPES,
+        Message<TYPES, I>,
+        Certificate = VIDCertificate<TYPES>,
+        Commitment = Commitment<TYPES::BlockPayload>,
+    >,
 {
+    /// Report the size of the mempool to the consensus metrics
+    async fn update_mempool_metrics(&self) {
+        let txns = self.transactions.read().await;
+        let metrics = &self.consensus.read().await.metrics;
+        metrics.outstanding_transactions.set(txns.len());
+        metrics.outstanding_transactions_memory_size.set(
+            usize::try_from(txns.size_in_bytes()).unwrap_or_else(|e| {
+                warn!("Conversion failed: {e}. Using the max value.");
+                usize::MAX
+            }),
+        );
+    }
+
+    /// Commitments of the transactions included by `parent_leaf` and its ancestors back to the
+    /// last decided leaf. Proposing any of them again would put a duplicate on the chain.
+    ///
+    /// The last decided leaf is included too, in case we haven't handled its `LeafDecided` event
+    /// and removed its transactions from the mempool yet.
+    async fn undecided_transactions(
+        &self,
+        parent_leaf: &Leaf<TYPES>,
+    ) -> HashSet<Commitment<TYPES::Transaction>> {
+        let consensus = self.consensus.read().await;
+        let mut txns = HashSet::new();
+        let mut next = Some(parent_leaf);
+        while let Some(leaf) = next {
+            if leaf.get_view_number() < consensus.last_decided_view {
+                break;
+            }
+            let payload = leaf.get_block_payload().or_else(|| {
+                consensus
+                    .saved_block_payloads
+                    .get(leaf.get_payload_commitment())
+                    .cloned()
+            });
+            match payload {
+                Some(payload) => txns.extend(payload.transaction_commitments()),
+                None => warn!(
+                    "Missing block payload of undecided leaf for view {:?}",
+                    leaf.get_view_number()
+                ),
+            }
+            next = consensus.saved_leaves.get(&leaf.get_parent_commitment());
+        }
+        txns
+    }
+
     #[instrument(skip_all, fields(id = self.id, view = *self.cur_view), name = "Transaction Handling Task", level = "error")]
     async fn wait_for_transactions(
         &self,
-        _parent_leaf: Leaf<TYPES>,
+        parent_leaf: Leaf<TYPES>,
     ) -> Option<Vec<TYPES::Transaction>> {
-        let task_start_time = Instant::now();
+        let task_start_time = self.clock.now();
 
-        // TODO (Keyao) Investigate the use of transaction hash
-        // <https://github.com/EspressoSystems/HotShot/issues/1811>
-        // let parent_leaf = self.parent_leaf().await?;
-        // let previous_used_txns = match parent_leaf.tarnsaction_commitments {
-        //     Some(txns) => txns,
-        //     None => HashSet::new(),
-        // };
+        let previous_used_txns = self.undecided_transactions(&parent_leaf).await;
 
         let receiver = self.transactions.subscribe().await;
 
         loop {
-            let all_txns = self.transactions.cloned().await;
-            debug!("Size of transactions: {}", all_txns.len());
-            // TODO (Keyao) Investigate the use of transaction hash
-            // <https://github.com/EspressoSystems/HotShot/issues/1811>
-            // let unclaimed_txns: Vec<_> = all_txns
-            //     .iter()
-            //     .filter(|(txn_hash, _txn)| !previous_used_txns.contains(txn_hash))
-            //     .collect();
-            let unclaimed_txns = all_txns;
-
-            let time_past = task_start_time.elapsed();
-            if unclaimed_txns.len() < self.api.min_transactions()
+            let unclaimed_txns = {
+                let txns = self.transactions.read().await;
+                txns.len()
+                    - previous_used_txns
+                        .iter()
+                        .filter(|txn_hash| txns.contains(txn_hash))
+                        .count()
+            };
+            debug!("Number of unclaimed transactions: {}", unclaimed_txns);
+
+            let time_past = self.clock.now().duration_since(task_start_time);
+            if unclaimed_txns < self.api.min_transactions()
                 && (time_past < self.api.propose_max_round_time())
             {
                 let duration = self.api.propose_max_round_time() - time_past;
-                let result = async_timeout(duration, receiver.recv()).await;
+                let result = self.clock.timeout(duration, receiver.recv()).await;
                 match result {
                     Err(_) => {
                         // Fall through below to updating new block
@@ -362,20 +392,19 @@ where
             }
             break;
         }
-        let all_txns = self.transactions.cloned().await;
-        // TODO (Keyao) Investigate the use of transaction hash
-        // <https://github.com/EspressoSystems/HotShot/issues/1811>
-        let txns: Vec<TYPES::Transaction> = all_txns.values().cloned().collect();
-        // let txns: Vec<TYPES::Transaction> = all_txns
-        //     .iter()
-        //     .filter_map(|(txn_hash, txn)| {
-        //         if previous_used_txns.contains(txn_hash) {
-        //             None
-        //         } else {
-        //             Some(txn.clone())
-        //         }
-        //     })
-        //     .collect();
+        // Don't propose transactions that outlived their time to live while we waited.
+        self.transactions
+            .modify(|txns| {
+                txns.remove_expired(self.clock.now());
+            })
+            .await;
+        // Whatever doesn't fit in the block stays in the mempool for a later view.
+        let max_block_size = u64::try_from(self.api.max_block_size().get()).unwrap_or(u64::MAX);
+        let txns = self.transactions.read().await.batch(
+            self.api.max_transactions().get(),
+            max_block_size,
+            &previous_used_txns,
+        );
         Some(txns)
     }
 
@@ -400,20 +429,27 @@ impl<
             ConsensusMessage = SequencingMessage<TYPES, I>,
         >,
         A: ConsensusApi<TYPES, Leaf<TYPES>, I> + 'static,
-    > TS for TransactionTaskState<TYPES, I, A>
+        M: Mempool<TYPES>,
+    > TS for TransactionTaskState<TYPES, I, A, M>
 where
     QuorumEx<TYPES, I>: ConsensusExchange<
         TYPES,
         Message<TYPES, I>,
         Certificate = QuorumCertificate<TYPES, Commitment<I::Leaf>>,
     >,
+    VIDEx<TYPES, I>: ConsensusExchange<
+        TYPES,
+        Message<TYPES, I>,
+        Certificate = VIDCertificate<TYPES>,
+        Commitment = Commitment<TYPES::BlockPayload>,
+    >,
 {
 }
 
 /// Type alias for DA Task Types
-pub type TransactionsTaskTypes<TYPES, I, A> = HSTWithEvent<
+pub type TransactionsTaskTypes<TYPES, I, A, M> = HSTWithEvent<
     ConsensusTaskError,
     HotShotEvent<TYPES, I>,
     ChannelStream<HotShotEvent<TYPES, I>>,
-    TransactionTaskState<TYPES, I, A>,
+    TransactionTaskState<TYPES, I, A, M>,
 >;
@@ -3,7 +3,7 @@ use async_compatibility_layer::art::async_spawn;
 use async_lock::RwLock;
 
 use bitvec::prelude::*;
-use commit::Commitment;
+use commit::{Commitment, Committable};
 use either::{Either, Left, Right};
 use futures::FutureExt;
 use hotshot_task::{
@@ -19,27 +19,75 @@ use hotshot_types::{
 };
 use hotshot_types::{
     consensus::{Consensus, View},
-    data::{Leaf, ProposalType},
-    message::{Message, SequencingMessage},
+    data::{Leaf, LeafType, ProposalType, VidDisperse, VidScheme, VidSchemeTrait},
+    message::{Message, SequencingMessage, VidShareRequest, VidShareResponse},
     traits::{
         consensus_api::ConsensusApi,
-        election::{ConsensusExchange, VIDExchangeType},
+        election::{ConsensusExchange, Membership, VIDExchangeType},
         node_implementation::{NodeImplementation, NodeType, VIDEx},
         signature_key::SignatureKey,
         state::ConsensusTime,
+        BlockPayload,
     },
     utils::ViewInner,
 };
 
 use snafu::Snafu;
 use std::marker::PhantomData;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Arc,
+};
 use tracing::{debug, error, instrument, warn};
 
 #[derive(Snafu, Debug)]
 /// Error type for consensus tasks
 pub struct ConsensusTaskError {}
 
+/// A share of a block payload held by a VID storage node
+type VidShare = <VidScheme as VidSchemeTrait>::Share;
+
+/// Data common to all the VID shares of a block payload
+type VidCommon = <VidScheme as VidSchemeTrait>::Common;
+
+/// A block payload being reconstructed from the shares of VID storage nodes
+pub struct PayloadReconstruction<TYPES: NodeType> {
+    /// The view in which the payload was dispersed
+    view: TYPES::Time,
+    /// The decided leaf to fill in with the payload, if we know it
+    leaf: Option<Leaf<TYPES>>,
+    /// The storage nodes we asked for a share and haven't heard back from
+    awaiting: HashSet<TYPES::SignatureKey>,
+    /// The storage nodes we haven't asked yet, in the order we will ask them
+    unasked: Vec<TYPES::SignatureKey>,
+    /// The verified shares received so far, grouped by the common data they verify against.
+    ///
+    /// Storage nodes may disagree on the common data, so shares are only ever combined with
+    /// shares verifying against the same common data.
+    shares: Vec<(VidCommon, Vec<VidShare>)>,
+}
+
+impl<TYPES: NodeType> PayloadReconstruction<TYPES> {
+    /// Add a share that verifies against `common`
+    fn add_share(&mut self, common: VidCommon, share: VidShare) {
+        match self.shares.iter_mut().find(|(known, _)| *known == common) {
+            Some((_, shares)) => {
+                if !shares.contains(&share) {
+                    shares.push(share);
+                }
+            }
+            None => self.shares.push((common, vec![share])),
+        }
+    }
+
+    /// The number of shares still needed to reach `num_chunks` shares verifying against the same
+    /// common data
+    fn missing_shares(&self, num_chunks: usize) -> usize {
+        let most = self.shares.iter().map(|(_, shares)| shares.len()).max();
+        num_chunks.saturating_sub(most.unwrap_or(0))
+    }
+}
+
 /// Tracks state of a VID task
 pub struct VIDTaskState<
     TYPES: NodeType,
@@ -70,6 +118,19 @@ pub struct VIDTaskState<
     /// The view and ID of the current vote collection task, if there is one.
     pub vote_collector: Option<(TYPES::Time, usize, usize)>,
 
+    /// Our shares of recently dispersed block payloads, by view, which we serve to nodes
+    /// reconstructing the payloads
+    pub vid_shares: BTreeMap<TYPES::Time, VidShareResponse<TYPES>>,
+
+    /// Number of views to keep our shares and pending reconstructions for
+    pub share_retention: u64,
+
+    /// Whether to reconstruct the payloads of decided leaves that are missing them
+    pub reconstruct_decided: bool,
+
+    /// Block payloads being reconstructed from VID shares, by commitment
+    pub reconstructions: HashMap<Commitment<TYPES::BlockPayload>, PayloadReconstruction<TYPES>>,
+
     /// Global events stream to publish events
     pub event_stream: ChannelStream<HotShotEvent<TYPES, I>>,
 
@@ -209,6 +270,7 @@ where
     >,
 {
     /// main task event handler
+    #[allow(clippy::too_many_lines)]
     #[instrument(skip_all, fields(id = self.id, view = *self.cur_view), name = "VID Main Task", level = "error")]
     pub async fn handle_event(
         &mut self,
@@ -249,9 +311,9 @@ where
 
                 let new_accumulator = VIDVoteAccumulator {
                     vid_vote_outcomes: HashMap::new(),
-                    success_threshold: self.vid_exchange.success_threshold(),
+                    success_threshold: self.vid_exchange.success_threshold(view),
                     sig_lists: Vec::new(),
-                    signers: bitvec![0; self.vid_exchange.total_nodes()],
+                    signers: bitvec![0; self.vid_exchange.total_nodes(view)],
                     phantom: PhantomData,
                 };
 
@@ -328,6 +390,8 @@ where
                     return None;
                 }
 
+                self.store_share(&disperse.data);
+
                 let vote_token = self.vid_exchange.make_vote_token(view);
                 match vote_token {
                     Err(e) => {
@@ -371,6 +435,41 @@ where
             HotShotEvent::VidCertRecv(_) => {
                 // RM TODO
             }
+            HotShotEvent::VidShareRequestRecv(request, sender) => {
+                match self.vid_shares.get(&request.view_number) {
+                    Some(stored) if stored.payload_commitment == request.payload_commitment => {
+                        self.event_stream
+                            .publish(HotShotEvent::VidShareResponseSend(
+                                stored.clone(),
+                                self.vid_exchange.public_key().clone(),
+                                sender,
+                            ))
+                            .await;
+                    }
+                    _ => debug!("No VID share to serve for view {:?}", request.view_number),
+                }
+            }
+            HotShotEvent::VidShareResponseRecv(response, sender) => {
+                self.handle_share_response(response, sender).await;
+            }
+            HotShotEvent::LeafDecided(leaves) => {
+                if !self.reconstruct_decided {
+                    return None;
+                }
+                for leaf in leaves {
+                    if leaf.get_block_payload().is_none() {
+                        self.request_payload(
+                            leaf.get_view_number(),
+                            leaf.get_payload_commitment(),
+                            Some(leaf),
+                        )
+                        .await;
+                    }
+                }
+            }
+            HotShotEvent::VidPayloadMissing(view, payload_commitment) => {
+                self.request_payload(view, payload_commitment, None).await;
+            }
             HotShotEvent::ViewChange(view) => {
                 if *self.cur_view >= *view {
                     return None;
@@ -380,6 +479,35 @@ where
                     error!("View changed by more than 1 going to view {:?}", view);
                 }
                 self.cur_view = view;
+                self.vid_exchange.membership().enter_view(view);
+
+                // Forget shares and give up on reconstructions older than the retention window.
+                let oldest_retained =
+                    TYPES::Time::new((*self.cur_view).saturating_sub(self.share_retention));
+                self.vid_shares = self.vid_shares.split_off(&oldest_retained);
+                self.reconstructions
+                    .retain(|payload_commitment, reconstruction| {
+                        let retained = reconstruction.view >= oldest_retained;
+                        if !retained {
+                            warn!(
+                                "Gave up reconstructing payload {:?} of view {:?}",
+                                payload_commitment, reconstruction.view
+                            );
+                        }
+                        retained
+                    });
+
+                // Storage nodes that haven't answered by now are presumed unavailable, so ask
+                // others in their place.
+                let stalled: Vec<_> = self
+                    .reconstructions
+                    .iter()
+                    .filter(|(_, reconstruction)| !reconstruction.awaiting.is_empty())
+                    .map(|(payload_commitment, _)| *payload_commitment)
+                    .collect();
+                for payload_commitment in stalled {
+                    self.ask_for_shares(payload_commitment, true).await;
+                }
 
                 // Start polling for VID disperse for the new view
                 self.vid_exchange
@@ -423,6 +551,304 @@ where
         None
     }
 
+    /// Instantiate VID for the storage nodes of `view`, returning the scheme and the number of
+    /// storage nodes
+    fn vid_scheme(&self, view: TYPES::Time) -> Option<(VidScheme, usize)> {
+        let num_storage_nodes = self.vid_exchange.membership().get_committee(view).len();
+        match self.api.vid().scheme(num_storage_nodes) {
+            Ok(vid) => Some((vid, num_storage_nodes)),
+            Err(e) => {
+                error!("Failed to set up VID for view {:?}: {}", view, e);
+                None
+            }
+        }
+    }
+
+    /// Keep our own share of a dispersed payload, if we are one of its storage nodes, so that we
+    /// can serve it to nodes reconstructing the payload.
+    fn store_share(&mut self, disperse: &VidDisperse<TYPES>) {
+        let view = disperse.view_number;
+        let committee = self.vid_exchange.membership().get_committee(view);
+        // Shares are dispersed in the order of the committee.
+        let Some(index) = committee
+            .iter()
+            .position(|key| key == self.vid_exchange.public_key())
+        else {
+            return;
+        };
+        let Some(share) = disperse.shares.get(index) else {
+            warn!("VID disperse for view {:?} has no share for us", view);
+            return;
+        };
+        let Some((vid, _)) = self.vid_scheme(view) else {
+            return;
+        };
+        if !matches!(vid.verify_share(share, &disperse.common), Ok(Ok(()))) {
+            warn!("Our VID share for view {:?} doesn't verify", view);
+            return;
+        }
+        self.vid_shares.insert(
+            view,
+            VidShareResponse {
+                view_number: view,
+                payload_commitment: disperse.payload_commitment,
+                share: share.clone(),
+                common: disperse.common.clone(),
+            },
+        );
+    }
+
+    /// Start reconstructing the payload dispersed in `view` by asking enough of its storage nodes
+    /// for their shares. If `leaf` is given, it is filled in with the payload and stored once the
+    /// payload is reconstructed.
+    async fn request_payload(
+        &mut self,
+        view: TYPES::Time,
+        payload_commitment: Commitment<TYPES::BlockPayload>,
+        leaf: Option<Leaf<TYPES>>,
+    ) {
+        if self.reconstructions.contains_key(&payload_commitment) {
+            return;
+        }
+        let public_key = self.vid_exchange.public_key();
+        let mut committee: Vec<_> = self
+            .vid_exchange
+            .membership()
+            .get_committee(view)
+            .into_iter()
+            .collect();
+        // Start with the storage nodes after us, so that nodes reconstructing the same payload
+        // spread their requests over the committee.
+        if let Some(own_index) = committee.iter().position(|key| key == public_key) {
+            committee.rotate_left(own_index);
+            committee.remove(0);
+        }
+        let mut reconstruction = PayloadReconstruction {
+            view,
+            leaf,
+            awaiting: HashSet::new(),
+            unasked: committee,
+            shares: Vec::new(),
+        };
+        if let Some(stored) = self.vid_shares.get(&view) {
+            if stored.payload_commitment == payload_commitment {
+                reconstruction.add_share(stored.common.clone(), stored.share.clone());
+            }
+        }
+
+        self.reconstructions
+            .insert(payload_commitment, reconstruction);
+        self.try_reconstruct(payload_commitment).await;
+    }
+
+    /// Ask storage nodes we haven't asked yet for their shares of a payload, as many as are needed
+    /// to reconstruct it. Unless `timed_out` is set, the storage nodes we are still waiting for are
+    /// counted as if they had answered.
+    async fn ask_for_shares(
+        &mut self,
+        payload_commitment: Commitment<TYPES::BlockPayload>,
+        timed_out: bool,
+    ) {
+        let Some(view) = self
+            .reconstructions
+            .get(&payload_commitment)
+            .map(|reconstruction| reconstruction.view)
+        else {
+            return;
+        };
+        let num_storage_nodes = self.vid_exchange.membership().get_committee(view).len();
+        let num_chunks = self.api.vid().num_chunks(num_storage_nodes);
+        let Some(reconstruction) = self.reconstructions.get_mut(&payload_commitment) else {
+            return;
+        };
+        let pending = if timed_out {
+            0
+        } else {
+            reconstruction.awaiting.len()
+        };
+        let count = reconstruction
+            .missing_shares(num_chunks)
+            .saturating_sub(pending)
+            .min(reconstruction.unasked.len());
+        let keys: Vec<_> = reconstruction.unasked.drain(..count).collect();
+        reconstruction.awaiting.extend(keys.iter().cloned());
+
+        debug!(
+            "Requesting VID shares for view {:?} from {} storage nodes",
+            view,
+            keys.len()
+        );
+        let public_key = self.vid_exchange.public_key().clone();
+        for key in keys {
+            self.event_stream
+                .publish(HotShotEvent::VidShareRequestSend(
+                    VidShareRequest {
+                        view_number: view,
+                        payload_commitment,
+                    },
+                    public_key.clone(),
+                    key,
+                ))
+                .await;
+        }
+    }
+
+    /// Verify a share sent in answer to one of our requests and add it to its reconstruction
+    async fn handle_share_response(
+        &mut self,
+        response: VidShareResponse<TYPES>,
+        sender: TYPES::SignatureKey,
+    ) {
+        let Some((vid, _)) = self.vid_scheme(response.view_number) else {
+            return;
+        };
+        let Some(reconstruction) = self.reconstructions.get_mut(&response.payload_commitment)
+        else {
+            debug!("Ignoring VID share for a payload we aren't reconstructing");
+            return;
+        };
+        if reconstruction.view != response.view_number || !reconstruction.awaiting.remove(&sender) {
+            warn!("Ignoring unrequested VID share from {:?}", sender);
+            return;
+        }
+        if matches!(
+            vid.verify_share(&response.share, &response.common),
+            Ok(Ok(()))
+        ) {
+            reconstruction.add_share(response.common, response.share);
+        } else {
+            warn!(
+                "Dropping VID share from {:?} that doesn't verify against its common data",
+                sender
+            );
+        }
+        self.try_reconstruct(response.payload_commitment).await;
+    }
+
+    /// Reconstruct a payload once enough shares verifying against the same common data have been
+    /// received. Otherwise ask more storage nodes if the ones we are waiting for can't make up
+    /// for the missing shares, and give up once every storage node has answered.
+    async fn try_reconstruct(&mut self, payload_commitment: Commitment<TYPES::BlockPayload>) {
+        let Some(view) = self
+            .reconstructions
+            .get(&payload_commitment)
+            .map(|reconstruction| reconstruction.view)
+        else {
+            return;
+        };
+        let Some((vid, num_storage_nodes)) = self.vid_scheme(view) else {
+            return;
+        };
+        let num_chunks = self.api.vid().num_chunks(num_storage_nodes);
+        let Some(reconstruction) = self.reconstructions.get_mut(&payload_commitment) else {
+            return;
+        };
+
+        let mut payload = None;
+        reconstruction.shares.retain(|(common, shares)| {
+            if payload.is_some() || shares.len() < num_chunks {
+                return true;
+            }
+            payload = Self::recover_payload(&vid, common, shares, payload_commitment);
+            // Shares that recovered a wrong payload are useless.
+            payload.is_some()
+        });
+
+        match payload {
+            Some(payload) => {
+                let reconstruction = self.reconstructions.remove(&payload_commitment);
+                self.deliver_payload(view, reconstruction.and_then(|r| r.leaf), payload)
+                    .await;
+            }
+            None => {
+                self.ask_for_shares(payload_commitment, false).await;
+                if self
+                    .reconstructions
+                    .get(&payload_commitment)
+                    .is_some_and(|reconstruction| reconstruction.awaiting.is_empty())
+                {
+                    error!(
+                        "Not enough valid VID shares to reconstruct payload of view {:?}",
+                        view
+                    );
+                    self.reconstructions.remove(&payload_commitment);
+                }
+            }
+        }
+    }
+
+    /// Recover a payload from shares and check it against the certified payload commitment.
+    ///
+    /// The VID commitment is recomputed from the recovered bytes rather than taken from the
+    /// storage nodes, so a payload is only accepted if it is exactly the one that was certified.
+    fn recover_payload(
+        vid: &VidScheme,
+        common: &VidCommon,
+        shares: &[VidShare],
+        payload_commitment: Commitment<TYPES::BlockPayload>,
+    ) -> Option<TYPES::BlockPayload> {
+        let bytes = vid
+            .recover_payload(shares, common)
+            .map_err(|e| warn!("Failed to recover payload from VID shares: {:?}", e))
+            .ok()?;
+        let vid_commitment = vid
+            .disperse(&bytes)
+            .map_err(|e| warn!("Failed to recompute VID commitment: {:?}", e))
+            .ok()?
+            .commit;
+        let payload = TYPES::BlockPayload::from_vid_bytes(&bytes, vid_commitment)
+            .map_err(|e| warn!("Recovered bytes are not a block payload: {:?}", e))
+            .ok()?;
+        if payload.commit() != payload_commitment {
+            warn!("Payload recovered from VID shares doesn't match the certified commitment");
+            return None;
+        }
+        Some(payload)
+    }
+
+    /// Make a reconstructed payload available: undecided payloads are saved in [`Consensus`],
+    /// and decided leaves are filled in with the payload and stored again.
+    async fn deliver_payload(
+        &self,
+        view: TYPES::Time,
+        leaf: Option<Leaf<TYPES>>,
+        payload: TYPES::BlockPayload,
+    ) {
+        debug!("Reconstructed block payload of view {:?}", view);
+        let mut consensus = self.consensus.write().await;
+        if view > consensus.last_decided_view {
+            consensus.saved_block_payloads.insert(payload.clone());
+        } else {
+            drop(consensus);
+            let leaves = match leaf {
+                Some(leaf) => vec![leaf],
+                None => self
+                    .api
+                    .get_decided_leaves(view, view)
+                    .await
+                    .unwrap_or_else(|e| {
+                        error!("Could not load decided leaf of view {:?}: {:?}", view, e);
+                        Vec::new()
+                    }),
+            };
+            let leaves: Vec<_> = leaves
+                .into_iter()
+                .filter_map(|mut leaf| {
+                    leaf.fill_block_payload(payload.clone()).ok()?;
+                    Some(leaf)
+                })
+                .collect();
+            if !leaves.is_empty() {
+                if let Err(e) = self.api.store_leaves(leaves).await {
+                    error!("Could not store leaf with reconstructed payload: {:?}", e);
+                }
+            }
+        }
+        self.event_stream
+            .publish(HotShotEvent::VidPayloadRecovered(payload, view))
+            .await;
+    }
+
     /// Filter the VID event.
     pub fn filter(event: &HotShotEvent<TYPES, I>) -> bool {
         matches!(
@@ -431,6 +857,10 @@ where
                 | HotShotEvent::VidDisperseRecv(_, _)
                 | HotShotEvent::VidVoteRecv(_)
                 | HotShotEvent::VidCertRecv(_)
+                | HotShotEvent::VidShareRequestRecv(_, _)
+                | HotShotEvent::VidShareResponseRecv(_, _)
+                | HotShotEvent::VidPayloadMissing(_, _)
+                | HotShotEvent::LeafDecided(_)
                 | HotShotEvent::ViewChange(_)
         )
     }
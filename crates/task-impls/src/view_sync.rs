@@ -1,6 +1,6 @@
 #![allow(clippy::module_name_repetitions)]
 use crate::events::HotShotEvent;
-use async_compatibility_layer::art::{async_sleep, async_spawn};
+use async_compatibility_layer::art::async_spawn;
 use commit::{Commitment, Committable};
 use either::Either::{self, Left, Right};
 use futures::FutureExt;
@@ -21,6 +21,7 @@ use bitvec::prelude::*;
 use hotshot_task::global_registry::GlobalRegistry;
 use hotshot_types::{
     certificate::ViewSyncCertificate,
+    clock::Clock,
     data::Leaf,
     message::{GeneralConsensusMessage, Message, Proposal, SequencingMessage},
     traits::{
@@ -100,6 +101,8 @@ pub struct ViewSyncTaskState<
 
     /// Timeout duration for view sync rounds
     pub view_sync_timeout: Duration,
+    /// Clock to time view sync rounds with
+    pub clock: Clock,
 
     /// Last view we garbage collected old tasks
     pub last_garbage_collected_view: TYPES::Time,
@@ -149,6 +152,8 @@ pub struct ViewSyncReplicaTaskState<
 {
     /// Timeout for view sync rounds
     pub view_sync_timeout: Duration,
+    /// Clock to time view sync rounds with
+    pub clock: Clock,
     /// Current round HotShot is in
     pub current_view: TYPES::Time,
     /// Round HotShot wishes to be in
@@ -311,6 +316,7 @@ where
                         api: self.api.clone(),
                         event_stream: self.event_stream.clone(),
                         view_sync_timeout: self.view_sync_timeout,
+                        clock: self.clock.clone(),
                         id: self.id,
                     };
 
@@ -386,11 +392,11 @@ where
                     commit_vote_outcomes: HashMap::new(),
                     finalize_vote_outcomes: HashMap::new(),
 
-                    success_threshold: self.exchange.success_threshold(),
-                    failure_threshold: self.exchange.failure_threshold(),
+                    success_threshold: self.exchange.success_threshold(vote_internal.round),
+                    failure_threshold: self.exchange.failure_threshold(vote_internal.round),
 
                     sig_lists: Vec::new(),
-                    signers: bitvec![0; self.exchange.total_nodes()],
+                    signers: bitvec![0; self.exchange.total_nodes(vote_internal.round)],
                     phantom: PhantomData,
                 };
 
@@ -447,6 +453,7 @@ where
                     self.current_view = new_view;
                     self.next_view = self.current_view;
                     self.num_timeouts_tracked = 0;
+                    self.exchange.membership().enter_view(new_view);
 
                     // Garbage collect old tasks
                     // We could put this into a separate async task, but that would require making several fields on ViewSyncTaskState thread-safe and harm readability.  In the common case this will have zero tasks to clean up.
@@ -541,6 +548,7 @@ where
                         api: self.api.clone(),
                         event_stream: self.event_stream.clone(),
                         view_sync_timeout: self.view_sync_timeout,
+                        clock: self.clock.clone(),
                         id: self.id,
                     };
 
@@ -793,8 +801,9 @@ where
                         async_spawn({
                             let stream = self.event_stream.clone();
                             let phase = self.phase.clone();
+                            let clock = self.clock.clone();
                             async move {
-                                async_sleep(self.view_sync_timeout).await;
+                                clock.sleep(self.view_sync_timeout).await;
                                 error!("Vote sending timed out in ViewSyncCertificateRecv");
                                 stream
                                     .publish(HotShotEvent::ViewSyncTimeout(
@@ -855,8 +864,9 @@ where
                         // TODO ED Add event to shutdown this task
                         async_spawn({
                             let stream = self.event_stream.clone();
+                            let clock = self.clock.clone();
                             async move {
-                                async_sleep(self.view_sync_timeout).await;
+                                clock.sleep(self.view_sync_timeout).await;
                                 error!("Vote sending timed out in ViewSyncTrigger");
                                 stream
                                     .publish(HotShotEvent::ViewSyncTimeout(
@@ -926,8 +936,9 @@ where
                             // TODO ED Add event to shutdown this task
                             async_spawn({
                                 let stream = self.event_stream.clone();
+                                let clock = self.clock.clone();
                                 async move {
-                                    async_sleep(self.view_sync_timeout).await;
+                                    clock.sleep(self.view_sync_timeout).await;
                                     error!("Vote sending timed out in ViewSyncTimeout");
                                     stream
                                         .publish(HotShotEvent::ViewSyncTimeout(
@@ -1045,11 +1056,11 @@ where
                             commit_vote_outcomes: HashMap::new(),
                             finalize_vote_outcomes: HashMap::new(),
 
-                            success_threshold: self.exchange.success_threshold(),
-                            failure_threshold: self.exchange.failure_threshold(),
+                            success_threshold: self.exchange.success_threshold(vote_internal.round),
+                            failure_threshold: self.exchange.failure_threshold(vote_internal.round),
 
                             sig_lists: Vec::new(),
-                            signers: bitvec![0; self.exchange.total_nodes()],
+                            signers: bitvec![0; self.exchange.total_nodes(vote_internal.round)],
                             phantom: PhantomData,
                         };
                         either::Left(new_accumulator)
@@ -0,0 +1,292 @@
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    sync::{Mutex, PoisonError},
+};
+
+use commit::{Commitment, Committable};
+use either::Either::{Left, Right};
+use hotshot_task_impls::network::OutgoingMessageTransform;
+use hotshot_types::{
+    data::{Leaf, QuorumProposal},
+    message::{
+        CommitteeConsensusMessage, GeneralConsensusMessage, Message, MessageKind, Proposal,
+        SequencingMessage,
+    },
+    simple_vote::{QuorumData, QuorumVote},
+    traits::{
+        block_contents::BlockHeader,
+        election::ConsensusExchange,
+        node_implementation::{NodeImplementation, NodeType, QuorumEx, QuorumMembership},
+        signature_key::SignatureKey,
+    },
+    vote2::Certificate2,
+};
+
+/// A way for a Byzantine node to misbehave
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ByzantineBehaviour {
+    /// as leader, send its proposal to half of the nodes and a conflicting one to the others
+    EquivocateProposals,
+    /// along with each vote, vote in the same view for the leaf it voted for last
+    DoubleVote,
+    /// vote for leaves that don't exist instead of the proposed ones
+    VoteForInvalidLeaves,
+    /// never send DA votes
+    WithholdDAVotes,
+    /// resend the first proposal, DA certificate and VID certificate it sent along with each
+    /// new one
+    ReplayCertificates,
+    /// send votes and proposals with signatures that don't match them
+    MalformedMessages,
+}
+
+/// denotes a node that misbehaves
+#[derive(Clone, Debug)]
+pub struct ByzantineNode {
+    /// the index of the node
+    pub idx: usize,
+    /// how the node misbehaves, applied in order to every message it sends
+    pub behaviours: Vec<ByzantineBehaviour>,
+}
+
+/// The kinds of messages [`ByzantineBehaviour::ReplayCertificates`] replays
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum CertificateKind {
+    /// a quorum proposal, carrying its justify QC
+    QuorumProposal,
+    /// a DA certificate
+    DACertificate,
+    /// a VID certificate
+    VidCertificate,
+}
+
+/// A message with its recipient, or `None` if it is broadcast
+type Outgoing<TYPES, I> = (Message<TYPES, I>, Option<<TYPES as NodeType>::SignatureKey>);
+
+/// Rewrites the messages of a Byzantine node according to its behaviours
+pub struct ByzantineTransform<TYPES: NodeType, I: NodeImplementation<TYPES>> {
+    /// public key of the node
+    public_key: TYPES::SignatureKey,
+    /// private key of the node, to sign the messages it makes up
+    private_key: <TYPES::SignatureKey as SignatureKey>::PrivateKey,
+    /// keys of every node in the test, in order
+    nodes: Vec<TYPES::SignatureKey>,
+    /// how the node misbehaves
+    behaviours: Vec<ByzantineBehaviour>,
+    /// leaf of the last quorum vote the node sent
+    last_vote: Mutex<Option<Commitment<Leaf<TYPES>>>>,
+    /// first message of each kind the node sent carrying a certificate
+    first_certificates: Mutex<HashMap<CertificateKind, Outgoing<TYPES, I>>>,
+}
+
+impl<TYPES: NodeType, I: NodeImplementation<TYPES>> ByzantineTransform<TYPES, I> {
+    /// the transform of node `node_id`, with keys generated the way the test runner does
+    pub fn new(
+        node_id: u64,
+        nodes: Vec<TYPES::SignatureKey>,
+        behaviours: Vec<ByzantineBehaviour>,
+    ) -> Self {
+        let (public_key, private_key) =
+            TYPES::SignatureKey::generated_from_seed_indexed([0u8; 32], node_id);
+        Self {
+            public_key,
+            private_key,
+            nodes,
+            behaviours,
+            last_vote: Mutex::default(),
+            first_certificates: Mutex::default(),
+        }
+    }
+}
+
+impl<TYPES, I> ByzantineTransform<TYPES, I>
+where
+    TYPES: NodeType,
+    I: NodeImplementation<
+        TYPES,
+        Leaf = Leaf<TYPES>,
+        ConsensusMessage = SequencingMessage<TYPES, I>,
+    >,
+    QuorumEx<TYPES, I>:
+        ConsensusExchange<TYPES, Message<TYPES, I>, Proposal = QuorumProposal<TYPES, Leaf<TYPES>>>,
+{
+    /// a message from this node
+    fn message(&self, kind: MessageKind<TYPES, I>) -> Message<TYPES, I> {
+        Message {
+            sender: self.public_key.clone(),
+            kind,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// a quorum vote for `leaf_commit` in `view`, signed by this node
+    fn vote(&self, leaf_commit: Commitment<Leaf<TYPES>>, view: TYPES::Time) -> Message<TYPES, I> {
+        let vote = QuorumVote::<TYPES, Leaf<TYPES>, QuorumMembership<TYPES, I>>::create_signed_vote(
+            QuorumData { leaf_commit },
+            view,
+            &self.public_key,
+            &self.private_key,
+        );
+        self.message(MessageKind::from_consensus_message(SequencingMessage(
+            Left(GeneralConsensusMessage::Vote(vote)),
+        )))
+    }
+
+    /// apply `behaviour` to `message`
+    fn misbehave(
+        &self,
+        behaviour: ByzantineBehaviour,
+        (message, recipient): Outgoing<TYPES, I>,
+    ) -> Vec<Outgoing<TYPES, I>> {
+        let MessageKind::Consensus(SequencingMessage(consensus_message)) = &message.kind else {
+            return vec![(message, recipient)];
+        };
+        match (behaviour, consensus_message) {
+            (
+                ByzantineBehaviour::EquivocateProposals,
+                Left(GeneralConsensusMessage::Proposal(proposal)),
+            ) if recipient.is_none() => {
+                // same proposal for a block with another header, signed like an honest proposal
+                let header = &proposal.data.block_header;
+                let data = QuorumProposal {
+                    block_header: TYPES::BlockHeader::new(header.payload_commitment(), header),
+                    ..proposal.data.clone()
+                };
+                let leaf = Leaf {
+                    view_number: data.view_number,
+                    justify_qc: data.justify_qc.clone(),
+                    parent_commitment: data.justify_qc.get_data().leaf_commit,
+                    block_header: data.block_header.clone(),
+                    block_payload: None,
+                    rejected: Vec::new(),
+                    timestamp: 0,
+                    proposer_id: self.public_key.to_bytes(),
+                };
+                let conflicting = Proposal {
+                    signature: TYPES::SignatureKey::sign(&self.private_key, leaf.commit().as_ref()),
+                    data,
+                };
+                let conflicting = self.message(MessageKind::from_consensus_message(
+                    SequencingMessage(Left(GeneralConsensusMessage::Proposal(conflicting))),
+                ));
+                let (first_half, second_half) = self.nodes.split_at(self.nodes.len() / 2);
+                first_half
+                    .iter()
+                    .map(|node| (message.clone(), Some(node.clone())))
+                    .chain(
+                        second_half
+                            .iter()
+                            .map(|node| (conflicting.clone(), Some(node.clone()))),
+                    )
+                    .collect()
+            }
+            (ByzantineBehaviour::DoubleVote, Left(GeneralConsensusMessage::Vote(vote))) => {
+                let mut last_vote = self
+                    .last_vote
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner);
+                let previous = last_vote.replace(vote.data.leaf_commit);
+                let double_vote = previous
+                    .filter(|leaf_commit| *leaf_commit != vote.data.leaf_commit)
+                    .map(|leaf_commit| {
+                        (self.vote(leaf_commit, vote.view_number), recipient.clone())
+                    });
+                std::iter::once((message, recipient))
+                    .chain(double_vote)
+                    .collect()
+            }
+            (
+                ByzantineBehaviour::VoteForInvalidLeaves,
+                Left(GeneralConsensusMessage::Vote(vote)),
+            ) => {
+                let invalid_leaf = commit::RawCommitmentBuilder::new("Invalid Leaf")
+                    .u64(*vote.view_number)
+                    .finalize();
+                vec![(self.vote(invalid_leaf, vote.view_number), recipient)]
+            }
+            (ByzantineBehaviour::WithholdDAVotes, Right(CommitteeConsensusMessage::DAVote(_))) => {
+                vec![]
+            }
+            (ByzantineBehaviour::ReplayCertificates, consensus_message) => {
+                let kind = match consensus_message {
+                    Left(GeneralConsensusMessage::Proposal(_)) => CertificateKind::QuorumProposal,
+                    Right(CommitteeConsensusMessage::DACertificate(_)) => {
+                        CertificateKind::DACertificate
+                    }
+                    Right(CommitteeConsensusMessage::VidCertificate(_)) => {
+                        CertificateKind::VidCertificate
+                    }
+                    _ => return vec![(message, recipient)],
+                };
+                let mut first_certificates = self
+                    .first_certificates
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner);
+                match first_certificates.get(&kind) {
+                    Some(first) => vec![(message, recipient), first.clone()],
+                    None => {
+                        first_certificates.insert(kind, (message.clone(), recipient.clone()));
+                        vec![(message, recipient)]
+                    }
+                }
+            }
+            (ByzantineBehaviour::MalformedMessages, consensus_message) => {
+                let wrong_signature = TYPES::SignatureKey::sign(&self.private_key, b"malformed");
+                let malformed = match consensus_message.clone() {
+                    Left(GeneralConsensusMessage::Proposal(mut proposal)) => {
+                        proposal.signature = wrong_signature;
+                        Left(GeneralConsensusMessage::Proposal(proposal))
+                    }
+                    Left(GeneralConsensusMessage::Vote(mut vote)) => {
+                        vote.signature.1 = wrong_signature;
+                        Left(GeneralConsensusMessage::Vote(vote))
+                    }
+                    Right(CommitteeConsensusMessage::DAProposal(mut proposal)) => {
+                        proposal.signature = wrong_signature;
+                        Right(CommitteeConsensusMessage::DAProposal(proposal))
+                    }
+                    Right(CommitteeConsensusMessage::VidDisperseMsg(mut proposal)) => {
+                        proposal.signature = wrong_signature;
+                        Right(CommitteeConsensusMessage::VidDisperseMsg(proposal))
+                    }
+                    _ => return vec![(message, recipient)],
+                };
+                vec![(
+                    self.message(MessageKind::from_consensus_message(SequencingMessage(
+                        malformed,
+                    ))),
+                    recipient,
+                )]
+            }
+            _ => vec![(message, recipient)],
+        }
+    }
+}
+
+impl<TYPES, I> OutgoingMessageTransform<TYPES, I> for ByzantineTransform<TYPES, I>
+where
+    TYPES: NodeType,
+    I: NodeImplementation<
+        TYPES,
+        Leaf = Leaf<TYPES>,
+        ConsensusMessage = SequencingMessage<TYPES, I>,
+    >,
+    QuorumEx<TYPES, I>:
+        ConsensusExchange<TYPES, Message<TYPES, I>, Proposal = QuorumProposal<TYPES, Leaf<TYPES>>>,
+{
+    fn transform(
+        &self,
+        message: Message<TYPES, I>,
+        recipient: Option<TYPES::SignatureKey>,
+    ) -> Vec<Outgoing<TYPES, I>> {
+        self.behaviours
+            .iter()
+            .fold(vec![(message, recipient)], |messages, behaviour| {
+                messages
+                    .into_iter()
+                    .flat_map(|message| self.misbehave(*behaviour, message))
+                    .collect()
+            })
+    }
+}
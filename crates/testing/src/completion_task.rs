@@ -1,6 +1,5 @@
 use std::{sync::Arc, time::Duration};
 
-use async_compatibility_layer::art::async_sleep;
 use futures::FutureExt;
 use hotshot::traits::TestableNodeImplementation;
 use hotshot_task::{
@@ -10,7 +9,7 @@ use hotshot_task::{
     task_impls::{HSTWithEventAndMessage, TaskBuilder},
     GeneratedStream,
 };
-use hotshot_types::traits::node_implementation::NodeType;
+use hotshot_types::{clock::Clock, traits::node_implementation::NodeType};
 use snafu::Snafu;
 
 use crate::test_runner::Node;
@@ -27,6 +26,8 @@ pub struct CompletionTaskErr {}
 pub struct CompletionTask<TYPES: NodeType, I: TestableNodeImplementation<TYPES>> {
     pub(crate) test_event_stream: ChannelStream<GlobalTestEvent>,
     pub(crate) handles: Vec<Node<TYPES, I>>,
+    /// the clock of the nodes, which the duration of the test is measured by
+    pub(crate) clock: Clock,
 }
 
 impl<TYPES: NodeType, I: TestableNodeImplementation<TYPES>> TS for CompletionTask<TYPES, I> {}
@@ -101,9 +102,11 @@ impl TimeBasedCompletionTaskDescription {
                 // normally I'd say "let's use Interval from async-std!"
                 // but doing this is easier than unifying async-std with tokio's slightly different
                 // interval abstraction
+                let clock = state.clock.clone();
                 let stream_generator = GeneratedStream::new(Arc::new(move || {
+                    let clock = clock.clone();
                     let fut = async move {
-                        async_sleep(self.duration).await;
+                        clock.sleep(self.duration).await;
                     };
                     Some(boxed_sync(fut))
                 }));
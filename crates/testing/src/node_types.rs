@@ -13,9 +13,11 @@ use hotshot::{
     },
     types::bn254::BLSPubKey,
 };
+use hotshot_task_impls::mempool::DefaultMempool;
 use hotshot_types::{
     block_impl::{VIDBlockHeader, VIDBlockPayload, VIDTransaction},
     certificate::ViewSyncCertificate,
+    clock::VirtualClock,
     data::{Leaf, QuorumProposal, ViewNumber},
     message::{Message, SequencingMessage},
     traits::{
@@ -126,6 +128,7 @@ pub type SequencingLibp2pExchange = Exchanges<
 
 impl NodeImplementation<TestTypes> for Libp2pImpl {
     type Storage = MemoryStorage<TestTypes, Leaf<TestTypes>>;
+    type Mempool = DefaultMempool<TestTypes>;
     type Leaf = Leaf<TestTypes>;
     type Exchanges = SequencingLibp2pExchange;
     type ConsensusMessage = SequencingMessage<TestTypes, Self>;
@@ -155,6 +158,7 @@ impl
         expected_node_count: usize,
         num_bootstrap: usize,
         da_committee_size: usize,
+        _simulation: Option<(u64, Arc<VirtualClock>)>,
     ) -> Box<
         dyn Fn(
                 u64,
@@ -262,6 +266,7 @@ impl
         expected_node_count: usize,
         num_bootstrap: usize,
         da_committee_size: usize,
+        simulation: Option<(u64, Arc<VirtualClock>)>,
     ) -> Box<
         dyn Fn(
                 u64,
@@ -284,32 +289,38 @@ impl
                 >>::Networking,
             ) + 'static,
     > {
-        let network_generator = Arc::new(<MemoryNetwork<
-            Message<TestTypes, MemoryImpl>,
-            <TestTypes as NodeType>::SignatureKey,
-        > as TestableNetworkingImplementation<
-            TestTypes,
-            Message<TestTypes, MemoryImpl>,
-        >>::generator(
-            expected_node_count,
-            num_bootstrap,
-            0,
-            da_committee_size,
-            false,
-        ));
-        let network_da_generator = Arc::new(<MemoryNetwork<
-            Message<TestTypes, MemoryImpl>,
-            <TestTypes as NodeType>::SignatureKey,
-        > as TestableNetworkingImplementation<
-            TestTypes,
-            Message<TestTypes, MemoryImpl>,
-        >>::generator(
-            expected_node_count,
-            num_bootstrap,
-            1,
-            da_committee_size,
-            true,
-        ));
+        let generator = |network_id, is_da| {
+            type Network = MemoryNetwork<
+                Message<TestTypes, MemoryImpl>,
+                <TestTypes as NodeType>::SignatureKey,
+            >;
+            match &simulation {
+                Some((seed, clock)) => <Network as TestableNetworkingImplementation<
+                    TestTypes,
+                    Message<TestTypes, MemoryImpl>,
+                >>::seeded_generator(
+                    expected_node_count,
+                    num_bootstrap,
+                    network_id,
+                    da_committee_size,
+                    is_da,
+                    *seed,
+                    clock.clone(),
+                ),
+                None => <Network as TestableNetworkingImplementation<
+                    TestTypes,
+                    Message<TestTypes, MemoryImpl>,
+                >>::generator(
+                    expected_node_count,
+                    num_bootstrap,
+                    network_id,
+                    da_committee_size,
+                    is_da,
+                ),
+            }
+        };
+        let network_generator = Arc::new(generator(0, false));
+        let network_da_generator = Arc::new(generator(1, true));
         Box::new(move |id| {
             let network = Arc::new(network_generator(id));
             let network_da = Arc::new(network_da_generator(id));
@@ -345,6 +356,7 @@ impl
 
 impl NodeImplementation<TestTypes> for MemoryImpl {
     type Storage = MemoryStorage<TestTypes, Leaf<TestTypes>>;
+    type Mempool = DefaultMempool<TestTypes>;
     type Leaf = Leaf<TestTypes>;
     type Exchanges = SequencingMemoryExchange;
     type ConsensusMessage = SequencingMessage<TestTypes, Self>;
@@ -401,6 +413,7 @@ impl
         expected_node_count: usize,
         num_bootstrap: usize,
         da_committee_size: usize,
+        _simulation: Option<(u64, Arc<VirtualClock>)>,
     ) -> Box<
         dyn Fn(
                 u64,
@@ -486,6 +499,7 @@ impl
 
 impl NodeImplementation<TestTypes> for WebImpl {
     type Storage = MemoryStorage<TestTypes, Leaf<TestTypes>>;
+    type Mempool = DefaultMempool<TestTypes>;
     type Leaf = Leaf<TestTypes>;
     type Exchanges = SequencingWebExchanges;
     type ConsensusMessage = SequencingMessage<TestTypes, Self>;
@@ -537,6 +551,7 @@ pub type CombinedExchange = Exchanges<
 
 impl NodeImplementation<TestTypes> for CombinedImpl {
     type Storage = MemoryStorage<TestTypes, Leaf<TestTypes>>;
+    type Mempool = DefaultMempool<TestTypes>;
     type Leaf = Leaf<TestTypes>;
     type Exchanges = CombinedExchange;
     type ConsensusMessage = SequencingMessage<TestTypes, Self>;
@@ -566,6 +581,7 @@ impl
         expected_node_count: usize,
         num_bootstrap: usize,
         da_committee_size: usize,
+        _simulation: Option<(u64, Arc<VirtualClock>)>,
     ) -> Box<
         dyn Fn(
                 u64,
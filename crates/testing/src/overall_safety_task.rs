@@ -1,13 +1,13 @@
 use commit::Commitment;
 use either::Either;
-use hotshot_task::{event_stream::EventStream, Merge};
-use hotshot_task_impls::events::HotShotEvent;
+use hotshot_task::event_stream::EventStream;
 use std::{
     collections::{hash_map::Entry, HashMap, HashSet},
     sync::Arc,
 };
 
 use async_compatibility_layer::channel::UnboundedStream;
+use ethereum_types::U256;
 use futures::FutureExt;
 use hotshot::{
     traits::{NodeImplementation, TestableNodeImplementation},
@@ -24,11 +24,18 @@ use hotshot_types::{
     error::RoundTimedoutState,
     event::{Event, EventType},
     simple_certificate::QuorumCertificate2,
-    traits::node_implementation::NodeType,
+    traits::{
+        election::{ConsensusExchange, Membership},
+        node_implementation::{ExchangesType, NodeType},
+    },
 };
 use snafu::Snafu;
+use tracing::warn;
 
-use crate::{test_launcher::TaskGenerator, test_runner::Node};
+use crate::{
+    test_launcher::TaskGenerator,
+    test_runner::{Node, NodeEvent},
+};
 pub type StateAndBlock<S, B> = (Vec<S>, Vec<B>);
 
 use super::GlobalTestEvent;
@@ -81,10 +88,44 @@ pub struct OverallSafetyTask<TYPES: NodeType, I: TestableNodeImplementation<TYPE
     pub ctx: RoundCtx<TYPES, I>,
     /// event stream for publishing safety violations
     pub test_event_stream: ChannelStream<GlobalTestEvent>,
+    /// ids of the nodes that misbehave, whose decides aren't checked
+    pub byzantine_nodes: HashSet<u64>,
 }
 
 impl<TYPES: NodeType, I: TestableNodeImplementation<TYPES>> TS for OverallSafetyTask<TYPES, I> {}
 
+impl<TYPES: NodeType, I: TestableNodeImplementation<TYPES>> OverallSafetyTask<TYPES, I> {
+    /// number of nodes that behave honestly
+    pub fn num_honest_nodes(&self) -> usize {
+        self.handles
+            .iter()
+            .filter(|node| !self.byzantine_nodes.contains(&node.node_id))
+            .count()
+    }
+
+    /// whether the Byzantine nodes have less than a third of the stake of the committee for
+    /// `view`, so that honest nodes can't decide conflicting leaves
+    pub fn byzantine_under_threshold(&self, view: TYPES::Time) -> bool {
+        let Some(node) = self.handles.first() else {
+            return true;
+        };
+        let membership = node
+            .handle
+            .hotshot
+            .inner
+            .exchanges
+            .quorum_exchange()
+            .membership();
+        let byzantine_stake = self
+            .handles
+            .iter()
+            .filter(|node| self.byzantine_nodes.contains(&node.node_id))
+            .filter_map(|node| membership.get_stake(view, &node.handle.get_public_key()))
+            .fold(U256::zero(), U256::saturating_add);
+        byzantine_stake.saturating_mul(U256::from(3u8)) < membership.total_stake(view)
+    }
+}
+
 /// Result of running a round of consensus
 #[derive(Debug)]
 pub struct RoundResult<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>> {
@@ -394,7 +435,7 @@ impl OverallSafetyPropertiesDescription {
             transaction_threshold,
         }: Self = self;
 
-        Box::new(move |mut state, mut registry, test_event_stream| {
+        Box::new(move |state, mut registry, test_event_stream| {
             async move {
                 let event_handler = HandleEvent::<OverallSafetyTaskTypes<TYPES, I>>(Arc::new(
                     move |event, state| {
@@ -445,6 +486,10 @@ impl OverallSafetyPropertiesDescription {
                         async move {
 
                             let (idx, maybe_event ) : (usize, Either<_, _>)= msg;
+                            // Byzantine nodes may decide anything
+                            if state.byzantine_nodes.contains(&state.handles[idx].node_id) {
+                                return (None, state);
+                            }
                             if let Either::Left(Event { view_number, event }) = maybe_event {
                                 let key = match event {
                                     EventType::Error { error } => {
@@ -488,15 +533,16 @@ impl OverallSafetyPropertiesDescription {
                                 };
 
                                 // update view count
+                                let num_honest_nodes = state.num_honest_nodes();
                                 let threshold =
-                                    (threshold_calculator)(state.handles.len(), state.handles.len());
+                                    (threshold_calculator)(num_honest_nodes, state.handles.len());
 
                                 let view = state.ctx.round_results.get_mut(&view_number).unwrap();
 
                                 if let Some(key) = key {
                                     view.update_status(
                                         threshold,
-                                        state.handles.len(),
+                                        num_honest_nodes,
                                         key,
                                         check_leaf,
                                         check_state,
@@ -536,6 +582,14 @@ impl OverallSafetyPropertiesDescription {
                                             }
                                             return (None, state);
                                         }
+                                        ViewStatus::Err(e)
+                                            if !state.byzantine_under_threshold(view_number) =>
+                                        {
+                                            // safety only holds while the Byzantine stake is under the threshold
+                                            warn!("Safety violation with too many Byzantine nodes: {:?}", e);
+                                            state.ctx.failed_views.insert(view_number);
+                                            return (None, state);
+                                        }
                                         ViewStatus::Err(e) => {
                                             return (
                                                 Some(HotShotTaskCompleted::Error(Box::new(e))),
@@ -556,23 +610,10 @@ impl OverallSafetyPropertiesDescription {
                     },
                 ));
 
+                // subscribe through the nodes, so that the events of restarted nodes are checked too
                 let mut streams = vec![];
-                for handle in &mut state.handles {
-                    let s1 =
-                        handle
-                            .handle
-                            .get_event_stream_known_impl(FilterEvent::default())
-                            .await
-                            .0;
-                    let s2 =
-                        handle
-                            .handle
-                            .get_internal_event_stream_known_impl(FilterEvent::default())
-                            .await
-                            .0;
-                    streams.push(
-                        Merge::new(s1, s2)
-                    );
+                for node in &state.handles {
+                    streams.push(node.subscribe().await);
                 }
                 let builder = TaskBuilder::<OverallSafetyTaskTypes<TYPES, I>>::new(
                     "Test Overall Safety Task".to_string(),
@@ -598,15 +639,7 @@ pub type OverallSafetyTaskTypes<TYPES, I> = HSTWithEventAndMessage<
     OverallSafetyTaskErr,
     GlobalTestEvent,
     ChannelStream<GlobalTestEvent>,
-    (
-        usize,
-        Either<Event<TYPES, <I as NodeImplementation<TYPES>>::Leaf>, HotShotEvent<TYPES, I>>,
-    ),
-    MergeN<
-        Merge<
-            UnboundedStream<Event<TYPES, <I as NodeImplementation<TYPES>>::Leaf>>,
-            UnboundedStream<HotShotEvent<TYPES, I>>,
-        >,
-    >,
+    (usize, NodeEvent<TYPES, I>),
+    MergeN<UnboundedStream<NodeEvent<TYPES, I>>>,
     OverallSafetyTask<TYPES, I>,
 >;
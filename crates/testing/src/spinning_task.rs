@@ -1,24 +1,85 @@
 use std::{
     collections::HashMap,
-    sync::{atomic::AtomicUsize, Arc},
+    sync::{atomic::AtomicUsize, Arc, Mutex},
     time::Duration,
 };
 
-use crate::{test_launcher::TaskGenerator, test_runner::Node, GlobalTestEvent};
-use async_compatibility_layer::art::async_sleep;
-use futures::FutureExt;
-use hotshot::{traits::TestableNodeImplementation, HotShotType, SystemContext};
+use crate::{
+    test_launcher::{Networks, TaskGenerator},
+    test_runner::{create_node_exchanges, Node},
+    GlobalTestEvent,
+};
+use async_compatibility_layer::art::async_spawn;
+use futures::{
+    future::{join, join4},
+    FutureExt, StreamExt,
+};
+use hotshot::{
+    traits::TestableNodeImplementation, types::SystemContextHandle, HotShotType, SystemContext,
+};
 use hotshot_task::{
     boxed_sync,
     event_stream::ChannelStream,
     task::{FilterEvent, HandleEvent, HandleMessage, HotShotTaskCompleted, HotShotTaskTypes, TS},
     task_impls::{HSTWithEventAndMessage, TaskBuilder},
-    GeneratedStream,
+    BoxSyncFuture, GeneratedStream,
+};
+use hotshot_types::{
+    consensus::ConsensusMetricsValue,
+    data::LeafType,
+    event::EventType,
+    message::Message,
+    traits::{
+        election::ConsensusExchange,
+        network::{CommunicationChannel, NetworkError, TransmitType},
+        node_implementation::{ExchangesType, NodeType},
+        signature_key::SignatureKey,
+        state::ConsensusTime,
+    },
+    HotShotConfig,
 };
-use hotshot_types::traits::node_implementation::NodeType;
 use snafu::Snafu;
+use tracing::info;
+
+/// errors of the spinning task
 #[derive(Snafu, Debug)]
-pub struct SpinningTaskErr {}
+pub enum SpinningTaskErr {
+    /// a restarted node didn't decide within `rejoin_within` views of coming back
+    NodeDidNotRejoin {
+        /// the index of the node
+        idx: usize,
+        /// highest view of the other nodes when the node came back
+        restart_view: u64,
+        /// views the node had to rejoin within
+        rejoin_within: u64,
+        /// view of the first decide of the node since it came back, if any
+        first_decide: Option<u64>,
+    },
+    /// a restarted node rejoined, but then fell more than `rejoin_within` views behind the
+    /// decides of the other nodes
+    NodeFellBehind {
+        /// the index of the node
+        idx: usize,
+        /// view of the latest decide of the node
+        decided_view: u64,
+        /// view of the latest decide of the other nodes
+        others_decided_view: u64,
+        /// views the node had to keep up within
+        rejoin_within: u64,
+    },
+    /// a node was still down for a restart when the test ended
+    NodeDidNotComeBack {
+        /// the index of the node
+        idx: usize,
+    },
+    /// a node could not be brought back from its storage
+    RestartFailed {
+        /// the index of the node
+        idx: usize,
+        /// why the node could not be brought back
+        reason: String,
+    },
+}
 
 /// Completion task types
 pub type SpinningTaskTypes<TYPES, I> = HSTWithEventAndMessage<
@@ -34,10 +95,258 @@ pub struct SpinningTask<TYPES: NodeType, I: TestableNodeImplementation<TYPES>> {
     pub(crate) handles: Vec<Node<TYPES, I>>,
     pub(crate) late_start: HashMap<u64, SystemContext<TYPES, I>>,
     pub(crate) changes: Vec<Vec<ChangeNode>>,
+    /// configuration to restart nodes with
+    pub(crate) config: HotShotConfig<
+        <TYPES::SignatureKey as SignatureKey>::StakeTableEntry,
+        TYPES::ElectionConfigType,
+    >,
+    /// nodes that were restarted, by index
+    pub(crate) restarted: Arc<Mutex<HashMap<usize, RestartedNode<TYPES>>>>,
+}
+
+/// a node that was restarted
+pub(crate) struct RestartedNode<TYPES: NodeType> {
+    /// views the node has to rejoin within
+    rejoin_within: u64,
+    /// how far the restart got
+    status: RestartStatus<TYPES>,
+}
+
+/// how far the restart of a node got
+pub(crate) enum RestartStatus<TYPES: NodeType> {
+    /// the node is down, and comes back later
+    Down,
+    /// the node could not be brought back
+    Failed(String),
+    /// the node came back
+    Up {
+        /// highest view of the other nodes when the node came back
+        restart_view: TYPES::Time,
+        /// view of the first decide of the node since it came back
+        first_decide: Arc<Mutex<Option<TYPES::Time>>>,
+    },
 }
 
 impl<TYPES: NodeType, I: TestableNodeImplementation<TYPES>> TS for SpinningTask<TYPES, I> {}
 
+impl<TYPES: NodeType, I: TestableNodeImplementation<TYPES>> SpinningTask<TYPES, I>
+where
+    SystemContext<TYPES, I>: HotShotType<TYPES, I>,
+    I::Exchanges: ExchangesType<
+        TYPES,
+        I::Leaf,
+        Message<TYPES, I>,
+        ElectionConfigs = (TYPES::ElectionConfigType, I::CommitteeElectionConfig),
+    >,
+{
+    /// shut down the node at `idx`, if it is up
+    async fn shut_down(&self, idx: usize) -> Option<SystemContextHandle<TYPES, I>> {
+        let node = self.handles.get(idx)?;
+        let mut handle = node.current_handle()?;
+        node.set_down();
+        handle.shut_down().await;
+        Some(handle)
+    }
+
+    /// shut down the node at `idx`, and bring it back from its storage after `down_for`
+    async fn restart(&self, idx: usize, down_for: Duration, rejoin_within: u64) {
+        let Some(handle) = self.shut_down(idx).await else {
+            return;
+        };
+        let node = self.handles[idx].clone();
+        let node_id = node.node_id;
+        info!("Restarting node {} in {:?}", node_id, down_for);
+        // Recorded before the node comes back, so that the test fails if it ends first
+        self.restarted.lock().unwrap().insert(
+            idx,
+            RestartedNode {
+                rejoin_within,
+                status: RestartStatus::Down,
+            },
+        );
+
+        let handles: Vec<_> = self.handles.clone();
+        let config = self.config.clone();
+        let restarted = self.restarted.clone();
+        async_spawn(async move {
+            // The networks of a node outlive its tasks, so the node comes back on the same ones
+            let exchanges = &handle.hotshot.inner.exchanges;
+            let networks: Networks<TYPES, I> = (
+                exchanges.quorum_exchange().network().clone(),
+                exchanges.committee_exchange().network().clone(),
+                exchanges.view_sync_exchange().network().clone(),
+                exchanges.vid_exchange().network().clone(),
+            );
+
+            // Messages sent to the node while it is down are lost, rather than delivered once it
+            // comes back, and don't hold up their senders meanwhile
+            let (quorum, committee, view_sync, vid) = &networks;
+            let _ = config
+                .clock
+                .timeout(
+                    down_for,
+                    join4(
+                        discard_messages(|transmit_type| quorum.recv_msgs(transmit_type)),
+                        discard_messages(|transmit_type| committee.recv_msgs(transmit_type)),
+                        discard_messages(|transmit_type| view_sync.recv_msgs(transmit_type)),
+                        discard_messages(|transmit_type| vid.recv_msgs(transmit_type)),
+                    ),
+                )
+                .await;
+
+            let (public_key, private_key, exchanges) =
+                create_node_exchanges::<TYPES, I>(node_id, networks, &config);
+            let status = match SystemContext::init_from_storage(
+                public_key,
+                private_key,
+                node_id,
+                config.clone(),
+                handle.storage().clone(),
+                exchanges,
+                ConsensusMetricsValue::new(),
+            )
+            .await
+            {
+                Ok((mut new_handle, _)) => {
+                    let first_decide = Arc::new(Mutex::new(None));
+                    let (mut events, _) = new_handle
+                        .get_event_stream_known_impl(FilterEvent::default())
+                        .await;
+                    let decided = first_decide.clone();
+                    async_spawn(async move {
+                        while let Some(event) = events.next().await {
+                            if let EventType::Decide { .. } = event.event {
+                                *decided.lock().unwrap() = Some(event.view_number);
+                                break;
+                            }
+                        }
+                    });
+                    new_handle.hotshot.start_consensus().await;
+                    node.set_up(new_handle).await;
+                    RestartStatus::Up {
+                        restart_view: highest_view(&handles, idx).await,
+                        first_decide,
+                    }
+                }
+                Err(e) => RestartStatus::Failed(format!("{e:?}")),
+            };
+            if let Some(node) = restarted.lock().unwrap().get_mut(&idx) {
+                node.status = status;
+            }
+        });
+    }
+
+    /// check that every restarted node came back, decided within its `rejoin_within` views, and
+    /// kept up with the decides of the other nodes since, unless the test ended before the other
+    /// nodes got that far
+    async fn check_rejoined(&self) -> Result<(), SpinningTaskErr> {
+        let restarted: Vec<_> = self
+            .restarted
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(idx, node)| {
+                let status = match &node.status {
+                    RestartStatus::Down => RestartStatus::Down,
+                    RestartStatus::Failed(reason) => RestartStatus::Failed(reason.clone()),
+                    RestartStatus::Up {
+                        restart_view,
+                        first_decide,
+                    } => RestartStatus::Up {
+                        restart_view: *restart_view,
+                        first_decide: first_decide.clone(),
+                    },
+                };
+                (*idx, node.rejoin_within, status)
+            })
+            .collect();
+        for (idx, rejoin_within, status) in restarted {
+            let (restart_view, first_decide) = match status {
+                RestartStatus::Down => return Err(SpinningTaskErr::NodeDidNotComeBack { idx }),
+                RestartStatus::Failed(reason) => {
+                    return Err(SpinningTaskErr::RestartFailed { idx, reason })
+                }
+                RestartStatus::Up {
+                    restart_view,
+                    first_decide,
+                } => (restart_view, *first_decide.lock().unwrap()),
+            };
+            let deadline = *restart_view + rejoin_within;
+            let rejoined = first_decide.is_some_and(|view| *view <= deadline);
+            if !rejoined
+                && (first_decide.is_some() || *highest_view(&self.handles, idx).await > deadline)
+            {
+                return Err(SpinningTaskErr::NodeDidNotRejoin {
+                    idx,
+                    restart_view: *restart_view,
+                    rejoin_within,
+                    first_decide: first_decide.map(|view| *view),
+                });
+            }
+            if !rejoined {
+                continue;
+            }
+
+            // Rejoining once isn't enough, the node must still be deciding
+            let Some(handle) = self.handles[idx].current_handle() else {
+                continue;
+            };
+            let decided_view = *handle.get_decided_leaf().await.get_view_number();
+            let others_decided_view = *highest_decided_view(&self.handles, idx).await;
+            if others_decided_view > decided_view + rejoin_within {
+                return Err(SpinningTaskErr::NodeFellBehind {
+                    idx,
+                    decided_view,
+                    others_decided_view,
+                    rejoin_within,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// receive and drop the messages `recv` gets, until it fails
+async fn discard_messages<'a, M>(
+    recv: impl Fn(TransmitType) -> BoxSyncFuture<'a, Result<Vec<M>, NetworkError>>,
+) {
+    let recv = &recv;
+    let discard = |transmit_type| async move { while recv(transmit_type).await.is_ok() {} };
+    join(
+        discard(TransmitType::Direct),
+        discard(TransmitType::Broadcast),
+    )
+    .await;
+}
+
+/// highest decided view of the running nodes other than `idx`
+async fn highest_decided_view<TYPES: NodeType, I: TestableNodeImplementation<TYPES>>(
+    handles: &[Node<TYPES, I>],
+    idx: usize,
+) -> TYPES::Time {
+    let mut view = TYPES::Time::genesis();
+    for (i, node) in handles.iter().enumerate() {
+        if let (true, Some(handle)) = (i != idx, node.current_handle()) {
+            view = view.max(handle.get_decided_leaf().await.get_view_number());
+        }
+    }
+    view
+}
+
+/// highest view of the running nodes other than `idx`
+async fn highest_view<TYPES: NodeType, I: TestableNodeImplementation<TYPES>>(
+    handles: &[Node<TYPES, I>],
+    idx: usize,
+) -> TYPES::Time {
+    let mut view = TYPES::Time::genesis();
+    for (i, node) in handles.iter().enumerate() {
+        if let (true, Some(handle)) = (i != idx, node.current_handle()) {
+            view = view.max(handle.get_current_view().await);
+        }
+    }
+    view
+}
+
 /// Spin the node up or down
 #[derive(Clone, Debug)]
 pub enum UpDown {
@@ -45,6 +354,15 @@ pub enum UpDown {
     Up,
     /// spin the node down
     Down,
+    /// spin the node down, then back up after `down_for` with the same keys and storage, resuming
+    /// from its persisted anchor. It must decide again within `rejoin_within` views of the view
+    /// the other nodes are in when it comes back.
+    Restart {
+        /// how long the node stays down
+        down_for: Duration,
+        /// views the node has to rejoin within
+        rejoin_within: u64,
+    },
 }
 
 /// denotes a change in node state
@@ -67,6 +385,12 @@ impl SpinningTaskDescription {
     ) -> TaskGenerator<SpinningTask<TYPES, I>>
     where
         SystemContext<TYPES, I>: HotShotType<TYPES, I>,
+        I::Exchanges: ExchangesType<
+            TYPES,
+            I::Leaf,
+            Message<TYPES, I>,
+            ElectionConfigs = (TYPES::ElectionConfigType, I::CommitteeElectionConfig),
+        >,
     {
         Box::new(move |state, mut registry, test_event_stream| {
             async move {
@@ -75,12 +399,17 @@ impl SpinningTaskDescription {
                         async move {
                             match event {
                                 GlobalTestEvent::ShutDown => {
-                                    (Some(HotShotTaskCompleted::ShutDown), state)
+                                    let result = match state.check_rejoined().await {
+                                        Ok(()) => HotShotTaskCompleted::ShutDown,
+                                        Err(e) => HotShotTaskCompleted::Error(Box::new(e)),
+                                    };
+                                    (Some(result), state)
                                 }
                             }
                         }
                         .boxed()
                     }));
+                let clock = state.config.clock.clone();
                 let atomic_idx = Arc::new(AtomicUsize::new(0));
                 let sleep_durations = Arc::new(
                     self.node_changes
@@ -92,10 +421,11 @@ impl SpinningTaskDescription {
                 let stream_generator = GeneratedStream::new(Arc::new(move || {
                     let atomic_idx = atomic_idx.clone();
                     let sleep_durations = sleep_durations.clone();
+                    let clock = clock.clone();
                     let atomic_idx = atomic_idx.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
                     sleep_durations.get(atomic_idx).copied().map(|duration| {
                         let fut = async move {
-                            async_sleep(duration).await;
+                            clock.sleep(duration).await;
                         };
                         boxed_sync(fut)
                     })
@@ -110,15 +440,19 @@ impl SpinningTaskDescription {
                                             if let Some(node) =
                                                 state.late_start.remove(&idx.try_into().unwrap())
                                             {
-                                                tracing::error!("Spinning up node late");
+                                                info!("Spinning up node late");
                                                 let handle = node.run_tasks().await;
                                                 handle.hotshot.start_consensus().await;
                                             }
                                         }
                                         UpDown::Down => {
-                                            if let Some(node) = state.handles.get_mut(idx) {
-                                                node.handle.shut_down().await;
-                                            }
+                                            state.shut_down(idx).await;
+                                        }
+                                        UpDown::Restart {
+                                            down_for,
+                                            rejoin_within,
+                                        } => {
+                                            state.restart(idx, down_for, rejoin_within).await;
                                         }
                                     }
                                 }
@@ -1,17 +1,21 @@
 use hotshot::types::SignatureKey;
+use hotshot_task_impls::network::OutgoingMessageTransform;
 use hotshot_types::traits::election::{ConsensusExchange, Membership};
-use std::{num::NonZeroUsize, sync::Arc, time::Duration};
+use std::{collections::HashMap, num::NonZeroUsize, sync::Arc, time::Duration};
 
 use hotshot::traits::{NodeImplementation, TestableNodeImplementation};
 use hotshot_types::message::{Message, SequencingMessage};
 
 use hotshot_types::{
-    traits::node_implementation::{NodeType, QuorumEx, TestableExchange},
-    ExecutionType, HotShotConfig,
+    clock::{Clock, VirtualClock},
+    data::{Leaf, QuorumProposal},
+    traits::node_implementation::{ExchangesType, NodeType, QuorumEx, TestableExchange},
+    ExecutionType, HotShotConfig, MempoolConfig, VidConfig, DEFAULT_DECIDED_RETENTION_VIEWS,
 };
 
 use super::completion_task::{CompletionTaskDescription, TimeBasedCompletionTaskDescription};
 use crate::{
+    byzantine::{ByzantineBehaviour, ByzantineNode, ByzantineTransform},
     spinning_task::SpinningTaskDescription,
     test_launcher::{ResourceGenerators, TestLauncher},
 };
@@ -61,6 +65,14 @@ pub struct TestMetadata {
     pub min_transactions: usize,
     /// timing data
     pub timing_data: TimingData,
+    /// If set, run the nodes on a virtual clock and deliver memory network messages after
+    /// latencies derived from this seed, so that a failing seed replays the same execution.
+    /// Simulations need a single-threaded tokio runtime, see [`TestRunner::run_test`].
+    ///
+    /// [`TestRunner::run_test`]: crate::test_runner::TestRunner::run_test
+    pub simulation_seed: Option<u64>,
+    /// nodes that misbehave
+    pub byzantine_nodes: Vec<ByzantineNode>,
 }
 
 impl Default for TimingData {
@@ -157,6 +169,8 @@ impl Default for TestMetadata {
         Self {
             timing_data: TimingData::default(),
             min_transactions: 0,
+            simulation_seed: None,
+            byzantine_nodes: vec![],
             total_nodes: 5,
             start_nodes: 5,
             num_bootstrap_nodes: 5,
@@ -182,9 +196,24 @@ impl TestMetadata {
         self,
     ) -> TestLauncher<TYPES, I>
     where
-        I: NodeImplementation<TYPES, ConsensusMessage = SequencingMessage<TYPES, I>>,
+        I: NodeImplementation<
+            TYPES,
+            Leaf = Leaf<TYPES>,
+            ConsensusMessage = SequencingMessage<TYPES, I>,
+        >,
+        QuorumEx<TYPES, I>: ConsensusExchange<
+            TYPES,
+            Message<TYPES, I>,
+            Proposal = QuorumProposal<TYPES, Leaf<TYPES>>,
+        >,
         <I as NodeImplementation<TYPES>>::Exchanges:
             TestableExchange<TYPES, <I as NodeImplementation<TYPES>>::Leaf, Message<TYPES, I>>,
+        I::Exchanges: ExchangesType<
+            TYPES,
+            I::Leaf,
+            Message<TYPES, I>,
+            ElectionConfigs = (TYPES::ElectionConfigType, I::CommitteeElectionConfig),
+        >,
         SystemContext<TYPES, I>: HotShotType<TYPES, I>,
     {
         let TestMetadata {
@@ -197,8 +226,11 @@ impl TestMetadata {
             completion_task_description,
             overall_safety_properties,
             spinning_properties,
+            simulation_seed,
+            byzantine_nodes,
             ..
         } = self.clone();
+        let simulation = simulation_seed.map(|seed| (seed, VirtualClock::new()));
 
         let known_nodes: Vec<<TYPES as NodeType>::SignatureKey> = (0..total_nodes)
             .map(|id| {
@@ -219,6 +251,7 @@ impl TestMetadata {
             num_bootstrap: num_bootstrap_nodes,
             min_transactions,
             max_transactions: NonZeroUsize::new(99999).unwrap(),
+            max_block_size: NonZeroUsize::new(usize::MAX).unwrap(),
             known_nodes_with_stake,
             da_committee_size,
             next_view_timeout: 500,
@@ -235,6 +268,16 @@ impl TestMetadata {
             >>::Membership::default_election_config(
                 total_nodes as u64
             )),
+            mempool: MempoolConfig::default(),
+            vid: VidConfig {
+                insecure_test_srs: true,
+                ..VidConfig::default()
+            },
+            decided_retention_views: Some(DEFAULT_DECIDED_RETENTION_VIEWS),
+            clock: match &simulation {
+                Some((_, clock)) => Clock::Virtual(Arc::clone(clock)),
+                None => Clock::Real,
+            },
         };
         let TimingData {
             next_view_timeout,
@@ -255,6 +298,20 @@ impl TestMetadata {
                 a.propose_max_round_time = propose_max_round_time;
             };
 
+        let byzantine_behaviours: HashMap<u64, Vec<ByzantineBehaviour>> = byzantine_nodes
+            .into_iter()
+            .map(|node| (node.idx as u64, node.behaviours))
+            .collect();
+        let byzantine = Box::new(move |node_id| {
+            byzantine_behaviours.get(&node_id).map(|behaviours| {
+                Arc::new(ByzantineTransform::<TYPES, I>::new(
+                    node_id,
+                    known_nodes.clone(),
+                    behaviours.clone(),
+                )) as Arc<dyn OutgoingMessageTransform<TYPES, I>>
+            })
+        });
+
         let txn_task_generator = txn_description.build();
         let completion_task_generator = completion_task_description.build_and_launch();
         let overall_safety_task_generator = overall_safety_properties.build();
@@ -268,9 +325,13 @@ impl TestMetadata {
                             _,
                             _,
                         >>::gen_comm_channels(
-                            total_nodes, num_bootstrap_nodes, da_committee_size
+                            total_nodes,
+                            num_bootstrap_nodes,
+                            da_committee_size,
+                            simulation,
                         ),
                     storage: Box::new(|_| I::construct_tmp_storage().unwrap()),
+                    byzantine,
                     config,
                 },
             metadata: self,
@@ -8,6 +8,7 @@ use hotshot_task::{
     task::HotShotTaskCompleted,
     task_launcher::TaskRunner,
 };
+use hotshot_task_impls::network::OutgoingMessageTransform;
 use hotshot_types::{
     message::Message,
     traits::{
@@ -95,6 +96,8 @@ where
     pub channel_generator: Generator<Networks<TYPES, I>>,
     /// generate a new storage for each node
     pub storage: Generator<<I as NodeImplementation<TYPES>>::Storage>,
+    /// generate how each node misbehaves, or `None` for honest nodes
+    pub byzantine: Generator<Option<Arc<dyn OutgoingMessageTransform<TYPES, I>>>>,
     /// configuration used to generate each hotshot node
     pub config: HotShotConfig<
         <TYPES::SignatureKey as SignatureKey>::StakeTableEntry,
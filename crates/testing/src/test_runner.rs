@@ -7,14 +7,28 @@ use crate::{
     spinning_task::UpDown,
     test_launcher::{Networks, TestLauncher},
 };
+use async_compatibility_layer::{
+    art::async_spawn,
+    channel::{unbounded, UnboundedSender, UnboundedStream},
+};
+use commit::{Commitment, Committable};
+use either::Either;
+use futures::StreamExt;
 use hotshot::types::SystemContextHandle;
 
-use hotshot::{traits::TestableNodeImplementation, HotShotInitializer, HotShotType, SystemContext};
+use hotshot::{
+    traits::{NodeImplementation, TestableNodeImplementation},
+    HotShotInitializer, HotShotType, SystemContext,
+};
 use hotshot_task::{
-    event_stream::ChannelStream, global_registry::GlobalRegistry, task_launcher::TaskRunner,
+    event_stream::ChannelStream, global_registry::GlobalRegistry, task::FilterEvent,
+    task_launcher::TaskRunner, Merge,
 };
+use hotshot_task_impls::events::HotShotEvent;
 use hotshot_types::{
+    clock::Clock,
     consensus::ConsensusMetricsValue,
+    event::{Event, EventType},
     message::Message,
     traits::{
         election::{ConsensusExchange, Membership},
@@ -24,15 +38,118 @@ use hotshot_types::{
     },
     HotShotConfig,
 };
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
 
 #[allow(deprecated)]
 use tracing::info;
 
+/// Pause the time of the runtime, which then only moves once every task is waiting. A
+/// [`VirtualClock`](hotshot_types::clock::VirtualClock) driven on it moves exactly when the nodes
+/// can't make progress without it.
+///
+/// # Panics
+/// Unless this runs on a single-threaded tokio runtime
+fn pause_runtime_time() {
+    #[cfg(async_executor_impl = "tokio")]
+    {
+        assert_eq!(
+            tokio::runtime::Handle::current().runtime_flavor(),
+            tokio::runtime::RuntimeFlavor::CurrentThread,
+            "Simulations need a single-threaded tokio runtime"
+        );
+        tokio::time::pause();
+    }
+    #[cfg(async_executor_impl = "async-std")]
+    panic!("Simulations need a single-threaded tokio runtime");
+}
+
+/// Undo [`pause_runtime_time`]
+fn resume_runtime_time() {
+    #[cfg(async_executor_impl = "tokio")]
+    tokio::time::resume();
+}
+
+/// An external or internal event of a node
+pub type NodeEvent<TYPES, I> =
+    Either<Event<TYPES, <I as NodeImplementation<TYPES>>::Leaf>, HotShotEvent<TYPES, I>>;
+
 #[derive(Clone)]
 pub struct Node<TYPES: NodeType, I: TestableNodeImplementation<TYPES>> {
     pub node_id: u64,
+    /// the handle the node started with
     pub handle: SystemContextHandle<TYPES, I>,
+    /// the handle the node runs on now, or `None` while it is down. Shared by the copies of the
+    /// node in every test task, and replaced by the spinning task when it restarts the node.
+    pub current: Arc<Mutex<Option<SystemContextHandle<TYPES, I>>>>,
+    /// where the events of the node are forwarded to, by [`Node::subscribe`]
+    subscribers: Arc<Mutex<Vec<UnboundedSender<NodeEvent<TYPES, I>>>>>,
+}
+
+impl<TYPES: NodeType, I: TestableNodeImplementation<TYPES>> Node<TYPES, I> {
+    /// a node running on `handle`
+    pub fn new(node_id: u64, handle: SystemContextHandle<TYPES, I>) -> Self {
+        Self {
+            node_id,
+            current: Arc::new(Mutex::new(Some(handle.clone()))),
+            handle,
+            subscribers: Arc::default(),
+        }
+    }
+
+    /// the handle the node runs on now, if it is up
+    pub fn current_handle(&self) -> Option<SystemContextHandle<TYPES, I>> {
+        self.current.lock().unwrap().clone()
+    }
+
+    /// record that the node is down
+    pub fn set_down(&self) {
+        *self.current.lock().unwrap() = None;
+    }
+
+    /// record that the node came back up on `handle`, whose events then reach the subscribers
+    /// of the node
+    pub async fn set_up(&self, mut handle: SystemContextHandle<TYPES, I>) {
+        let subscribers = self.subscribers.lock().unwrap().clone();
+        for subscriber in subscribers {
+            forward_events(&mut handle, subscriber).await;
+        }
+        *self.current.lock().unwrap() = Some(handle);
+    }
+
+    /// the external and internal events of the node, including those of the handles it runs on
+    /// after restarting
+    pub async fn subscribe(&self) -> UnboundedStream<NodeEvent<TYPES, I>> {
+        let (sender, receiver) = unbounded();
+        if let Some(mut handle) = self.current_handle() {
+            forward_events(&mut handle, sender.clone()).await;
+        }
+        self.subscribers.lock().unwrap().push(sender);
+        receiver.into_stream()
+    }
+}
+
+/// forward the external and internal events of `handle` to `subscriber`
+async fn forward_events<TYPES: NodeType, I: TestableNodeImplementation<TYPES>>(
+    handle: &mut SystemContextHandle<TYPES, I>,
+    subscriber: UnboundedSender<NodeEvent<TYPES, I>>,
+) {
+    let (external, _) = handle
+        .get_event_stream_known_impl(FilterEvent::default())
+        .await;
+    let (internal, _) = handle
+        .get_internal_event_stream_known_impl(FilterEvent::default())
+        .await;
+    let mut events = Merge::new(external, internal);
+    async_spawn(async move {
+        while let Some(event) = events.next().await {
+            if subscriber.send(event).await.is_err() {
+                break;
+            }
+        }
+    });
 }
 
 /// The runner of a test network
@@ -62,7 +179,11 @@ where
     >,
 {
     /// excecute test
-    pub async fn run_test(mut self)
+    ///
+    /// If the test is a simulation, i.e. the nodes run on a virtual clock, this must run on a
+    /// single-threaded tokio runtime, whose time is paused for the test so that the virtual clock
+    /// only moves once every task is waiting.
+    pub async fn run_test(self)
     where
         I::Exchanges: ExchangesType<
             TYPES,
@@ -71,6 +192,28 @@ where
             ElectionConfigs = (TYPES::ElectionConfigType, I::CommitteeElectionConfig),
         >,
     {
+        self.run_test_recording_decides().await;
+    }
+
+    /// excecute test like [`run_test`](Self::run_test), and return the commitments of the leaves
+    /// each node started with the test decided, in the order the node decided them
+    pub async fn run_test_recording_decides(mut self) -> Vec<Vec<Commitment<I::Leaf>>>
+    where
+        I::Exchanges: ExchangesType<
+            TYPES,
+            I::Leaf,
+            Message<TYPES, I>,
+            ElectionConfigs = (TYPES::ElectionConfigType, I::CommitteeElectionConfig),
+        >,
+    {
+        let virtual_clock = match &self.launcher.resource_generator.config.clock {
+            Clock::Virtual(clock) => {
+                pause_runtime_time();
+                Some(Arc::clone(clock))
+            }
+            Clock::Real => None,
+        };
+
         let spinning_changes = self
             .launcher
             .metadata
@@ -109,6 +252,8 @@ where
         let txn_task_state = TxnTask {
             handles: nodes.clone(),
             next_node_idx: Some(0),
+            clock: launcher.resource_generator.config.clock.clone(),
+            simulation_seed: launcher.metadata.simulation_seed,
         };
         let (id, task) = (launcher.txn_task_generator)(
             txn_task_state,
@@ -123,6 +268,7 @@ where
         let completion_task_state = CompletionTask {
             handles: nodes.clone(),
             test_event_stream: test_event_stream.clone(),
+            clock: launcher.resource_generator.config.clock.clone(),
         };
         let (id, task) = (launcher.completion_task_generator)(
             completion_task_state,
@@ -138,6 +284,8 @@ where
             handles: nodes.clone(),
             late_start,
             changes: spinning_changes.into_iter().map(|(_, b)| b).collect(),
+            config: launcher.resource_generator.config.clone(),
+            restarted: Arc::default(),
         };
 
         let (id, task) = (launcher.spinning_task_generator)(
@@ -153,6 +301,12 @@ where
             handles: nodes.clone(),
             ctx: RoundCtx::default(),
             test_event_stream: test_event_stream.clone(),
+            byzantine_nodes: launcher
+                .metadata
+                .byzantine_nodes
+                .iter()
+                .map(|node| node.idx as u64)
+                .collect(),
         };
         let (id, task) = (launcher.overall_safety_task_generator)(
             overall_safety_task_state,
@@ -162,6 +316,34 @@ where
         .await;
         task_runner = task_runner.add_task(id, "Test Overall Safety Task".to_string(), task);
 
+        // Record what every node decides, including after it restarts
+        let mut decides = Vec::new();
+        for node in &nodes {
+            let decided = Arc::new(Mutex::new(Vec::new()));
+            let mut events = node.subscribe().await;
+            let recorder = decided.clone();
+            async_spawn(async move {
+                while let Some(event) = events.next().await {
+                    if let Either::Left(Event {
+                        event: EventType::Decide { leaf_chain, .. },
+                        ..
+                    }) = event
+                    {
+                        recorder
+                            .lock()
+                            .unwrap()
+                            .extend(leaf_chain.iter().map(Committable::commit));
+                    }
+                }
+            });
+            decides.push(decided);
+        }
+
+        // In simulation mode, move the nodes' virtual clock forward whenever they are all waiting
+        if let Some(clock) = &virtual_clock {
+            async_spawn(Arc::clone(clock).drive());
+        }
+
         // Start hotshot
         for node in nodes {
             if !late_start_nodes.contains(&node.node_id) {
@@ -170,6 +352,10 @@ where
         }
 
         let results = task_runner.launch().await;
+        if let Some(clock) = virtual_clock {
+            clock.stop();
+            resume_runtime_time();
+        }
 
         let mut error_list = vec![];
         for (name, result) in results {
@@ -186,6 +372,10 @@ where
         if !error_list.is_empty() {
             panic!("TEST FAILED! Results: {:?}", error_list);
         }
+        decides
+            .iter()
+            .map(|decided| decided.lock().unwrap().clone())
+            .collect()
     }
 
     /// add nodes
@@ -210,13 +400,14 @@ where
             let hotshot = self
                 .add_node_with_config(networks, storage, initializer, config)
                 .await;
+            if let Some(transform) = (self.launcher.resource_generator.byzantine)(node_id) {
+                hotshot.set_outgoing_message_transform(transform).await;
+            }
             if late_start.contains(&node_id) {
                 self.late_start.insert(node_id, hotshot);
             } else {
-                self.nodes.push(Node {
-                    node_id,
-                    handle: hotshot.run_tasks().await,
-                });
+                self.nodes
+                    .push(Node::new(node_id, hotshot.run_tasks().await));
             }
             results.push(node_id);
         }
@@ -246,29 +437,8 @@ where
         let node_id = self.next_node_id;
         self.next_node_id += 1;
 
-        let known_nodes_with_stake = config.known_nodes_with_stake.clone();
-        // Generate key pair for certificate aggregation
-        let private_key = TYPES::SignatureKey::generated_from_seed_indexed([0u8; 32], node_id).1;
-        let public_key = TYPES::SignatureKey::from_private(&private_key);
-        let entry = public_key.get_stake_table_entry(1u64);
-        let quorum_election_config = config.election_config.clone().unwrap_or_else(|| {
-            <QuorumEx<TYPES,I> as ConsensusExchange<
-                TYPES,
-                Message<TYPES, I>,
-            >>::Membership::default_election_config(config.total_nodes.get() as u64)
-        });
-        let committee_election_config = I::committee_election_config_generator();
-        let exchanges = I::Exchanges::create(
-            known_nodes_with_stake.clone(),
-            (
-                quorum_election_config,
-                committee_election_config(config.da_committee_size as u64),
-            ),
-            networks,
-            public_key.clone(),
-            entry.clone(),
-            private_key.clone(),
-        );
+        let (public_key, private_key, exchanges) =
+            create_node_exchanges::<TYPES, I>(node_id, networks, &config);
         SystemContext::new(
             public_key,
             private_key,
@@ -283,3 +453,52 @@ where
         .expect("Could not init hotshot")
     }
 }
+
+/// The keys of node `node_id`, generated the same way for every test, and its exchanges over
+/// `networks`
+#[allow(clippy::type_complexity)]
+pub(crate) fn create_node_exchanges<TYPES: NodeType, I: TestableNodeImplementation<TYPES>>(
+    node_id: u64,
+    networks: Networks<TYPES, I>,
+    config: &HotShotConfig<
+        <TYPES::SignatureKey as SignatureKey>::StakeTableEntry,
+        TYPES::ElectionConfigType,
+    >,
+) -> (
+    TYPES::SignatureKey,
+    <TYPES::SignatureKey as SignatureKey>::PrivateKey,
+    I::Exchanges,
+)
+where
+    I::Exchanges: ExchangesType<
+        TYPES,
+        I::Leaf,
+        Message<TYPES, I>,
+        ElectionConfigs = (TYPES::ElectionConfigType, I::CommitteeElectionConfig),
+    >,
+{
+    let known_nodes_with_stake = config.known_nodes_with_stake.clone();
+    // Generate key pair for certificate aggregation
+    let private_key = TYPES::SignatureKey::generated_from_seed_indexed([0u8; 32], node_id).1;
+    let public_key = TYPES::SignatureKey::from_private(&private_key);
+    let entry = public_key.get_stake_table_entry(1u64);
+    let quorum_election_config = config.election_config.clone().unwrap_or_else(|| {
+        <QuorumEx<TYPES,I> as ConsensusExchange<
+            TYPES,
+            Message<TYPES, I>,
+        >>::Membership::default_election_config(config.total_nodes.get() as u64)
+    });
+    let committee_election_config = I::committee_election_config_generator();
+    let exchanges = I::Exchanges::create(
+        known_nodes_with_stake,
+        (
+            quorum_election_config,
+            committee_election_config(config.da_committee_size as u64),
+        ),
+        networks,
+        public_key.clone(),
+        entry,
+        private_key.clone(),
+    );
+    (public_key, private_key, exchanges)
+}
@@ -1,7 +1,6 @@
 use crate::test_runner::Node;
-use async_compatibility_layer::art::async_sleep;
 use futures::FutureExt;
-use hotshot::traits::TestableNodeImplementation;
+use hotshot::{traits::TestableNodeImplementation, types::SystemContextHandle};
 use hotshot_task::{
     boxed_sync,
     event_stream::ChannelStream,
@@ -10,12 +9,17 @@ use hotshot_task::{
     GeneratedStream,
 };
 use hotshot_types::{
+    clock::Clock,
     message::SequencingMessage,
     traits::node_implementation::{NodeImplementation, NodeType},
 };
-use rand::thread_rng;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
 use snafu::Snafu;
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::warn;
 
 use super::{test_launcher::TaskGenerator, GlobalTestEvent};
 
@@ -26,6 +30,35 @@ use super::{test_launcher::TaskGenerator, GlobalTestEvent};
 #[derive(Snafu, Debug)]
 pub struct TxnTaskErr {}
 
+/// a [`TxnWorkload`] that can't be generated
+#[derive(Snafu, Debug, PartialEq)]
+pub enum TxnWorkloadErr {
+    /// Poisson arrivals need a positive, finite rate
+    InvalidTps {
+        /// the configured transactions per second
+        tps: f64,
+    },
+    /// bursts need at least one transaction, and time between them
+    InvalidBurst {
+        /// the configured transactions per burst
+        burst_size: usize,
+        /// the configured time between bursts
+        period: Duration,
+    },
+    /// uniform sizes need `min <= max`
+    InvalidSizeRange {
+        /// the configured smallest padding
+        min: u64,
+        /// the configured largest padding
+        max: u64,
+    },
+    /// exponential sizes need a non-negative, finite mean
+    InvalidMeanSize {
+        /// the configured mean padding
+        mean: f64,
+    },
+}
+
 /// state of task that decides when things are completed
 pub struct TxnTask<TYPES: NodeType, I: TestableNodeImplementation<TYPES>> {
     // TODO should this be in a rwlock? Or maybe a similar abstraction to the registry is in order
@@ -33,10 +66,74 @@ pub struct TxnTask<TYPES: NodeType, I: TestableNodeImplementation<TYPES>> {
     pub handles: Vec<Node<TYPES, I>>,
     /// Optional index of the next node.
     pub next_node_idx: Option<usize>,
+    /// The clock of the nodes, which transactions are submitted by.
+    pub clock: Clock,
+    /// Seed of the simulation, if this is one. Round robin transactions are then drawn from it
+    /// rather than from entropy, so that they are the same every time.
+    pub simulation_seed: Option<u64>,
 }
 
 impl<TYPES: NodeType, I: TestableNodeImplementation<TYPES>> TS for TxnTask<TYPES, I> {}
 
+impl<TYPES: NodeType, I: TestableNodeImplementation<TYPES>> TxnTask<TYPES, I> {
+    /// handles of the nodes that are running
+    fn running(&self) -> Vec<SystemContextHandle<TYPES, I>> {
+        self.handles
+            .iter()
+            .filter_map(Node::current_handle)
+            .collect()
+    }
+
+    /// the node to submit the next transaction of a workload to, if any is running
+    async fn target(
+        &self,
+        target: &TxnTarget,
+        rng: &Mutex<StdRng>,
+    ) -> Option<SystemContextHandle<TYPES, I>> {
+        let running = self.running();
+        let random = || running.choose(&mut *rng.lock().unwrap()).cloned();
+        match target {
+            TxnTarget::Random => random(),
+            TxnTarget::Fixed(idx) => self.handles.get(*idx)?.current_handle(),
+            TxnTarget::Leader => {
+                let handle = running.first()?;
+                let leader = handle.get_leader(handle.get_current_view().await).await;
+                running
+                    .iter()
+                    .find(|handle| handle.get_public_key() == leader)
+                    .cloned()
+                    .or_else(random)
+            }
+        }
+    }
+
+    /// the next running node in round robin order, moving past it
+    fn next_round_robin(&mut self) -> Option<SystemContextHandle<TYPES, I>> {
+        let start = self.next_node_idx?;
+        let num_nodes = self.handles.len();
+        (0..num_nodes).find_map(|offset| {
+            let idx = (start + offset) % num_nodes;
+            let handle = self.handles[idx].current_handle()?;
+            self.next_node_idx = Some((idx + 1) % num_nodes);
+            Some(handle)
+        })
+    }
+}
+
+/// submit a random transaction with `padding` bytes of padding to `handle`
+async fn submit_random_transaction<TYPES: NodeType, I: TestableNodeImplementation<TYPES>>(
+    handle: &SystemContextHandle<TYPES, I>,
+    rng: &Mutex<StdRng>,
+    padding: u64,
+) {
+    let leaf = handle.get_decided_leaf().await;
+    let txn = I::leaf_create_random_transaction(&leaf, &mut *rng.lock().unwrap(), padding);
+    // the node may have gone down since it was picked
+    if let Err(e) = handle.submit_transaction(txn).await {
+        warn!("Could not send transaction: {:?}", e);
+    }
+}
+
 /// types for task that deices when things are completed
 pub type TxnTaskTypes<TYPES, I> = HSTWithEventAndMessage<
     TxnTaskErr,
@@ -53,8 +150,131 @@ pub enum TxnTaskDescription {
     /// submit transactions in a round robin style using
     /// every `Duration` seconds
     RoundRobinTimeBased(Duration),
-    /// TODO
-    DistributionBased, // others?
+    /// submit transactions as described by a workload
+    DistributionBased(TxnWorkload),
+}
+
+/// a transaction workload drawn from random distributions
+#[derive(Clone, Debug)]
+pub struct TxnWorkload {
+    /// when transactions are submitted
+    pub arrivals: TxnArrivals,
+    /// how many bytes of padding each transaction gets
+    pub sizes: TxnSizes,
+    /// which nodes transactions are submitted to
+    pub target: TxnTarget,
+    /// seed of the random choices, so that a workload can be replayed
+    pub seed: u64,
+}
+
+impl TxnWorkload {
+    /// check that the distributions of the workload can be sampled
+    /// # Errors
+    /// If a distribution has invalid parameters
+    pub fn validate(&self) -> Result<(), TxnWorkloadErr> {
+        match self.arrivals {
+            TxnArrivals::Poisson { tps } if !(tps.is_finite() && tps > 0.0) => {
+                return Err(TxnWorkloadErr::InvalidTps { tps });
+            }
+            TxnArrivals::Bursty { burst_size, period } if burst_size == 0 || period.is_zero() => {
+                return Err(TxnWorkloadErr::InvalidBurst { burst_size, period });
+            }
+            TxnArrivals::Poisson { .. } | TxnArrivals::Bursty { .. } => {}
+        }
+        match self.sizes {
+            TxnSizes::Uniform { min, max } if min > max => {
+                Err(TxnWorkloadErr::InvalidSizeRange { min, max })
+            }
+            TxnSizes::Exponential { mean } if !(mean.is_finite() && mean >= 0.0) => {
+                Err(TxnWorkloadErr::InvalidMeanSize { mean })
+            }
+            TxnSizes::Fixed(_) | TxnSizes::Uniform { .. } | TxnSizes::Exponential { .. } => Ok(()),
+        }
+    }
+}
+
+/// when transactions are submitted
+#[derive(Clone, Debug)]
+pub enum TxnArrivals {
+    /// one at a time, with exponentially distributed gaps averaging `tps` transactions per second
+    Poisson {
+        /// the target transactions per second, which must be positive
+        tps: f64,
+    },
+    /// `burst_size` transactions at once, every `period`
+    Bursty {
+        /// transactions per burst
+        burst_size: usize,
+        /// time between bursts
+        period: Duration,
+    },
+}
+
+/// distribution of the padding of transactions, in bytes
+#[derive(Clone, Debug)]
+pub enum TxnSizes {
+    /// always the same padding
+    Fixed(u64),
+    /// uniformly distributed between `min` and `max`, inclusive
+    Uniform {
+        /// smallest padding
+        min: u64,
+        /// largest padding
+        max: u64,
+    },
+    /// exponentially distributed: mostly small transactions, with a few large ones
+    Exponential {
+        /// mean padding
+        mean: f64,
+    },
+}
+
+/// which nodes transactions are submitted to
+#[derive(Clone, Debug)]
+pub enum TxnTarget {
+    /// a running node chosen uniformly at random for each transaction
+    Random,
+    /// always the node at this index, skipping transactions while it is down
+    Fixed(usize),
+    /// the leader of the current view, or a random node if the leader isn't running
+    Leader,
+}
+
+/// sample an exponential distribution with mean `mean`
+fn sample_exponential(rng: &mut StdRng, mean: f64) -> f64 {
+    // inverse transform sampling; `1 - u` is in (0, 1], so the log is finite
+    -mean * (1.0 - rng.gen::<f64>()).ln()
+}
+
+impl TxnArrivals {
+    /// time until the next submission
+    fn next_gap(&self, rng: &mut StdRng) -> Duration {
+        match self {
+            TxnArrivals::Poisson { tps } => {
+                Duration::from_secs_f64(sample_exponential(rng, 1.0 / tps))
+            }
+            TxnArrivals::Bursty { period, .. } => *period,
+        }
+    }
+
+    /// number of transactions in each submission
+    fn batch_size(&self) -> usize {
+        match self {
+            TxnArrivals::Poisson { .. } => 1,
+            TxnArrivals::Bursty { burst_size, .. } => *burst_size,
+        }
+    }
+}
+
+impl TxnSizes {
+    /// padding of the next transaction
+    fn sample(&self, rng: &mut StdRng) -> u64 {
+        match self {
+            TxnSizes::Fixed(size) => *size,
+            TxnSizes::Uniform { min, max } => rng.gen_range(*min..=*max),
+            TxnSizes::Exponential { mean } => sample_exponential(rng, *mean) as u64,
+        }
+    }
 }
 
 impl TxnTaskDescription {
@@ -66,15 +286,27 @@ impl TxnTaskDescription {
         TYPES: NodeType,
         I: NodeImplementation<TYPES, ConsensusMessage = SequencingMessage<TYPES, I>>,
     {
+        // reject a workload that can't be sampled before the test starts, rather than in the
+        // middle of it
+        if let TxnTaskDescription::DistributionBased(workload) = &self {
+            if let Err(e) = workload.validate() {
+                panic!("Invalid transaction workload: {e:?}");
+            }
+        }
         Box::new(move |state, mut registry, test_event_stream| {
             async move {
                 // consistency check
-                match self {
-                    TxnTaskDescription::RoundRobinTimeBased(_) => {
-                        assert!(state.next_node_idx.is_some())
-                    }
-                    TxnTaskDescription::DistributionBased => assert!(state.next_node_idx.is_none()),
+                if let TxnTaskDescription::RoundRobinTimeBased(_) = self {
+                    assert!(state.next_node_idx.is_some())
                 }
+                let rng = Arc::new(Mutex::new(match &self {
+                    TxnTaskDescription::RoundRobinTimeBased(_) => state
+                        .simulation_seed
+                        .map_or_else(StdRng::from_entropy, StdRng::seed_from_u64),
+                    TxnTaskDescription::DistributionBased(workload) => {
+                        StdRng::seed_from_u64(workload.seed)
+                    }
+                }));
                 // TODO we'll possibly want multiple criterion including:
                 // - certain number of txns committed
                 // - anchor of certain depth
@@ -90,55 +322,62 @@ impl TxnTaskDescription {
                         }
                         .boxed()
                     }));
+                let description = self.clone();
+                let handler_rng = rng.clone();
                 let message_handler =
                     HandleMessage::<TxnTaskTypes<TYPES, I>>(Arc::new(move |_, mut state| {
+                        let description = description.clone();
+                        let rng = handler_rng.clone();
                         async move {
-                            if let Some(idx) = state.next_node_idx {
-                                // submit to idx handle
-                                // increment state
-                                state.next_node_idx = Some((idx + 1) % state.handles.len());
-                                match state.handles.get(idx) {
-                                    None => {
-                                        // should do error
-                                        unimplemented!()
-                                    }
-                                    Some(node) => {
-                                        // use rand::seq::IteratorRandom;
+                            match description {
+                                TxnTaskDescription::RoundRobinTimeBased(_) => {
+                                    // submit to the next running node, if any
+                                    if let Some(handle) = state.next_round_robin() {
                                         // we're assuming all nodes have the same leaf.
                                         // If they don't match, this is probably fine since
                                         // it should be caught by an assertion (and the txn will be rejected anyway)
-                                        let leaf = node.handle.get_decided_leaf().await;
-                                        let txn = I::leaf_create_random_transaction(
-                                            &leaf,
-                                            &mut thread_rng(),
-                                            0,
-                                        );
-                                        node.handle
-                                            .submit_transaction(txn.clone())
-                                            .await
-                                            .expect("Could not send transaction");
-                                        (None, state)
+                                        submit_random_transaction(&handle, &rng, 0).await;
+                                    }
+                                }
+                                TxnTaskDescription::DistributionBased(workload) => {
+                                    for _ in 0..workload.arrivals.batch_size() {
+                                        let padding =
+                                            workload.sizes.sample(&mut rng.lock().unwrap());
+                                        let Some(handle) =
+                                            state.target(&workload.target, &rng).await
+                                        else {
+                                            // no node is running
+                                            break;
+                                        };
+                                        submit_random_transaction(&handle, &rng, padding).await;
                                     }
                                 }
-                            } else {
-                                // TODO make an issue
-                                // in the case that this is random
-                                // which I haven't implemented yet
-                                unimplemented!()
                             }
+                            (None, state)
                         }
                         .boxed()
                     }));
+                let clock = state.clock.clone();
                 let stream_generator = match self {
                     TxnTaskDescription::RoundRobinTimeBased(duration) => {
                         GeneratedStream::new(Arc::new(move || {
+                            let clock = clock.clone();
+                            let fut = async move {
+                                clock.sleep(duration).await;
+                            };
+                            Some(boxed_sync(fut))
+                        }))
+                    }
+                    TxnTaskDescription::DistributionBased(workload) => {
+                        GeneratedStream::new(Arc::new(move || {
+                            let clock = clock.clone();
+                            let gap = workload.arrivals.next_gap(&mut rng.lock().unwrap());
                             let fut = async move {
-                                async_sleep(duration).await;
+                                clock.sleep(gap).await;
                             };
                             Some(boxed_sync(fut))
                         }))
                     }
-                    TxnTaskDescription::DistributionBased => unimplemented!(),
                 };
                 let builder = TaskBuilder::<TxnTaskTypes<TYPES, I>>::new(
                     "Test Transaction Submission Task".to_string(),
@@ -1,85 +1,121 @@
-#![cfg(foo)]
-use hotshot::{
-    certificate::QuorumCertificate,
-    data::LeafType,
-    demos::vdemo::{
-        random_quorom_certificate, random_transaction, random_validating_leaf, VDemoBlock,
-        VDemoState,
+use commit::Committable;
+use hotshot::traits::{implementations::AtomicStorage, Storage};
+use hotshot_testing::node_types::TestTypes;
+use hotshot_types::{
+    block_impl::{VIDBlockHeader, VIDBlockPayload, VIDTransaction},
+    data::{fake_commitment, genesis_proposer_id, Leaf, LeafType, ViewNumber},
+    simple_certificate::QuorumCertificate2,
+    traits::{
+        block_contents::BlockHeader,
+        state::ConsensusTime,
+        storage::{StoredView, TestableStorage, UndecidedState, ViewEntry},
     },
-    traits::{BlockPayload, State, Storage},
 };
-use hotshot_types::{data::ViewNumber, traits::state::TestableState};
-use rand::thread_rng;
 
-type AtomicStorage = hotshot::traits::implementations::AtomicStorage<DEntryState>;
+type TestStorage = AtomicStorage<TestTypes, Leaf<TestTypes>>;
+
+fn stored_view(view_number: u64) -> StoredView<TestTypes, Leaf<TestTypes>> {
+    let payload = VIDBlockPayload::genesis();
+    let header = VIDBlockHeader {
+        block_number: view_number,
+        ..VIDBlockHeader::genesis(payload.clone())
+    };
+    StoredView::from_qc_block_and_state(
+        QuorumCertificate2 {
+            view_number: ViewNumber::new(view_number),
+            ..QuorumCertificate2::genesis()
+        },
+        header,
+        Some(payload),
+        (),
+        fake_commitment(),
+        Vec::new(),
+        genesis_proposer_id(),
+    )
+}
 
 #[cfg_attr(
     async_executor_impl = "tokio",
     tokio::test(flavor = "multi_thread", worker_threads = 2)
 )]
 #[cfg_attr(async_executor_impl = "async-std", async_std::test)]
-async fn test_happy_path_qcs() {
+async fn test_atomic_storage_reload() {
     // This folder will be destroyed when the last handle to it closes
     let file = tempfile::tempdir().expect("Could not create temp dir");
     let path = file.path();
-    println!("Using store in {:?}", path);
-    let mut store = AtomicStorage::open(path).expect("Could not open atomic store");
-
-    // Add some certificates
-    let mut certs = Vec::<QuorumCertificate<H_256>>::new();
-    for i in 0..10 {
-        let cert = QuorumCertificate {
-            view_number: ViewNumber::new(i),
-            ..random_quorom_certificate()
-        };
-        println!("Inserting {:?}", cert);
+    let store = TestStorage::create(path).expect("Could not create atomic store");
+
+    let mut entries: Vec<ViewEntry<_, _>> = (0..10).map(|i| stored_view(i).into()).collect();
+    entries.push(ViewEntry::Failed(ViewNumber::new(10)));
+    store.append(entries).await.unwrap();
+    store.commit().await.unwrap();
+    let committed = store.get_full_state().await;
+    assert_eq!(committed.stored.len(), 10);
+    assert_eq!(committed.failed.len(), 1);
+
+    // Changes that are not committed must not survive a restart
+    store.append_single_view(stored_view(11)).await.unwrap();
+    drop(store);
+
+    let store = TestStorage::open(path).expect("Could not open atomic store");
+    assert_eq!(store.get_full_state().await, committed);
+    assert_eq!(
+        store.get_anchored_view().await.unwrap(),
+        stored_view(9),
+        "anchored view should be the latest committed view"
+    );
+
+    // Garbage collection is persisted as well
+    assert_eq!(
         store
-            .update(|mut m| {
-                let cert = cert.clone();
-                async move { m.insert_qc(cert).await }
-            })
+            .cleanup_storage_up_to_view(ViewNumber::new(5))
             .await
-            .unwrap();
-        certs.push(cert);
-    }
-
-    // read them all back 3 times
-    // 1st time: normal readback
-    // 2nd: after dropping and re-opening the store
-    for i in 0..2 {
-        if i == 1 {
-            drop(store);
-            store = AtomicStorage::open(path).expect("Could not open atomic store");
-        }
-
-        for cert in &certs {
-            match store
-                .get_qc_for_view(cert.view_number)
-                .await
-                .expect("Could not read view_number")
-            {
-                Some(c) => {
-                    println!("read {:?}", c);
-                    assert_eq!(&c, cert);
-                }
-                None => panic!("Could not read {:?}: {:?}", cert.view_number, cert),
-            }
-            match store
-                .get_qc(&cert.block_hash)
-                .await
-                .expect("Could not read qc by hash")
-            {
-                Some(c) => {
-                    println!("read {:?}", c);
-                    assert_eq!(&c, cert);
-                }
-                None => panic!(
-                    "Could not read block_hash {:?}: {:?}",
-                    cert.block_hash, cert
-                ),
-            }
-        }
-    }
+            .unwrap(),
+        5
+    );
+    store.commit().await.unwrap();
+    drop(store);
+
+    let store = TestStorage::open(path).expect("Could not open atomic store");
+    let state = store.get_full_state().await;
+    assert_eq!(
+        state.stored.keys().copied().collect::<Vec<_>>(),
+        (5..10).map(ViewNumber::new).collect::<Vec<_>>()
+    );
+    assert_eq!(state.failed.len(), 1);
+
+    // Lookups no longer find the removed views
+    assert_eq!(store.get_by_block_height(3).await.unwrap(), None);
+    assert_eq!(
+        store
+            .get_view_by_transaction(VIDTransaction(vec![0]).commit())
+            .await
+            .unwrap(),
+        Some(stored_view(5))
+    );
+
+    // Changes made after a compaction are replayed on top of the snapshot
+    store
+        .append(vec![
+            stored_view(10).into(),
+            ViewEntry::Failed(ViewNumber::new(11)),
+        ])
+        .await
+        .unwrap();
+    store.commit().await.unwrap();
+    store.append_single_view(stored_view(12)).await.unwrap();
+    store.commit().await.unwrap();
+    let committed = store.get_full_state().await;
+    drop(store);
+
+    let store = TestStorage::open(path).expect("Could not open atomic store");
+    let state = store.get_full_state().await;
+    assert_eq!(state, committed);
+    assert_eq!(
+        state.stored.keys().copied().collect::<Vec<_>>(),
+        [5, 6, 7, 8, 9, 10, 12].map(ViewNumber::new).to_vec()
+    );
+    assert_eq!(state.failed.len(), 2);
 }
 
 #[cfg_attr(
@@ -87,66 +123,114 @@ async fn test_happy_path_qcs() {
     tokio::test(flavor = "multi_thread", worker_threads = 2)
 )]
 #[cfg_attr(async_executor_impl = "async-std", async_std::test)]
-async fn test_happy_path_leaves() {
-    // This folder will be destroyed when the last handle to it closes
+async fn test_atomic_storage_tmp() {
+    let store = TestStorage::construct_tmp_storage().expect("Could not create tmp storage");
+    assert!(store.get_anchored_view().await.is_err());
+
+    let genesis = stored_view(ViewNumber::genesis().get_u64());
+    store.append_single_view(genesis.clone()).await.unwrap();
+    store.commit().await.unwrap();
+    assert_eq!(store.get_anchored_view().await.unwrap(), genesis);
+}
+
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_atomic_storage_undecided_state() {
     let file = tempfile::tempdir().expect("Could not create temp dir");
     let path = file.path();
-    println!("Using store in {:?}", path);
-    let mut store = AtomicStorage::open(path).expect("Could not open atomic store");
-
-    // Add some leaves
-    let mut leaves = Vec::<Leaf<DEntryBlock, State, H_256>>::new();
-    for _ in 0..10 {
-        let leaf = random_validating_leaf(DEntryBlock {
-            previous_block: StateHash::random(),
-            ..Default::default()
-        });
-        println!("Inserting {:?}", leaf);
+    let store = TestStorage::create(path).expect("Could not create atomic store");
+    assert_eq!(store.get_undecided_state().await.unwrap(), None);
+
+    let undecided = UndecidedState {
+        leaves: vec![stored_view(2), stored_view(3)],
+        high_qc: stored_view(3).justify_qc,
+        locked_view: ViewNumber::new(1),
+    };
+    store.append_single_view(stored_view(1)).await.unwrap();
+    store
+        .update_undecided_state(undecided.clone())
+        .await
+        .unwrap();
+    store.commit().await.unwrap();
+    drop(store);
+
+    let store = TestStorage::open(path).expect("Could not open atomic store");
+    assert_eq!(store.get_undecided_state().await.unwrap(), Some(undecided));
+    assert_eq!(store.get_anchored_view().await.unwrap(), stored_view(1));
+}
+
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_atomic_storage_queries() {
+    let file = tempfile::tempdir().expect("Could not create temp dir");
+    let path = file.path();
+    let store = TestStorage::create(path).expect("Could not create atomic store");
+    store
+        .append((0..10).map(|i| stored_view(i).into()).collect())
+        .await
+        .unwrap();
+    store.commit().await.unwrap();
+    drop(store);
+
+    // Queries are served from the reloaded store
+    let store = TestStorage::open(path).expect("Could not open atomic store");
+    assert_eq!(
+        store.get_view(ViewNumber::new(3)).await.unwrap(),
+        Some(stored_view(3))
+    );
+    assert_eq!(store.get_view(ViewNumber::new(10)).await.unwrap(), None);
+
+    let leaf_commitment = Leaf::from_stored_view(stored_view(4)).unwrap().commit();
+    assert_eq!(
+        store.get_leaf_by_commitment(leaf_commitment).await.unwrap(),
+        Some(stored_view(4))
+    );
+    assert_eq!(
+        store
+            .get_leaf_by_commitment(fake_commitment())
+            .await
+            .unwrap(),
+        None
+    );
+
+    assert_eq!(
+        store.get_by_block_height(5).await.unwrap(),
+        Some(stored_view(5))
+    );
+    assert_eq!(store.get_by_block_height(10).await.unwrap(), None);
+
+    assert_eq!(
+        store
+            .get_views_in_range(ViewNumber::new(2), ViewNumber::new(4))
+            .await
+            .unwrap(),
+        (2..=4).map(stored_view).collect::<Vec<_>>()
+    );
+    assert!(store
+        .get_views_in_range(ViewNumber::new(4), ViewNumber::new(2))
+        .await
+        .unwrap()
+        .is_empty());
+
+    // Every view carries the genesis payload, so the first view including it is returned
+    assert_eq!(
+        store
+            .get_view_by_transaction(VIDTransaction(vec![0]).commit())
+            .await
+            .unwrap(),
+        Some(stored_view(0))
+    );
+    assert_eq!(
         store
-            .update(|mut m| {
-                let leaf = leaf.clone();
-                async move { m.insert_leaf(leaf).await }
-            })
+            .get_view_by_transaction(VIDTransaction(vec![1]).commit())
             .await
-            .unwrap();
-        leaves.push(leaf);
-    }
-
-    // read them all back 2 times
-    // 1st time: normal readback
-    // 2nd: after dropping and re-opening the store
-    for i in 0..2 {
-        if i == 1 {
-            drop(store);
-            store = AtomicStorage::open(path).expect("Could not open atomic store");
-        }
-
-        for leaf in &leaves {
-            match store
-                .get_leaf(&leaf.hash())
-                .await
-                .expect("Could not read leaf hash")
-            {
-                Some(l) => {
-                    println!("read {:?}", l);
-                    assert_eq!(&l, leaf);
-                }
-                None => {
-                    panic!("Could not read leaf hash {:?}: {:?}", leaf.hash(), leaf)
-                }
-            }
-            let hash = BlockContents::hash(&leaf.deltas);
-            match store
-                .get_leaf_by_block(&hash)
-                .await
-                .expect("Could not read leaf by block")
-            {
-                Some(l) => {
-                    println!("read {:?}", l);
-                    assert_eq!(&l, leaf);
-                }
-                None => panic!("Could not read leaf hash {:?}: {:?}", hash, leaf),
-            }
-        }
-    }
+            .unwrap(),
+        None
+    );
 }
@@ -30,6 +30,178 @@ async fn test_success() {
         .await;
 }
 
+/// Test Poisson arrivals of transactions with exponentially distributed sizes, sent to leaders.
+#[cfg(test)]
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_poisson_workload() {
+    use hotshot_testing::{
+        node_types::{MemoryImpl, TestTypes},
+        test_builder::TestMetadata,
+        txn_task::{TxnArrivals, TxnSizes, TxnTarget, TxnTaskDescription, TxnWorkload},
+    };
+
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+    let metadata = TestMetadata {
+        txn_description: TxnTaskDescription::DistributionBased(TxnWorkload {
+            arrivals: TxnArrivals::Poisson { tps: 200.0 },
+            sizes: TxnSizes::Exponential { mean: 512.0 },
+            target: TxnTarget::Leader,
+            seed: 0,
+        }),
+        ..TestMetadata::default_multiple_rounds()
+    };
+    metadata
+        .gen_launcher::<TestTypes, MemoryImpl>()
+        .launch()
+        .run_test()
+        .await;
+}
+
+/// Test bursts of transactions with uniformly distributed sizes, sent to random nodes.
+#[cfg(test)]
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_bursty_workload() {
+    use hotshot_testing::{
+        node_types::{MemoryImpl, TestTypes},
+        test_builder::TestMetadata,
+        txn_task::{TxnArrivals, TxnSizes, TxnTarget, TxnTaskDescription, TxnWorkload},
+    };
+    use std::time::Duration;
+
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+    let metadata = TestMetadata {
+        txn_description: TxnTaskDescription::DistributionBased(TxnWorkload {
+            arrivals: TxnArrivals::Bursty {
+                burst_size: 100,
+                period: Duration::from_millis(500),
+            },
+            sizes: TxnSizes::Uniform { min: 0, max: 1024 },
+            target: TxnTarget::Random,
+            seed: 0,
+        }),
+        ..TestMetadata::default_multiple_rounds()
+    };
+    metadata
+        .gen_launcher::<TestTypes, MemoryImpl>()
+        .launch()
+        .run_test()
+        .await;
+}
+
+/// Test that workloads whose distributions can't be sampled are rejected.
+#[cfg(test)]
+#[test]
+fn test_invalid_workloads() {
+    use hotshot_testing::txn_task::{
+        TxnArrivals, TxnSizes, TxnTarget, TxnWorkload, TxnWorkloadErr,
+    };
+
+    let workload = |arrivals, sizes| TxnWorkload {
+        arrivals,
+        sizes,
+        target: TxnTarget::Random,
+        seed: 0,
+    };
+    assert_eq!(
+        workload(TxnArrivals::Poisson { tps: 0.0 }, TxnSizes::Fixed(0)).validate(),
+        Err(TxnWorkloadErr::InvalidTps { tps: 0.0 })
+    );
+    assert_eq!(
+        workload(
+            TxnArrivals::Poisson { tps: 1.0 },
+            TxnSizes::Uniform { min: 2, max: 1 }
+        )
+        .validate(),
+        Err(TxnWorkloadErr::InvalidSizeRange { min: 2, max: 1 })
+    );
+    assert_eq!(
+        workload(
+            TxnArrivals::Poisson { tps: 1.0 },
+            TxnSizes::Uniform { min: 1, max: 1 }
+        )
+        .validate(),
+        Ok(())
+    );
+}
+
+/// Test a seeded simulation, with a node leaving so that views also time out on the virtual clock.
+/// Simulations run on a single-threaded tokio runtime.
+#[cfg(test)]
+#[cfg(async_executor_impl = "tokio")]
+#[tokio::test]
+async fn test_simulation() {
+    use std::time::Duration;
+
+    use hotshot_testing::{
+        node_types::{MemoryImpl, TestTypes},
+        spinning_task::{ChangeNode, SpinningTaskDescription, UpDown},
+        test_builder::TestMetadata,
+    };
+
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+    let mut metadata = TestMetadata {
+        simulation_seed: Some(2023),
+        ..TestMetadata::default_more_nodes_less_success()
+    };
+    metadata.spinning_properties = SpinningTaskDescription {
+        node_changes: vec![(
+            Duration::new(4, 0),
+            vec![ChangeNode {
+                idx: 19,
+                updown: UpDown::Down,
+            }],
+        )],
+    };
+    metadata
+        .gen_launcher::<TestTypes, MemoryImpl>()
+        .launch()
+        .run_test()
+        .await;
+}
+
+/// Test that a seed replays: two simulations with the same seed decide the same leaves, in the
+/// same order, on every node.
+#[cfg(test)]
+#[cfg(async_executor_impl = "tokio")]
+#[tokio::test]
+async fn test_simulation_replays_seed() {
+    use hotshot_testing::{
+        node_types::{MemoryImpl, TestTypes},
+        test_builder::TestMetadata,
+    };
+
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+    let metadata = TestMetadata {
+        simulation_seed: Some(2024),
+        ..TestMetadata::default()
+    };
+    let first = metadata
+        .clone()
+        .gen_launcher::<TestTypes, MemoryImpl>()
+        .launch()
+        .run_test_recording_decides()
+        .await;
+    let second = metadata
+        .gen_launcher::<TestTypes, MemoryImpl>()
+        .launch()
+        .run_test_recording_decides()
+        .await;
+    assert!(first.iter().all(|decided| !decided.is_empty()));
+    assert_eq!(first, second);
+}
+
 /// Test one node leaving the network.
 #[cfg(test)]
 #[cfg_attr(
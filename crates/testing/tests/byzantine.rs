@@ -0,0 +1,86 @@
+/// Test one DA committee member with every Byzantine behaviour.
+#[cfg(test)]
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_byzantine_all_behaviours() {
+    use hotshot_testing::{
+        byzantine::{ByzantineBehaviour, ByzantineNode},
+        node_types::{MemoryImpl, TestTypes},
+        test_builder::TestMetadata,
+    };
+
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+    let metadata = TestMetadata {
+        byzantine_nodes: vec![ByzantineNode {
+            idx: 13,
+            behaviours: vec![
+                ByzantineBehaviour::EquivocateProposals,
+                ByzantineBehaviour::DoubleVote,
+                ByzantineBehaviour::VoteForInvalidLeaves,
+                ByzantineBehaviour::WithholdDAVotes,
+                ByzantineBehaviour::ReplayCertificates,
+                ByzantineBehaviour::MalformedMessages,
+            ],
+        }],
+        ..TestMetadata::default_more_nodes_less_success()
+    };
+    metadata
+        .gen_launcher::<TestTypes, MemoryImpl>()
+        .launch()
+        .run_test()
+        .await;
+}
+
+/// Test f Byzantine nodes, each misbehaving in its own way.
+#[cfg(test)]
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_byzantine_f_nodes() {
+    use hotshot_testing::{
+        byzantine::{ByzantineBehaviour, ByzantineNode},
+        node_types::{MemoryImpl, TestTypes},
+        overall_safety_task::OverallSafetyPropertiesDescription,
+        test_builder::TestMetadata,
+    };
+
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+    let behaviours = [
+        ByzantineBehaviour::EquivocateProposals,
+        ByzantineBehaviour::DoubleVote,
+        ByzantineBehaviour::VoteForInvalidLeaves,
+        ByzantineBehaviour::WithholdDAVotes,
+        ByzantineBehaviour::ReplayCertificates,
+        ByzantineBehaviour::MalformedMessages,
+    ];
+    // f = 6 of the 20 nodes, including DA committee members so that they can withhold DA votes
+    let metadata = TestMetadata {
+        byzantine_nodes: behaviours
+            .into_iter()
+            .enumerate()
+            .map(|(i, behaviour)| ByzantineNode {
+                idx: 8 + i,
+                behaviours: vec![behaviour],
+            })
+            .collect(),
+        overall_safety_properties: OverallSafetyPropertiesDescription {
+            num_successful_views: 8,
+            // views led by Byzantine nodes may fail
+            num_failed_views: 20,
+            ..Default::default()
+        },
+        ..TestMetadata::default_more_nodes_less_success()
+    };
+    metadata
+        .gen_launcher::<TestTypes, MemoryImpl>()
+        .launch()
+        .run_test()
+        .await;
+}
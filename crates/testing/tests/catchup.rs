@@ -236,3 +236,56 @@ async fn test_catchup_in_view_sync() {
         .run_test()
         .await;
 }
+
+/// Restart a node from its storage and check that it rejoins consensus within a few views.
+#[cfg(test)]
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_catchup_restart() {
+    use std::time::Duration;
+
+    use hotshot_testing::{
+        completion_task::{CompletionTaskDescription, TimeBasedCompletionTaskDescription},
+        node_types::{MemoryImpl, TestTypes},
+        overall_safety_task::OverallSafetyPropertiesDescription,
+        spinning_task::{ChangeNode, SpinningTaskDescription, UpDown},
+        test_builder::TestMetadata,
+    };
+
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+    let mut metadata = TestMetadata::default_more_nodes_less_success();
+    let restarted_nodes = vec![ChangeNode {
+        idx: 5,
+        updown: UpDown::Restart {
+            down_for: Duration::from_millis(1000),
+            rejoin_within: 10,
+        },
+    }];
+
+    metadata.spinning_properties = SpinningTaskDescription {
+        node_changes: vec![(Duration::from_millis(500), restarted_nodes)],
+    };
+
+    metadata.completion_task_description =
+        CompletionTaskDescription::TimeBasedCompletionTaskBuilder(
+            TimeBasedCompletionTaskDescription {
+                duration: Duration::from_millis(20000),
+            },
+        );
+    metadata.overall_safety_properties = OverallSafetyPropertiesDescription {
+        check_leaf: true,
+        ..Default::default()
+    };
+    // views led by the node while it is down fail
+    metadata.overall_safety_properties.num_failed_views = 5;
+
+    metadata
+        .gen_launcher::<TestTypes, MemoryImpl>()
+        .launch()
+        .run_test()
+        .await;
+}
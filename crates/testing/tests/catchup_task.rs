@@ -0,0 +1,213 @@
+use commit::Committable;
+use hotshot::{tasks::add_catchup_task, types::SystemContextHandle, HotShotConsensusApi};
+use hotshot_task_impls::{events::HotShotEvent, harness::run_harness};
+use hotshot_testing::{
+    node_types::{MemoryImpl, TestTypes},
+    task_helpers::{build_system_handle, key_pair_for_id},
+};
+use hotshot_types::{
+    data::{genesis_proposer_id, Leaf, ViewNumber},
+    message::{CatchupRequest, CatchupResponse},
+    simple_certificate::QuorumCertificate2,
+    simple_vote::QuorumData,
+    traits::{consensus_api::ConsensusSharedApi, state::ConsensusTime},
+};
+use std::collections::HashMap;
+
+/// Build the leaf of view 1, extending the genesis leaf of `handle`.
+async fn child_of_genesis(handle: &SystemContextHandle<TestTypes, MemoryImpl>) -> Leaf<TestTypes> {
+    let genesis = handle.get_decided_leaf().await;
+    Leaf {
+        view_number: ViewNumber::new(1),
+        justify_qc: QuorumCertificate2 {
+            data: QuorumData {
+                leaf_commit: genesis.commit(),
+            },
+            ..QuorumCertificate2::genesis()
+        },
+        parent_commitment: genesis.commit(),
+        block_header: genesis.block_header.clone(),
+        block_payload: None,
+        rejected: Vec::new(),
+        timestamp: 0,
+        proposer_id: genesis_proposer_id(),
+    }
+}
+
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_catchup_task_serves_ancestors() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let handle = build_system_handle(1).await.0;
+    let api: HotShotConsensusApi<TestTypes, MemoryImpl> = HotShotConsensusApi {
+        inner: handle.hotshot.inner.clone(),
+    };
+    let pub_key = *api.public_key();
+    let peer = key_pair_for_id(2).1;
+
+    let leaf = child_of_genesis(&handle).await;
+    handle
+        .get_consensus()
+        .write()
+        .await
+        .saved_leaves
+        .insert(leaf.commit(), leaf.clone());
+
+    let request = CatchupRequest::Ancestors {
+        commitment: leaf.commit(),
+        stop_before: ViewNumber::genesis(),
+    };
+
+    let mut input = Vec::new();
+    let mut output = HashMap::new();
+
+    input.push(HotShotEvent::CatchupRequestRecv(request.clone(), peer));
+    input.push(HotShotEvent::Shutdown);
+
+    output.insert(HotShotEvent::CatchupRequestRecv(request.clone(), peer), 1);
+    // The walk stops before the genesis leaf, which the peer already has.
+    output.insert(
+        HotShotEvent::CatchupResponseSend(
+            CatchupResponse {
+                request,
+                leaves: vec![leaf],
+            },
+            pub_key,
+            peer,
+        ),
+        1,
+    );
+    output.insert(HotShotEvent::Shutdown, 1);
+
+    let build_fn = |task_runner, event_stream| add_catchup_task(task_runner, event_stream, handle);
+
+    run_harness(input, output, None, build_fn).await;
+}
+
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_catchup_task_verifies_response() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let handle = build_system_handle(2).await.0;
+    let api: HotShotConsensusApi<TestTypes, MemoryImpl> = HotShotConsensusApi {
+        inner: handle.hotshot.inner.clone(),
+    };
+    let pub_key = *api.public_key();
+    let peer = key_pair_for_id(1).1;
+
+    let leaf = child_of_genesis(&handle).await;
+    // Not certified by any QC we know of, so it must be dropped.
+    let forged = Leaf {
+        view_number: ViewNumber::new(2),
+        ..leaf.clone()
+    };
+
+    let request = CatchupRequest::Ancestors {
+        commitment: leaf.commit(),
+        stop_before: ViewNumber::genesis(),
+    };
+    let response = CatchupResponse {
+        request: request.clone(),
+        leaves: vec![forged.clone(), leaf.clone()],
+    };
+
+    let mut input = Vec::new();
+    let mut output = HashMap::new();
+
+    input.push(HotShotEvent::LeafMissing(leaf.commit(), peer));
+    input.push(HotShotEvent::CatchupResponseRecv(response.clone(), peer));
+    input.push(HotShotEvent::Shutdown);
+
+    output.insert(HotShotEvent::LeafMissing(leaf.commit(), peer), 1);
+    output.insert(HotShotEvent::CatchupRequestSend(request, pub_key, peer), 1);
+    output.insert(HotShotEvent::CatchupResponseRecv(response, peer), 1);
+    output.insert(HotShotEvent::Shutdown, 1);
+
+    let consensus = handle.get_consensus();
+    let build_fn = |task_runner, event_stream| add_catchup_task(task_runner, event_stream, handle);
+
+    run_harness(input, output, None, build_fn).await;
+
+    let consensus = consensus.read().await;
+    assert_eq!(consensus.saved_leaves.get(&leaf.commit()), Some(&leaf));
+    assert!(!consensus.saved_leaves.contains_key(&forged.commit()));
+    assert_eq!(
+        consensus
+            .state_map
+            .get(&ViewNumber::new(1))
+            .and_then(|view| view.get_leaf_commitment()),
+        Some(leaf.commit())
+    );
+}
+
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_catchup_task_drops_unsolicited_response() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let handle = build_system_handle(2).await.0;
+    let peer = key_pair_for_id(1).1;
+
+    let leaf = child_of_genesis(&handle).await;
+    // Certified by the high QC, but we never asked for it.
+    handle
+        .get_consensus()
+        .write()
+        .await
+        .high_qc
+        .data
+        .leaf_commit = leaf.commit();
+    let response = CatchupResponse {
+        request: CatchupRequest::Ancestors {
+            commitment: leaf.commit(),
+            stop_before: ViewNumber::genesis(),
+        },
+        leaves: vec![leaf.clone()],
+    };
+    let range_response = CatchupResponse {
+        request: CatchupRequest::ViewRange {
+            from: ViewNumber::genesis(),
+            to: ViewNumber::new(1),
+        },
+        leaves: vec![leaf.clone()],
+    };
+
+    let mut input = Vec::new();
+    let mut output = HashMap::new();
+
+    input.push(HotShotEvent::CatchupResponseRecv(response.clone(), peer));
+    input.push(HotShotEvent::CatchupResponseRecv(
+        range_response.clone(),
+        peer,
+    ));
+    input.push(HotShotEvent::Shutdown);
+
+    output.insert(HotShotEvent::CatchupResponseRecv(response, peer), 1);
+    output.insert(HotShotEvent::CatchupResponseRecv(range_response, peer), 1);
+    output.insert(HotShotEvent::Shutdown, 1);
+
+    let consensus = handle.get_consensus();
+    let build_fn = |task_runner, event_stream| add_catchup_task(task_runner, event_stream, handle);
+
+    run_harness(input, output, None, build_fn).await;
+
+    assert!(!consensus
+        .read()
+        .await
+        .saved_leaves
+        .contains_key(&leaf.commit()));
+}
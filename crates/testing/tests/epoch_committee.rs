@@ -0,0 +1,267 @@
+use std::num::NonZeroU64;
+
+use ethereum_types::U256;
+use hotshot::demo::DemoState;
+use hotshot::traits::election::epoch_committee::{EpochCommittee, EpochElectionConfig};
+use hotshot::traits::election::stake_weighted::StakeVoteToken;
+use hotshot::types::bn254::BLSPubKey;
+use hotshot::types::SignatureKey;
+use hotshot_testing::task_helpers::key_pair_for_id;
+use hotshot_types::block_impl::{VIDBlockHeader, VIDBlockPayload, VIDTransaction};
+use hotshot_types::data::{Leaf, ViewNumber};
+use hotshot_types::traits::election::{Membership, VoteToken};
+use hotshot_types::traits::epoch::{EpochError, EpochMembership, EpochNumber, StakeTableChange};
+use hotshot_types::traits::node_implementation::NodeType;
+use hotshot_types::traits::state::ConsensusTime;
+
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    Hash,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct EpochTypes;
+
+impl NodeType for EpochTypes {
+    type Time = ViewNumber;
+    type BlockHeader = VIDBlockHeader;
+    type BlockPayload = VIDBlockPayload;
+    type SignatureKey = BLSPubKey;
+    type VoteTokenType = StakeVoteToken<Self::SignatureKey>;
+    type Transaction = VIDTransaction;
+    type ElectionConfigType = EpochElectionConfig;
+    type StateType = DemoState;
+}
+
+type EpochMembershipImpl = EpochCommittee<EpochTypes, Leaf<EpochTypes>>;
+
+/// Nodes 0 to 3 with a stake of 1 each, in epochs of 10 views.
+fn membership() -> EpochMembershipImpl {
+    let entries = (0..4)
+        .map(|id| key_pair_for_id(id).1.get_stake_table_entry(1))
+        .collect();
+    EpochMembershipImpl::create_election(
+        entries,
+        EpochElectionConfig::new(10, NonZeroU64::new(10).unwrap()),
+    )
+}
+
+/// The public keys of nodes `ids`
+fn keys(ids: impl IntoIterator<Item = u64>) -> Vec<BLSPubKey> {
+    ids.into_iter().map(|id| key_pair_for_id(id).1).collect()
+}
+
+/// The committee of `view`, sorted
+fn committee(membership: &EpochMembershipImpl, view: u64) -> Vec<BLSPubKey> {
+    membership
+        .get_committee(ViewNumber::new(view))
+        .into_iter()
+        .collect()
+}
+
+/// The public keys of nodes `ids`, sorted
+fn sorted_keys(ids: impl IntoIterator<Item = u64>) -> Vec<BLSPubKey> {
+    let mut keys = keys(ids);
+    keys.sort();
+    keys
+}
+
+/// Node `id` joining with `stake`
+fn join(id: u64, stake: u64) -> StakeTableChange<BLSPubKey> {
+    StakeTableChange::Join {
+        key: key_pair_for_id(id).1,
+        stake: U256::from(stake),
+    }
+}
+
+#[test]
+fn test_epoch_committee_changes_at_epoch_boundaries() {
+    let membership = membership();
+
+    // Node 4 joins in epoch 2, and node 0 leaves in epoch 3.
+    membership
+        .schedule_change(ViewNumber::new(5), EpochNumber(2), join(4, 2))
+        .unwrap();
+    membership
+        .schedule_change(
+            ViewNumber::new(5),
+            EpochNumber(3),
+            StakeTableChange::Leave {
+                key: key_pair_for_id(0).1,
+            },
+        )
+        .unwrap();
+
+    assert_eq!(committee(&membership, 19), sorted_keys(0..4));
+    assert_eq!(committee(&membership, 20), sorted_keys(0..5));
+    assert_eq!(committee(&membership, 29), sorted_keys(0..5));
+    assert_eq!(committee(&membership, 30), sorted_keys(1..5));
+    // Epochs without changes keep the committee of the epoch before them.
+    assert_eq!(committee(&membership, 1_000_000), sorted_keys(1..5));
+    // The leader of a view is on the committee of its epoch.
+    assert_ne!(
+        membership.get_leader(ViewNumber::new(30)),
+        key_pair_for_id(0).1
+    );
+}
+
+#[test]
+fn test_epoch_committee_change_deadline() {
+    let membership = membership();
+    let leave = StakeTableChange::Leave {
+        key: key_pair_for_id(1).1,
+    };
+
+    // Changes for epoch 3 must be scheduled before view 20, one epoch before it starts.
+    assert_eq!(
+        membership.schedule_change(ViewNumber::new(20), EpochNumber(3), leave.clone()),
+        Err(EpochError::TooLate {
+            epoch: EpochNumber(3),
+            deadline: 20
+        })
+    );
+    assert_eq!(committee(&membership, 30), sorted_keys(0..4));
+    membership
+        .schedule_change(ViewNumber::new(19), EpochNumber(3), leave)
+        .unwrap();
+    assert_eq!(committee(&membership, 30), sorted_keys([0, 2, 3]));
+
+    // Nothing can be scheduled for the epochs within the lookahead of epoch 0.
+    assert!(membership
+        .schedule_change(ViewNumber::new(0), EpochNumber(1), join(4, 1))
+        .is_err());
+}
+
+#[test]
+fn test_epoch_committee_does_not_depend_on_progress() {
+    let ahead = membership();
+    let behind = membership();
+    ahead.enter_view(ViewNumber::new(1_000));
+    behind.enter_view(ViewNumber::new(1));
+
+    // Whether a change is accepted depends on the view it is scheduled in, not on how far the
+    // node has got, so both nodes take it and agree on the committees.
+    for membership in [&ahead, &behind] {
+        membership
+            .schedule_change(ViewNumber::new(5), EpochNumber(2), join(4, 1))
+            .unwrap();
+    }
+    for view in [10, 20, 2_000] {
+        assert_eq!(committee(&ahead, view), committee(&behind, view));
+    }
+    assert_eq!(committee(&behind, 20), sorted_keys(0..5));
+
+    // Scheduling the same change again, e.g. when replaying blocks, has no effect.
+    ahead
+        .schedule_change(ViewNumber::new(5), EpochNumber(2), join(4, 1))
+        .unwrap();
+    assert_eq!(ahead.total_stake(ViewNumber::new(20)), U256::from(5));
+}
+
+#[test]
+fn test_epoch_committee_thresholds_follow_the_epoch_of_the_view() {
+    let membership = membership();
+    membership
+        .schedule_change(ViewNumber::new(0), EpochNumber(2), join(4, 5))
+        .unwrap();
+
+    // Total stake 4 in epochs 0 and 1.
+    let view = ViewNumber::new(15);
+    membership.enter_view(view);
+    assert_eq!(membership.current_epoch(), EpochNumber(1));
+    assert_eq!(membership.total_nodes(view), 4);
+    assert_eq!(membership.success_threshold(view).get(), 3);
+    assert!(membership
+        .make_vote_token(view, &key_pair_for_id(4).0)
+        .unwrap()
+        .is_none());
+
+    // Total stake 9 in epoch 2, which the thresholds of its views follow even before the node
+    // enters it.
+    let view = ViewNumber::new(20);
+    assert_eq!(membership.total_nodes(view), 5);
+    assert_eq!(membership.success_threshold(view).get(), 7);
+    assert_eq!(membership.total_stake(view), U256::from(9));
+    membership.enter_view(view);
+    assert_eq!(membership.current_epoch(), EpochNumber(2));
+    let token = membership
+        .make_vote_token(view, &key_pair_for_id(4).0)
+        .unwrap()
+        .unwrap();
+    assert_eq!(token.vote_count().get(), 5);
+}
+
+#[test]
+fn test_epoch_committee_is_never_empty() {
+    let membership = membership();
+    for id in 0..4 {
+        membership
+            .schedule_change(
+                ViewNumber::new(0),
+                EpochNumber(2),
+                StakeTableChange::Leave {
+                    key: key_pair_for_id(id).1,
+                },
+            )
+            .unwrap();
+    }
+
+    // Everyone left, so the committee of epoch 1 carries over and there is still a leader.
+    let view = ViewNumber::new(20);
+    assert_eq!(membership.total_nodes(view), 4);
+    assert!(keys(0..4).contains(&membership.get_leader(view)));
+}
+
+#[test]
+fn test_epoch_committee_total_stake_fits_in_u64() {
+    let membership = membership();
+    membership
+        .schedule_change(
+            ViewNumber::new(0),
+            EpochNumber(2),
+            StakeTableChange::UpdateStake {
+                key: key_pair_for_id(0).1,
+                stake: U256::from(u64::MAX),
+            },
+        )
+        .unwrap();
+
+    // The total stake of epoch 2 wouldn't fit in the u64 votes are counted in, so the committee
+    // of epoch 1 carries over.
+    let view = ViewNumber::new(20);
+    assert_eq!(membership.total_stake(view), U256::from(4));
+    assert_eq!(membership.success_threshold(view).get(), 3);
+}
+
+#[test]
+fn test_epoch_committee_persists_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = EpochElectionConfig::new(10, NonZeroU64::new(10).unwrap())
+        .with_schedule_path(dir.path().join("schedule"));
+    let entries = || {
+        (0..4)
+            .map(|id| key_pair_for_id(id).1.get_stake_table_entry(1))
+            .collect()
+    };
+
+    let membership = EpochMembershipImpl::create_election(entries(), config.clone());
+    membership
+        .schedule_change(ViewNumber::new(5), EpochNumber(2), join(4, 1))
+        .unwrap();
+    drop(membership);
+
+    // A restarted node gets the changes back, and replaying them has no effect.
+    let restarted = EpochMembershipImpl::create_election(entries(), config);
+    assert_eq!(committee(&restarted, 20), sorted_keys(0..5));
+    restarted
+        .schedule_change(ViewNumber::new(5), EpochNumber(2), join(4, 1))
+        .unwrap();
+    assert_eq!(restarted.total_stake(ViewNumber::new(20)), U256::from(5));
+}
@@ -1,8 +1,12 @@
 use std::collections::BTreeSet;
 use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
+use async_compatibility_layer::art::async_sleep;
 use async_compatibility_layer::logging::setup_logging;
+use async_lock::RwLock;
 use hotshot::demo::DemoState;
 use hotshot::traits::election::static_committee::{
     GeneralStaticCommittee, StaticElectionConfig, StaticVoteToken,
@@ -13,16 +17,22 @@ use hotshot::traits::implementations::{
 use hotshot::traits::NodeImplementation;
 use hotshot::types::bn254::{BLSPrivKey, BLSPubKey};
 use hotshot::types::SignatureKey;
+use hotshot_task::boxed_sync;
+use hotshot_task_impls::mempool::DefaultMempool;
 use hotshot_types::block_impl::{VIDBlockHeader, VIDBlockPayload, VIDTransaction};
 use hotshot_types::certificate::ViewSyncCertificate;
+use hotshot_types::clock::{Clock, VirtualClock};
 use hotshot_types::data::{DAProposal, Leaf, QuorumProposal};
 use hotshot_types::message::{Message, SequencingMessage};
 use hotshot_types::traits::election::{
     CommitteeExchange, QuorumExchange, VIDExchange, ViewSyncExchange,
 };
 use hotshot_types::traits::network::TestableNetworkingImplementation;
-use hotshot_types::traits::network::{ConnectedNetwork, TransmitType};
+use hotshot_types::traits::network::{
+    ChaosNetwork, ConnectedNetwork, LinkFault, LinkRule, NetworkReliability, TransmitType,
+};
 use hotshot_types::traits::node_implementation::{ChannelMaps, Exchanges, NodeType};
+use hotshot_types::traits::signature_key::EncodedPublicKey;
 use hotshot_types::vote::{DAVote, ViewSyncVote};
 use hotshot_types::{
     data::ViewNumber,
@@ -83,6 +93,7 @@ pub type ThisViewSyncVote = ViewSyncVote<Test>;
 
 impl NodeImplementation<Test> for TestImpl {
     type Storage = MemoryStorage<Test, Self::Leaf>;
+    type Mempool = DefaultMempool<Test>;
     type Leaf = Leaf<Test>;
     type Exchanges = Exchanges<
         Test,
@@ -400,3 +411,212 @@ async fn memory_network_test_in_flight_message_count() {
     assert_eq!(network1.in_flight_message_count(), Some(0));
     assert_eq!(network2.in_flight_message_count(), Some(0));
 }
+
+/// Make `MemoryNetwork`s for `pub_key_1` and `pub_key_2`, with faults given by `chaos`
+fn chaos_networks(
+    pub_key_1: BLSPubKey,
+    pub_key_2: BLSPubKey,
+    chaos: ChaosNetwork,
+) -> (
+    MemoryNetwork<Message<Test, TestImpl>, BLSPubKey>,
+    MemoryNetwork<Message<Test, TestImpl>, BLSPubKey>,
+) {
+    let group: Arc<MasterMap<Message<Test, TestImpl>, <Test as NodeType>::SignatureKey>> =
+        MasterMap::new();
+    trace!(?group);
+    let reliability: Arc<RwLock<dyn NetworkReliability>> = Arc::new(RwLock::new(chaos));
+    let network1 = MemoryNetwork::new(
+        pub_key_1,
+        NetworkingMetricsValue::new(),
+        group.clone(),
+        Some(reliability.clone()),
+    );
+    let network2 = MemoryNetwork::new(
+        pub_key_2,
+        NetworkingMetricsValue::new(),
+        group,
+        Some(reliability),
+    );
+    (network1, network2)
+}
+
+// Messages across a partition are dropped until it heals
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn memory_network_chaos_partition() {
+    setup_logging();
+    let pub_key_1 = get_pubkey();
+    let pub_key_2 = get_pubkey();
+    let (network1, network2) = chaos_networks(
+        pub_key_1,
+        pub_key_2,
+        ChaosNetwork::new(0).with_partition(
+            &[vec![pub_key_1], vec![pub_key_2]],
+            Duration::ZERO,
+            Duration::from_millis(500),
+        ),
+    );
+
+    // Split: nothing gets through in either direction
+    for message in gen_messages(3, 100, pub_key_1) {
+        network1.direct_message(message, pub_key_2).await.unwrap();
+    }
+    for message in gen_messages(3, 200, pub_key_2) {
+        network2
+            .broadcast_message(message, BTreeSet::from([pub_key_1]))
+            .await
+            .unwrap();
+    }
+    async_sleep(Duration::from_millis(100)).await;
+    assert_eq!(network1.in_flight_message_count(), Some(0));
+    assert_eq!(network2.in_flight_message_count(), Some(0));
+
+    // Healed: messages get through again
+    async_sleep(Duration::from_millis(500)).await;
+    let sent_message = gen_messages(1, 300, pub_key_1).remove(0);
+    network1
+        .direct_message(sent_message.clone(), pub_key_2)
+        .await
+        .unwrap();
+    let mut recv_messages = network2.recv_msgs(TransmitType::Direct).await.unwrap();
+    fake_message_eq(sent_message, recv_messages.pop().unwrap());
+}
+
+// A slow link in one direction delays messages in that direction only
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn memory_network_chaos_asymmetric_delay() {
+    setup_logging();
+    let pub_key_1 = get_pubkey();
+    let pub_key_2 = get_pubkey();
+    let clock = VirtualClock::new();
+    let (network1, network2) = chaos_networks(
+        pub_key_1,
+        pub_key_2,
+        ChaosNetwork::new(0)
+            .with_rule(LinkRule::between(
+                &[pub_key_1],
+                &[pub_key_2],
+                LinkFault::Delay(Duration::from_millis(500)),
+            ))
+            .with_clock(Clock::Virtual(clock.clone())),
+    );
+
+    network1
+        .direct_message(gen_messages(1, 100, pub_key_1).remove(0), pub_key_2)
+        .await
+        .unwrap();
+    network2
+        .direct_message(gen_messages(1, 200, pub_key_2).remove(0), pub_key_1)
+        .await
+        .unwrap();
+    async_sleep(Duration::from_millis(100)).await;
+    assert_eq!(network1.in_flight_message_count(), Some(1));
+    assert_eq!(network2.in_flight_message_count(), Some(0));
+
+    // The delay is on the network's clock, not on real time
+    clock.advance(Duration::from_millis(499));
+    async_sleep(Duration::from_millis(100)).await;
+    assert_eq!(network2.in_flight_message_count(), Some(0));
+    clock.advance(Duration::from_millis(1));
+    async_sleep(Duration::from_millis(100)).await;
+    assert_eq!(network2.in_flight_message_count(), Some(1));
+}
+
+/// Whether `chaos` delivers the next message from `from` to `to`
+async fn chaos_delivers(
+    chaos: &ChaosNetwork,
+    from: &EncodedPublicKey,
+    to: &EncodedPublicKey,
+) -> bool {
+    let delivered = Arc::new(AtomicBool::new(false));
+    let sent = delivered.clone();
+    chaos
+        .chaos_send_link_msg(
+            from,
+            to,
+            vec![0],
+            Arc::new(move |_| {
+                sent.store(true, Ordering::SeqCst);
+                boxed_sync(async {})
+            }),
+        )
+        .await;
+    delivered.load(Ordering::SeqCst)
+}
+
+// Random faults replay with the same seed, however sends over different links interleave
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn memory_network_chaos_replay() {
+    setup_logging();
+    let key_1 = get_pubkey().to_bytes();
+    let key_2 = get_pubkey().to_bytes();
+    let chaos = || {
+        ChaosNetwork::new(0).with_rule(LinkRule {
+            from: None,
+            to: None,
+            fault: LinkFault::drop(1, 2).unwrap(),
+            starts_after: Duration::ZERO,
+            lasts: None,
+        })
+    };
+
+    let first = chaos();
+    let (mut forward, mut backward) = (Vec::new(), Vec::new());
+    for _ in 0..20 {
+        forward.push(chaos_delivers(&first, &key_1, &key_2).await);
+        backward.push(chaos_delivers(&first, &key_2, &key_1).await);
+    }
+
+    let second = chaos();
+    let (mut replayed_backward, mut replayed_forward) = (Vec::new(), Vec::new());
+    for _ in 0..20 {
+        replayed_backward.push(chaos_delivers(&second, &key_2, &key_1).await);
+    }
+    for _ in 0..20 {
+        replayed_forward.push(chaos_delivers(&second, &key_1, &key_2).await);
+    }
+    assert_eq!(forward, replayed_forward);
+    assert_eq!(backward, replayed_backward);
+
+    assert_eq!(LinkFault::drop(3, 2), None);
+    assert_eq!(LinkFault::drop(1, 0), None);
+}
+
+// Duplicated messages arrive as many times as asked
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn memory_network_chaos_duplicate() {
+    setup_logging();
+    let pub_key_1 = get_pubkey();
+    let pub_key_2 = get_pubkey();
+    let (network1, network2) = chaos_networks(
+        pub_key_1,
+        pub_key_2,
+        ChaosNetwork::new(0).with_rule(LinkRule::sent_by(&[pub_key_1], LinkFault::Duplicate(3))),
+    );
+
+    network1
+        .direct_message(gen_messages(1, 100, pub_key_1).remove(0), pub_key_2)
+        .await
+        .unwrap();
+    async_sleep(Duration::from_millis(100)).await;
+    assert_eq!(network2.in_flight_message_count(), Some(3));
+}
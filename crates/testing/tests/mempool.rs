@@ -0,0 +1,199 @@
+use bincode::config::Options;
+use commit::Committable;
+use hotshot_task_impls::mempool::{
+    DefaultMempool, Mempool, MempoolError, MempoolOrdering, TransactionComparator,
+};
+use hotshot_testing::{node_types::TestTypes, task_helpers::key_pair_for_id};
+use hotshot_types::{block_impl::VIDTransaction, EvictionPolicy, MempoolConfig};
+use hotshot_utils::bincode::bincode_opts;
+use std::{
+    collections::HashSet,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// A transaction whose "fee" is its first byte.
+fn txn(fee: u8) -> VIDTransaction {
+    VIDTransaction(vec![fee, 0, 0, 0])
+}
+
+/// Serialized size of a transaction
+fn size(txn: &VIDTransaction) -> u64 {
+    bincode_opts().serialized_size(txn).unwrap()
+}
+
+/// Higher fees are included first.
+fn by_fee() -> MempoolOrdering<VIDTransaction> {
+    let comparator: TransactionComparator<VIDTransaction> = Arc::new(|a, b| b.0[0].cmp(&a.0[0]));
+    MempoolOrdering::Priority(comparator)
+}
+
+#[test]
+fn test_mempool_fifo_batch() {
+    let sender = key_pair_for_id(0).1;
+    let now = Instant::now();
+    let mut mempool = DefaultMempool::<TestTypes>::new(MempoolConfig::default());
+    for fee in [3, 1, 2] {
+        mempool.insert(txn(fee), sender, now).unwrap();
+    }
+    assert_eq!(
+        mempool.insert(txn(1), sender, now),
+        Err(MempoolError::Duplicate)
+    );
+
+    assert_eq!(
+        mempool.batch(2, u64::MAX, &HashSet::new()),
+        vec![txn(3), txn(1)]
+    );
+    assert_eq!(mempool.remove(&txn(3).commit()), Some(txn(3)));
+    assert_eq!(
+        mempool.batch(10, u64::MAX, &HashSet::new()),
+        vec![txn(1), txn(2)]
+    );
+    assert_eq!(mempool.len(), 2);
+}
+
+#[test]
+fn test_mempool_priority_and_eviction() {
+    let sender = key_pair_for_id(0).1;
+    let now = Instant::now();
+    let config = MempoolConfig {
+        max_transactions: 2,
+        eviction_policy: EvictionPolicy::EvictLowestPriority,
+        ..MempoolConfig::default()
+    };
+    let mut mempool = DefaultMempool::<TestTypes>::with_ordering(config, by_fee());
+    mempool.insert(txn(2), sender, now).unwrap();
+    mempool.insert(txn(5), sender, now).unwrap();
+
+    // Doesn't outrank anything held, so there is no room for it.
+    assert_eq!(mempool.insert(txn(1), sender, now), Err(MempoolError::Full));
+    // Outranks the cheapest transaction, which is evicted.
+    mempool.insert(txn(9), sender, now).unwrap();
+    assert_eq!(
+        mempool.batch(10, u64::MAX, &HashSet::new()),
+        vec![txn(9), txn(5)]
+    );
+
+    let config = MempoolConfig {
+        max_transactions: 2,
+        eviction_policy: EvictionPolicy::EvictOldest,
+        ..MempoolConfig::default()
+    };
+    let mut mempool = DefaultMempool::<TestTypes>::new(config);
+    for fee in [1, 2, 3] {
+        mempool.insert(txn(fee), sender, now).unwrap();
+    }
+    assert_eq!(
+        mempool.batch(10, u64::MAX, &HashSet::new()),
+        vec![txn(2), txn(3)]
+    );
+}
+
+#[test]
+fn test_mempool_limits() {
+    let alice = key_pair_for_id(0).1;
+    let bob = key_pair_for_id(1).1;
+    let now = Instant::now();
+    let txn_size = size(&txn(0));
+    let config = MempoolConfig {
+        max_bytes: 3 * txn_size,
+        max_transactions_per_sender: 2,
+        transaction_ttl: Duration::from_secs(10),
+        ..MempoolConfig::default()
+    };
+    let mut mempool = DefaultMempool::<TestTypes>::new(config);
+
+    mempool.insert(txn(1), alice, now).unwrap();
+    mempool.insert(txn(2), alice, now).unwrap();
+    assert_eq!(
+        mempool.insert(txn(3), alice, now),
+        Err(MempoolError::SenderLimit)
+    );
+    mempool
+        .insert(txn(4), bob, now + Duration::from_secs(5))
+        .unwrap();
+    assert_eq!(mempool.size_in_bytes(), 3 * txn_size);
+    assert_eq!(mempool.insert(txn(5), bob, now), Err(MempoolError::Full));
+    let large = VIDTransaction(vec![0; 4 * 4]);
+    assert_eq!(
+        mempool.insert(large.clone(), bob, now),
+        Err(MempoolError::TooLarge { size: size(&large) })
+    );
+
+    // Alice's transactions expire first.
+    assert_eq!(mempool.remove_expired(now + Duration::from_secs(10)), 2);
+    assert_eq!(mempool.batch(10, u64::MAX, &HashSet::new()), vec![txn(4)]);
+    assert_eq!(mempool.remove_expired(now + Duration::from_secs(15)), 1);
+    assert!(mempool.is_empty());
+}
+
+#[test]
+fn test_mempool_expiry_in_arrival_order() {
+    let sender = key_pair_for_id(0).1;
+    let now = Instant::now();
+    let config = MempoolConfig {
+        transaction_ttl: Duration::from_secs(10),
+        ..MempoolConfig::default()
+    };
+    let mut mempool = DefaultMempool::<TestTypes>::new(config);
+    mempool.insert(txn(1), sender, now).unwrap();
+    mempool
+        .insert(txn(2), sender, now + Duration::from_secs(5))
+        .unwrap();
+    // A transaction stamped earlier than the one before it counts as received with it.
+    mempool.insert(txn(3), sender, now).unwrap();
+    mempool
+        .insert(txn(4), sender, now + Duration::from_secs(8))
+        .unwrap();
+
+    assert_eq!(mempool.remove_expired(now + Duration::from_secs(9)), 0);
+    assert_eq!(mempool.remove_expired(now + Duration::from_secs(10)), 1);
+    assert_eq!(mempool.remove_expired(now + Duration::from_secs(15)), 2);
+    assert_eq!(mempool.batch(10, u64::MAX, &HashSet::new()), vec![txn(4)]);
+    assert_eq!(mempool.remove_expired(now + Duration::from_secs(18)), 1);
+    assert!(mempool.is_empty());
+}
+
+#[test]
+fn test_mempool_batch_budget() {
+    let sender = key_pair_for_id(0).1;
+    let now = Instant::now();
+    let mut mempool = DefaultMempool::<TestTypes>::new(MempoolConfig::default());
+    let large = VIDTransaction(vec![7; 64]);
+    mempool.insert(txn(1), sender, now).unwrap();
+    mempool.insert(large.clone(), sender, now).unwrap();
+    mempool.insert(txn(2), sender, now).unwrap();
+    mempool.insert(txn(3), sender, now).unwrap();
+
+    // The large transaction doesn't fit, so the smaller ones after it are taken instead.
+    let budget = 3 * size(&txn(0));
+    assert_eq!(
+        mempool.batch(10, budget, &HashSet::new()),
+        vec![txn(1), txn(2), txn(3)]
+    );
+    assert_eq!(
+        mempool.batch(2, budget, &HashSet::new()),
+        vec![txn(1), txn(2)]
+    );
+    // Nothing is removed, so the leftovers are available for the next block.
+    assert_eq!(
+        mempool.batch(10, u64::MAX, &HashSet::new()),
+        vec![txn(1), large, txn(2), txn(3)]
+    );
+}
+
+#[test]
+fn test_mempool_batch_excludes() {
+    let sender = key_pair_for_id(0).1;
+    let now = Instant::now();
+    let mut mempool = DefaultMempool::<TestTypes>::new(MempoolConfig::default());
+    for fee in [1, 2, 3] {
+        mempool.insert(txn(fee), sender, now).unwrap();
+    }
+
+    // Already included by an undecided block.
+    let exclude = HashSet::from([txn(2).commit()]);
+    assert_eq!(mempool.batch(2, u64::MAX, &exclude), vec![txn(1), txn(3)]);
+    assert!(mempool.contains(&txn(2).commit()));
+}
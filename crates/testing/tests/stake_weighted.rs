@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+use bitvec::bitvec;
+use commit::Committable;
+use either::Either;
+use ethereum_types::U256;
+
+use hotshot::demo::DemoState;
+use hotshot::traits::election::stake_weighted::{
+    StakeVoteToken, StakeWeightedCommittee, StakeWeightedElectionConfig,
+};
+use hotshot::types::bn254::BLSPubKey;
+use hotshot::types::SignatureKey;
+use hotshot_testing::task_helpers::key_pair_for_id;
+use hotshot_types::block_impl::{VIDBlockHeader, VIDBlockPayload, VIDTransaction};
+use hotshot_types::data::{Leaf, ViewNumber};
+use hotshot_types::simple_certificate::SimpleCertificate;
+use hotshot_types::simple_vote::{DAData, DAVote};
+use hotshot_types::traits::election::{Checked, Membership, VoteToken};
+use hotshot_types::traits::node_implementation::NodeType;
+use hotshot_types::traits::state::ConsensusTime;
+use hotshot_types::vote2::{Certificate2, VoteAccumulator2};
+
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    Hash,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct StakeTypes;
+
+impl NodeType for StakeTypes {
+    type Time = ViewNumber;
+    type BlockHeader = VIDBlockHeader;
+    type BlockPayload = VIDBlockPayload;
+    type SignatureKey = BLSPubKey;
+    type VoteTokenType = StakeVoteToken<Self::SignatureKey>;
+    type Transaction = VIDTransaction;
+    type ElectionConfigType = StakeWeightedElectionConfig;
+    type StateType = DemoState;
+}
+
+type StakeMembership = StakeWeightedCommittee<StakeTypes, Leaf<StakeTypes>>;
+
+/// Nodes 0 to 3 with stakes 1, 1, 2 and 6.
+fn membership() -> StakeMembership {
+    let entries = [1, 1, 2, 6]
+        .into_iter()
+        .enumerate()
+        .map(|(id, stake)| key_pair_for_id(id as u64).1.get_stake_table_entry(stake))
+        .collect();
+    StakeMembership::create_election(
+        entries,
+        <StakeMembership as Membership<StakeTypes>>::default_election_config(4),
+    )
+}
+
+#[test]
+fn test_stake_weighted_leader_election() {
+    let membership = membership();
+
+    // The leader of a view is the same every time it is computed.
+    for view in 0..10 {
+        assert_eq!(
+            membership.get_leader(ViewNumber::new(view)),
+            membership.get_leader(ViewNumber::new(view))
+        );
+    }
+
+    let views = 10_000;
+    let mut led: HashMap<BLSPubKey, u64> = HashMap::new();
+    for view in 0..views {
+        *led.entry(membership.get_leader(ViewNumber::new(view)))
+            .or_default() += 1;
+    }
+    // Each node leads roughly in proportion to its share of the total stake of 10.
+    for (id, stake) in [1, 1, 2, 6].into_iter().enumerate() {
+        let count = led
+            .get(&key_pair_for_id(id as u64).1)
+            .copied()
+            .unwrap_or_default();
+        let expected = views * stake / 10;
+        assert!(
+            count.abs_diff(expected) < views / 50,
+            "node {id} led {count} views, expected about {expected}"
+        );
+    }
+}
+
+#[test]
+fn test_stake_weighted_vote_tokens() {
+    let membership = membership();
+    let view = ViewNumber::new(1);
+
+    let (private_key, public_key) = key_pair_for_id(3);
+    let token = membership
+        .make_vote_token(view, &private_key)
+        .unwrap()
+        .unwrap();
+    assert_eq!(token.vote_count().get(), 6);
+    assert!(matches!(
+        membership.validate_vote_token(view, public_key, Checked::Unchecked(token.clone())),
+        Ok(Checked::Valid(_))
+    ));
+    // A token is only valid for the node it was made for.
+    assert!(matches!(
+        membership.validate_vote_token(view, key_pair_for_id(0).1, Checked::Unchecked(token)),
+        Ok(Checked::Inval(_))
+    ));
+
+    // Nodes without stake on the committee don't vote.
+    assert!(membership
+        .make_vote_token(view, &key_pair_for_id(4).0)
+        .unwrap()
+        .is_none());
+
+    // More than two thirds and one third of the total stake of 10.
+    assert_eq!(membership.success_threshold(view).get(), 7);
+    assert_eq!(membership.failure_threshold(view).get(), 4);
+}
+
+#[test]
+fn test_stake_weighted_vote_accumulation() {
+    type Vote = DAVote<StakeTypes, VIDBlockPayload, StakeMembership>;
+    type Cert = SimpleCertificate<StakeTypes, DAData<VIDBlockPayload>>;
+
+    let membership = membership();
+    let view = ViewNumber::new(1);
+    let data = DAData {
+        payload_commit: VIDBlockPayload::genesis().commit(),
+    };
+    let vote = |id| {
+        let (private_key, public_key) = key_pair_for_id(id);
+        Vote::create_signed_vote(data.clone(), view, &public_key, &private_key)
+    };
+    assert_eq!(Cert::threshold(&membership, view), U256::from(7));
+
+    let accumulator = VoteAccumulator2::<StakeTypes, Vote, Cert> {
+        vote_outcomes: HashMap::new(),
+        sig_lists: Vec::new(),
+        signers: bitvec![0; membership.total_nodes(view)],
+        phantom: PhantomData,
+    };
+
+    // Node 4 is not in the stake table, so its vote doesn't count.
+    let Either::Left(accumulator) = accumulator.accumulate(&vote(4), &membership) else {
+        panic!("a vote from an unknown node formed a certificate");
+    };
+    assert!(accumulator.vote_outcomes.is_empty());
+
+    // Node 3 holds 6 of the 7 needed, and a duplicate vote doesn't add to it.
+    let Either::Left(accumulator) = accumulator.accumulate(&vote(3), &membership) else {
+        panic!("6 of 10 stake formed a certificate");
+    };
+    let Either::Left(accumulator) = accumulator.accumulate(&vote(3), &membership) else {
+        panic!("a duplicate vote formed a certificate");
+    };
+    assert_eq!(accumulator.vote_outcomes[&data.commit()].0, U256::from(6));
+
+    // Node 0's stake of 1 makes up the threshold.
+    let Either::Right(cert) = accumulator.accumulate(&vote(0), &membership) else {
+        panic!("7 of 10 stake didn't form a certificate");
+    };
+    assert!(cert.is_valid_cert(&membership));
+}
+
+#[test]
+#[should_panic(expected = "must fit in a u64")]
+fn test_stake_weighted_rejects_total_stake_past_u64() {
+    // Vote counts are added up in a u64, which this total stake would overflow.
+    let entries = (0..2)
+        .map(|id| key_pair_for_id(id).1.get_stake_table_entry(u64::MAX))
+        .collect();
+    let _ = StakeMembership::create_election(
+        entries,
+        <StakeMembership as Membership<StakeTypes>>::default_election_config(2),
+    );
+}
@@ -8,11 +8,12 @@ use hotshot_testing::{
 use hotshot_types::traits::election::VIDExchangeType;
 use hotshot_types::{
     block_impl::VIDTransaction,
-    data::{DAProposal, VidDisperse, VidSchemeTrait, ViewNumber},
+    data::{DAProposal, VidDisperse, VidSchemeTrait, VidSetup, VidSetupError, ViewNumber},
     traits::{
         consensus_api::ConsensusSharedApi, election::ConsensusExchange,
         node_implementation::ExchangesType, state::ConsensusTime,
     },
+    VidConfig,
 };
 use std::collections::HashMap;
 
@@ -99,3 +100,170 @@ async fn test_vid_task() {
 
     run_harness(input, output, None, build_fn).await;
 }
+
+#[test]
+fn test_vid_setup() {
+    let config = VidConfig {
+        erasure_code_rate: (1, 4),
+        insecure_test_srs: true,
+        ..VidConfig::default()
+    };
+    let setup = VidSetup::new(&config, 10).unwrap();
+    assert_eq!(setup.num_chunks(10), 3);
+    assert_eq!(setup.num_chunks(4), 1);
+    assert_eq!(setup.num_chunks(1), 1);
+
+    let vid = setup.scheme(10).unwrap();
+    let payload = vec![1, 2, 3, 4, 5];
+    let disperse = vid.disperse(&payload).unwrap();
+    assert_eq!(disperse.shares.len(), 10);
+    // Any 3 shares are enough to recover the payload.
+    assert_eq!(
+        vid.recover_payload(&disperse.shares[7..], &disperse.common)
+            .unwrap(),
+        payload
+    );
+
+    assert!(matches!(
+        VidSetup::new(
+            &VidConfig {
+                erasure_code_rate: (5, 4),
+                ..VidConfig::default()
+            },
+            10
+        ),
+        Err(VidSetupError::InvalidErasureCodeRate { .. })
+    ));
+    assert!(matches!(
+        VidSetup::new(
+            &VidConfig {
+                srs_path: Some("/nonexistent/srs.bin".into()),
+                ..VidConfig::default()
+            },
+            10
+        ),
+        Err(VidSetupError::ReadSrs { .. })
+    ));
+    // Without an SRS file, the test SRS must be asked for explicitly.
+    assert!(matches!(
+        VidSetup::new(&VidConfig::default(), 10),
+        Err(VidSetupError::MissingSrs)
+    ));
+}
+
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_vid_share_reconstruction() {
+    use hotshot_task_impls::harness::run_harness;
+    use hotshot_testing::task_helpers::{build_system_handle, key_pair_for_id};
+    use hotshot_types::{
+        block_impl::VIDBlockPayload,
+        message::{Proposal, VidShareRequest, VidShareResponse},
+        traits::election::Membership,
+    };
+
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    // Node 2 is the leader of view 2 and one of its storage nodes.
+    let handle = build_system_handle(2).await.0;
+    let api: HotShotConsensusApi<TestTypes, MemoryImpl> = HotShotConsensusApi {
+        inner: handle.hotshot.inner.clone(),
+    };
+    let vid_exchange = api.inner.exchanges.vid_exchange().clone();
+    let pub_key = *api.public_key();
+    let view = ViewNumber::new(2);
+
+    let committee: Vec<_> = vid_exchange
+        .membership()
+        .get_committee(view)
+        .into_iter()
+        .collect();
+    let vid = api.vid().scheme(committee.len()).unwrap();
+    let transactions = vec![VIDTransaction(vec![1, 2]), VIDTransaction(vec![3])];
+    let vid_disperse = vid
+        .disperse(&VIDBlockPayload::encode_transactions(&transactions))
+        .unwrap();
+    let block = VIDBlockPayload {
+        transactions,
+        payload_commitment: vid_disperse.commit,
+    };
+    let vid_proposal = Proposal {
+        data: VidDisperse {
+            view_number: view,
+            payload_commitment: block.commit(),
+            shares: vid_disperse.shares.clone(),
+            common: vid_disperse.common.clone(),
+        },
+        signature: vid_exchange.sign_vid_disperse(&block.commit()),
+    };
+    let request = VidShareRequest {
+        view_number: view,
+        payload_commitment: block.commit(),
+    };
+    let response = |index: usize| VidShareResponse {
+        view_number: view,
+        payload_commitment: block.commit(),
+        share: vid_disperse.shares[index].clone(),
+        common: vid_disperse.common.clone(),
+    };
+    let own_index = committee.iter().position(|key| *key == pub_key).unwrap();
+
+    let mut input = Vec::new();
+    let mut output = HashMap::new();
+
+    // Node 2 keeps its own share and serves it.
+    let requester = key_pair_for_id(1).1;
+    input.push(HotShotEvent::VidDisperseRecv(vid_proposal.clone(), pub_key));
+    input.push(HotShotEvent::VidShareRequestRecv(
+        request.clone(),
+        requester,
+    ));
+    output.insert(HotShotEvent::VidDisperseRecv(vid_proposal, pub_key), 1);
+    let vote_token = vid_exchange.make_vote_token(view).unwrap().unwrap();
+    output.insert(
+        HotShotEvent::VidVoteSend(vid_exchange.create_vid_message(
+            block.commit(),
+            view,
+            vote_token,
+        )),
+        1,
+    );
+    output.insert(
+        HotShotEvent::VidShareRequestRecv(request.clone(), requester),
+        1,
+    );
+    output.insert(
+        HotShotEvent::VidShareResponseSend(response(own_index), pub_key, requester),
+        1,
+    );
+
+    // Pretend node 2 lost the payload: it asks enough other storage nodes for their shares, which
+    // is all of them since every share is needed, and reconstructs the payload from their answers
+    // and its own share.
+    input.push(HotShotEvent::VidPayloadMissing(view, block.commit()));
+    output.insert(HotShotEvent::VidPayloadMissing(view, block.commit()), 1);
+    for (index, key) in committee.iter().enumerate() {
+        if index == own_index {
+            continue;
+        }
+        output.insert(
+            HotShotEvent::VidShareRequestSend(request.clone(), pub_key, *key),
+            1,
+        );
+        input.push(HotShotEvent::VidShareResponseRecv(response(index), *key));
+        output.insert(HotShotEvent::VidShareResponseRecv(response(index), *key), 1);
+    }
+    output.insert(HotShotEvent::VidPayloadRecovered(block, view), 1);
+
+    input.push(HotShotEvent::Shutdown);
+    output.insert(HotShotEvent::Shutdown, 1);
+
+    let build_fn =
+        |task_runner, event_stream| add_vid_task(task_runner, event_stream, vid_exchange, handle);
+
+    run_harness(input, output, None, build_fn).await;
+}
@@ -13,16 +13,22 @@ use crate::{
     },
 };
 use ark_serialize::CanonicalDeserialize;
+use bincode::Options;
 use commit::{Commitment, Committable};
+use hotshot_utils::bincode::bincode_opts;
 use serde::{Deserialize, Serialize};
 use sha3::{Digest, Keccak256};
 use snafu::Snafu;
 
-// TODO <https://github.com/EspressoSystems/HotShot/issues/1693>
-/// Number of storage nodes for VID initiation.
+/// Number of storage nodes for the VID commitment of the genesis block payload.
+///
+/// The genesis payload is never dispersed, but every node must compute the same commitment for
+/// it regardless of how VID is configured, so it is always committed to with these parameters.
+/// Blocks proposed later use the configured [`VidSetup`](crate::data::VidSetup).
 pub const NUM_STORAGE_NODES: usize = 8;
-// TODO <https://github.com/EspressoSystems/HotShot/issues/1693>
-/// Number of chunks for VID initiation.
+/// Number of chunks for the VID commitment of the genesis block payload.
+///
+/// See [`NUM_STORAGE_NODES`].
 pub const NUM_CHUNKS: usize = 8;
 
 /// The transaction in a [`VIDBlockPayload`].
@@ -58,6 +64,8 @@ pub enum BlockPayloadError {
     GenesisAfterStart,
     /// invalid block
     InvalidBlock,
+    /// The bytes recovered from VID shares don't encode a list of transactions
+    InvalidEncoding,
 }
 
 /// A [`BlockPayload`] that contains a list of `VIDTransaction`.
@@ -70,22 +78,45 @@ pub struct VIDBlockPayload {
 }
 
 impl VIDBlockPayload {
+    /// Encode transactions as the bytes dispersed with VID.
+    ///
+    /// Unlike plain concatenation, the encoding keeps the boundaries between transactions, so that
+    /// [`BlockPayload::from_vid_bytes`] can recover them.
+    ///
+    /// This is a breaking change: payload commitments of proposed blocks differ from those computed
+    /// by nodes that disperse the concatenated transactions, so all nodes of a network must be
+    /// upgraded together. The genesis payload is still committed to as concatenated bytes, see
+    /// [`VIDBlockPayload::genesis`].
+    /// # Panics
+    /// If the transactions can't be serialized, which doesn't happen for byte vectors.
+    #[must_use]
+    pub fn encode_transactions(transactions: &[VIDTransaction]) -> Vec<u8> {
+        bincode_opts()
+            .serialize(transactions)
+            .expect("serializing transactions should succeed")
+    }
+
     /// Create a genesis block payload with transaction bytes `vec![0]`, to be used for
     /// consensus task initiation.
+    ///
+    /// Its commitment is to the plain concatenation of its transactions rather than to
+    /// [`VIDBlockPayload::encode_transactions`], so the genesis commitment is unchanged. The
+    /// genesis payload is never dispersed, so it never needs to be rebuilt from VID shares.
     /// # Panics
     /// If the `VidScheme` construction fails.
     #[must_use]
     pub fn genesis() -> Self {
-        // TODO <https://github.com/EspressoSystems/HotShot/issues/1686>
+        // The genesis payload is public and fixed, so committing to it with a test SRS is sound.
         let srs = test_srs(NUM_STORAGE_NODES);
-        // TODO We are using constant numbers for now, but they will change as the quorum size
-        // changes.
-        // TODO <https://github.com/EspressoSystems/HotShot/issues/1693>
         let vid = VidScheme::new(NUM_CHUNKS, NUM_STORAGE_NODES, &srs).unwrap();
-        let txn = vec![0];
-        let vid_disperse = vid.disperse(&txn).unwrap();
+        let transactions = vec![VIDTransaction(vec![0])];
+        let bytes: Vec<u8> = transactions
+            .iter()
+            .flat_map(|txn| txn.0.iter().copied())
+            .collect();
+        let vid_disperse = vid.disperse(&bytes).unwrap();
         VIDBlockPayload {
-            transactions: vec![VIDTransaction(txn)],
+            transactions,
             payload_commitment: vid_disperse.commit,
         }
     }
@@ -129,6 +160,19 @@ impl BlockPayload for VIDBlockPayload {
             .map(commit::Committable::commit)
             .collect()
     }
+
+    fn from_vid_bytes(
+        bytes: &[u8],
+        payload_commitment: <VidScheme as VidSchemeTrait>::Commit,
+    ) -> Result<Self, Self::Error> {
+        let transactions = bincode_opts()
+            .deserialize(bytes)
+            .map_err(|_| BlockPayloadError::InvalidEncoding)?;
+        Ok(Self {
+            transactions,
+            payload_commitment,
+        })
+    }
 }
 
 /// A [`BlockHeader`] that commits to [`VIDBlockPayload`].
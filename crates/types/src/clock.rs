@@ -0,0 +1,224 @@
+//! Sources of time for the tasks of a node
+//!
+//! Tasks sleep and time out through a [`Clock`] rather than the async runtime, so that tests can
+//! swap real time for a [`VirtualClock`], which only moves when the test moves it. Timers then
+//! fire in the order of their deadlines, however fast or slow the machine running the test is.
+//!
+//! A simulation runs every node on a single-threaded tokio runtime with the runtime's own time
+//! paused, and moves the virtual clock with [`VirtualClock::drive`] whenever no task can make
+//! progress, so that the same seed always replays the same execution.
+
+use async_compatibility_layer::art::{async_sleep, async_timeout};
+use futures::future::{select, Either};
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+/// Runtime time `drive` waits for. A paused runtime only moves its time, and so only finishes this
+/// sleep, once every other task is waiting.
+const IDLE_PROBE: Duration = Duration::from_millis(1);
+
+/// Error of a [`Clock::timeout`] whose future didn't finish in time
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Elapsed;
+
+/// Where a node's tasks get the time from
+#[derive(Clone, Debug, Default)]
+pub enum Clock {
+    /// Wall-clock time, through the async runtime
+    #[default]
+    Real,
+    /// Time that only moves when the [`VirtualClock`] is advanced
+    Virtual(Arc<VirtualClock>),
+}
+
+impl Clock {
+    /// The current time
+    #[must_use]
+    pub fn now(&self) -> Instant {
+        match self {
+            Clock::Real => Instant::now(),
+            Clock::Virtual(clock) => clock.now(),
+        }
+    }
+
+    /// Sleep for `duration`
+    pub async fn sleep(&self, duration: Duration) {
+        match self {
+            Clock::Real => async_sleep(duration).await,
+            Clock::Virtual(clock) => clock.sleep(duration).await,
+        }
+    }
+
+    /// Wait for `future` for at most `duration`
+    /// # Errors
+    /// If `future` didn't finish within `duration`
+    pub async fn timeout<F: Future>(
+        &self,
+        duration: Duration,
+        future: F,
+    ) -> Result<F::Output, Elapsed> {
+        match self {
+            Clock::Real => async_timeout(duration, future).await.map_err(|_| Elapsed),
+            Clock::Virtual(clock) => {
+                match select(Box::pin(future), Box::pin(clock.sleep(duration))).await {
+                    Either::Left((output, _)) => Ok(output),
+                    Either::Right(((), _)) => Err(Elapsed),
+                }
+            }
+        }
+    }
+}
+
+/// A clock whose time only moves when it is advanced, waking the tasks sleeping until then
+#[derive(Debug)]
+pub struct VirtualClock {
+    /// The real time the clock started at, so that virtual times can be given as `Instant`s
+    origin: Instant,
+    /// Virtual time and the tasks sleeping on it
+    state: Mutex<VirtualTime>,
+    /// Whether `drive` should return
+    stopped: AtomicBool,
+}
+
+/// State of a [`VirtualClock`]
+#[derive(Debug, Default)]
+struct VirtualTime {
+    /// Time since the clock started
+    now: Duration,
+    /// Id of the next sleeping task, breaking ties between tasks with the same deadline
+    next_id: u64,
+    /// Wakers of sleeping tasks, by deadline and id. `None` until the task is first polled.
+    sleepers: BTreeMap<(Duration, u64), Option<Waker>>,
+}
+
+impl VirtualClock {
+    /// A virtual clock starting now
+    #[must_use]
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            origin: Instant::now(),
+            state: Mutex::default(),
+            stopped: AtomicBool::new(false),
+        })
+    }
+
+    /// Lock the state of the clock
+    fn state(&self) -> MutexGuard<'_, VirtualTime> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The current virtual time
+    #[must_use]
+    pub fn now(&self) -> Instant {
+        self.origin + self.elapsed()
+    }
+
+    /// Virtual time since the clock started
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        self.state().now
+    }
+
+    /// Sleep until `duration` of virtual time has passed
+    pub fn sleep(self: &Arc<Self>, duration: Duration) -> VirtualSleep {
+        let mut state = self.state();
+        let deadline = state.now + duration;
+        let id = state.next_id;
+        state.next_id += 1;
+        state.sleepers.insert((deadline, id), None);
+        VirtualSleep {
+            clock: Arc::clone(self),
+            key: (deadline, id),
+        }
+    }
+
+    /// Move time forward by `duration`, waking the tasks sleeping until then
+    pub fn advance(&self, duration: Duration) {
+        let mut state = self.state();
+        state.now += duration;
+        Self::wake_due(state);
+    }
+
+    /// Wake the tasks with the earliest deadline, moving time forward to it if it is still to
+    /// come. Does nothing if no task is sleeping.
+    pub fn advance_to_next(&self) {
+        let mut state = self.state();
+        let Some(&(deadline, _)) = state.sleepers.keys().next() else {
+            return;
+        };
+        state.now = state.now.max(deadline);
+        Self::wake_due(state);
+    }
+
+    /// Wake the tasks whose deadline has passed. They stay in `sleepers` until they are polled.
+    fn wake_due(mut state: MutexGuard<'_, VirtualTime>) {
+        let now = state.now;
+        let wakers: Vec<Waker> = state
+            .sleepers
+            .range(..=(now, u64::MAX))
+            .filter_map(|(_, waker)| waker.clone())
+            .collect();
+        drop(state);
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// Advance to the next deadline each time every other task is waiting, until `stop` is
+    /// called. Woken tasks always get to send and handle their messages before time moves on, so
+    /// a timer never fires just because the machine is slow.
+    ///
+    /// The runtime tells `drive` that the other tasks are waiting by finishing a sleep on its own
+    /// time, so this needs a single-threaded tokio runtime with its time paused. On any other
+    /// runtime time moves on every millisecond instead, and executions don't replay.
+    pub async fn drive(self: Arc<Self>) {
+        while !self.stopped.load(Ordering::Relaxed) {
+            async_sleep(IDLE_PROBE).await;
+            self.advance_to_next();
+        }
+    }
+
+    /// Make `drive` return
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Future of [`VirtualClock::sleep`]
+#[derive(Debug)]
+pub struct VirtualSleep {
+    /// The clock to sleep on
+    clock: Arc<VirtualClock>,
+    /// Deadline and id of this sleep in the clock's `sleepers`
+    key: (Duration, u64),
+}
+
+impl Future for VirtualSleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.clock.state();
+        if state.now >= self.key.0 {
+            state.sleepers.remove(&self.key);
+            Poll::Ready(())
+        } else {
+            state.sleepers.insert(self.key, Some(cx.waker().clone()));
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for VirtualSleep {
+    fn drop(&mut self) {
+        self.clock.state().sleepers.remove(&self.key);
+    }
+}
@@ -13,6 +13,7 @@ use crate::{
     traits::{
         metrics::{Counter, Gauge, Histogram, Label, Metrics},
         node_implementation::NodeType,
+        storage::{StoredView, UndecidedState},
         BlockPayload,
     },
     utils::Terminator,
@@ -21,6 +22,7 @@ use commit::Commitment;
 use derivative::Derivative;
 use std::{
     collections::{hash_map::Entry, BTreeMap, HashMap},
+    ops::Bound::{Excluded, Unbounded},
     sync::{Arc, Mutex},
 };
 use tracing::error;
@@ -338,6 +340,36 @@ impl<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>> Consensus<TYPES, LEAF> {
         self.state_map = self.state_map.split_off(&new_anchor_view);
     }
 
+    /// Collect the state that is not decided yet, so that it can be persisted and later used to
+    /// rebuild this [`Consensus`] after a restart.
+    ///
+    /// The returned leaves are the ones in `state_map` after `last_decided_view`, with their block
+    /// payloads filled in if they are available.
+    #[must_use]
+    pub fn undecided_state(&self) -> UndecidedState<TYPES, LEAF> {
+        let leaves = self
+            .state_map
+            .range((Excluded(self.last_decided_view), Unbounded))
+            .filter_map(|(_view_number, view)| view.get_leaf_commitment())
+            .filter_map(|leaf| self.saved_leaves.get(&leaf))
+            .map(|leaf| {
+                let mut leaf = leaf.clone();
+                if let Some(payload) = self.saved_block_payloads.get(leaf.get_payload_commitment())
+                {
+                    if let Err(e) = leaf.fill_block_payload(payload.clone()) {
+                        error!("Saved block payload and commitment don't match: {:?}", e);
+                    }
+                }
+                StoredView::from(leaf)
+            })
+            .collect();
+        UndecidedState {
+            leaves,
+            high_qc: self.high_qc.clone(),
+            locked_view: self.locked_view,
+        }
+    }
+
     /// Gets the last decided state
     /// # Panics
     /// if the last decided view's state does not exist in the state map
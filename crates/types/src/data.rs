@@ -11,10 +11,11 @@ use crate::{
         node_implementation::NodeType,
         signature_key::{EncodedPublicKey, SignatureKey},
         state::{ConsensusTime, TestableBlock, TestableState},
-        storage::StoredView,
+        storage::{StorageError, StoredView},
         BlockPayload, State,
     },
     vote2::Certificate2,
+    VidConfig,
 };
 use ark_bls12_381::Bls12_381;
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
@@ -27,11 +28,14 @@ use hotshot_utils::bincode::bincode_opts;
 use jf_primitives::pcs::{checked_fft_size, prelude::UnivariateKzgPCS, PolynomialCommitmentScheme};
 use rand::Rng;
 use serde::{Deserialize, Serialize};
-use snafu::Snafu;
+use snafu::{ResultExt, Snafu};
 use std::{
     fmt::{Debug, Display},
     hash::Hash,
+    path::{Path, PathBuf},
+    sync::Arc,
 };
+use tracing::warn;
 
 /// Type-safe wrapper around `u64` so we know the thing we're talking about is a view number.
 #[derive(
@@ -162,6 +166,126 @@ pub fn test_srs(
     .unwrap()
 }
 
+/// KZG structured reference string used to instantiate [`VidScheme`]
+pub type VidSrs = <UnivariateKzgPCS<Bls12_381> as PolynomialCommitmentScheme>::SRS;
+
+/// Error setting up [`VidScheme`]
+#[derive(Snafu, Debug)]
+#[snafu(visibility(pub))]
+pub enum VidSetupError {
+    /// The SRS file could not be read
+    ReadSrs {
+        /// Path of the SRS file
+        path: PathBuf,
+        /// Underlying error
+        source: std::io::Error,
+    },
+    /// The SRS file doesn't hold a valid SRS
+    DeserializeSrs {
+        /// Path of the SRS file
+        path: PathBuf,
+        /// Description of the error
+        reason: String,
+    },
+    /// The erasure code rate is not a ratio in `(0, 1]`
+    InvalidErasureCodeRate {
+        /// The rate that was configured
+        rate: (u64, u64),
+    },
+    /// No SRS file is configured, and the insecure test SRS is not enabled
+    MissingSrs,
+    /// [`VidScheme`] rejected the parameters, e.g. because the SRS is too small
+    InvalidParameters {
+        /// Description of the error
+        reason: String,
+    },
+}
+
+/// Everything needed to instantiate [`VidScheme`] for a given number of storage nodes
+#[derive(Clone, custom_debug::Debug)]
+pub struct VidSetup {
+    /// The KZG structured reference string
+    #[debug(skip)]
+    srs: Arc<VidSrs>,
+    /// The ratio of the number of shares needed to recover a payload to the number of shares
+    erasure_code_rate: (u64, u64),
+}
+
+impl VidSetup {
+    /// Set up VID as configured, loading the SRS from `config.srs_path`.
+    ///
+    /// If no SRS file is configured and `config.insecure_test_srs` is set, an insecure SRS for up
+    /// to `max_storage_nodes` is generated instead. That is only acceptable for testing.
+    ///
+    /// # Errors
+    /// Returns an error if the erasure code rate is invalid, or the SRS is neither configured nor
+    /// allowed to be generated, or cannot be loaded.
+    pub fn new(config: &VidConfig, max_storage_nodes: usize) -> Result<Self, VidSetupError> {
+        let (numerator, denominator) = config.erasure_code_rate;
+        if numerator == 0 || numerator > denominator {
+            return Err(VidSetupError::InvalidErasureCodeRate {
+                rate: config.erasure_code_rate,
+            });
+        }
+        let srs = match &config.srs_path {
+            Some(path) => load_srs(path)?,
+            None if !config.insecure_test_srs => return Err(VidSetupError::MissingSrs),
+            None => {
+                warn!("No SRS file configured for VID, using an insecure test SRS");
+                test_srs(max_storage_nodes)
+            }
+        };
+        Ok(Self {
+            srs: Arc::new(srs),
+            erasure_code_rate: config.erasure_code_rate,
+        })
+    }
+
+    /// Number of shares needed to recover a payload dispersed among `num_storage_nodes`
+    #[must_use]
+    pub fn num_chunks(&self, num_storage_nodes: usize) -> usize {
+        let (numerator, denominator) = self.erasure_code_rate;
+        let storage_nodes = u64::try_from(num_storage_nodes).unwrap_or(u64::MAX);
+        // ceil(storage_nodes * rate)
+        let num_chunks = (storage_nodes.saturating_mul(numerator) + denominator - 1) / denominator;
+        usize::try_from(num_chunks)
+            .map_or(num_storage_nodes, |num_chunks| {
+                num_chunks.min(num_storage_nodes)
+            })
+            .max(1)
+    }
+
+    /// Instantiate [`VidScheme`] for dispersal among `num_storage_nodes`
+    ///
+    /// # Errors
+    /// Returns an error if the SRS doesn't support that many storage nodes.
+    pub fn scheme(&self, num_storage_nodes: usize) -> Result<VidScheme, VidSetupError> {
+        VidScheme::new(
+            self.num_chunks(num_storage_nodes),
+            num_storage_nodes,
+            &*self.srs,
+        )
+        .map_err(|e| VidSetupError::InvalidParameters {
+            reason: format!("{e:?}"),
+        })
+    }
+}
+
+/// Load a KZG structured reference string for VID from a file written with
+/// `CanonicalSerialize::serialize_uncompressed`.
+///
+/// # Errors
+/// Returns an error if the file cannot be read or doesn't hold a valid SRS.
+pub fn load_srs(path: &Path) -> Result<VidSrs, VidSetupError> {
+    let bytes = std::fs::read(path).context(ReadSrsSnafu { path })?;
+    <VidSrs as ark_serialize_04::CanonicalDeserialize>::deserialize_uncompressed(&*bytes).map_err(
+        |e| VidSetupError::DeserializeSrs {
+            path: path.to_path_buf(),
+            reason: e.to_string(),
+        },
+    )
+}
+
 /// Proposal to append a block.
 #[derive(custom_debug::Debug, Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
 #[serde(bound(deserialize = ""))]
@@ -349,7 +473,14 @@ pub trait LeafType:
     /// Identity of the network participant who proposed this leaf.
     fn get_proposer_id(&self) -> EncodedPublicKey;
     /// Create a leaf from information stored about a view.
-    fn from_stored_view(stored_view: StoredView<Self::NodeType, Self>) -> Self;
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`StorageError::MissingBlockPayload`] if this leaf type needs the block payload
+    /// and the view was stored without it.
+    fn from_stored_view(
+        stored_view: StoredView<Self::NodeType, Self>,
+    ) -> Result<Self, StorageError>;
 }
 
 /// The [`NodeType`] in a [`LeafType`].
@@ -553,8 +684,25 @@ impl<TYPES: NodeType> LeafType for ValidatingLeaf<TYPES> {
         self.proposer_id.clone()
     }
 
-    fn from_stored_view(_stored_view: StoredView<Self::NodeType, Self>) -> Self {
-        unimplemented!("Unimplemented for validating consensus which will be removed.");
+    fn from_stored_view(
+        stored_view: StoredView<Self::NodeType, Self>,
+    ) -> Result<Self, StorageError> {
+        let Some(deltas) = stored_view.block_payload else {
+            return Err(StorageError::MissingBlockPayload {
+                view_number: *stored_view.view_number,
+            });
+        };
+        Ok(Self {
+            view_number: stored_view.view_number,
+            height: stored_view.block_header.block_number(),
+            justify_qc: stored_view.justify_qc,
+            parent_commitment: stored_view.parent,
+            deltas,
+            state: stored_view.state,
+            rejected: stored_view.rejected,
+            timestamp: stored_view.timestamp,
+            proposer_id: stored_view.proposer_id,
+        })
     }
 }
 
@@ -667,8 +815,10 @@ impl<TYPES: NodeType> LeafType for Leaf<TYPES> {
         self.proposer_id.clone()
     }
 
-    fn from_stored_view(stored_view: StoredView<Self::NodeType, Self>) -> Self {
-        Self {
+    fn from_stored_view(
+        stored_view: StoredView<Self::NodeType, Self>,
+    ) -> Result<Self, StorageError> {
+        Ok(Self {
             view_number: stored_view.view_number,
             justify_qc: stored_view.justify_qc,
             parent_commitment: stored_view.parent,
@@ -677,7 +827,7 @@ impl<TYPES: NodeType> LeafType for Leaf<TYPES> {
             rejected: stored_view.rejected,
             timestamp: stored_view.timestamp,
             proposer_id: stored_view.proposer_id,
-        }
+        })
     }
 }
 
@@ -9,10 +9,12 @@
 )]
 #![allow(clippy::module_name_repetitions)]
 
-use std::{num::NonZeroUsize, time::Duration};
+use clock::Clock;
+use std::{num::NonZeroUsize, path::PathBuf, time::Duration};
 
 pub mod block_impl;
 pub mod certificate;
+pub mod clock;
 pub mod consensus;
 pub mod data;
 pub mod error;
@@ -48,6 +50,8 @@ pub struct HotShotConfig<ENTRY, ELECTIONCONFIG> {
     pub min_transactions: usize,
     /// Maximum transactions per block
     pub max_transactions: NonZeroUsize,
+    /// Maximum total serialized size of the transactions in a block, in bytes
+    pub max_block_size: NonZeroUsize,
     /// List of known node's public keys and stake value for certificate aggregation, serving as public parameter
     pub known_nodes_with_stake: Vec<ENTRY>,
     /// List of DA committee nodes for static DA committe
@@ -68,4 +72,113 @@ pub struct HotShotConfig<ENTRY, ELECTIONCONFIG> {
     pub propose_max_round_time: Duration,
     /// the election configuration
     pub election_config: Option<ELECTIONCONFIG>,
+    /// Limits on the transactions a node holds while waiting for them to be included in a block
+    #[serde(default)]
+    pub mempool: MempoolConfig,
+    /// Parameters of the verifiable information dispersal of block payloads
+    #[serde(default)]
+    pub vid: VidConfig,
+    /// Number of views before the newest decided view whose leaves are kept in the storage, or
+    /// `None` to keep the full decided history
+    #[serde(default = "default_decided_retention_views")]
+    pub decided_retention_views: Option<u64>,
+    /// Where the consensus, view sync and transaction tasks get the time from. Real time unless a
+    /// test simulates it.
+    #[serde(skip)]
+    pub clock: Clock,
+}
+
+/// Default for [`HotShotConfig::decided_retention_views`]
+pub const DEFAULT_DECIDED_RETENTION_VIEWS: u64 = 1000;
+
+/// Default for [`HotShotConfig::decided_retention_views`]
+#[allow(clippy::unnecessary_wraps)]
+fn default_decided_retention_views() -> Option<u64> {
+    Some(DEFAULT_DECIDED_RETENTION_VIEWS)
+}
+
+/// Parameters of the verifiable information dispersal (VID) of block payloads
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct VidConfig {
+    /// The erasure code rate, as the ratio of the number of shares needed to recover a payload
+    /// to the number of storage nodes. Defaults to `(1, 1)`, i.e. every share is needed.
+    #[serde(default = "default_erasure_code_rate")]
+    pub erasure_code_rate: (u64, u64),
+    /// File holding the KZG structured reference string, as written by `ark-serialize`. Required
+    /// unless `insecure_test_srs` is set.
+    pub srs_path: Option<PathBuf>,
+    /// Generate an insecure SRS for testing when `srs_path` is unset, instead of failing. Only
+    /// for tests and local development networks.
+    #[serde(default)]
+    pub insecure_test_srs: bool,
+    /// Number of views a storage node keeps its VID shares for, to serve them to nodes
+    /// reconstructing a payload
+    #[serde(default = "default_share_retention_views")]
+    pub share_retention_views: u64,
+    /// Reconstruct the payload of every decided leaf we didn't receive the payload of, by asking
+    /// its storage nodes for their shares. Off by default, in which case payloads are only
+    /// reconstructed when requested.
+    #[serde(default)]
+    pub reconstruct_decided_payloads: bool,
+}
+
+/// Default for [`VidConfig::erasure_code_rate`]
+fn default_erasure_code_rate() -> (u64, u64) {
+    (1, 1)
+}
+
+/// Default for [`VidConfig::share_retention_views`]
+fn default_share_retention_views() -> u64 {
+    100
+}
+
+impl Default for VidConfig {
+    fn default() -> Self {
+        Self {
+            erasure_code_rate: default_erasure_code_rate(),
+            srs_path: None,
+            insecure_test_srs: false,
+            share_retention_views: default_share_retention_views(),
+            reconstruct_decided_payloads: false,
+        }
+    }
+}
+
+/// What a full mempool does with a new transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum EvictionPolicy {
+    /// Reject the new transaction
+    RejectNew,
+    /// Evict the transactions that arrived first to make room
+    EvictOldest,
+    /// Evict the transactions that would be included in a block last, if the new transaction
+    /// would be included before them; otherwise reject the new transaction
+    EvictLowestPriority,
+}
+
+/// Limits on the mempool of a `HotShot` node
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct MempoolConfig {
+    /// Maximum number of transactions held at once
+    pub max_transactions: usize,
+    /// Maximum total serialized size of the transactions held at once, in bytes
+    pub max_bytes: u64,
+    /// Maximum number of transactions held at once that were submitted through the same node
+    pub max_transactions_per_sender: usize,
+    /// How long a transaction is held before it is dropped
+    pub transaction_ttl: Duration,
+    /// What to do with a new transaction when one of the limits above is reached
+    pub eviction_policy: EvictionPolicy,
+}
+
+impl Default for MempoolConfig {
+    fn default() -> Self {
+        Self {
+            max_transactions: 100_000,
+            max_bytes: 256 * 1024 * 1024,
+            max_transactions_per_sender: 10_000,
+            transaction_ttl: Duration::from_secs(600),
+            eviction_policy: EvictionPolicy::RejectNew,
+        }
+    }
 }
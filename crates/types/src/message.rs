@@ -6,7 +6,7 @@
 use crate::vote2::HasViewNumber;
 use crate::{
     certificate::{DACertificate, VIDCertificate},
-    data::{DAProposal, ProposalType, VidDisperse},
+    data::{DAProposal, LeafType, ProposalType, VidDisperse, VidScheme, VidSchemeTrait},
     simple_vote::QuorumVote,
     traits::{
         network::{NetworkMsg, ViewMessage},
@@ -15,10 +15,12 @@ use crate::{
             ViewSyncProposalType,
         },
         signature_key::EncodedSignature,
+        state::ConsensusTime,
     },
     vote::{DAVote, TimeoutVote, VIDVote, ViewSyncVote, VoteType},
 };
 
+use commit::{Commitment, Committable};
 use derivative::Derivative;
 use either::Either::{self, Left, Right};
 use serde::{Deserialize, Serialize};
@@ -48,6 +50,9 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> ViewMessage<TYPES> for Messa
     fn purpose(&self) -> MessagePurpose {
         self.kind.purpose()
     }
+    fn transaction_commitment(&self) -> Option<Commitment<TYPES::Transaction>> {
+        self.kind.transaction_commitment()
+    }
 }
 
 /// A wrapper type for implementing `PassType` on a vector of `Message`.
@@ -79,6 +84,8 @@ pub enum MessagePurpose {
     VidVote,
     /// VID certificate, like [`DAC`].
     VidCert,
+    /// Catchup request or response, only ever sent directly to a peer
+    Catchup,
 }
 
 // TODO (da) make it more customized to the consensus layer, maybe separating the specific message
@@ -91,6 +98,8 @@ pub enum MessageKind<TYPES: NodeType, I: NodeImplementation<TYPES>> {
     Consensus(I::ConsensusMessage),
     /// Messages relating to sharing data between nodes
     Data(DataMessage<TYPES>),
+    /// Messages used by nodes that fell behind to fetch missing data from peers
+    Catchup(CatchupMessage<TYPES, I::Leaf>),
     /// Phantom data.
     _Unreachable(PhantomData<I>),
 }
@@ -117,6 +126,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> ViewMessage<TYPES> for Messa
         match &self {
             MessageKind::Consensus(message) => message.view_number(),
             MessageKind::Data(DataMessage::SubmitTransaction(_, v)) => *v,
+            MessageKind::Catchup(message) => message.view_number(),
             MessageKind::_Unreachable(_) => unimplemented!(),
         }
     }
@@ -127,6 +137,17 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> ViewMessage<TYPES> for Messa
             MessageKind::Data(message) => match message {
                 DataMessage::SubmitTransaction(_, _) => MessagePurpose::Data,
             },
+            MessageKind::Catchup(_) => MessagePurpose::Catchup,
+            MessageKind::_Unreachable(_) => unimplemented!(),
+        }
+    }
+
+    fn transaction_commitment(&self) -> Option<Commitment<TYPES::Transaction>> {
+        match &self {
+            MessageKind::Data(DataMessage::SubmitTransaction(transaction, _)) => {
+                Some(transaction.commit())
+            }
+            MessageKind::Consensus(_) | MessageKind::Catchup(_) => None,
             MessageKind::_Unreachable(_) => unimplemented!(),
         }
     }
@@ -488,6 +509,96 @@ pub enum DataMessage<TYPES: NodeType> {
     SubmitTransaction(TYPES::Transaction, TYPES::Time),
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(bound(deserialize = ""))]
+/// Messages of the catchup protocol, which lets a node fetch leaves it missed from a peer.
+///
+/// Every leaf carries its justify QC, which certifies the leaf's parent, so a node that trusts a
+/// QC can verify the whole chain of fetched ancestors by comparing commitments.
+pub enum CatchupMessage<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>> {
+    /// Ask a peer for leaves
+    Request(CatchupRequest<TYPES, LEAF>),
+    /// Leaves sent in answer to a [`CatchupRequest`]
+    Response(CatchupResponse<TYPES, LEAF>),
+    /// Ask a VID storage node for its share of a block payload
+    VidShareRequest(VidShareRequest<TYPES>),
+    /// A VID share sent in answer to a [`VidShareRequest`]
+    VidShareResponse(VidShareResponse<TYPES>),
+}
+
+impl<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>> CatchupMessage<TYPES, LEAF> {
+    /// Get the view number the message refers to.
+    pub fn view_number(&self) -> TYPES::Time {
+        match self {
+            CatchupMessage::Request(CatchupRequest::Ancestors { stop_before, .. }) => *stop_before,
+            CatchupMessage::Request(CatchupRequest::ViewRange { from, .. }) => *from,
+            CatchupMessage::Response(response) => response
+                .leaves
+                .first()
+                .map_or_else(TYPES::Time::genesis, LeafType::get_view_number),
+            CatchupMessage::VidShareRequest(request) => request.view_number,
+            CatchupMessage::VidShareResponse(response) => response.view_number,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(bound(deserialize = ""))]
+/// Leaves a node that fell behind asks a peer for.
+pub enum CatchupRequest<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>> {
+    /// The leaf with the given commitment and its ancestors, newest first, stopping before the
+    /// leaf of view `stop_before`
+    Ancestors {
+        /// Commitment of the newest leaf to fetch
+        commitment: Commitment<LEAF>,
+        /// View at which to stop walking back, typically the last view the requester decided
+        stop_before: TYPES::Time,
+    },
+    /// The decided leaves with a view number in `from..=to`
+    ViewRange {
+        /// First view of the range
+        from: TYPES::Time,
+        /// Last view of the range
+        to: TYPES::Time,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(bound(deserialize = ""))]
+/// Answer to a [`CatchupRequest`].
+pub struct CatchupResponse<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>> {
+    /// The request being answered
+    pub request: CatchupRequest<TYPES, LEAF>,
+    /// The leaves the responder had, newest first, with their block payloads filled in where the
+    /// responder had them
+    pub leaves: Vec<LEAF>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(bound(deserialize = ""))]
+/// Request for the VID share a storage node holds of a block payload, so that the payload can be
+/// reconstructed by a node that didn't receive it.
+pub struct VidShareRequest<TYPES: NodeType> {
+    /// The view in which the payload was dispersed
+    pub view_number: TYPES::Time,
+    /// Commitment to the payload
+    pub payload_commitment: Commitment<TYPES::BlockPayload>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(bound(deserialize = ""))]
+/// Answer to a [`VidShareRequest`].
+pub struct VidShareResponse<TYPES: NodeType> {
+    /// The view in which the payload was dispersed
+    pub view_number: TYPES::Time,
+    /// Commitment to the payload
+    pub payload_commitment: Commitment<TYPES::BlockPayload>,
+    /// The responder's share of the payload
+    pub share: <VidScheme as VidSchemeTrait>::Share,
+    /// The VID common data the share verifies against
+    pub common: <VidScheme as VidSchemeTrait>::Common,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
 #[serde(bound(deserialize = ""))]
 /// Prepare qc from the leader
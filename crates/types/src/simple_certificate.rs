@@ -62,8 +62,8 @@ impl<TYPES: NodeType, VOTEABLE: Voteable + 'static> Certificate2<TYPES>
             return true;
         }
         let real_qc_pp = <TYPES::SignatureKey as SignatureKey>::get_public_parameter(
-            membership.get_committee_qc_stake_table(),
-            U256::from(membership.success_threshold().get()),
+            membership.get_committee_qc_stake_table(self.view_number),
+            Self::threshold(membership, self.view_number),
         );
         <TYPES::SignatureKey as SignatureKey>::check(
             &real_qc_pp,
@@ -71,8 +71,12 @@ impl<TYPES: NodeType, VOTEABLE: Voteable + 'static> Certificate2<TYPES>
             self.signatures.as_ref().unwrap(),
         )
     }
-    fn threshold<MEMBERSHIP: Membership<TYPES>>(membership: &MEMBERSHIP) -> u64 {
-        membership.success_threshold().into()
+    /// More than two thirds of the total stake of the committee
+    fn threshold<MEMBERSHIP: Membership<TYPES>>(
+        membership: &MEMBERSHIP,
+        view_number: TYPES::Time,
+    ) -> U256 {
+        membership.success_stake_threshold(view_number)
     }
     fn get_data(&self) -> &Self::Voteable {
         &self.data
@@ -2,6 +2,8 @@
 pub mod block_contents;
 pub mod consensus_api;
 pub mod election;
+pub mod epoch;
+pub mod mempool;
 pub mod metrics;
 pub mod network;
 pub mod node_implementation;
@@ -3,6 +3,7 @@
 //! This module provides the [`Transaction`], [`BlockPayload`], and [`BlockHeader`] traits, which
 //! describe the behaviors that a block is expected to have.
 
+use crate::data::{VidScheme, VidSchemeTrait};
 use commit::{Commitment, Committable};
 use serde::{de::DeserializeOwned, Serialize};
 
@@ -55,6 +56,16 @@ pub trait BlockPayload:
     /// returns hashes of all the transactions in this block
     /// TODO make this ordered with a vec
     fn transaction_commitments(&self) -> HashSet<Commitment<Self::Transaction>>;
+
+    /// Rebuild a payload from the bytes recovered from its VID shares, whose VID commitment is
+    /// `payload_commitment`
+    ///
+    /// # Errors
+    /// If the bytes are not an encoding of a payload.
+    fn from_vid_bytes(
+        bytes: &[u8],
+        payload_commitment: <VidScheme as VidSchemeTrait>::Commit,
+    ) -> Result<Self, Self::Error>;
 }
 
 /// Header of a block, which commits to a [`BlockPayload`].
@@ -1,7 +1,7 @@
 //! Contains the [`ConsensusApi`] trait.
 
 use crate::{
-    data::LeafType,
+    data::{LeafType, VidSetup},
     error::HotShotError,
     event::{Event, EventType},
     message::{DataMessage, SequencingMessage},
@@ -10,10 +10,11 @@ use crate::{
         network::NetworkError,
         node_implementation::{NodeImplementation, NodeType},
         signature_key::SignatureKey,
-        storage::StorageError,
+        storage::{StorageError, UndecidedState},
     },
 };
 use async_trait::async_trait;
+use commit::Commitment;
 
 use std::{num::NonZeroUsize, sync::Arc, time::Duration};
 
@@ -36,16 +37,44 @@ pub trait ConsensusSharedApi<
     /// If this time is reached, the leader has to send a propose without transactions.
     fn propose_max_round_time(&self) -> Duration;
 
-    /// Store a leaf in the storage
-    async fn store_leaf(
+    /// Store newly decided leaves in the storage.
+    ///
+    /// Previously decided leaves are kept so that the storage can serve the decided history.
+    async fn store_leaves(&self, leaves: Vec<LEAF>) -> Result<(), StorageError>;
+
+    /// Store newly decided leaves and, if given, the consensus state that has not been decided
+    /// yet, replacing the undecided state that was stored before.
+    ///
+    /// Both are written to the storage in a single commit.
+    async fn store_consensus_state(
         &self,
-        old_anchor_view: TYPES::Time,
-        leaf: LEAF,
+        leaves: Vec<LEAF>,
+        undecided: Option<UndecidedState<TYPES, LEAF>>,
     ) -> Result<(), StorageError>;
 
+    /// Get the decided leaf with the given commitment from the storage, if it is stored
+    async fn get_decided_leaf(
+        &self,
+        commitment: Commitment<LEAF>,
+    ) -> Result<Option<LEAF>, StorageError>;
+
+    /// Get the decided leaves with a view number in `from..=to` from the storage, in ascending
+    /// view order
+    async fn get_decided_leaves(
+        &self,
+        from: TYPES::Time,
+        to: TYPES::Time,
+    ) -> Result<Vec<LEAF>, StorageError>;
+
     /// Retuns the maximum transactions allowed in a block
     fn max_transactions(&self) -> NonZeroUsize;
 
+    /// Returns the maximum total serialized size of the transactions in a block, in bytes
+    fn max_block_size(&self) -> NonZeroUsize;
+
+    /// Returns the setup with which block payloads are dispersed
+    fn vid(&self) -> &VidSetup;
+
     /// Returns the minimum transactions that must be in a block
     fn min_transactions(&self) -> usize;
 
@@ -5,7 +5,7 @@
 
 use super::{
     node_implementation::{NodeImplementation, NodeType},
-    signature_key::{EncodedPublicKey, EncodedSignature},
+    signature_key::{EncodedPublicKey, EncodedSignature, StakeTableEntryType},
 };
 use crate::{
     certificate::{
@@ -39,7 +39,13 @@ use ethereum_types::U256;
 use hotshot_utils::bincode::bincode_opts;
 use serde::{Deserialize, Serialize};
 use snafu::Snafu;
-use std::{collections::BTreeSet, fmt::Debug, hash::Hash, marker::PhantomData, num::NonZeroU64};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Debug,
+    hash::Hash,
+    marker::PhantomData,
+    num::NonZeroU64,
+};
 use tracing::error;
 
 /// Error for election problems
@@ -218,9 +224,10 @@ pub trait Membership<TYPES: NodeType>:
         config: TYPES::ElectionConfigType,
     ) -> Self;
 
-    /// Clone the public key and corresponding stake table for current elected committee
+    /// Clone the public key and corresponding stake table for the committee of view `view_number`
     fn get_committee_qc_stake_table(
         &self,
+        view_number: TYPES::Time,
     ) -> Vec<<TYPES::SignatureKey as SignatureKey>::StakeTableEntry>;
 
     /// The leader of the committee for view `view_number`.
@@ -240,24 +247,79 @@ pub trait Membership<TYPES: NodeType>:
         priv_key: &<TYPES::SignatureKey as SignatureKey>::PrivateKey,
     ) -> Result<Option<TYPES::VoteTokenType>, ElectionError>;
 
-    /// Checks the claims of a received vote token
+    /// Checks the claims of a received vote token for view `view_number`
     ///
     /// # Errors
     /// TODO tbd
     fn validate_vote_token(
         &self,
+        view_number: TYPES::Time,
         pub_key: TYPES::SignatureKey,
         token: Checked<TYPES::VoteTokenType>,
     ) -> Result<Checked<TYPES::VoteTokenType>, ElectionError>;
 
-    /// Returns the number of total nodes in the committee
-    fn total_nodes(&self) -> usize;
+    /// Returns the number of total nodes in the committee for view `view_number`
+    fn total_nodes(&self, view_number: TYPES::Time) -> usize;
+
+    /// Returns the success threshold of the committee for view `view_number`
+    fn success_threshold(&self, view_number: TYPES::Time) -> NonZeroU64;
+
+    /// Returns the failure threshold of the committee for view `view_number`
+    fn failure_threshold(&self, view_number: TYPES::Time) -> NonZeroU64;
+
+    /// Called when the node enters view `view_number`, so that a membership whose committee
+    /// changes over time can switch to the committee of that view
+    fn enter_view(&self, _view_number: TYPES::Time) {}
+
+    /// The stake of `key` in the stake table of the committee for view `view_number`, or `None`
+    /// if it is not a member
+    fn get_stake(&self, view_number: TYPES::Time, key: &TYPES::SignatureKey) -> Option<U256>;
 
-    /// Returns the threshold for a specific `Membership` implementation
-    fn success_threshold(&self) -> NonZeroU64;
+    /// The total stake of the committee for view `view_number`
+    fn total_stake(&self, view_number: TYPES::Time) -> U256;
 
-    /// Returns the threshold for a specific `Membership` implementation
-    fn failure_threshold(&self) -> NonZeroU64;
+    /// The stake a certificate of the committee for view `view_number` must be signed with: more
+    /// than two thirds of its total stake
+    fn success_stake_threshold(&self, view_number: TYPES::Time) -> U256 {
+        self.total_stake(view_number) * 2 / 3 + 1
+    }
+}
+
+/// The stake of each member of a committee, so that [`Membership::get_stake`] and
+/// [`Membership::total_stake`] don't have to scan the stake table of the committee
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StakeMap<KEY: SignatureKey> {
+    /// member -> stake
+    stakes: BTreeMap<KEY, U256>,
+    /// total stake of the members
+    total: U256,
+}
+
+impl<KEY: SignatureKey> StakeMap<KEY> {
+    /// The stakes of the members of the stake table `entries`
+    #[must_use]
+    pub fn new(entries: &[KEY::StakeTableEntry]) -> Self {
+        let stakes: BTreeMap<KEY, U256> = entries
+            .iter()
+            .map(|entry| (KEY::get_public_key(entry), entry.get_stake()))
+            .collect();
+        let total = stakes
+            .values()
+            .fold(U256::zero(), |total, stake| total.saturating_add(*stake));
+        Self { stakes, total }
+    }
+
+    /// The stake of `key`, or `None` if it is not a member
+    #[must_use]
+    pub fn get(&self, key: &KEY) -> Option<U256> {
+        self.stakes.get(key).copied()
+    }
+
+    /// The total stake of the members
+    #[must_use]
+    pub fn total(&self) -> U256 {
+        self.total
+    }
 }
 
 /// Protocol for exchanging proposals and votes to make decisions in a distributed network.
@@ -305,19 +367,19 @@ pub trait ConsensusExchange<TYPES: NodeType, M: NetworkMsg>: Send + Sync {
         &self.get_leader(view_number) == self.public_key()
     }
 
-    /// Threshold required to approve a [`Proposal`](Self::Proposal).
-    fn success_threshold(&self) -> NonZeroU64 {
-        self.membership().success_threshold()
+    /// Threshold required to approve a [`Proposal`](Self::Proposal) at time `view_number`.
+    fn success_threshold(&self, view_number: TYPES::Time) -> NonZeroU64 {
+        self.membership().success_threshold(view_number)
     }
 
-    /// Threshold required to know a success threshold will not be reached
-    fn failure_threshold(&self) -> NonZeroU64 {
-        self.membership().failure_threshold()
+    /// Threshold required to know a success threshold will not be reached at time `view_number`
+    fn failure_threshold(&self, view_number: TYPES::Time) -> NonZeroU64 {
+        self.membership().failure_threshold(view_number)
     }
 
-    /// The total number of nodes in the committee.
-    fn total_nodes(&self) -> usize {
-        self.membership().total_nodes()
+    /// The total number of nodes in the committee at time `view_number`.
+    fn total_nodes(&self, view_number: TYPES::Time) -> usize {
+        self.membership().total_nodes(view_number)
     }
 
     /// Attempts to generate a vote token for participation at time `view_number`.
@@ -338,37 +400,38 @@ pub trait ConsensusExchange<TYPES: NodeType, M: NetworkMsg>: Send + Sync {
             return true;
         }
         let leaf_commitment = qc.leaf_commitment();
+        let view_number = qc.view_number();
 
         match qc.signatures() {
             AssembledSignature::DA(qc) => {
                 let real_commit = VoteData::DA(leaf_commitment).commit();
                 let real_qc_pp = <TYPES::SignatureKey as SignatureKey>::get_public_parameter(
-                    self.membership().get_committee_qc_stake_table(),
-                    U256::from(self.membership().success_threshold().get()),
+                    self.membership().get_committee_qc_stake_table(view_number),
+                    U256::from(self.membership().success_threshold(view_number).get()),
                 );
                 <TYPES::SignatureKey as SignatureKey>::check(&real_qc_pp, real_commit.as_ref(), &qc)
             }
             AssembledSignature::VID(qc) => {
                 let real_commit = VoteData::VID(leaf_commitment).commit();
                 let real_qc_pp = <TYPES::SignatureKey as SignatureKey>::get_public_parameter(
-                    self.membership().get_committee_qc_stake_table(),
-                    U256::from(self.membership().success_threshold().get()),
+                    self.membership().get_committee_qc_stake_table(view_number),
+                    U256::from(self.membership().success_threshold(view_number).get()),
                 );
                 <TYPES::SignatureKey as SignatureKey>::check(&real_qc_pp, real_commit.as_ref(), &qc)
             }
             AssembledSignature::Yes(qc) => {
                 let real_commit = VoteData::Yes(leaf_commitment).commit();
                 let real_qc_pp = <TYPES::SignatureKey as SignatureKey>::get_public_parameter(
-                    self.membership().get_committee_qc_stake_table(),
-                    U256::from(self.membership().success_threshold().get()),
+                    self.membership().get_committee_qc_stake_table(view_number),
+                    U256::from(self.membership().success_threshold(view_number).get()),
                 );
                 <TYPES::SignatureKey as SignatureKey>::check(&real_qc_pp, real_commit.as_ref(), &qc)
             }
             AssembledSignature::No(qc) => {
                 let real_commit = VoteData::No(leaf_commitment).commit();
                 let real_qc_pp = <TYPES::SignatureKey as SignatureKey>::get_public_parameter(
-                    self.membership().get_committee_qc_stake_table(),
-                    U256::from(self.membership().success_threshold().get()),
+                    self.membership().get_committee_qc_stake_table(view_number),
+                    U256::from(self.membership().success_threshold(view_number).get()),
                 );
                 <TYPES::SignatureKey as SignatureKey>::check(&real_qc_pp, real_commit.as_ref(), &qc)
             }
@@ -386,18 +449,19 @@ pub trait ConsensusExchange<TYPES: NodeType, M: NetworkMsg>: Send + Sync {
         }
     }
 
-    /// Validate a vote by checking its signature and token.
+    /// Validate a vote at time `view_number` by checking its signature and token.
     fn is_valid_vote(
         &self,
+        view_number: TYPES::Time,
         key: &TYPES::SignatureKey,
         encoded_signature: &EncodedSignature,
         data: &VoteData<Self::Commitment>,
         vote_token: &Checked<TYPES::VoteTokenType>,
     ) -> bool {
         let is_valid_signature = key.validate(encoded_signature, data.commit().as_ref());
-        let valid_vote_token = self
-            .membership()
-            .validate_vote_token(key.clone(), vote_token.clone());
+        let valid_vote_token =
+            self.membership()
+                .validate_vote_token(view_number, key.clone(), vote_token.clone());
         let is_valid_vote_token = match valid_vote_token {
             Err(_) => {
                 error!("Vote token was invalid");
@@ -440,6 +504,7 @@ pub trait ConsensusExchange<TYPES: NodeType, M: NetworkMsg>: Send + Sync {
         Self::Certificate,
     > {
         if !self.is_valid_vote(
+            vote.get_view(),
             &vote.get_key(),
             &vote.get_signature(),
             &vote.get_data(),
@@ -454,7 +519,7 @@ pub trait ConsensusExchange<TYPES: NodeType, M: NetworkMsg>: Send + Sync {
         // TODO ED Could we make this part of the vote in the future?  It's only a usize.
         let append_node_id = self
             .membership()
-            .get_committee_qc_stake_table()
+            .get_committee_qc_stake_table(vote.get_view())
             .iter()
             .position(|x| *x == stake_table_entry.clone())
             .unwrap();
@@ -463,7 +528,8 @@ pub trait ConsensusExchange<TYPES: NodeType, M: NetworkMsg>: Send + Sync {
         match accumulator.append(
             vote.clone(),
             append_node_id,
-            self.membership().get_committee_qc_stake_table(),
+            self.membership()
+                .get_committee_qc_stake_table(vote.get_view()),
         ) {
             Either::Left(accumulator) => Either::Left(accumulator),
             Either::Right(signatures) => Either::Right(Self::Certificate::create_certificate(
@@ -1154,7 +1220,11 @@ impl<
                         .to_bytes(),
                     round,
                 };
-                (certificate_internal, self.failure_threshold(), vote_data)
+                (
+                    certificate_internal,
+                    self.failure_threshold(round),
+                    vote_data,
+                )
             }
             ViewSyncCertificate::Commit(certificate_internal)
             | ViewSyncCertificate::Finalize(certificate_internal) => {
@@ -1164,15 +1234,19 @@ impl<
                         .to_bytes(),
                     round,
                 };
-                (certificate_internal, self.success_threshold(), vote_data)
+                (
+                    certificate_internal,
+                    self.success_threshold(round),
+                    vote_data,
+                )
             }
         };
         match certificate_internal.signatures {
             AssembledSignature::ViewSyncPreCommit(raw_signatures) => {
                 let real_commit = VoteData::ViewSyncPreCommit(vote_data.commit()).commit();
                 let real_qc_pp = <TYPES::SignatureKey as SignatureKey>::get_public_parameter(
-                    self.membership().get_committee_qc_stake_table(),
-                    U256::from(self.membership().failure_threshold().get()),
+                    self.membership().get_committee_qc_stake_table(round),
+                    U256::from(self.membership().failure_threshold(round).get()),
                 );
                 <TYPES::SignatureKey as SignatureKey>::check(
                     &real_qc_pp,
@@ -1183,8 +1257,8 @@ impl<
             AssembledSignature::ViewSyncCommit(raw_signatures) => {
                 let real_commit = VoteData::ViewSyncCommit(vote_data.commit()).commit();
                 let real_qc_pp = <TYPES::SignatureKey as SignatureKey>::get_public_parameter(
-                    self.membership().get_committee_qc_stake_table(),
-                    U256::from(self.membership().success_threshold().get()),
+                    self.membership().get_committee_qc_stake_table(round),
+                    U256::from(self.membership().success_threshold(round).get()),
                 );
                 <TYPES::SignatureKey as SignatureKey>::check(
                     &real_qc_pp,
@@ -1195,8 +1269,8 @@ impl<
             AssembledSignature::ViewSyncFinalize(raw_signatures) => {
                 let real_commit = VoteData::ViewSyncFinalize(vote_data.commit()).commit();
                 let real_qc_pp = <TYPES::SignatureKey as SignatureKey>::get_public_parameter(
-                    self.membership().get_committee_qc_stake_table(),
-                    U256::from(self.membership().success_threshold().get()),
+                    self.membership().get_committee_qc_stake_table(round),
+                    U256::from(self.membership().success_threshold(round).get()),
                 );
                 <TYPES::SignatureKey as SignatureKey>::check(
                     &real_qc_pp,
@@ -1334,8 +1408,8 @@ pub trait TimeoutExchangeType<TYPES: NodeType, M: NetworkMsg>: ConsensusExchange
         if let AssembledSignature::Timeout(qc) = qc.signatures() {
             let real_commit = VoteData::Timeout(comparison_commitment).commit();
             let real_qc_pp = <TYPES::SignatureKey as SignatureKey>::get_public_parameter(
-                self.membership().get_committee_qc_stake_table(),
-                U256::from(self.membership().success_threshold().get()),
+                self.membership().get_committee_qc_stake_table(view_number),
+                U256::from(self.membership().success_threshold(view_number).get()),
             );
             <TYPES::SignatureKey as SignatureKey>::check(&real_qc_pp, real_commit.as_ref(), &qc)
         } else {
@@ -0,0 +1,167 @@
+//! Epochs: ranges of consecutive views over which the committees don't change
+//!
+//! The committees of an epoch come from the stake table snapshot taken when the epoch starts.
+//! Changes to the stake table, such as validators joining or leaving, are scheduled for an epoch
+//! and applied at its boundary, so every node switches to the new committees at the same view.
+//! A change must be scheduled [`LOOKAHEAD_EPOCHS`] epochs ahead of the epoch it is for, so that
+//! the committees of the views right after an epoch boundary are known before nodes reach it.
+
+use super::{election::Membership, node_implementation::NodeType, signature_key::SignatureKey};
+use ethereum_types::U256;
+use serde::{Deserialize, Serialize};
+use snafu::Snafu;
+use std::num::NonZeroU64;
+
+/// Number of epochs ahead of the epoch of the view it is scheduled in that a change to the stake
+/// table must at least be scheduled for
+pub const LOOKAHEAD_EPOCHS: u64 = 1;
+
+/// Number of an epoch, counting from 0
+#[derive(
+    Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct EpochNumber(pub u64);
+
+impl EpochNumber {
+    /// The epoch after this one
+    #[must_use]
+    pub fn next(self) -> Self {
+        Self(self.0 + 1)
+    }
+}
+
+impl std::ops::Deref for EpochNumber {
+    type Target = u64;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Maps views to the epochs they belong to
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EpochSchedule {
+    /// Number of views in each epoch
+    pub epoch_length: NonZeroU64,
+}
+
+impl EpochSchedule {
+    /// Create a schedule with epochs of `epoch_length` views
+    #[must_use]
+    pub fn new(epoch_length: NonZeroU64) -> Self {
+        Self { epoch_length }
+    }
+
+    /// The epoch `view` belongs to
+    #[must_use]
+    pub fn epoch(&self, view: u64) -> EpochNumber {
+        EpochNumber(view / self.epoch_length)
+    }
+
+    /// The first view of `epoch`
+    #[must_use]
+    pub fn first_view(&self, epoch: EpochNumber) -> u64 {
+        *epoch * self.epoch_length.get()
+    }
+
+    /// Whether `view` is the first view of its epoch
+    #[must_use]
+    pub fn is_epoch_start(&self, view: u64) -> bool {
+        view % self.epoch_length == 0
+    }
+
+    /// The first view in which it is too late to schedule a change for `epoch`, which is
+    /// [`LOOKAHEAD_EPOCHS`] epochs before it starts
+    #[must_use]
+    pub fn change_deadline(&self, epoch: EpochNumber) -> u64 {
+        self.first_view(EpochNumber(epoch.saturating_sub(LOOKAHEAD_EPOCHS)))
+    }
+}
+
+/// A change to the stake table, applied at the start of the epoch it is scheduled for
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(bound(deserialize = ""))]
+pub enum StakeTableChange<K: SignatureKey> {
+    /// A validator joins with the given stake
+    Join {
+        /// Key of the validator
+        key: K,
+        /// Stake of the validator
+        stake: U256,
+    },
+    /// A validator leaves
+    Leave {
+        /// Key of the validator
+        key: K,
+    },
+    /// The stake of a validator changes
+    UpdateStake {
+        /// Key of the validator
+        key: K,
+        /// New stake of the validator
+        stake: U256,
+    },
+}
+
+/// Errors scheduling a [`StakeTableChange`]
+#[derive(Snafu, Debug, Clone, PartialEq, Eq)]
+#[snafu(visibility(pub))]
+pub enum EpochError {
+    /// The change was scheduled in a view at or past the deadline for changes to the epoch
+    TooLate {
+        /// The epoch the change was scheduled for
+        epoch: EpochNumber,
+        /// The first view in which it is too late to schedule a change for the epoch
+        deadline: u64,
+    },
+}
+
+/// A [`Membership`] whose committees change from epoch to epoch
+///
+/// The methods of [`Membership`] answer for the epoch of the view they are given. The committees
+/// of an epoch only depend on the changes scheduled for it and the epochs before it, and whether
+/// a change is accepted only on the view it is scheduled in, so every node that schedules the
+/// same changes agrees on the committees, however far it has got.
+pub trait EpochMembership<TYPES: NodeType>: Membership<TYPES> {
+    /// How views are grouped into epochs
+    fn epoch_schedule(&self) -> EpochSchedule;
+
+    /// The epoch of the view the node is in
+    fn current_epoch(&self) -> EpochNumber;
+
+    /// The epoch `view` belongs to
+    fn epoch(&self, view: TYPES::Time) -> EpochNumber {
+        self.epoch_schedule().epoch(*view)
+    }
+
+    /// Check whether a change scheduled in `view` for `epoch` would be accepted, without
+    /// scheduling it.
+    ///
+    /// # Errors
+    /// Returns [`EpochError::TooLate`] if `view` is at or past the
+    /// [deadline](EpochSchedule::change_deadline) for changes to `epoch`.
+    fn check_change(&self, view: TYPES::Time, epoch: EpochNumber) -> Result<(), EpochError> {
+        let deadline = self.epoch_schedule().change_deadline(epoch);
+        if *view >= deadline {
+            return Err(EpochError::TooLate { epoch, deadline });
+        }
+        Ok(())
+    }
+
+    /// Schedule a change to the stake table in `view`, e.g. the view of the decided block that
+    /// carries it, taking effect from the start of `epoch`.
+    ///
+    /// Every node must schedule the same changes in the same order for them to agree on the
+    /// committees. Scheduling a change again in the same view, e.g. when replaying blocks after
+    /// a restart, has no effect. A change that doesn't apply to the stake table when the epoch
+    /// starts, e.g. a validator leaving that never joined, is ignored.
+    ///
+    /// # Errors
+    /// Returns any error of [`Self::check_change`], in which case nothing is scheduled.
+    fn schedule_change(
+        &self,
+        view: TYPES::Time,
+        epoch: EpochNumber,
+        change: StakeTableChange<TYPES::SignatureKey>,
+    ) -> Result<(), EpochError>;
+}
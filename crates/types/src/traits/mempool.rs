@@ -0,0 +1,78 @@
+//! The mempool, holding the transactions a node has received until they are decided
+
+use super::node_implementation::NodeType;
+use crate::MempoolConfig;
+use commit::Commitment;
+use snafu::Snafu;
+use std::{collections::HashSet, time::Instant};
+
+/// Reasons a transaction is not admitted to a [`Mempool`]
+#[derive(Snafu, Debug, Clone, PartialEq, Eq)]
+pub enum MempoolError {
+    /// The transaction is already in the mempool
+    Duplicate,
+    /// The transaction alone is larger than the mempool's byte limit
+    TooLarge {
+        /// Serialized size of the transaction
+        size: u64,
+    },
+    /// The mempool is full and the eviction policy doesn't make room for the transaction
+    Full,
+    /// The node the transaction came through already has as many transactions held as allowed
+    SenderLimit,
+}
+
+/// Holds the transactions a node has received but not yet seen decided, and chooses which of
+/// them go into the next block it proposes
+pub trait Mempool<TYPES: NodeType>: Clone + Send + Sync + 'static {
+    /// Create an empty mempool enforcing the limits of `config`
+    fn create(config: MempoolConfig) -> Self;
+
+    /// Add a transaction received from `sender`.
+    ///
+    /// # Errors
+    /// Returns a [`MempoolError`] if the transaction is not admitted. Admitting it may evict
+    /// other transactions, depending on the implementation.
+    fn insert(
+        &mut self,
+        transaction: TYPES::Transaction,
+        sender: TYPES::SignatureKey,
+        now: Instant,
+    ) -> Result<(), MempoolError>;
+
+    /// Remove a transaction, e.g. because it was included in a decided block
+    fn remove(&mut self, commitment: &Commitment<TYPES::Transaction>)
+        -> Option<TYPES::Transaction>;
+
+    /// Whether the transaction is held
+    fn contains(&self, commitment: &Commitment<TYPES::Transaction>) -> bool;
+
+    /// Drop the transactions that have been held for longer than their time to live, returning
+    /// how many were dropped
+    fn remove_expired(&mut self, now: Instant) -> usize;
+
+    /// Number of transactions held
+    fn len(&self) -> usize;
+
+    /// Whether no transaction is held
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Total serialized size of the transactions held, in bytes
+    fn size_in_bytes(&self) -> u64;
+
+    /// Up to `max_count` transactions with a total serialized size of at most `max_bytes`, in
+    /// the order they should be included in a block. Transactions that don't fit in what is
+    /// left of the byte budget are skipped in favor of later ones that do.
+    ///
+    /// Transactions in `exclude`, e.g. because an undecided block already includes them, are
+    /// left out. The transactions stay in the mempool until they are removed, so the ones left
+    /// out are available for later blocks.
+    fn batch(
+        &self,
+        max_count: usize,
+        max_bytes: u64,
+        exclude: &HashSet<Commitment<TYPES::Transaction>>,
+    ) -> Vec<TYPES::Transaction>;
+}
@@ -2,7 +2,6 @@
 //!
 //! Contains types and traits used by `HotShot` to abstract over network access
 
-use async_compatibility_layer::art::async_sleep;
 #[cfg(async_executor_impl = "async-std")]
 use async_std::future::TimeoutError;
 use hotshot_task::{boxed_sync, BoxSyncFuture};
@@ -11,17 +10,34 @@ use libp2p_networking::network::NetworkNodeHandleError;
 use tokio::time::error::Elapsed as TimeoutError;
 #[cfg(not(any(async_executor_impl = "async-std", async_executor_impl = "tokio")))]
 compile_error! {"Either config option \"async-std\" or \"tokio\" must be enabled for this crate."}
-use super::{election::Membership, node_implementation::NodeType, signature_key::SignatureKey};
-use crate::{data::ViewNumber, message::MessagePurpose};
+use super::{
+    election::Membership,
+    node_implementation::NodeType,
+    signature_key::{EncodedPublicKey, SignatureKey},
+};
+use crate::{
+    clock::{Clock, VirtualClock},
+    data::ViewNumber,
+    message::MessagePurpose,
+};
 use async_compatibility_layer::channel::UnboundedSendError;
 use async_trait::async_trait;
+use commit::Commitment;
 use rand::{
     distributions::{Bernoulli, Uniform},
     prelude::Distribution,
+    rngs::StdRng,
+    Rng, SeedableRng,
 };
 use serde::{Deserialize, Serialize};
 use snafu::Snafu;
-use std::{collections::BTreeSet, fmt::Debug, sync::Arc, time::Duration};
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeSet, HashMap},
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
 
 impl From<NetworkNodeHandleError> for NetworkError {
     fn from(error: NetworkNodeHandleError) -> Self {
@@ -67,6 +83,9 @@ pub enum WebServerNetworkError {
     EndpointError,
     /// Client disconnected
     ClientDisconnected,
+    /// The web server has no route for messages of this purpose. Internal messages never leave
+    /// the node, and catchup messages need a network that delivers direct messages between nodes.
+    UnsupportedPurpose,
 }
 
 /// the type of transmission
@@ -221,6 +240,8 @@ pub trait ViewMessage<TYPES: NodeType> {
     // TODO move out of this trait.
     /// get the purpose of the message
     fn purpose(&self) -> MessagePurpose;
+    /// get the commitment of the transaction the message submits, if it submits one
+    fn transaction_commitment(&self) -> Option<Commitment<TYPES::Transaction>>;
 }
 
 /// API for interacting directly with a consensus committee
@@ -361,6 +382,27 @@ pub trait TestableNetworkingImplementation<TYPES: NodeType, M: NetworkMsg> {
         is_da: bool,
     ) -> Box<dyn Fn(u64) -> Self + 'static>;
 
+    /// like `generator`, for a simulation seeded with `seed` and running on `clock`.
+    /// Networks that can derive the latencies of their messages from the seed deliver them by
+    /// `clock`, the others behave as with `generator`.
+    fn seeded_generator(
+        expected_node_count: usize,
+        num_bootstrap: usize,
+        network_id: usize,
+        da_committee_size: usize,
+        is_da: bool,
+        _seed: u64,
+        _clock: Arc<VirtualClock>,
+    ) -> Box<dyn Fn(u64) -> Self + 'static> {
+        Self::generator(
+            expected_node_count,
+            num_bootstrap,
+            network_id,
+            da_committee_size,
+            is_da,
+        )
+    }
+
     /// Get the number of messages in-flight.
     ///
     /// Some implementations will not be able to tell how many messages there are in-flight. These implementations should return `None`.
@@ -417,6 +459,11 @@ pub trait NetworkReliability: Debug + Sync + std::marker::Send {
         1
     }
 
+    /// the clock delayed packets wait on
+    fn clock(&self) -> Clock {
+        Clock::Real
+    }
+
     /// given a message and a way to send the message,
     /// decide whether or not to send the message
     /// how long to delay the message
@@ -431,6 +478,7 @@ pub trait NetworkReliability: Debug + Sync + std::marker::Send {
         let sample_keep = self.sample_keep();
         let delay = self.sample_delay();
         let repeats = self.sample_repeat();
+        let clock = self.clock();
         let mut msgs = Vec::new();
         for _idx in 0..repeats {
             let scrambled = self.scramble(msg.clone());
@@ -438,7 +486,7 @@ pub trait NetworkReliability: Debug + Sync + std::marker::Send {
         }
         let closure = async move {
             if sample_keep {
-                async_sleep(delay).await;
+                clock.sleep(delay).await;
                 for msg in msgs {
                     send_fn(msg).await;
                 }
@@ -446,6 +494,18 @@ pub trait NetworkReliability: Debug + Sync + std::marker::Send {
         };
         boxed_sync(closure)
     }
+
+    /// like `chaos_send_msg`, for a message sent by the node with key `from`
+    /// to the node with key `to`, for networks whose reliability depends on the link
+    fn chaos_send_link_msg(
+        &self,
+        _from: &EncodedPublicKey,
+        _to: &EncodedPublicKey,
+        msg: Vec<u8>,
+        send_fn: Arc<dyn Send + Sync + 'static + Fn(Vec<u8>) -> BoxSyncFuture<'static, ()>>,
+    ) -> BoxSyncFuture<'static, ()> {
+        self.chaos_send_msg(msg, send_fn)
+    }
 }
 
 /// ideal network
@@ -615,7 +675,265 @@ impl PartiallySynchronousNetwork {
     }
 }
 
-/// A chaotic network using all the networking calls
+/// The probability of dropping a message, as a ratio in `[0, 1]`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DropRatio {
+    /// numerator for probability of dropping messages
+    numerator: u32,
+    /// denominator for probability of dropping messages
+    denominator: u32,
+}
+
+impl DropRatio {
+    /// the probability `numerator` / `denominator`, or `None` if that is not in `[0, 1]`
+    #[must_use]
+    pub fn new(numerator: u32, denominator: u32) -> Option<Self> {
+        (denominator > 0 && numerator <= denominator).then_some(DropRatio {
+            numerator,
+            denominator,
+        })
+    }
+
+    /// decide whether to drop a message
+    fn sample(self, rng: &mut impl Rng) -> bool {
+        // `new` only accepts valid ratios, so this never falls back to keeping the message
+        Bernoulli::from_ratio(self.numerator, self.denominator).is_ok_and(|drop| drop.sample(rng))
+    }
+}
+
+/// A fault on a link between two nodes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkFault {
+    /// drop each message with the given probability
+    Drop(DropRatio),
+    /// delay every message by this long
+    Delay(Duration),
+    /// deliver every message this many times
+    Duplicate(usize),
+    /// delay each message by a random duration up to this long,
+    /// so messages can overtake each other
+    Reorder(Duration),
+    /// flip a random bit of every message
+    Corrupt,
+}
+
+impl LinkFault {
+    /// drop every message
+    #[must_use]
+    pub fn drop_all() -> Self {
+        LinkFault::Drop(DropRatio {
+            numerator: 1,
+            denominator: 1,
+        })
+    }
+
+    /// drop each message with probability `numerator` / `denominator`, or `None` if that is not a
+    /// probability
+    #[must_use]
+    pub fn drop(numerator: u32, denominator: u32) -> Option<Self> {
+        DropRatio::new(numerator, denominator).map(LinkFault::Drop)
+    }
+}
+
+/// A fault on the links from some nodes to others, for some period of time.
+/// Links are directed, so a rule from `a` to `b` leaves messages from `b` to `a` alone.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LinkRule {
+    /// senders the rule applies to, or every sender if `None`
+    pub from: Option<BTreeSet<EncodedPublicKey>>,
+    /// recipients the rule applies to, or every recipient if `None`
+    pub to: Option<BTreeSet<EncodedPublicKey>>,
+    /// what happens to messages on the links
+    pub fault: LinkFault,
+    /// time since the network started when the rule takes effect
+    pub starts_after: Duration,
+    /// how long the rule lasts, or forever if `None`
+    pub lasts: Option<Duration>,
+}
+
+impl LinkRule {
+    /// `fault` on every link from `from` to `to`, from the start of the network on
+    #[must_use]
+    pub fn between<K: SignatureKey>(from: &[K], to: &[K], fault: LinkFault) -> Self {
+        LinkRule {
+            from: Some(from.iter().map(SignatureKey::to_bytes).collect()),
+            to: Some(to.iter().map(SignatureKey::to_bytes).collect()),
+            fault,
+            starts_after: Duration::ZERO,
+            lasts: None,
+        }
+    }
+
+    /// `fault` on every link from `from` to any node, from the start of the network on
+    #[must_use]
+    pub fn sent_by<K: SignatureKey>(from: &[K], fault: LinkFault) -> Self {
+        LinkRule {
+            from: Some(from.iter().map(SignatureKey::to_bytes).collect()),
+            to: None,
+            fault,
+            starts_after: Duration::ZERO,
+            lasts: None,
+        }
+    }
+
+    /// only apply the rule from `starts_after` since the network started, for `lasts`
+    #[must_use]
+    pub fn during(mut self, starts_after: Duration, lasts: Duration) -> Self {
+        self.starts_after = starts_after;
+        self.lasts = Some(lasts);
+        self
+    }
+
+    /// whether the rule applies to a message from `from` to `to`, `elapsed` after the network
+    /// started
+    fn applies(&self, from: &EncodedPublicKey, to: &EncodedPublicKey, elapsed: Duration) -> bool {
+        elapsed >= self.starts_after
+            && self
+                .lasts
+                .map_or(true, |lasts| elapsed < self.starts_after + lasts)
+            && self.from.as_ref().map_or(true, |keys| keys.contains(from))
+            && self.to.as_ref().map_or(true, |keys| keys.contains(to))
+    }
+}
+
+/// A chaotic network, whose faults depend on which link a message is sent over.
+/// Faults are given by `LinkRule`s, which can cover links in one direction only,
+/// and can heal after a while, e.g. to partition the network for some time.
+/// Random choices for a message are derived from a seed, the link and how many messages were sent
+/// over the link before, so a scenario can be replayed with the same seed however the sends of
+/// different nodes interleave. Timing follows the network's [`Clock`].
+#[derive(Debug)]
 pub struct ChaosNetwork {
-    // TODO
+    /// the faults of the network
+    rules: Vec<LinkRule>,
+    /// seed of the random choices of the faults
+    seed: u64,
+    /// number of messages sent over each link so far
+    sent: Mutex<HashMap<(EncodedPublicKey, EncodedPublicKey), u64>>,
+    /// the clock rules are timed by and delayed messages wait on
+    clock: Clock,
+    /// when the network was started
+    start: Instant,
+}
+
+impl ChaosNetwork {
+    /// create a new `ChaosNetwork` without faults on real time, making random choices from `seed`
+    #[must_use]
+    pub fn new(seed: u64) -> Self {
+        ChaosNetwork {
+            rules: Vec::new(),
+            seed,
+            sent: Mutex::default(),
+            clock: Clock::Real,
+            start: Instant::now(),
+        }
+    }
+
+    /// time the rules by `clock` and delay messages on it, starting the network now
+    #[must_use]
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.start = clock.now();
+        self.clock = clock;
+        self
+    }
+
+    /// add a fault to the network
+    #[must_use]
+    pub fn with_rule(mut self, rule: LinkRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// split the network into `groups`, dropping every message between nodes in different groups,
+    /// from `starts_after` since the network started until the partition heals after `lasts`
+    #[must_use]
+    pub fn with_partition<K: SignatureKey>(
+        mut self,
+        groups: &[Vec<K>],
+        starts_after: Duration,
+        lasts: Duration,
+    ) -> Self {
+        for (i, from) in groups.iter().enumerate() {
+            for (j, to) in groups.iter().enumerate() {
+                if i != j {
+                    self.rules.push(
+                        LinkRule::between(from, to, LinkFault::drop_all())
+                            .during(starts_after, lasts),
+                    );
+                }
+            }
+        }
+        self
+    }
+}
+
+impl NetworkReliability for ChaosNetwork {
+    fn clock(&self) -> Clock {
+        self.clock.clone()
+    }
+
+    fn chaos_send_link_msg(
+        &self,
+        from: &EncodedPublicKey,
+        to: &EncodedPublicKey,
+        msg: Vec<u8>,
+        send_fn: Arc<dyn Send + Sync + 'static + Fn(Vec<u8>) -> BoxSyncFuture<'static, ()>>,
+    ) -> BoxSyncFuture<'static, ()> {
+        let elapsed = self.clock.now().saturating_duration_since(self.start);
+        let sent = {
+            let mut sent = self.sent.lock().unwrap_or_else(PoisonError::into_inner);
+            let count = sent.entry((from.clone(), to.clone())).or_default();
+            *count += 1;
+            *count
+        };
+        let mut hasher = DefaultHasher::new();
+        self.seed.hash(&mut hasher);
+        from.hash(&mut hasher);
+        to.hash(&mut hasher);
+        sent.hash(&mut hasher);
+        let mut rng = StdRng::seed_from_u64(hasher.finish());
+        let mut keep = true;
+        let mut delay = Duration::ZERO;
+        let mut repeats = 1;
+        let mut corrupt = false;
+        for rule in self
+            .rules
+            .iter()
+            .filter(|rule| rule.applies(from, to, elapsed))
+        {
+            match rule.fault {
+                LinkFault::Drop(ratio) => keep &= !ratio.sample(&mut rng),
+                LinkFault::Delay(duration) => delay += duration,
+                LinkFault::Duplicate(copies) => repeats = repeats.max(copies),
+                LinkFault::Reorder(max_delay) => {
+                    delay += Duration::from_millis(
+                        Uniform::new_inclusive(0, max_delay.as_millis())
+                            .sample(&mut rng)
+                            .try_into()
+                            .unwrap_or(u64::MAX),
+                    );
+                }
+                LinkFault::Corrupt => corrupt = true,
+            }
+        }
+        let mut msgs = Vec::new();
+        for _idx in 0..repeats {
+            let mut msg = msg.clone();
+            if corrupt && !msg.is_empty() {
+                let bit = rng.gen_range(0..msg.len() * 8);
+                msg[bit / 8] ^= 1 << (bit % 8);
+            }
+            msgs.push(msg);
+        }
+        let clock = self.clock.clone();
+        let closure = async move {
+            if keep {
+                clock.sleep(delay).await;
+                for msg in msgs {
+                    send_fn(msg).await;
+                }
+            }
+        };
+        boxed_sync(closure)
+    }
 }
@@ -9,12 +9,14 @@ use super::{
         CommitteeExchangeType, ConsensusExchange, ElectionConfig, QuorumExchangeType,
         TimeoutExchange, TimeoutExchangeType, VIDExchangeType, ViewSyncExchangeType, VoteToken,
     },
+    mempool::Mempool,
     network::{CommunicationChannel, NetworkMsg, TestableNetworkingImplementation},
     state::{ConsensusTime, TestableBlock, TestableState},
     storage::{StorageError, StorageState, TestableStorage},
     State,
 };
 use crate::{
+    clock::VirtualClock,
     data::{Leaf, LeafType, TestableLeaf},
     message::{ConsensusMessageType, Message, SequencingMessage},
     traits::{
@@ -120,6 +122,9 @@ pub trait NodeImplementation<TYPES: NodeType>:
     /// Storage type for this consensus implementation
     type Storage: Storage<TYPES, Self::Leaf> + Clone;
 
+    /// Mempool type holding the transactions a node has received until they are decided
+    type Mempool: Mempool<TYPES>;
+
     /// Consensus message type.
     type ConsensusMessage: ConsensusMessageType<TYPES, Self>
         + Clone
@@ -210,12 +215,15 @@ pub trait ExchangesType<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>, MESSA
 pub trait TestableExchange<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>, MESSAGE: NetworkMsg>:
     ExchangesType<TYPES, LEAF, MESSAGE>
 {
-    /// generate communication channels
+    /// generate communication channels. In a simulation, given as its seed and virtual clock,
+    /// networks that support it deliver messages by the clock after latencies derived from the
+    /// seed.
     #[allow(clippy::type_complexity)]
     fn gen_comm_channels(
         expected_node_count: usize,
         num_bootstrap: usize,
         da_committee_size: usize,
+        simulation: Option<(u64, Arc<VirtualClock>)>,
     ) -> Box<
         dyn Fn(
                 u64,
@@ -626,6 +634,10 @@ pub type CommitteeMembership<TYPES, I> =
 /// Protocol for determining membership in a view sync committee.
 pub type ViewSyncMembership<TYPES, I> = QuorumMembership<TYPES, I>;
 
+/// Protocol for determining membership in the VID storage committee.
+pub type VIDMembership<TYPES, I> =
+    <VIDEx<TYPES, I> as ConsensusExchange<TYPES, Message<TYPES, I>>>::Membership;
+
 /// Type for the underlying quorum `ConnectedNetwork` that will be shared (for now) b/t Communication Channels
 pub type QuorumNetwork<TYPES, I> = <QuorumCommChannel<TYPES, I> as CommunicationChannel<
     TYPES,
@@ -22,6 +22,12 @@ use tagged_base64::tagged;
 )]
 pub struct EncodedPublicKey(#[debug(with = "custom_debug::hexbuf")] pub Vec<u8>);
 
+/// An entry of a stake table, holding a public key and its stake
+pub trait StakeTableEntryType {
+    /// Get the stake of the entry
+    fn get_stake(&self) -> U256;
+}
+
 /// Type saftey wrapper for byte encoded signature
 #[derive(
     Clone, custom_debug::Debug, Hash, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord,
@@ -52,7 +58,8 @@ pub trait SignatureKey:
     /// The private key type for this signature algorithm
     type PrivateKey: Send + Sync + Sized + Clone;
     /// The type of the entry that contain both public key and stake value
-    type StakeTableEntry: Send
+    type StakeTableEntry: StakeTableEntryType
+        + Send
         + Sync
         + Sized
         + Clone
@@ -6,6 +6,7 @@ use displaydoc::Display;
 use jf_primitives::errors::PrimitivesError;
 
 /// Snapshots of the stake table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SnapshotVersion {
     /// the latest "Head" where all new changes are applied to
     Head,
@@ -74,10 +75,9 @@ pub trait StakeTableScheme {
         I: IntoIterator<Item = &'a <Self as StakeTableScheme>::Key>,
         <Self as StakeTableScheme>::Key: 'a,
     {
-        let _ = existing_keys
+        existing_keys
             .into_iter()
-            .try_for_each(|key| Self::deregister(self, key));
-        Ok(())
+            .try_for_each(|key| Self::deregister(self, key))
     }
 
     /// Returns the commitment to the `version` of stake table.
@@ -193,6 +193,14 @@ pub enum StakeTableError {
     StakeOverflow,
     /// The historical snapshot requested is not supported.
     SnapshotUnsupported,
+    /// Every location of the stake table is held by a key
+    TableFull,
+    /// Failed to serialize or deserialize the stake table
+    SerializationError,
+    /// The stake table was serialized in an unsupported format version
+    UnsupportedFormatVersion,
+    /// Failed to read or write the stake table
+    IoError,
 }
 
 impl ark_std::error::Error for StakeTableError {}
@@ -2,12 +2,15 @@
 
 use super::{node_implementation::NodeType, signature_key::EncodedPublicKey};
 use crate::{
-    data::LeafType, simple_certificate::QuorumCertificate2, traits::BlockPayload,
+    data::LeafType,
+    simple_certificate::QuorumCertificate2,
+    traits::{block_contents::BlockHeader, BlockPayload},
     vote2::HasViewNumber,
 };
 use async_trait::async_trait;
-use commit::Commitment;
+use commit::{Commitment, Committable};
 use derivative::Derivative;
+use serde::{Deserialize, Serialize};
 use snafu::Snafu;
 use std::collections::{BTreeMap, BTreeSet};
 /// Errors that can occur in the storage layer.
@@ -16,6 +19,19 @@ use std::collections::{BTreeMap, BTreeSet};
 pub enum StorageError {
     /// No genesis view was inserted
     NoGenesisView,
+    /// The underlying persistent store failed
+    #[snafu(display("Persistent storage failure: {description}"))]
+    Persistence {
+        /// Description of the failure
+        description: String,
+    },
+    /// A stored view can not be turned into a leaf because it was stored without its block
+    /// payload
+    #[snafu(display("The view {view_number} was stored without its block payload"))]
+    MissingBlockPayload {
+        /// The view number of the stored view
+        view_number: u64,
+    },
 }
 
 /// Result for a storage type
@@ -27,6 +43,12 @@ pub type Result<T = ()> = std::result::Result<T, StorageError>;
 /// underlying storage.
 ///
 /// This trait has been constructed for object saftey over convenience.
+///
+/// Consensus appends every decided view to the storage, and then prunes the views older than
+/// [`HotShotConfig::decided_retention_views`](crate::HotShotConfig::decided_retention_views)
+/// with [`cleanup_storage_up_to_view`](Self::cleanup_storage_up_to_view). The query methods
+/// below serve the decided history that is kept, which is all of it if no retention is
+/// configured.
 #[async_trait]
 pub trait Storage<TYPES, LEAF>: Clone + Send + Sync + Sized + 'static
 where
@@ -42,6 +64,39 @@ where
     /// Commit this storage.
     async fn commit(&self) -> Result;
 
+    /// Replace the persisted [`UndecidedState`] with `state`.
+    ///
+    /// Unlike [`append`](Self::append), this overwrites whatever undecided state was stored
+    /// before, since leaves that were undecided may since have been decided or abandoned.
+    async fn update_undecided_state(&self, state: UndecidedState<TYPES, LEAF>) -> Result;
+    /// Get the most recently persisted [`UndecidedState`], if any.
+    async fn get_undecided_state(&self) -> Result<Option<UndecidedState<TYPES, LEAF>>>;
+
+    /// Get the view with the given view number, if it is stored.
+    async fn get_view(&self, view: TYPES::Time) -> Result<Option<StoredView<TYPES, LEAF>>>;
+    /// Get the view whose leaf has the given commitment, if it is stored.
+    async fn get_leaf_by_commitment(
+        &self,
+        commitment: Commitment<LEAF>,
+    ) -> Result<Option<StoredView<TYPES, LEAF>>>;
+    /// Get the view whose block is at the given height, if it is stored.
+    async fn get_by_block_height(&self, height: u64) -> Result<Option<StoredView<TYPES, LEAF>>>;
+    /// Get all stored views with a view number in `from..=to`, in ascending view order.
+    async fn get_views_in_range(
+        &self,
+        from: TYPES::Time,
+        to: TYPES::Time,
+    ) -> Result<Vec<StoredView<TYPES, LEAF>>>;
+    /// Get the view whose block payload contains the transaction with the given commitment, if
+    /// it is stored.
+    ///
+    /// Views stored without a block payload can't be searched, see
+    /// [`StoredView::contains_transaction`].
+    async fn get_view_by_transaction(
+        &self,
+        commitment: Commitment<TYPES::Transaction>,
+    ) -> Result<Option<StoredView<TYPES, LEAF>>>;
+
     /// Insert a single view. Shorthand for
     /// ```rust,ignore
     /// storage.append(vec![ViewEntry::Success(view)]).await
@@ -52,8 +107,6 @@ where
     // future improvement:
     // async fn get_future_views(&self) -> Vec<FutureView>;
     //     async fn add_transaction(&self, transactions: Transaction) -> TransactionHash;
-    //     async fn get_transactions(&self) -> Vec<Transaction>;
-    //     async fn get_transaction(&self, hash: TransactionHash) -> Option<Transaction>;
     //     async fn remove_transaction(&self, hash: TransactionHash) -> Option<Transaction>;
 }
 
@@ -121,8 +174,9 @@ where
 }
 
 /// A view stored in the [`Storage`]
-#[derive(Clone, Debug, Derivative)]
+#[derive(Clone, Debug, Derivative, Serialize, Deserialize)]
 #[derivative(PartialEq)]
+#[serde(bound(deserialize = ""))]
 pub struct StoredView<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>> {
     /// The view number of this view
     pub view_number: TYPES::Time,
@@ -148,6 +202,21 @@ pub struct StoredView<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>> {
     pub proposer_id: EncodedPublicKey,
 }
 
+/// Consensus state that has not been decided yet.
+///
+/// This is persisted alongside the decided views so that a restarted node can rebuild its
+/// `Consensus` from [`Storage`] instead of starting over from the anchored view alone.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(bound(deserialize = ""))]
+pub struct UndecidedState<TYPES: NodeType, LEAF: LeafType<NodeType = TYPES>> {
+    /// The leaves proposed after the last decided view, in ascending view order
+    pub leaves: Vec<StoredView<TYPES, LEAF>>,
+    /// The highest QC this node has seen
+    pub high_qc: QuorumCertificate2<TYPES, LEAF>,
+    /// The view of the leaf this node is locked on
+    pub locked_view: TYPES::Time,
+}
+
 impl<TYPES, LEAF> StoredView<TYPES, LEAF>
 where
     TYPES: NodeType,
@@ -178,4 +247,27 @@ where
             proposer_id,
         }
     }
+
+    /// The height of the block in this view.
+    pub fn block_height(&self) -> u64 {
+        self.block_header.block_number()
+    }
+
+    /// The commitment of the leaf this view was built from.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the leaf can not be rebuilt from this view, see [`LeafType::from_stored_view`].
+    pub fn leaf_commitment(&self) -> Result<Commitment<LEAF>> {
+        Ok(LEAF::from_stored_view(self.clone())?.commit())
+    }
+
+    /// Whether the block payload of this view contains the transaction with the given commitment.
+    ///
+    /// Always `false` if this view was stored without its block payload.
+    pub fn contains_transaction(&self, commitment: &Commitment<TYPES::Transaction>) -> bool {
+        self.block_payload
+            .as_ref()
+            .is_some_and(|payload| payload.transaction_commitments().contains(commitment))
+    }
 }
@@ -18,7 +18,7 @@ use crate::{
     traits::{
         election::Membership,
         node_implementation::NodeType,
-        signature_key::{EncodedPublicKey, EncodedSignature, SignatureKey},
+        signature_key::{EncodedPublicKey, EncodedSignature, SignatureKey, StakeTableEntryType},
     },
 };
 
@@ -66,9 +66,12 @@ pub trait Certificate2<TYPES: NodeType>: HasViewNumber<TYPES> {
 
     /// Checks if the cert is valid
     fn is_valid_cert<MEMBERSHIP: Membership<TYPES>>(&self, membership: &MEMBERSHIP) -> bool;
-    /// Returns the amount of stake needed to create this certificate
-    // TODO: Make this a static ratio of the total stake of `Membership`
-    fn threshold<MEMBERSHIP: Membership<TYPES>>(membership: &MEMBERSHIP) -> u64;
+    /// Returns the amount of stake needed to create this certificate for view `view_number`, as a
+    /// fraction of the total stake of the committee of `membership` for that view
+    fn threshold<MEMBERSHIP: Membership<TYPES>>(
+        membership: &MEMBERSHIP,
+        view_number: TYPES::Time,
+    ) -> U256;
     /// Get the commitment which was voted on
     fn get_data(&self) -> &Self::Voteable;
     /// Get the vote commitment which the votes commit to
@@ -100,8 +103,8 @@ impl<
     /// Add a vote to the total accumulated votes.  Returns the accumulator or the certificate if we
     /// have accumulated enough votes to exceed the threshold for creating a certificate.
     ///
-    /// # Panics
-    /// Panics if the vote comes from a node not in the stake table
+    /// Each vote counts for the stake of its signer. Votes from nodes not in the stake table of
+    /// `membership` are rejected.
     pub fn accumulate(mut self, vote: &VOTE, membership: &VOTE::Membership) -> Either<Self, CERT> {
         let key = vote.get_signing_key();
 
@@ -111,13 +114,18 @@ impl<
             return Either::Left(self);
         }
 
-        // TODO: Lookup the actual stake
-        let stake_table_entry: <<TYPES as NodeType>::SignatureKey as SignatureKey>::StakeTableEntry = key.get_stake_table_entry(1u64);
-        let stake_table = membership.get_committee_qc_stake_table();
-        let vote_node_id = stake_table
+        let stake_table = membership.get_committee_qc_stake_table(vote.get_view_number());
+        let Some(vote_node_id) = stake_table
             .iter()
-            .position(|x| *x == stake_table_entry.clone())
-            .unwrap();
+            .position(|entry| TYPES::SignatureKey::get_public_key(entry) == key)
+        else {
+            error!(
+                "Vote from a node not in the stake table! Vote Data {:?}",
+                vote.get_data()
+            );
+            return Either::Left(self);
+        };
+        let vote_stake = stake_table[vote_node_id].get_stake();
 
         let encoded_key = key.to_bytes();
 
@@ -131,7 +139,7 @@ impl<
         let (total_stake_casted, total_vote_map) = self
             .vote_outcomes
             .entry(vote_commitment)
-            .or_insert_with(|| (0, BTreeMap::new()));
+            .or_insert_with(|| (U256::zero(), BTreeMap::new()));
 
         // Check for duplicate vote
         // TODO ED Re-encoding signature key to bytes until we get rid of EncodedKey
@@ -147,19 +155,19 @@ impl<
         self.signers.set(vote_node_id, true);
         self.sig_lists.push(original_signature);
 
-        // TODO: Get the stake from the stake table entry.
-        *total_stake_casted += 1;
+        *total_stake_casted = total_stake_casted.saturating_add(vote_stake);
         total_vote_map.insert(
             encoded_key.clone(),
             (vote.get_signature(), vote.get_data_commitment()),
         );
 
-        if *total_stake_casted >= CERT::threshold(membership) {
+        let threshold = CERT::threshold(membership, vote.get_view_number());
+        if *total_stake_casted >= threshold {
             // Assemble QC
             let real_qc_pp: <<TYPES as NodeType>::SignatureKey as SignatureKey>::QCParams =
                 <TYPES::SignatureKey as SignatureKey>::get_public_parameter(
                     stake_table.clone(),
-                    U256::from(CERT::threshold(membership)),
+                    threshold,
                 );
 
             let real_qc_sig = <TYPES::SignatureKey as SignatureKey>::assemble(
@@ -184,7 +192,7 @@ impl<
 type VoteMap2<COMMITMENT> = HashMap<
     COMMITMENT,
     (
-        u64,
+        U256,
         BTreeMap<EncodedPublicKey, (EncodedSignature, COMMITMENT)>,
     ),
 >;
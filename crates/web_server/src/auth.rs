@@ -0,0 +1,316 @@
+//! Authentication and rate limiting of submissions to the web server
+//!
+//! Every `post*` route takes a [`SignedSubmission`]: the data to post, signed by the key of the
+//! node posting it together with the route, the view and a nonce. The server only accepts
+//! submissions signed by keys in its stake table, for the route and view they are posted to, and
+//! never accepts the same nonce from a key twice, so captured submissions can't be replayed. It
+//! only accepts proposals from the leader of their view, as given by a [`LeaderSchedule`], and
+//! caps how many submissions each key can make to each route within a time window.
+
+use crate::config::{
+    DEFAULT_MAX_SUBMISSION_AGE, DEFAULT_RATE_LIMIT_REQUESTS, DEFAULT_RATE_LIMIT_WINDOW,
+};
+use hotshot_types::traits::{
+    election::Membership,
+    node_implementation::NodeType,
+    signature_key::{EncodedPublicKey, EncodedSignature, SignatureKey},
+    state::ConsensusTime,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Debug,
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// Data posted to the web server, signed by the node posting it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedSubmission {
+    /// Key of the node posting the data
+    pub key: EncodedPublicKey,
+    /// Signature of the route, view, nonce and payload by `key`
+    pub signature: EncodedSignature,
+    /// Name of the route the submission is for
+    pub route: String,
+    /// View the submission is for, for routes that take one
+    pub view_number: Option<u64>,
+    /// Microseconds since the Unix epoch when the submission was signed, never reused by `key`
+    pub nonce: u64,
+    /// The data to post, stored by the server as-is
+    pub payload: Vec<u8>,
+}
+
+impl SignedSubmission {
+    /// Sign `payload` for `route` and `view_number` with `private_key`, the private key of `key`
+    pub fn new<KEY: SignatureKey>(
+        key: &KEY,
+        private_key: &KEY::PrivateKey,
+        route: &str,
+        view_number: Option<u64>,
+        nonce: u64,
+        payload: Vec<u8>,
+    ) -> Self {
+        let signature = KEY::sign(
+            private_key,
+            &signed_data(route, view_number, nonce, &payload),
+        );
+        Self {
+            key: key.to_bytes(),
+            signature,
+            route: route.to_string(),
+            view_number,
+            nonce,
+            payload,
+        }
+    }
+
+    /// The key that signed the submission, if the signature is valid
+    pub fn signer<KEY: SignatureKey>(&self) -> Option<KEY> {
+        let data = signed_data(&self.route, self.view_number, self.nonce, &self.payload);
+        KEY::from_bytes(&self.key).filter(|key| key.validate(&self.signature, &data))
+    }
+}
+
+/// The data a [`SignedSubmission`] signs, with every field length-prefixed or of fixed size so
+/// that no two submissions sign the same data
+fn signed_data(route: &str, view_number: Option<u64>, nonce: u64, payload: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(route.len() + payload.len() + 25);
+    data.extend_from_slice(&(route.len() as u64).to_le_bytes());
+    data.extend_from_slice(route.as_bytes());
+    match view_number {
+        Some(view_number) => {
+            data.push(1);
+            data.extend_from_slice(&view_number.to_le_bytes());
+        }
+        None => data.push(0),
+    }
+    data.extend_from_slice(&nonce.to_le_bytes());
+    data.extend_from_slice(payload);
+    data
+}
+
+/// Microseconds since the Unix epoch, the unit of [`SignedSubmission::nonce`]
+pub fn unix_micros() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_micros() as u64)
+}
+
+/// Nonces for the submissions of one key: the current time, but always greater than the last
+/// one, so that submissions signed within the same microsecond don't share a nonce
+#[derive(Debug, Default)]
+pub struct Nonces {
+    /// The last nonce handed out
+    last: AtomicU64,
+}
+
+impl Nonces {
+    /// The nonce for the next submission
+    pub fn next(&self) -> u64 {
+        let now = unix_micros();
+        let previous = self
+            .last
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |last| {
+                Some(now.max(last + 1))
+            })
+            .unwrap_or_default();
+        now.max(previous + 1)
+    }
+}
+
+/// Who leads each view, and so may post its proposal
+pub trait LeaderSchedule<KEY>: Debug + Send + Sync {
+    /// The leader of view `view_number`
+    fn leader(&self, view_number: u64) -> KEY;
+}
+
+/// The leaders of a [`Membership`], so the web server checks proposals against the same
+/// schedule as the nodes, whether it rotates through the nodes, weighs them by stake or changes
+/// with the epoch
+#[derive(Debug)]
+pub struct MembershipLeaders<TYPES: NodeType, MEMBERSHIP: Membership<TYPES>> {
+    /// The membership of the nodes posting to the web server
+    membership: MEMBERSHIP,
+    /// Phantom for the node types
+    _types: PhantomData<TYPES>,
+}
+
+impl<TYPES: NodeType, MEMBERSHIP: Membership<TYPES>> MembershipLeaders<TYPES, MEMBERSHIP> {
+    /// The leaders of `membership`
+    pub fn new(membership: MEMBERSHIP) -> Self {
+        Self {
+            membership,
+            _types: PhantomData,
+        }
+    }
+}
+
+impl<TYPES: NodeType, MEMBERSHIP: Membership<TYPES>> LeaderSchedule<TYPES::SignatureKey>
+    for MembershipLeaders<TYPES, MEMBERSHIP>
+{
+    fn leader(&self, view_number: u64) -> TYPES::SignatureKey {
+        self.membership.get_leader(TYPES::Time::new(view_number))
+    }
+}
+
+/// At most `max_requests` submissions every `window`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+    /// How many submissions are allowed in each window
+    pub max_requests: u64,
+    /// Length of the window
+    pub window: Duration,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            max_requests: DEFAULT_RATE_LIMIT_REQUESTS,
+            window: DEFAULT_RATE_LIMIT_WINDOW,
+        }
+    }
+}
+
+/// Who may submit to the web server, and how often
+#[derive(Clone, Debug)]
+pub struct AuthConfig<KEY> {
+    /// Keys allowed to submit
+    pub stake_table: Vec<KEY>,
+    /// Whether keys can add themselves to the stake table through `poststaketable`. Otherwise
+    /// only keys already in the stake table can add others. Anyone can then submit, so this is
+    /// only for local testing.
+    pub open_registration: bool,
+    /// Leaders that proposals must come from, or `None` to accept proposals from any key
+    pub leaders: Option<Arc<dyn LeaderSchedule<KEY>>>,
+    /// How far the nonce of a submission may be from the time it is received
+    pub max_submission_age: Duration,
+    /// Limits for routes, by route name, overriding `default_rate_limit`
+    pub rate_limits: HashMap<String, RateLimit>,
+    /// Limit for routes without one in `rate_limits`
+    pub default_rate_limit: RateLimit,
+}
+
+impl<KEY> Default for AuthConfig<KEY> {
+    /// Nobody may submit, until keys are added to the stake table
+    fn default() -> Self {
+        Self {
+            stake_table: Vec::new(),
+            open_registration: false,
+            leaders: None,
+            max_submission_age: DEFAULT_MAX_SUBMISSION_AGE,
+            rate_limits: HashMap::new(),
+            default_rate_limit: RateLimit::default(),
+        }
+    }
+}
+
+impl<KEY> AuthConfig<KEY> {
+    /// Only accept submissions from `stake_table`
+    pub fn new(stake_table: Vec<KEY>) -> Self {
+        Self {
+            stake_table,
+            ..Self::default()
+        }
+    }
+
+    /// Let keys add themselves to the stake table, so that anyone can submit
+    pub fn open() -> Self {
+        Self {
+            open_registration: true,
+            ..Self::default()
+        }
+    }
+
+    /// Only accept proposals from the leader of their view in `leaders`
+    #[must_use]
+    pub fn with_leaders(mut self, leaders: Arc<dyn LeaderSchedule<KEY>>) -> Self {
+        self.leaders = Some(leaders);
+        self
+    }
+
+    /// Limit submissions to the route named `route`
+    #[must_use]
+    pub fn with_rate_limit(mut self, route: &str, limit: RateLimit) -> Self {
+        self.rate_limits.insert(route.to_string(), limit);
+        self
+    }
+
+    /// The limit of submissions to the route named `route`
+    pub fn rate_limit(&self, route: &str) -> RateLimit {
+        self.rate_limits
+            .get(route)
+            .copied()
+            .unwrap_or(self.default_rate_limit)
+    }
+}
+
+/// Remembers the nonces each key used within the last `max_age`, rejecting them when they come
+/// again. Older nonces are rejected as stale, so they don't need to be remembered.
+#[derive(Debug)]
+pub(crate) struct ReplayGuard<KEY> {
+    /// key -> nonces it used within the last `max_age`
+    seen: HashMap<KEY, BTreeSet<u64>>,
+    /// How far a nonce may be from the current time
+    max_age: Duration,
+}
+
+impl<KEY: SignatureKey> ReplayGuard<KEY> {
+    pub(crate) fn new(max_age: Duration) -> Self {
+        Self {
+            seen: HashMap::new(),
+            max_age,
+        }
+    }
+
+    /// Whether `nonce` is within `max_age` of `now`, both in microseconds since the Unix epoch
+    pub(crate) fn is_fresh(&self, nonce: u64, now: u64) -> bool {
+        now.abs_diff(nonce) <= self.max_age.as_micros() as u64
+    }
+
+    /// Record that `key` used `nonce`, returning whether it hadn't before. Forgets the nonces of
+    /// `key` that are no longer fresh at `now`.
+    pub(crate) fn record(&mut self, key: &KEY, nonce: u64, now: u64) -> bool {
+        let oldest = now.saturating_sub(self.max_age.as_micros() as u64);
+        let seen = self.seen.entry(key.clone()).or_default();
+        *seen = seen.split_off(&oldest);
+        seen.insert(nonce)
+    }
+}
+
+/// Counts submissions of each key to each route in fixed windows
+#[derive(Debug)]
+pub(crate) struct RateLimiter<KEY> {
+    /// (key, route) -> (start of the current window, submissions in it)
+    windows: HashMap<(KEY, String), (Instant, u64)>,
+}
+
+impl<KEY: SignatureKey> RateLimiter<KEY> {
+    pub(crate) fn new() -> Self {
+        Self {
+            windows: HashMap::new(),
+        }
+    }
+
+    /// Count a submission by `key` to `route`, returning whether it is within `limit`
+    pub(crate) fn check(&mut self, key: &KEY, route: &str, limit: RateLimit) -> bool {
+        let now = Instant::now();
+        let (start, count) = self
+            .windows
+            .entry((key.clone(), route.to_string()))
+            .or_insert((now, 0));
+        if now.duration_since(*start) >= limit.window {
+            *start = now;
+            *count = 0;
+        }
+        if *count >= limit.max_requests {
+            return false;
+        }
+        *count += 1;
+        true
+    }
+}
@@ -1,3 +1,6 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
 pub const DEFAULT_WEB_SERVER_PORT: u16 = 9000;
 pub const DEFAULT_WEB_SERVER_DA_PORT: u16 = 9001;
 pub const DEFAULT_WEB_SERVER_VIEW_SYNC_PORT: u16 = 9002;
@@ -8,6 +11,18 @@ pub const MAX_VIEWS: usize = 25;
 pub const MAX_TXNS: usize = 500;
 /// How many transactions to return at once
 pub const TX_BATCH_SIZE: u64 = 1;
+/// How many bytes of messages and transactions to keep
+pub const MAX_STORED_BYTES: usize = 1 << 30;
+/// Longest a GET can wait for there to be something to return
+pub const MAX_LONG_POLL_WAIT: Duration = Duration::from_secs(30);
+/// How long `WebServerNetwork` asks GETs to wait for there to be something to return
+pub const LONG_POLL_WAIT: Duration = Duration::from_secs(1);
+/// How many submissions each key can make to a route in every rate limit window, by default
+pub const DEFAULT_RATE_LIMIT_REQUESTS: u64 = 1000;
+/// Length of the rate limit window, by default
+pub const DEFAULT_RATE_LIMIT_WINDOW: Duration = Duration::from_secs(1);
+/// How far the nonce of a submission may be from the time it is received, by default
+pub const DEFAULT_MAX_SUBMISSION_AGE: Duration = Duration::from_secs(60);
 
 pub fn get_proposal_route(view_number: u64) -> String {
     format!("api/proposal/{view_number}")
@@ -69,6 +84,16 @@ pub fn post_transactions_route() -> String {
     "api/transactions".to_string()
 }
 
+/// `route`, waiting up to `wait` for there to be something to return. Not available for
+/// [`get_recent_proposal_route`].
+pub fn long_poll_route(route: &str, wait: Duration) -> String {
+    format!("{route}/wait/{}", wait.as_millis())
+}
+
+pub fn get_transaction_route(commitment: &str) -> String {
+    format!("api/transaction/{commitment}")
+}
+
 pub fn post_staketable_route() -> String {
     "api/staketable".to_string()
 }
@@ -88,3 +113,66 @@ pub fn post_view_sync_vote_route(view_number: u64) -> String {
 pub fn get_view_sync_vote_route(view_number: u64, index: u64) -> String {
     format!("api/view_sync_vote/{view_number}/{index}")
 }
+
+/// Classes of messages that can each go to web servers of their own
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum MessageClass {
+    /// Quorum proposals
+    Proposal,
+    /// Quorum votes
+    Vote,
+    /// View sync votes and certificates
+    ViewSync,
+    /// VID disperse data, votes and certificates
+    Vid,
+    /// DA proposals, votes and certificates
+    Da,
+    /// Transactions
+    Transaction,
+}
+
+impl MessageClass {
+    /// Every class of messages
+    pub const ALL: [MessageClass; 6] = [
+        MessageClass::Proposal,
+        MessageClass::Vote,
+        MessageClass::ViewSync,
+        MessageClass::Vid,
+        MessageClass::Da,
+        MessageClass::Transaction,
+    ];
+}
+
+/// URLs of the web servers for each class of messages. A class with several is sent to all of
+/// them and read from whichever last responded, failing over to the next when it doesn't. A
+/// class with none goes to the default web servers.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RelayUrls {
+    /// Web servers for quorum proposals
+    pub proposal: Vec<String>,
+    /// Web servers for quorum votes
+    pub vote: Vec<String>,
+    /// Web servers for view sync messages
+    pub view_sync: Vec<String>,
+    /// Web servers for VID messages
+    pub vid: Vec<String>,
+    /// Web servers for DA messages
+    pub da: Vec<String>,
+    /// Web servers for transactions
+    pub transaction: Vec<String>,
+}
+
+impl RelayUrls {
+    /// The URLs of the web servers for `class`
+    pub fn get(&self, class: MessageClass) -> &[String] {
+        match class {
+            MessageClass::Proposal => &self.proposal,
+            MessageClass::Vote => &self.vote,
+            MessageClass::ViewSync => &self.view_sync,
+            MessageClass::Vid => &self.vid,
+            MessageClass::Da => &self.da,
+            MessageClass::Transaction => &self.transaction,
+        }
+    }
+}
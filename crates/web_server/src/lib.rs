@@ -1,121 +1,308 @@
+pub mod auth;
 pub mod config;
+pub mod storage;
 
-use crate::config::{MAX_TXNS, MAX_VIEWS, TX_BATCH_SIZE};
-use async_compatibility_layer::channel::OneShotReceiver;
+use crate::{
+    auth::{unix_micros, AuthConfig, RateLimiter, ReplayGuard, SignedSubmission},
+    config::{MAX_LONG_POLL_WAIT, TX_BATCH_SIZE},
+    storage::{MessageKind, Retention, StorageConfig, WebServerStorage},
+};
+use async_compatibility_layer::{art::async_timeout, channel::OneShotReceiver};
 use async_lock::RwLock;
 use clap::Args;
+use event_listener::{Event, EventListener};
 use futures::FutureExt;
 use tracing::error;
 
 use hotshot_types::traits::signature_key::{EncodedPublicKey, SignatureKey};
 use rand::{distributions::Alphanumeric, rngs::StdRng, thread_rng, Rng, SeedableRng};
-use std::{collections::HashMap, io, path::PathBuf};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 use tide_disco::{
     api::ApiError,
     error::ServerError,
     method::{ReadState, WriteState},
-    Api, App, StatusCode,
+    Api, App, RequestParams, StatusCode,
 };
 use tracing::{debug, info};
 
 type State<KEY> = RwLock<WebServerState<KEY>>;
 type Error = ServerError;
 
+/// What is posted to `posttransaction`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PostedTransaction {
+    /// The commitment of the transaction, as displayed, which it can be looked up by
+    pub commitment: String,
+    /// The message submitting the transaction, returned by `gettransactions`
+    pub transaction: Vec<u8>,
+}
+
+/// A transaction looked up by its commitment
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransactionStatus {
+    /// The transaction is waiting to be completed. Holds the message submitting it.
+    Pending(Vec<u8>),
+    /// The transaction has been marked completed through `postcompletedtransaction`, and is no
+    /// longer kept
+    Completed,
+}
+
+/// Routes only the leader of the view can post to
+const LEADER_ROUTES: [&str; 3] = ["postproposal", "postviddisperse", "secret"];
+
 /// State that tracks proposals and votes the server receives
 /// Data is stored as a `Vec<u8>` to not incur overhead from deserializing
 struct WebServerState<KEY> {
-    /// view number -> (secret, proposal)
-    proposals: HashMap<u64, (String, Vec<u8>)>,
-
-    view_sync_proposals: HashMap<u64, Vec<(u64, Vec<u8>)>>,
-
-    view_sync_proposal_index: HashMap<u64, u64>,
-    /// view number -> (secret, da_certificates)
-    da_certificates: HashMap<u64, (String, Vec<u8>)>,
-    /// view for oldest proposals in memory
-    oldest_proposal: u64,
+    /// proposals, votes, certificates and transactions
+    storage: Box<dyn WebServerStorage>,
+    /// how many views, transactions and bytes to keep in `storage`
+    retention: Retention,
+    /// view number -> secret for the leader's proposal endpoint
+    secrets: HashMap<u64, String>,
     /// view for the most recent proposal to help nodes catchup
     recent_proposal: u64,
-    /// view for teh oldest DA certificate
-    oldest_certificate: u64,
-
-    oldest_view_sync_proposal: u64,
-    /// view number -> Vec(index, vote)
-    votes: HashMap<u64, Vec<(u64, Vec<u8>)>>,
-
-    view_sync_votes: HashMap<u64, Vec<(u64, Vec<u8>)>>,
-    /// view number -> highest vote index for that view number
-    vote_index: HashMap<u64, u64>,
-
-    view_sync_vote_index: HashMap<u64, u64>,
-    /// view number of oldest votes in memory
-    oldest_vote: u64,
-
-    oldest_view_sync_vote: u64,
-
-    vid_disperses: HashMap<u64, (String, Vec<u8>)>,
-    oldest_vid_disperse: u64,
-    recent_vid_disperse: u64,
-
-    vid_votes: HashMap<u64, Vec<(u64, Vec<u8>)>>,
-    oldest_vid_vote: u64,
-    // recent_vid_vote: u64,
-    vid_certificates: HashMap<u64, (String, Vec<u8>)>,
-    oldest_vid_certificate: u64,
-    // recent_vid_certificate: u64,
-    vid_vote_index: HashMap<u64, u64>,
-
-    /// index -> transaction
-    // TODO ED Make indexable by hash of tx
-    transactions: HashMap<u64, Vec<u8>>,
-    txn_lookup: HashMap<Vec<u8>, u64>,
+
+    /// commitment of transaction -> index, for pending and completed transactions
+    txn_lookup: HashMap<String, u64>,
+    /// index -> commitment of the transactions marked completed, which are no longer in
+    /// `storage`
+    completed_txns: BTreeMap<u64, String>,
     /// highest transaction index
     num_txns: u64,
 
     /// shutdown signal
     shutdown: Option<OneShotReceiver<()>>,
-    /// stake table with leader keys
-    stake_table: Vec<KEY>,
+    /// notified whenever something is posted, waking the GETs waiting for it
+    posted: Event,
+    /// stake table with leader keys, and who may submit how often
+    auth: AuthConfig<KEY>,
+    /// submissions of each key to each route in the current rate limit window
+    rate_limiter: RateLimiter<KEY>,
+    /// nonces each key used recently, so submissions can't be replayed
+    replay_guard: ReplayGuard<KEY>,
     /// prng for generating endpoint
     _prng: StdRng,
 }
 
 impl<KEY: SignatureKey + 'static> WebServerState<KEY> {
-    fn new() -> Self {
-        Self {
-            proposals: HashMap::new(),
-            da_certificates: HashMap::new(),
-            votes: HashMap::new(),
-            num_txns: 0,
-            oldest_vote: 0,
-            oldest_proposal: 0,
-            recent_proposal: 0,
-            oldest_certificate: 0,
+    /// Create the state over `storage`, serving anything already in it and accepting the keys
+    /// that registered before along with those of `auth`
+    ///
+    /// # Errors
+    /// Returns an error if the keys of `auth` can't be added to `storage`
+    fn new(
+        mut auth: AuthConfig<KEY>,
+        storage: Box<dyn WebServerStorage>,
+        retention: Retention,
+    ) -> io::Result<Self> {
+        let configured_keys = std::mem::take(&mut auth.stake_table);
+        for key in storage.keys() {
+            match KEY::from_bytes(&EncodedPublicKey(key)) {
+                Some(key) => auth.stake_table.push(key),
+                None => error!("Ignoring a stored key that isn't a signature key"),
+            }
+        }
+        let recent_proposal = storage.newest_view(MessageKind::Proposal).unwrap_or(0);
+        let txn_indices = storage.transaction_indices();
+        let completed_txns = storage.completed();
+        let num_txns = txn_indices
+            .last()
+            .into_iter()
+            .chain(completed_txns.keys().next_back())
+            .max()
+            .map_or(0, |index| index + 1);
+        let txn_lookup = txn_indices
+            .into_iter()
+            .filter_map(|index| {
+                let txn = posted_transaction(&storage.transaction(index)?)?;
+                Some((txn.commitment, index))
+            })
+            .chain(
+                completed_txns
+                    .iter()
+                    .map(|(index, commitment)| (commitment.clone(), *index)),
+            )
+            .collect();
+        let mut state = Self {
+            secrets: storage.secrets(),
+            storage,
+            retention,
+            recent_proposal,
+            txn_lookup,
+            completed_txns,
+            num_txns,
             shutdown: None,
-            stake_table: Vec::new(),
-            vote_index: HashMap::new(),
-            transactions: HashMap::new(),
-            txn_lookup: HashMap::new(),
+            posted: Event::new(),
+            replay_guard: ReplayGuard::new(auth.max_submission_age),
+            auth,
+            rate_limiter: RateLimiter::new(),
             _prng: StdRng::from_entropy(),
-            view_sync_proposals: HashMap::new(),
-            view_sync_votes: HashMap::new(),
-            view_sync_vote_index: HashMap::new(),
+        };
+        for key in configured_keys {
+            if !state.auth.stake_table.contains(&key) {
+                state.add_to_stake_table(key)?;
+            }
+        }
+        Ok(state)
+    }
 
-            vid_disperses: HashMap::new(),
-            oldest_vid_disperse: 0,
-            recent_vid_disperse: 0,
+    /// Add `key` to the stake table, with a secret for its first submission as leader
+    ///
+    /// # Errors
+    /// Returns an error if the key or the secret can't be stored
+    fn add_to_stake_table(&mut self, key: KEY) -> io::Result<()> {
+        let node_index = self.auth.stake_table.len() as u64;
+        //generate secret for leader's first submission endpoint when key is added
+        let secret: String = thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(30)
+            .map(char::from)
+            .collect();
+        self.storage.add_key(key.to_bytes().0)?;
+        self.storage.insert_secret(node_index, secret.clone())?;
+        self.secrets.insert(node_index, secret);
+        self.auth.stake_table.push(key);
+        Ok(())
+    }
 
-            vid_votes: HashMap::new(),
-            oldest_vid_vote: 0,
-            // recent_vid_vote: 0,
-            vid_certificates: HashMap::new(),
-            oldest_vid_certificate: 0,
-            // recent_vid_certificate: 0,
-            vid_vote_index: HashMap::new(),
+    /// Check the signature of `submission`, that it was signed for `route` and `view_number`
+    /// and not submitted before, and that its signer may post to `route`, returning the signer
+    /// and the payload
+    fn check_submission(
+        &mut self,
+        route: &str,
+        view_number: Option<u64>,
+        submission: &[u8],
+    ) -> Result<(KEY, Vec<u8>), Error> {
+        let submission: SignedSubmission =
+            bincode::deserialize(submission).map_err(|_| ServerError {
+                status: StatusCode::BadRequest,
+                message: "Submissions must be signed".to_string(),
+            })?;
+        let Some(signer) = submission.signer::<KEY>() else {
+            return Err(ServerError {
+                status: StatusCode::Unauthorized,
+                message: "Invalid signature".to_string(),
+            });
+        };
+        if submission.route != route || submission.view_number != view_number {
+            return Err(ServerError {
+                status: StatusCode::Unauthorized,
+                message: format!("Submission not signed for {route} and view {view_number:?}"),
+            });
+        }
+        let now = unix_micros();
+        if !self.replay_guard.is_fresh(submission.nonce, now) {
+            return Err(ServerError {
+                status: StatusCode::Unauthorized,
+                message: "Submission is too old or too far in the future".to_string(),
+            });
+        }
+        // Keys outside the stake table can only register themselves
+        if !self.auth.stake_table.contains(&signer)
+            && !(self.auth.open_registration && route == "poststaketable")
+        {
+            return Err(ServerError {
+                status: StatusCode::Forbidden,
+                message: "Only keys in the stake table can submit".to_string(),
+            });
+        }
+        if let (Some(view_number), Some(leaders)) = (view_number, &self.auth.leaders) {
+            if LEADER_ROUTES.contains(&route) && leaders.leader(view_number) != signer {
+                return Err(ServerError {
+                    status: StatusCode::Forbidden,
+                    message: format!("Only the leader can post to {route} for view {view_number}"),
+                });
+            }
+        }
+        // Replays are rejected before they count against the rate limit of the key
+        if !self.replay_guard.record(&signer, submission.nonce, now) {
+            return Err(ServerError {
+                status: StatusCode::Unauthorized,
+                message: "Submission was already received".to_string(),
+            });
+        }
+        if !self
+            .rate_limiter
+            .check(&signer, route, self.auth.rate_limit(route))
+        {
+            return Err(ServerError {
+                status: StatusCode::TooManyRequests,
+                message: format!("Too many submissions to {route}"),
+            });
+        }
+        Ok((signer, submission.payload))
+    }
+
+    /// Store a message of `kind` for `view_number`, evicting whatever is beyond the retention
+    fn store(
+        &mut self,
+        kind: MessageKind,
+        view_number: u64,
+        message: Vec<u8>,
+    ) -> Result<(), Error> {
+        self.storage
+            .append(kind, view_number, message)
+            .map_err(storage_error)?;
+        self.posted.notify(usize::MAX);
+        self.enforce_retention(Some(kind))
+    }
+
+    /// Evict whatever is beyond the retention, forgetting evicted transactions
+    fn enforce_retention(&mut self, kind: Option<MessageKind>) -> Result<(), Error> {
+        let evicted = self
+            .storage
+            .enforce_retention(kind, &self.retention)
+            .map_err(storage_error)?;
+        for txn in evicted.iter().filter_map(|txn| posted_transaction(txn)) {
+            self.txn_lookup.remove(&txn.commitment);
+        }
+        // Completed transactions are no longer stored, but as many of them as pending ones are
+        // remembered
+        while self.completed_txns.len() > self.retention.max_transactions {
+            let Some((index, commitment)) = self.completed_txns.pop_first() else {
+                break;
+            };
+            self.storage
+                .remove_completed(index)
+                .map_err(storage_error)?;
+            self.txn_lookup.remove(&commitment);
+        }
+        Ok(())
+    }
+
+    /// The first message of `kind` for `view_number`, if it has been posted
+    fn single_message(
+        &self,
+        kind: MessageKind,
+        view_number: u64,
+    ) -> Result<Option<Vec<Vec<u8>>>, Error> {
+        Ok(self
+            .storage
+            .messages(kind, view_number, 0)
+            .into_iter()
+            .next()
+            .map(|message| vec![message]))
+    }
 
-            oldest_view_sync_vote: 0,
-            oldest_view_sync_proposal: 0,
-            view_sync_proposal_index: HashMap::new(),
+    /// The messages of `kind` for `view_number` from `index` to the most recent
+    fn messages_from(
+        &self,
+        kind: MessageKind,
+        view_number: u64,
+        index: u64,
+    ) -> Result<Option<Vec<Vec<u8>>>, Error> {
+        let messages = self.storage.messages(kind, view_number, index);
+        if messages.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(messages))
         }
     }
     pub fn with_shutdown_signal(mut self, shutdown_listener: Option<OneShotReceiver<()>>) -> Self {
@@ -128,6 +315,8 @@ impl<KEY: SignatureKey + 'static> WebServerState<KEY> {
 }
 
 /// Trait defining methods needed for the `WebServerState`
+///
+/// The getters return `Ok(None)` if nothing they would return has been posted yet.
 pub trait WebServerDataSource<KEY> {
     fn get_proposal(&self, view_number: u64) -> Result<Option<Vec<Vec<u8>>>, Error>;
     fn get_recent_proposal(&self) -> Result<Option<Vec<Vec<u8>>>, Error>;
@@ -146,6 +335,7 @@ pub trait WebServerDataSource<KEY> {
 
     #[allow(clippy::type_complexity)]
     fn get_transactions(&self, index: u64) -> Result<Option<(u64, Vec<Vec<u8>>)>, Error>;
+    fn get_transaction(&self, commitment: &str) -> Result<TransactionStatus, Error>;
     fn get_da_certificate(&self, index: u64) -> Result<Option<Vec<Vec<u8>>>, Error>;
     fn post_vote(&mut self, view_number: u64, vote: Vec<u8>) -> Result<(), Error>;
     fn post_view_sync_vote(&mut self, view_number: u64, vote: Vec<u8>) -> Result<(), Error>;
@@ -156,8 +346,8 @@ pub trait WebServerDataSource<KEY> {
 
     fn post_da_certificate(&mut self, view_number: u64, cert: Vec<u8>) -> Result<(), Error>;
     fn post_transaction(&mut self, txn: Vec<u8>) -> Result<(), Error>;
-    fn post_staketable(&mut self, key: Vec<u8>) -> Result<(), Error>;
-    fn post_completed_transaction(&mut self, block: Vec<u8>) -> Result<(), Error>;
+    fn post_staketable(&mut self, submission: Vec<u8>) -> Result<(), Error>;
+    fn post_completed_transaction(&mut self, commitment: Vec<u8>) -> Result<(), Error>;
     fn post_secret_proposal(&mut self, _view_number: u64, _proposal: Vec<u8>) -> Result<(), Error>;
     fn proposal(&self, view_number: u64) -> Option<(String, Vec<u8>)>;
 
@@ -169,50 +359,35 @@ pub trait WebServerDataSource<KEY> {
     fn get_vid_disperse(&self, view_number: u64) -> Result<Option<Vec<Vec<u8>>>, Error>;
     fn get_vid_votes(&self, view_number: u64, index: u64) -> Result<Option<Vec<Vec<u8>>>, Error>;
     fn get_vid_certificate(&self, index: u64) -> Result<Option<Vec<Vec<u8>>>, Error>;
+    /// A listener notified the next time anything is posted
+    fn listen(&self) -> EventListener;
+    fn authenticate(
+        &mut self,
+        route: &str,
+        view_number: Option<u64>,
+        submission: Vec<u8>,
+    ) -> Result<Vec<u8>, Error>;
 }
 
-impl<KEY: SignatureKey> WebServerDataSource<KEY> for WebServerState<KEY> {
+impl<KEY: SignatureKey + 'static> WebServerDataSource<KEY> for WebServerState<KEY> {
     fn proposal(&self, view_number: u64) -> Option<(String, Vec<u8>)> {
-        self.proposals.get(&view_number).cloned()
+        let secret = self.secrets.get(&view_number)?.clone();
+        let proposal = self
+            .storage
+            .messages(MessageKind::Proposal, view_number, 0)
+            .into_iter()
+            .next()
+            .unwrap_or_default();
+        Some((secret, proposal))
     }
     /// Return the proposal the server has received for a particular view
     fn get_proposal(&self, view_number: u64) -> Result<Option<Vec<Vec<u8>>>, Error> {
-        match self.proposals.get(&view_number) {
-            Some(proposal) => {
-                if proposal.1.is_empty() {
-                    Err(ServerError {
-                        status: StatusCode::NotImplemented,
-                        message: format!("Proposal not found for view {view_number}"),
-                    })
-                } else {
-                    Ok(Some(vec![proposal.1.clone()]))
-                }
-            }
-            None => Err(ServerError {
-                status: StatusCode::NotImplemented,
-                message: format!("Proposal not found for view {view_number}"),
-            }),
-        }
+        self.single_message(MessageKind::Proposal, view_number)
     }
 
     /// Return the VID disperse data that the server has received for a particular view
     fn get_vid_disperse(&self, view_number: u64) -> Result<Option<Vec<Vec<u8>>>, Error> {
-        match self.vid_disperses.get(&view_number) {
-            Some(disperse) => {
-                if disperse.1.is_empty() {
-                    Err(ServerError {
-                        status: StatusCode::NotImplemented,
-                        message: format!("VID disperse not found for view {view_number}"),
-                    })
-                } else {
-                    Ok(Some(vec![disperse.1.clone()]))
-                }
-            }
-            None => Err(ServerError {
-                status: StatusCode::NotImplemented,
-                message: format!("VID disperse not found for view {view_number}"),
-            }),
-        }
+        self.single_message(MessageKind::VidDisperse, view_number)
     }
 
     fn get_recent_proposal(&self) -> Result<Option<Vec<Vec<u8>>>, Error> {
@@ -224,50 +399,17 @@ impl<KEY: SignatureKey> WebServerDataSource<KEY> for WebServerState<KEY> {
         view_number: u64,
         index: u64,
     ) -> Result<Option<Vec<Vec<u8>>>, Error> {
-        let proposals = self.view_sync_proposals.get(&view_number);
-        let mut ret_proposals = vec![];
-        if let Some(cert) = proposals {
-            for i in index..*self.view_sync_proposal_index.get(&view_number).unwrap() {
-                ret_proposals.push(cert[i as usize].1.clone());
-            }
-        }
-        if !ret_proposals.is_empty() {
-            Ok(Some(ret_proposals))
-        } else {
-            Ok(None)
-        }
+        self.messages_from(MessageKind::ViewSyncProposal, view_number, index)
     }
 
     /// Return all votes the server has received for a particular view from provided index to most recent
     fn get_votes(&self, view_number: u64, index: u64) -> Result<Option<Vec<Vec<u8>>>, Error> {
-        let votes = self.votes.get(&view_number);
-        let mut ret_votes = vec![];
-        if let Some(votes) = votes {
-            for i in index..*self.vote_index.get(&view_number).unwrap() {
-                ret_votes.push(votes[i as usize].1.clone());
-            }
-        }
-        if !ret_votes.is_empty() {
-            Ok(Some(ret_votes))
-        } else {
-            Ok(None)
-        }
+        self.messages_from(MessageKind::Vote, view_number, index)
     }
 
     /// Return all VID votes the server has received for a particular view from provided index to most recent
     fn get_vid_votes(&self, view_number: u64, index: u64) -> Result<Option<Vec<Vec<u8>>>, Error> {
-        let vid_votes = self.vid_votes.get(&view_number);
-        let mut ret_votes = vec![];
-        if let Some(vid_votes) = vid_votes {
-            for i in index..*self.vid_vote_index.get(&view_number).unwrap() {
-                ret_votes.push(vid_votes[i as usize].1.clone());
-            }
-        }
-        if !ret_votes.is_empty() {
-            Ok(Some(ret_votes))
-        } else {
-            Ok(None)
-        }
+        self.messages_from(MessageKind::VidVote, view_number, index)
     }
 
     fn get_view_sync_votes(
@@ -275,202 +417,96 @@ impl<KEY: SignatureKey> WebServerDataSource<KEY> for WebServerState<KEY> {
         view_number: u64,
         index: u64,
     ) -> Result<Option<Vec<Vec<u8>>>, Error> {
-        let votes = self.view_sync_votes.get(&view_number);
-        let mut ret_votes = vec![];
-        if let Some(votes) = votes {
-            // error!("Passed in index is: {} self index is: {}", index, *self.vote_index.get(&view_number).unwrap());
-            for i in index..*self.view_sync_vote_index.get(&view_number).unwrap() {
-                ret_votes.push(votes[i as usize].1.clone());
-            }
-        }
-        if !ret_votes.is_empty() {
-            Ok(Some(ret_votes))
-        } else {
-            Ok(None)
-        }
+        self.messages_from(MessageKind::ViewSyncVote, view_number, index)
     }
 
     #[allow(clippy::type_complexity)]
     /// Return the transaction at the specified index (which will help with Nginx caching, but reduce performance otherwise)
     /// In the future we will return batches of transactions
     fn get_transactions(&self, index: u64) -> Result<Option<(u64, Vec<Vec<u8>>)>, Error> {
-        let mut txns_to_return = vec![];
-
-        let lowest_in_memory_txs = if self.num_txns < MAX_TXNS.try_into().unwrap() {
-            0
-        } else {
-            self.num_txns as usize - MAX_TXNS
+        // Evicted and completed transactions leave gaps in the indices
+        let mut indices = self
+            .storage
+            .transaction_indices()
+            .into_iter()
+            .filter(|idx| *idx >= index);
+        let Some(starting_index) = indices.next() else {
+            return Ok(None);
         };
 
-        let starting_index = if (index as usize) < lowest_in_memory_txs {
-            lowest_in_memory_txs
-        } else {
-            index as usize
-        };
+        // Only consecutive transactions are returned together, so that the starting index and
+        // the number of transactions tell where to continue from
+        let txns_to_return: Vec<Vec<u8>> = std::iter::once(starting_index)
+            .chain(indices)
+            .zip(starting_index..)
+            .take_while(|(idx, expected)| idx == expected)
+            .take(TX_BATCH_SIZE as usize)
+            .filter_map(|(idx, _)| posted_transaction(&self.storage.transaction(idx)?))
+            .map(|txn| txn.transaction)
+            .collect();
 
-        for idx in starting_index..=self.num_txns.try_into().unwrap() {
-            if let Some(txn) = self.transactions.get(&(idx as u64)) {
-                txns_to_return.push(txn.clone())
-            }
-            if txns_to_return.len() >= TX_BATCH_SIZE as usize {
-                break;
-            }
-        }
+        debug!("Returning this many txs {}", txns_to_return.len());
+        //starting_index is the oldest index of the returned txns
+        Ok(Some((starting_index, txns_to_return)))
+    }
 
-        if !txns_to_return.is_empty() {
-            debug!("Returning this many txs {}", txns_to_return.len());
-            //starting_index is the oldest index of the returned txns
-            Ok(Some((starting_index as u64, txns_to_return)))
-        } else {
-            Err(ServerError {
-                // TODO ED: Why does NoContent status code cause errors?
-                status: StatusCode::NotImplemented,
-                message: format!("Transaction not found for index {index}"),
+    /// Return the transaction with `commitment`, or whether it has been completed
+    fn get_transaction(&self, commitment: &str) -> Result<TransactionStatus, Error> {
+        self.txn_lookup
+            .get(commitment)
+            .and_then(|index| {
+                if self.completed_txns.contains_key(index) {
+                    return Some(TransactionStatus::Completed);
+                }
+                let txn = posted_transaction(&self.storage.transaction(*index)?)?;
+                Some(TransactionStatus::Pending(txn.transaction))
+            })
+            .ok_or_else(|| ServerError {
+                status: StatusCode::NotFound,
+                message: format!("Transaction not found for commitment {commitment}"),
             })
-        }
     }
 
     /// Return the da certificate the server has received for a particular view
     fn get_da_certificate(&self, index: u64) -> Result<Option<Vec<Vec<u8>>>, Error> {
-        match self.da_certificates.get(&index) {
-            Some(cert) => {
-                if cert.1.is_empty() {
-                    Err(ServerError {
-                        status: StatusCode::NotImplemented,
-                        message: format!("DA Certificate not found for view {index}"),
-                    })
-                } else {
-                    Ok(Some(vec![cert.1.clone()]))
-                }
-            }
-            None => Err(ServerError {
-                status: StatusCode::NotImplemented,
-                message: format!("Proposal not found for view {index}"),
-            }),
-        }
+        self.single_message(MessageKind::DaCertificate, index)
     }
 
     /// Return the VID certificate the server has received for a particular view
     fn get_vid_certificate(&self, index: u64) -> Result<Option<Vec<Vec<u8>>>, Error> {
-        match self.vid_certificates.get(&index) {
-            Some(vid_cert) => {
-                if vid_cert.1.is_empty() {
-                    Err(ServerError {
-                        status: StatusCode::NotImplemented,
-                        message: format!("VID Certificate not found for view {index}"),
-                    })
-                } else {
-                    Ok(Some(vec![vid_cert.1.clone()]))
-                }
-            }
-            None => Err(ServerError {
-                status: StatusCode::NotImplemented,
-                message: format!("VID certificate not found for view {index}"),
-            }),
-        }
+        self.single_message(MessageKind::VidCertificate, index)
+    }
+
+    fn listen(&self) -> EventListener {
+        self.posted.listen()
     }
 
     /// Stores a received vote in the `WebServerState`
     fn post_vote(&mut self, view_number: u64, vote: Vec<u8>) -> Result<(), Error> {
-        // Only keep vote history for MAX_VIEWS number of views
-        if self.votes.len() >= MAX_VIEWS {
-            self.votes.remove(&self.oldest_vote);
-            while !self.votes.contains_key(&self.oldest_vote) {
-                self.oldest_vote += 1;
-            }
-        }
-        let next_index = self.vote_index.entry(view_number).or_insert(0);
-        self.votes
-            .entry(view_number)
-            .and_modify(|current_votes| current_votes.push((*next_index, vote.clone())))
-            .or_insert_with(|| vec![(*next_index, vote)]);
-        self.vote_index
-            .entry(view_number)
-            .and_modify(|index| *index += 1);
-        Ok(())
+        self.store(MessageKind::Vote, view_number, vote)
     }
 
     /// Stores a received VID vote in the `WebServerState`
     fn post_vid_vote(&mut self, view_number: u64, vote: Vec<u8>) -> Result<(), Error> {
-        // Only keep vote history for MAX_VIEWS number of views
-        if self.vid_votes.len() >= MAX_VIEWS {
-            self.vid_votes.remove(&self.oldest_vote);
-            while !self.vid_votes.contains_key(&self.oldest_vid_vote) {
-                self.oldest_vid_vote += 1;
-            }
-        }
-        let next_index = self.vid_vote_index.entry(view_number).or_insert(0);
-        self.vid_votes
-            .entry(view_number)
-            .and_modify(|current_votes| current_votes.push((*next_index, vote.clone())))
-            .or_insert_with(|| vec![(*next_index, vote)]);
-        self.vid_vote_index
-            .entry(view_number)
-            .and_modify(|index| *index += 1);
-        Ok(())
+        self.store(MessageKind::VidVote, view_number, vote)
     }
 
     fn post_view_sync_vote(&mut self, view_number: u64, vote: Vec<u8>) -> Result<(), Error> {
-        // Only keep vote history for MAX_VIEWS number of views
-        if self.view_sync_votes.len() >= MAX_VIEWS {
-            self.view_sync_votes.remove(&self.oldest_view_sync_vote);
-            while !self
-                .view_sync_votes
-                .contains_key(&self.oldest_view_sync_vote)
-            {
-                self.oldest_view_sync_vote += 1;
-            }
-        }
-        let next_index = self.view_sync_vote_index.entry(view_number).or_insert(0);
-        self.view_sync_votes
-            .entry(view_number)
-            .and_modify(|current_votes| current_votes.push((*next_index, vote.clone())))
-            .or_insert_with(|| vec![(*next_index, vote)]);
-        self.view_sync_vote_index
-            .entry(view_number)
-            .and_modify(|index| *index += 1);
-        Ok(())
+        self.store(MessageKind::ViewSyncVote, view_number, vote)
     }
     /// Stores a received proposal in the `WebServerState`
-    fn post_proposal(&mut self, view_number: u64, mut proposal: Vec<u8>) -> Result<(), Error> {
+    fn post_proposal(&mut self, view_number: u64, proposal: Vec<u8>) -> Result<(), Error> {
         error!("Received proposal for view {}", view_number);
 
         if view_number > self.recent_proposal {
             self.recent_proposal = view_number;
         }
-
-        // Only keep proposal history for MAX_VIEWS number of view
-        if self.proposals.len() >= MAX_VIEWS {
-            self.proposals.remove(&self.oldest_proposal);
-            while !self.proposals.contains_key(&self.oldest_proposal) {
-                self.oldest_proposal += 1;
-            }
-        }
-        self.proposals
-            .entry(view_number)
-            .and_modify(|(_, empty_proposal)| empty_proposal.append(&mut proposal))
-            .or_insert_with(|| (String::new(), proposal));
-        Ok(())
+        self.store(MessageKind::Proposal, view_number, proposal)
     }
 
-    fn post_vid_disperse(&mut self, view_number: u64, mut disperse: Vec<u8>) -> Result<(), Error> {
+    fn post_vid_disperse(&mut self, view_number: u64, disperse: Vec<u8>) -> Result<(), Error> {
         error!("Received VID disperse for view {}", view_number);
-        if view_number > self.recent_vid_disperse {
-            self.recent_vid_disperse = view_number;
-        }
-
-        // Only keep proposal history for MAX_VIEWS number of view
-        if self.vid_disperses.len() >= MAX_VIEWS {
-            self.vid_disperses.remove(&self.oldest_vid_disperse);
-            while !self.vid_disperses.contains_key(&self.oldest_vid_disperse) {
-                self.oldest_vid_disperse += 1;
-            }
-        }
-        self.vid_disperses
-            .entry(view_number)
-            .and_modify(|(_, empty_proposal)| empty_proposal.append(&mut disperse))
-            .or_insert_with(|| (String::new(), disperse));
-        Ok(())
+        self.store(MessageKind::VidDisperse, view_number, disperse)
     }
 
     fn post_view_sync_proposal(
@@ -478,84 +514,39 @@ impl<KEY: SignatureKey> WebServerDataSource<KEY> for WebServerState<KEY> {
         view_number: u64,
         proposal: Vec<u8>,
     ) -> Result<(), Error> {
-        // Only keep proposal history for MAX_VIEWS number of view
-        if self.view_sync_proposals.len() >= MAX_VIEWS {
-            self.view_sync_proposals
-                .remove(&self.oldest_view_sync_proposal);
-            while !self
-                .view_sync_proposals
-                .contains_key(&self.oldest_view_sync_proposal)
-            {
-                self.oldest_view_sync_proposal += 1;
-            }
-        }
-        let next_index = self
-            .view_sync_proposal_index
-            .entry(view_number)
-            .or_insert(0);
-        self.view_sync_proposals
-            .entry(view_number)
-            .and_modify(|current_props| current_props.push((*next_index, proposal.clone())))
-            .or_insert_with(|| vec![(*next_index, proposal)]);
-        self.view_sync_proposal_index
-            .entry(view_number)
-            .and_modify(|index| *index += 1);
-        Ok(())
+        self.store(MessageKind::ViewSyncProposal, view_number, proposal)
     }
 
     /// Stores a received DA certificate in the `WebServerState`
-    fn post_da_certificate(&mut self, view_number: u64, mut cert: Vec<u8>) -> Result<(), Error> {
+    fn post_da_certificate(&mut self, view_number: u64, cert: Vec<u8>) -> Result<(), Error> {
         debug!("Received DA Certificate for view {}", view_number);
-
-        // Only keep proposal history for MAX_VIEWS number of view
-        if self.da_certificates.len() >= MAX_VIEWS {
-            self.da_certificates.remove(&self.oldest_certificate);
-            while !self.da_certificates.contains_key(&self.oldest_certificate) {
-                self.oldest_certificate += 1;
-            }
-        }
-        self.da_certificates
-            .entry(view_number)
-            .and_modify(|(_, empty_cert)| empty_cert.append(&mut cert))
-            .or_insert_with(|| (String::new(), cert));
-        Ok(())
+        self.store(MessageKind::DaCertificate, view_number, cert)
     }
 
     fn post_vid_certificate(
         &mut self,
         view_number: u64,
-        mut certificate: Vec<u8>,
+        certificate: Vec<u8>,
     ) -> Result<(), Error> {
         error!("Received VID Certificate for view {}", view_number);
-
-        // Only keep proposal history for MAX_VIEWS number of view
-        if self.vid_certificates.len() >= MAX_VIEWS {
-            self.vid_certificates.remove(&self.oldest_vid_certificate);
-            while !self
-                .vid_certificates
-                .contains_key(&self.oldest_vid_certificate)
-            {
-                self.oldest_vid_certificate += 1;
-            }
-        }
-        self.vid_certificates
-            .entry(view_number)
-            .and_modify(|(_, empty_cert)| empty_cert.append(&mut certificate))
-            .or_insert_with(|| (String::new(), certificate));
-        Ok(())
+        self.store(MessageKind::VidCertificate, view_number, certificate)
     }
 
-    /// Stores a received group of transactions in the `WebServerState`
+    /// Stores a received [`PostedTransaction`] in the `WebServerState`
     fn post_transaction(&mut self, txn: Vec<u8>) -> Result<(), Error> {
-        if self.transactions.len() >= MAX_TXNS {
-            let old_txn = self.transactions.remove(&(self.num_txns - MAX_TXNS as u64));
-            if let Some(old_txn) = old_txn {
-                self.txn_lookup.remove(&old_txn);
-            }
-        }
-        self.txn_lookup.insert(txn.clone(), self.num_txns);
-        self.transactions.insert(self.num_txns, txn);
+        let Some(posted) = posted_transaction(&txn) else {
+            return Err(ServerError {
+                status: StatusCode::BadRequest,
+                message: "Transactions must be posted as a PostedTransaction".to_string(),
+            });
+        };
+        self.storage
+            .insert_transaction(self.num_txns, txn)
+            .map_err(storage_error)?;
+        self.txn_lookup.insert(posted.commitment, self.num_txns);
         self.num_txns += 1;
+        self.posted.notify(usize::MAX);
+        self.enforce_retention(None)?;
 
         debug!(
             "Received transaction!  Number of transactions received is: {}",
@@ -565,79 +556,172 @@ impl<KEY: SignatureKey> WebServerDataSource<KEY> for WebServerState<KEY> {
         Ok(())
     }
 
-    fn post_staketable(&mut self, key: Vec<u8>) -> Result<(), Error> {
-        // KALEY TODO: need security checks here
-        let new_key = KEY::from_bytes(&(EncodedPublicKey(key)));
-        if let Some(new_key) = new_key {
-            let node_index = self.stake_table.len() as u64;
-            //generate secret for leader's first submission endpoint when key is added
-            let secret = thread_rng()
-                .sample_iter(&Alphanumeric)
-                .take(30)
-                .map(char::from)
-                .collect();
-            self.proposals.insert(node_index, (secret, Vec::new()));
-            self.stake_table.push(new_key);
-            Ok(())
-        } else {
-            Err(ServerError {
+    /// Adds a key to the stake table. Keys in the stake table can add others, and, with open
+    /// registration, keys can add themselves.
+    fn post_staketable(&mut self, submission: Vec<u8>) -> Result<(), Error> {
+        let (signer, key) = self.check_submission("poststaketable", None, &submission)?;
+        let Some(new_key) = KEY::from_bytes(&(EncodedPublicKey(key))) else {
+            return Err(ServerError {
                 status: StatusCode::BadRequest,
                 message: "Only signature keys can be added to stake table".to_string(),
-            })
+            });
+        };
+        if self.auth.stake_table.contains(&new_key) {
+            return Ok(());
+        }
+        if !self.auth.stake_table.contains(&signer) && signer != new_key {
+            return Err(ServerError {
+                status: StatusCode::Forbidden,
+                message: "Only keys in the stake table can add keys to it".to_string(),
+            });
         }
+        self.add_to_stake_table(new_key).map_err(storage_error)
     }
 
-    fn post_completed_transaction(&mut self, txn: Vec<u8>) -> Result<(), Error> {
-        if let Some(idx) = self.txn_lookup.remove(&txn) {
-            self.transactions.remove(&idx);
-            Ok(())
-        } else {
-            Err(ServerError {
+    /// Returns the payload of a submission to `route`, if its signer may post to it
+    fn authenticate(
+        &mut self,
+        route: &str,
+        view_number: Option<u64>,
+        submission: Vec<u8>,
+    ) -> Result<Vec<u8>, Error> {
+        let (_, payload) = self.check_submission(route, view_number, &submission)?;
+        Ok(payload)
+    }
+
+    /// Marks the transaction with the posted commitment completed. It is no longer kept or returned
+    /// with the others, but looking it up by its commitment tells that it was completed.
+    fn post_completed_transaction(&mut self, commitment: Vec<u8>) -> Result<(), Error> {
+        let commitment = String::from_utf8(commitment).map_err(|_| ServerError {
+            status: StatusCode::BadRequest,
+            message: "Transaction commitments must be posted as UTF-8".to_string(),
+        })?;
+        let Some(&idx) = self.txn_lookup.get(&commitment) else {
+            return Err(ServerError {
                 status: StatusCode::BadRequest,
                 message: "Transaction Not Found".to_string(),
-            })
+            });
+        };
+        if self.completed_txns.contains_key(&idx) {
+            return Ok(());
         }
+        self.storage
+            .insert_completed(idx, commitment.clone())
+            .map_err(storage_error)?;
+        self.storage
+            .remove_transaction(idx)
+            .map_err(storage_error)?;
+        self.completed_txns.insert(idx, commitment);
+        self.enforce_retention(None)
     }
 
     //KALEY TODO: this will be merged with post_proposal once it is fully working,
     //but keeping it separate to not break things in the meantime
-    fn post_secret_proposal(
-        &mut self,
-        view_number: u64,
-        mut proposal: Vec<u8>,
-    ) -> Result<(), Error> {
+    fn post_secret_proposal(&mut self, view_number: u64, proposal: Vec<u8>) -> Result<(), Error> {
         debug!("Received proposal for view {}", view_number);
 
-        // Only keep proposal history for MAX_VIEWS number of views
-        if self.proposals.len() >= MAX_VIEWS {
-            self.proposals.remove(&self.oldest_proposal);
-            while !self.proposals.contains_key(&self.oldest_proposal) {
-                self.oldest_proposal += 1;
-            }
-        }
-        self.proposals
-            .entry(view_number)
-            .and_modify(|(_, empty_proposal)| empty_proposal.append(&mut proposal));
+        self.secrets.remove(&view_number);
+        self.storage
+            .remove_secret(view_number)
+            .map_err(storage_error)?;
+        self.store(MessageKind::Proposal, view_number, proposal)?;
 
         //generate new secret for the next time this node is leader
-        let secret = thread_rng()
+        let secret: String = thread_rng()
             .sample_iter(&Alphanumeric)
             .take(30)
             .map(char::from)
             .collect();
-        let next_view_for_leader = view_number + self.stake_table.len() as u64;
-        self.proposals
-            .insert(next_view_for_leader, (secret, Vec::new()));
+        let next_view_for_leader = view_number + self.auth.stake_table.len() as u64;
+        self.storage
+            .insert_secret(next_view_for_leader, secret.clone())
+            .map_err(storage_error)?;
+        self.secrets.insert(next_view_for_leader, secret);
         Ok(())
     }
 }
 
+/// Report a failure of the storage
+fn storage_error(e: io::Error) -> Error {
+    error!("Web server storage failed: {e}");
+    ServerError {
+        status: StatusCode::InternalServerError,
+        message: format!("Storage failed: {e}"),
+    }
+}
+
 #[derive(Args, Default)]
 pub struct Options {
     #[arg(long = "web-server-api-path", env = "WEB_SERVER_API_PATH")]
     pub api_path: Option<PathBuf>,
 }
 
+/// Answer a GET with `get`, waiting up to the optional `wait` parameter, in milliseconds, for
+/// there to be something to return. `None` if there still isn't.
+async fn long_poll<State, KEY, T>(
+    req: &RequestParams,
+    state: &State,
+    get: impl Fn(&<State as ReadState>::State) -> Result<Option<T>, Error> + Send + Sync,
+) -> Result<Option<T>, Error>
+where
+    State: ReadState + Send + Sync,
+    <State as ReadState>::State: Send + Sync + WebServerDataSource<KEY>,
+    T: Send + 'static,
+{
+    let wait: Option<u64> = req.opt_integer_param("wait")?;
+    wait_for(state, Duration::from_millis(wait.unwrap_or(0)), get).await
+}
+
+/// Answer with `get` once it has something to return, or with `None` after `wait`, capped at
+/// [`MAX_LONG_POLL_WAIT`]. `get` runs again each time something is posted. The state is only
+/// locked while `get` runs, so waiting requests don't hold up posts.
+async fn wait_for<State, KEY, T>(
+    state: &State,
+    wait: Duration,
+    get: impl Fn(&<State as ReadState>::State) -> Result<Option<T>, Error> + Send + Sync,
+) -> Result<Option<T>, Error>
+where
+    State: ReadState + Send + Sync,
+    <State as ReadState>::State: Send + Sync + WebServerDataSource<KEY>,
+    T: Send + 'static,
+{
+    let deadline = Instant::now() + wait.min(MAX_LONG_POLL_WAIT);
+    loop {
+        // Listen before looking, so that a post in between isn't missed
+        let (posted, found) = state
+            .read(|state| {
+                let posted = state.listen();
+                let found = get(state);
+                async move { (posted, found) }.boxed()
+            })
+            .await;
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if !matches!(found, Ok(None)) || remaining.is_zero() {
+            return found;
+        }
+        // Look again once something is posted, or one last time at the deadline
+        let _ = async_timeout(remaining, posted).await;
+    }
+}
+
+/// The answer to a GET for `what`, failing with `NotImplemented` if nothing has been posted, as
+/// clients expect
+fn posted<T>(found: Option<T>, what: impl FnOnce() -> String) -> Result<Option<T>, Error> {
+    match found {
+        Some(found) => Ok(Some(found)),
+        None => Err(ServerError {
+            // TODO ED: Why does NoContent status code cause errors?
+            status: StatusCode::NotImplemented,
+            message: format!("{} not found", what()),
+        }),
+    }
+}
+
+/// The transaction posted as `txn`, or `None` if it wasn't posted as a [`PostedTransaction`]
+fn posted_transaction(txn: &[u8]) -> Option<PostedTransaction> {
+    bincode::deserialize(txn).ok()
+}
+
 /// Sets up all API routes
 fn define_api<State, KEY>(options: &Options) -> Result<Api<State, Error>, ApiError>
 where
@@ -656,73 +740,112 @@ where
             Api::<State, Error>::new(toml)?
         }
     };
-    api.get("getproposal", |req, state| {
+    api.at("getproposal", |req, state| {
         async move {
             let view_number: u64 = req.integer_param("view_number")?;
-            state.get_proposal(view_number)
+            let found =
+                long_poll(&req, state, move |state| state.get_proposal(view_number)).await?;
+            posted(found, || format!("Proposal for view {view_number}"))
         }
         .boxed()
     })?
-    .get("getviddisperse", |req, state| {
+    .at("getviddisperse", |req, state| {
         async move {
             let view_number: u64 = req.integer_param("view_number")?;
-            state.get_vid_disperse(view_number)
+            let found = long_poll(&req, state, move |state| {
+                state.get_vid_disperse(view_number)
+            })
+            .await?;
+            posted(found, || format!("VID disperse for view {view_number}"))
         }
         .boxed()
     })?
     .get("getrecentproposal", |_req, state| {
-        async move { state.get_recent_proposal() }.boxed()
+        async move {
+            posted(state.get_recent_proposal()?, || {
+                "Recent proposal".to_string()
+            })
+        }
+        .boxed()
     })?
-    .get("getviewsyncproposal", |req, state| {
+    .at("getviewsyncproposal", |req, state| {
         async move {
             let view_number: u64 = req.integer_param("view_number")?;
             let index: u64 = req.integer_param("index")?;
-            state.get_view_sync_proposal(view_number, index)
+            long_poll(&req, state, move |state| {
+                state.get_view_sync_proposal(view_number, index)
+            })
+            .await
         }
         .boxed()
     })?
-    .get("getcertificate", |req, state| {
+    .at("getcertificate", |req, state| {
         async move {
             let view_number: u64 = req.integer_param("view_number")?;
-            state.get_da_certificate(view_number)
+            let found = long_poll(&req, state, move |state| {
+                state.get_da_certificate(view_number)
+            })
+            .await?;
+            posted(found, || format!("DA Certificate for view {view_number}"))
         }
         .boxed()
     })?
-    .get("getvidcertificate", |req, state| {
+    .at("getvidcertificate", |req, state| {
         async move {
             let view_number: u64 = req.integer_param("view_number")?;
-            state.get_vid_certificate(view_number)
+            let found = long_poll(&req, state, move |state| {
+                state.get_vid_certificate(view_number)
+            })
+            .await?;
+            posted(found, || format!("VID Certificate for view {view_number}"))
         }
         .boxed()
     })?
-    .get("getvotes", |req, state| {
+    .at("getvotes", |req, state| {
         async move {
             let view_number: u64 = req.integer_param("view_number")?;
             let index: u64 = req.integer_param("index")?;
-            state.get_votes(view_number, index)
+            long_poll(&req, state, move |state| {
+                state.get_votes(view_number, index)
+            })
+            .await
         }
         .boxed()
     })?
-    .get("getvidvotes", |req, state| {
+    .at("getvidvotes", |req, state| {
         async move {
             let view_number: u64 = req.integer_param("view_number")?;
             let index: u64 = req.integer_param("index")?;
-            state.get_vid_votes(view_number, index)
+            long_poll(&req, state, move |state| {
+                state.get_vid_votes(view_number, index)
+            })
+            .await
         }
         .boxed()
     })?
-    .get("getviewsyncvotes", |req, state| {
+    .at("getviewsyncvotes", |req, state| {
         async move {
             let view_number: u64 = req.integer_param("view_number")?;
             let index: u64 = req.integer_param("index")?;
-            state.get_view_sync_votes(view_number, index)
+            long_poll(&req, state, move |state| {
+                state.get_view_sync_votes(view_number, index)
+            })
+            .await
+        }
+        .boxed()
+    })?
+    .get("gettransaction", |req, state| {
+        async move {
+            let commitment: &str = req.string_param("commitment")?;
+            state.get_transaction(commitment)
         }
         .boxed()
     })?
-    .get("gettransactions", |req, state| {
+    .at("gettransactions", |req, state| {
         async move {
             let index: u64 = req.integer_param("index")?;
-            state.get_transactions(index)
+            let found = long_poll(&req, state, move |state| state.get_transactions(index)).await?;
+            posted(found, || format!("Transaction for index {index}"))
         }
         .boxed()
     })?
@@ -730,7 +853,7 @@ where
         async move {
             let view_number: u64 = req.integer_param("view_number")?;
             // Using body_bytes because we don't want to deserialize; body_auto or body_json deserializes automatically
-            let vote = req.body_bytes();
+            let vote = state.authenticate("postvote", Some(view_number), req.body_bytes())?;
             state.post_vote(view_number, vote)
         }
         .boxed()
@@ -739,7 +862,7 @@ where
         async move {
             let view_number: u64 = req.integer_param("view_number")?;
             // Using body_bytes because we don't want to deserialize; body_auto or body_json deserializes automatically
-            let vote = req.body_bytes();
+            let vote = state.authenticate("postvidvote", Some(view_number), req.body_bytes())?;
             state.post_vid_vote(view_number, vote)
         }
         .boxed()
@@ -748,7 +871,8 @@ where
         async move {
             let view_number: u64 = req.integer_param("view_number")?;
             // Using body_bytes because we don't want to deserialize; body_auto or body_json deserializes automatically
-            let vote = req.body_bytes();
+            let vote =
+                state.authenticate("postviewsyncvote", Some(view_number), req.body_bytes())?;
             state.post_view_sync_vote(view_number, vote)
         }
         .boxed()
@@ -756,7 +880,8 @@ where
     .post("postproposal", |req, state| {
         async move {
             let view_number: u64 = req.integer_param("view_number")?;
-            let proposal = req.body_bytes();
+            let proposal =
+                state.authenticate("postproposal", Some(view_number), req.body_bytes())?;
             state.post_proposal(view_number, proposal)
         }
         .boxed()
@@ -764,7 +889,8 @@ where
     .post("postviddisperse", |req, state| {
         async move {
             let view_number: u64 = req.integer_param("view_number")?;
-            let disperse = req.body_bytes();
+            let disperse =
+                state.authenticate("postviddisperse", Some(view_number), req.body_bytes())?;
             state.post_vid_disperse(view_number, disperse)
         }
         .boxed()
@@ -772,7 +898,8 @@ where
     .post("postviewsyncproposal", |req, state| {
         async move {
             let view_number: u64 = req.integer_param("view_number")?;
-            let proposal = req.body_bytes();
+            let proposal =
+                state.authenticate("postviewsyncproposal", Some(view_number), req.body_bytes())?;
             state.post_view_sync_proposal(view_number, proposal)
         }
         .boxed()
@@ -780,7 +907,8 @@ where
     .post("postcertificate", |req, state| {
         async move {
             let view_number: u64 = req.integer_param("view_number")?;
-            let cert = req.body_bytes();
+            let cert =
+                state.authenticate("postcertificate", Some(view_number), req.body_bytes())?;
             state.post_da_certificate(view_number, cert)
         }
         .boxed()
@@ -788,14 +916,15 @@ where
     .post("postvidcertificate", |req, state| {
         async move {
             let view_number: u64 = req.integer_param("view_number")?;
-            let cert = req.body_bytes();
+            let cert =
+                state.authenticate("postvidcertificate", Some(view_number), req.body_bytes())?;
             state.post_vid_certificate(view_number, cert)
         }
         .boxed()
     })?
     .post("posttransaction", |req, state| {
         async move {
-            let txns = req.body_bytes();
+            let txns = state.authenticate("posttransaction", None, req.body_bytes())?;
             state.post_transaction(txns)
         }
         .boxed()
@@ -803,16 +932,17 @@ where
     .post("poststaketable", |req, state| {
         async move {
             //works one key at a time for now
-            let key = req.body_bytes();
-            state.post_staketable(key)
+            let submission = req.body_bytes();
+            state.post_staketable(submission)
         }
         .boxed()
     })?
     .post("postcompletedtransaction", |req, state| {
         async move {
             //works one txn at a time for now
-            let txn = req.body_bytes();
-            state.post_completed_transaction(txn)
+            let commitment =
+                state.authenticate("postcompletedtransaction", None, req.body_bytes())?;
+            state.post_completed_transaction(commitment)
         }
         .boxed()
     })?
@@ -820,11 +950,11 @@ where
         async move {
             let view_number: u64 = req.integer_param("view_number")?;
             let secret: &str = req.string_param("secret")?;
+            let proposal = state.authenticate("secret", Some(view_number), req.body_bytes())?;
             //if secret is correct and view_number->proposal is empty, proposal is valid
             if let Some(prop) = state.proposal(view_number) {
                 if prop.1.is_empty() {
                     if prop.0 == secret {
-                        let proposal = req.body_bytes();
                         state.post_secret_proposal(view_number, proposal)
                     } else {
                         Err(ServerError {
@@ -853,14 +983,31 @@ where
     Ok(api)
 }
 
+/// Runs a web server that only accepts submissions as allowed by `auth`
 pub async fn run_web_server<KEY: SignatureKey + 'static>(
     shutdown_listener: Option<OneShotReceiver<()>>,
     port: u16,
+    auth: AuthConfig<KEY>,
+) -> io::Result<()> {
+    run_web_server_with_config(shutdown_listener, port, auth, StorageConfig::default()).await
+}
+
+/// Runs a web server that only accepts submissions as allowed by `auth`, and keeps them as
+/// configured by `storage`. A web server restarted with the same storage directory serves what
+/// was posted to it before.
+pub async fn run_web_server_with_config<KEY: SignatureKey + 'static>(
+    shutdown_listener: Option<OneShotReceiver<()>>,
+    port: u16,
+    auth: AuthConfig<KEY>,
+    storage: StorageConfig,
 ) -> io::Result<()> {
     let options = Options::default();
 
     let api = define_api(&options).unwrap();
-    let state = State::new(WebServerState::new().with_shutdown_signal(shutdown_listener));
+    let state = State::new(
+        WebServerState::new(auth, storage.open()?, storage.retention)?
+            .with_shutdown_signal(shutdown_listener),
+    );
     let mut app = App::<State<KEY>, Error>::with_state(state);
 
     app.register_module("api", api).unwrap();
@@ -871,3 +1018,426 @@ pub async fn run_web_server<KEY: SignatureKey + 'static>(
 
     app_future.await
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        auth::{LeaderSchedule, RateLimit},
+        config::DEFAULT_MAX_SUBMISSION_AGE,
+        storage::{DiskStorage, MemoryStorage},
+    };
+    use hotshot_signature_key::bn254::{BLSPrivKey, BLSPubKey};
+    use std::sync::Arc;
+
+    /// Key `index` of the test nodes
+    fn key(index: u64) -> (BLSPubKey, BLSPrivKey) {
+        BLSPubKey::generated_from_seed_indexed([0u8; 32], index)
+    }
+
+    /// A submission of `payload` to `route` for `view_number` by node `index`, with `nonce`
+    fn submission(
+        index: u64,
+        route: &str,
+        view_number: Option<u64>,
+        nonce: u64,
+        payload: &[u8],
+    ) -> Vec<u8> {
+        let (key, private_key) = key(index);
+        let submission = SignedSubmission::new(
+            &key,
+            &private_key,
+            route,
+            view_number,
+            nonce,
+            payload.to_vec(),
+        );
+        bincode::serialize(&submission).unwrap()
+    }
+
+    /// Transaction `index`, as posted with commitment `c<index>`
+    fn posted_txn(index: u64) -> Vec<u8> {
+        bincode::serialize(&PostedTransaction {
+            commitment: format!("c{index}"),
+            transaction: txn(index),
+        })
+        .unwrap()
+    }
+
+    /// The message submitting transaction `index`
+    fn txn(index: u64) -> Vec<u8> {
+        format!("txn {index}").into_bytes()
+    }
+
+    /// A web server state in memory, accepting submissions as allowed by `auth`
+    fn web_server(auth: AuthConfig<BLSPubKey>) -> WebServerState<BLSPubKey> {
+        WebServerState::new(auth, Box::new(MemoryStorage::new()), Retention::default()).unwrap()
+    }
+
+    /// Node 0 leads every view
+    #[derive(Debug)]
+    struct FirstNodeLeads;
+
+    impl LeaderSchedule<BLSPubKey> for FirstNodeLeads {
+        fn leader(&self, _view_number: u64) -> BLSPubKey {
+            key(0).0
+        }
+    }
+
+    #[test]
+    fn test_only_stake_table_can_submit() {
+        let mut state = web_server(AuthConfig::new(vec![key(0).0]));
+        let now = unix_micros();
+        assert!(state
+            .authenticate(
+                "postvote",
+                Some(1),
+                submission(0, "postvote", Some(1), now, b"vote")
+            )
+            .is_ok());
+        let err = state
+            .authenticate(
+                "postvote",
+                Some(1),
+                submission(1, "postvote", Some(1), now, b"vote"),
+            )
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::Forbidden);
+    }
+
+    #[test]
+    fn test_default_auth_is_closed() {
+        let mut state = web_server(AuthConfig::default());
+        let (new_key, _) = key(0);
+        let registration = submission(
+            0,
+            "poststaketable",
+            None,
+            unix_micros(),
+            &new_key.to_bytes().0,
+        );
+        let err = state.post_staketable(registration).unwrap_err();
+        assert_eq!(err.status, StatusCode::Forbidden);
+
+        let mut state = web_server(AuthConfig::open());
+        let registration = submission(
+            0,
+            "poststaketable",
+            None,
+            unix_micros(),
+            &new_key.to_bytes().0,
+        );
+        assert!(state.post_staketable(registration).is_ok());
+    }
+
+    #[test]
+    fn test_submission_is_bound_to_route_and_view() {
+        let mut state = web_server(AuthConfig::new(vec![key(0).0]));
+        let vote = submission(0, "postvote", Some(1), unix_micros(), b"vote");
+        let err = state
+            .authenticate("postvote", Some(2), vote.clone())
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::Unauthorized);
+        let err = state
+            .authenticate("postviewsyncvote", Some(1), vote.clone())
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::Unauthorized);
+        assert!(state.authenticate("postvote", Some(1), vote).is_ok());
+    }
+
+    #[test]
+    fn test_replays_are_rejected_without_using_the_rate_limit() {
+        let auth = AuthConfig::new(vec![key(0).0]).with_rate_limit(
+            "postvote",
+            RateLimit {
+                max_requests: 2,
+                window: Duration::from_secs(60),
+            },
+        );
+        let mut state = web_server(auth);
+        let now = unix_micros();
+        let vote = submission(0, "postvote", Some(1), now, b"vote");
+        assert!(state
+            .authenticate("postvote", Some(1), vote.clone())
+            .is_ok());
+        for _ in 0..5 {
+            let err = state
+                .authenticate("postvote", Some(1), vote.clone())
+                .unwrap_err();
+            assert_eq!(err.status, StatusCode::Unauthorized);
+        }
+        let next_vote = submission(0, "postvote", Some(1), now + 1, b"vote");
+        assert!(state.authenticate("postvote", Some(1), next_vote).is_ok());
+    }
+
+    #[test]
+    fn test_stale_submissions_are_rejected() {
+        let mut state = web_server(AuthConfig::new(vec![key(0).0]));
+        let age = DEFAULT_MAX_SUBMISSION_AGE.as_micros() as u64;
+        for nonce in [unix_micros() - 2 * age, unix_micros() + 2 * age] {
+            let err = state
+                .authenticate(
+                    "postvote",
+                    Some(1),
+                    submission(0, "postvote", Some(1), nonce, b"vote"),
+                )
+                .unwrap_err();
+            assert_eq!(err.status, StatusCode::Unauthorized);
+        }
+    }
+
+    #[test]
+    fn test_proposals_come_from_the_scheduled_leader() {
+        let auth = AuthConfig::new(vec![key(0).0, key(1).0]).with_leaders(Arc::new(FirstNodeLeads));
+        let mut state = web_server(auth);
+        let now = unix_micros();
+        // Node 1 would lead view 1 if leaders took turns in stake table order
+        let err = state
+            .authenticate(
+                "postproposal",
+                Some(1),
+                submission(1, "postproposal", Some(1), now, b"proposal"),
+            )
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::Forbidden);
+        assert!(state
+            .authenticate(
+                "postproposal",
+                Some(1),
+                submission(0, "postproposal", Some(1), now, b"proposal"),
+            )
+            .is_ok());
+        // Anyone in the stake table can vote
+        assert!(state
+            .authenticate(
+                "postvote",
+                Some(1),
+                submission(1, "postvote", Some(1), now, b"vote")
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn test_restart_keeps_stake_table_and_secrets() {
+        let dir = tempfile::tempdir().unwrap();
+        let disk =
+            || -> Box<dyn WebServerStorage> { Box::new(DiskStorage::open(dir.path()).unwrap()) };
+        let mut state = WebServerState::new(
+            AuthConfig::new(vec![key(0).0]),
+            disk(),
+            Retention::default(),
+        )
+        .unwrap();
+        let registration = submission(
+            0,
+            "poststaketable",
+            None,
+            unix_micros(),
+            &key(1).0.to_bytes().0,
+        );
+        state.post_staketable(registration).unwrap();
+        state.post_secret_proposal(0, b"proposal".to_vec()).unwrap();
+        let secrets = state.secrets.clone();
+        drop(state);
+
+        // Node 1 registered at runtime, so only the storage knows about it.
+        let mut state = WebServerState::new(
+            AuthConfig::new(vec![key(0).0]),
+            disk(),
+            Retention::default(),
+        )
+        .unwrap();
+        assert_eq!(state.auth.stake_table, vec![key(0).0, key(1).0]);
+        assert_eq!(state.secrets, secrets);
+        assert!(!state.secrets.contains_key(&0));
+        assert_eq!(state.recent_proposal, 0);
+        assert!(state
+            .authenticate(
+                "postvote",
+                Some(1),
+                submission(1, "postvote", Some(1), unix_micros(), b"vote"),
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn test_missing_messages_are_none() {
+        let mut state = web_server(AuthConfig::new(vec![key(0).0]));
+        assert_eq!(state.get_proposal(1).unwrap(), None);
+        assert_eq!(state.get_da_certificate(1).unwrap(), None);
+        assert_eq!(state.get_votes(1, 0).unwrap(), None);
+        assert_eq!(state.get_transactions(0).unwrap(), None);
+
+        state.post_proposal(1, b"proposal".to_vec()).unwrap();
+        state.post_transaction(posted_txn(0)).unwrap();
+        assert_eq!(
+            state.get_proposal(1).unwrap(),
+            Some(vec![b"proposal".to_vec()])
+        );
+        assert_eq!(state.get_proposal(2).unwrap(), None);
+        assert_eq!(state.get_transactions(0).unwrap(), Some((0, vec![txn(0)])));
+    }
+
+    #[test]
+    fn test_posts_notify_listeners() {
+        let mut state = web_server(AuthConfig::new(vec![key(0).0]));
+        let vote = state.listen();
+        state.post_vote(1, b"vote".to_vec()).unwrap();
+        assert!(vote.now_or_never().is_some());
+
+        let txn = state.listen();
+        state.post_transaction(posted_txn(0)).unwrap();
+        assert!(txn.now_or_never().is_some());
+
+        // Nothing has been posted since
+        assert!(state.listen().now_or_never().is_none());
+    }
+
+    #[cfg_attr(
+        async_executor_impl = "tokio",
+        tokio::test(flavor = "multi_thread", worker_threads = 2)
+    )]
+    #[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+    async fn test_long_poll_wakes_on_post() {
+        let state: Arc<State<BLSPubKey>> =
+            Arc::new(RwLock::new(web_server(AuthConfig::new(vec![key(0).0]))));
+        let poster = state.clone();
+        async_compatibility_layer::art::async_spawn(async move {
+            async_compatibility_layer::art::async_sleep(Duration::from_millis(50)).await;
+            poster
+                .write()
+                .await
+                .post_proposal(1, b"proposal".to_vec())
+                .unwrap();
+        });
+
+        let start = Instant::now();
+        let found = wait_for::<_, BLSPubKey, _>(&*state, Duration::from_secs(10), |state| {
+            state.get_proposal(1)
+        })
+        .await
+        .unwrap();
+        assert_eq!(found, Some(vec![b"proposal".to_vec()]));
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[cfg_attr(
+        async_executor_impl = "tokio",
+        tokio::test(flavor = "multi_thread", worker_threads = 2)
+    )]
+    #[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+    async fn test_long_poll_times_out_with_none() {
+        let state: State<BLSPubKey> = RwLock::new(web_server(AuthConfig::new(vec![key(0).0])));
+        state
+            .write()
+            .await
+            .post_proposal(2, b"proposal".to_vec())
+            .unwrap();
+
+        let start = Instant::now();
+        let found = wait_for::<_, BLSPubKey, _>(&state, Duration::from_millis(100), |state| {
+            state.get_proposal(1)
+        })
+        .await
+        .unwrap();
+        assert_eq!(found, None);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_transactions_are_looked_up_by_commitment() {
+        let mut state = web_server(AuthConfig::new(vec![key(0).0]));
+        state.post_transaction(posted_txn(0)).unwrap();
+        state.post_transaction(posted_txn(1)).unwrap();
+        assert_eq!(
+            state.get_transaction("c1").unwrap(),
+            TransactionStatus::Pending(txn(1))
+        );
+        assert_eq!(
+            state.get_transaction("c2").unwrap_err().status,
+            StatusCode::NotFound
+        );
+        // Transactions without a commitment can't be looked up
+        assert_eq!(
+            state.post_transaction(txn(2)).unwrap_err().status,
+            StatusCode::BadRequest
+        );
+    }
+
+    #[test]
+    fn test_completed_transactions_are_freed() {
+        let mut state = WebServerState::<BLSPubKey>::new(
+            AuthConfig::new(vec![key(0).0]),
+            Box::new(MemoryStorage::new()),
+            Retention {
+                max_transactions: 2,
+                ..Retention::default()
+            },
+        )
+        .unwrap();
+        state.post_transaction(posted_txn(0)).unwrap();
+        state.post_transaction(posted_txn(1)).unwrap();
+        state.post_completed_transaction(b"c0".to_vec()).unwrap();
+        assert_eq!(
+            state.get_transaction("c0").unwrap(),
+            TransactionStatus::Completed
+        );
+        // The batch starts at the first transaction returned, not the completed one before it
+        assert_eq!(state.get_transactions(0).unwrap(), Some((1, vec![txn(1)])));
+
+        // The completed transaction no longer takes up room, so nothing pending is evicted
+        state.post_transaction(posted_txn(2)).unwrap();
+        assert_eq!(state.storage.transaction_count(), 2);
+        assert_eq!(
+            state.get_transaction("c1").unwrap(),
+            TransactionStatus::Pending(txn(1))
+        );
+
+        // As many completed transactions as pending ones are remembered
+        state.post_completed_transaction(b"c1".to_vec()).unwrap();
+        state.post_completed_transaction(b"c2".to_vec()).unwrap();
+        state.post_transaction(posted_txn(3)).unwrap();
+        state.post_completed_transaction(b"c3".to_vec()).unwrap();
+        assert_eq!(
+            state.get_transaction("c0").unwrap_err().status,
+            StatusCode::NotFound
+        );
+        assert_eq!(
+            state.get_transaction("c3").unwrap(),
+            TransactionStatus::Completed
+        );
+        assert_eq!(state.get_transactions(0).unwrap(), None);
+    }
+
+    #[test]
+    fn test_restart_keeps_completed_transactions() {
+        let dir = tempfile::tempdir().unwrap();
+        let disk =
+            || -> Box<dyn WebServerStorage> { Box::new(DiskStorage::open(dir.path()).unwrap()) };
+        let mut state = WebServerState::new(
+            AuthConfig::new(vec![key(0).0]),
+            disk(),
+            Retention::default(),
+        )
+        .unwrap();
+        state.post_transaction(posted_txn(0)).unwrap();
+        state.post_transaction(posted_txn(1)).unwrap();
+        state.post_completed_transaction(b"c1".to_vec()).unwrap();
+        drop(state);
+
+        let mut state = WebServerState::new(
+            AuthConfig::new(vec![key(0).0]),
+            disk(),
+            Retention::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            state.get_transaction("c1").unwrap(),
+            TransactionStatus::Completed
+        );
+        assert_eq!(state.get_transactions(1).unwrap(), None);
+        // Indices of completed transactions aren't reused
+        state.post_transaction(posted_txn(2)).unwrap();
+        assert_eq!(state.get_transactions(1).unwrap(), Some((2, vec![txn(2)])));
+    }
+}
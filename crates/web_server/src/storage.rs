@@ -0,0 +1,611 @@
+//! Where the web server keeps the messages and transactions posted to it
+//!
+//! Messages are kept per kind and view, in the order they were posted, and transactions by
+//! their index, along with the commitments of the completed transactions, the keys in the stake
+//! table and the secrets of the leaders.
+//! [`MemoryStorage`] loses everything on restart, while [`DiskStorage`] also writes everything
+//! to a directory, so a restarted web server can keep serving recent views to nodes that are
+//! behind, and keeps accepting the nodes that registered with it. How many messages and
+//! transactions are kept is bounded by a [`Retention`].
+
+use crate::config::{MAX_STORED_BYTES, MAX_TXNS, MAX_VIEWS};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// The kinds of messages stored per view
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MessageKind {
+    /// Quorum or DA proposals
+    Proposal,
+    /// DA certificates
+    DaCertificate,
+    /// Quorum or DA votes
+    Vote,
+    /// View sync certificates
+    ViewSyncProposal,
+    /// View sync votes
+    ViewSyncVote,
+    /// VID disperse data
+    VidDisperse,
+    /// VID votes
+    VidVote,
+    /// VID certificates
+    VidCertificate,
+}
+
+impl MessageKind {
+    /// Every kind of message
+    pub const ALL: [MessageKind; 8] = [
+        MessageKind::Proposal,
+        MessageKind::DaCertificate,
+        MessageKind::Vote,
+        MessageKind::ViewSyncProposal,
+        MessageKind::ViewSyncVote,
+        MessageKind::VidDisperse,
+        MessageKind::VidVote,
+        MessageKind::VidCertificate,
+    ];
+
+    /// Name of the kind, used as the name of its directory by [`DiskStorage`]
+    pub fn name(self) -> &'static str {
+        match self {
+            MessageKind::Proposal => "proposal",
+            MessageKind::DaCertificate => "certificate",
+            MessageKind::Vote => "votes",
+            MessageKind::ViewSyncProposal => "view_sync_proposal",
+            MessageKind::ViewSyncVote => "view_sync_vote",
+            MessageKind::VidDisperse => "vid_disperse",
+            MessageKind::VidVote => "vid_votes",
+            MessageKind::VidCertificate => "vid_certificate",
+        }
+    }
+}
+
+/// How much the web server keeps before evicting the oldest views and transactions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Retention {
+    /// Views kept for each kind of message
+    pub max_views: usize,
+    /// Transactions kept
+    pub max_transactions: usize,
+    /// Total size of the messages and transactions kept
+    pub max_bytes: usize,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self {
+            max_views: MAX_VIEWS,
+            max_transactions: MAX_TXNS,
+            max_bytes: MAX_STORED_BYTES,
+        }
+    }
+}
+
+/// Where the web server keeps messages and transactions
+pub trait WebServerStorage: Send + Sync {
+    /// Add a message of `kind` for `view`, returning its index among the messages of the view
+    ///
+    /// # Errors
+    /// Returns an error if the message can't be written
+    fn append(&mut self, kind: MessageKind, view: u64, message: Vec<u8>) -> io::Result<u64>;
+
+    /// The messages of `kind` for `view`, starting at index `from`
+    fn messages(&self, kind: MessageKind, view: u64, from: u64) -> Vec<Vec<u8>>;
+
+    /// How many messages of `kind` there have been for `view`
+    fn message_count(&self, kind: MessageKind, view: u64) -> u64;
+
+    /// How many views have messages of `kind`
+    fn view_count(&self, kind: MessageKind) -> usize;
+
+    /// The oldest view with messages of `kind`
+    fn oldest_view(&self, kind: MessageKind) -> Option<u64>;
+
+    /// The newest view with messages of `kind`
+    fn newest_view(&self, kind: MessageKind) -> Option<u64>;
+
+    /// Remove the messages of `kind` for `view`
+    ///
+    /// # Errors
+    /// Returns an error if the messages can't be removed
+    fn remove_view(&mut self, kind: MessageKind, view: u64) -> io::Result<()>;
+
+    /// Add the transaction with index `index`
+    ///
+    /// # Errors
+    /// Returns an error if the transaction can't be written
+    fn insert_transaction(&mut self, index: u64, txn: Vec<u8>) -> io::Result<()>;
+
+    /// The transaction with index `index`
+    fn transaction(&self, index: u64) -> Option<Vec<u8>>;
+
+    /// The indices of the transactions, oldest first
+    fn transaction_indices(&self) -> Vec<u64>;
+
+    /// How many transactions there are
+    fn transaction_count(&self) -> usize;
+
+    /// The index of the oldest transaction
+    fn oldest_transaction(&self) -> Option<u64>;
+
+    /// Remove the transaction with index `index`, returning it
+    ///
+    /// # Errors
+    /// Returns an error if the transaction can't be removed
+    fn remove_transaction(&mut self, index: u64) -> io::Result<Option<Vec<u8>>>;
+
+    /// Total size of the messages and transactions
+    fn size_bytes(&self) -> usize;
+
+    /// Record that the transaction with index `index` and `commitment` was completed
+    ///
+    /// # Errors
+    /// Returns an error if the record can't be written
+    fn insert_completed(&mut self, index: u64, commitment: String) -> io::Result<()>;
+
+    /// Forget the completed transaction with index `index`
+    ///
+    /// # Errors
+    /// Returns an error if the record can't be removed
+    fn remove_completed(&mut self, index: u64) -> io::Result<()>;
+
+    /// The commitments of the completed transactions, by index
+    fn completed(&self) -> BTreeMap<u64, String>;
+
+    /// Add an encoded key to the stake table
+    ///
+    /// # Errors
+    /// Returns an error if the key can't be written
+    fn add_key(&mut self, key: Vec<u8>) -> io::Result<()>;
+
+    /// The encoded keys of the stake table, in the order they were added
+    fn keys(&self) -> Vec<Vec<u8>>;
+
+    /// Set the secret of the leader's proposal endpoint for `view`
+    ///
+    /// # Errors
+    /// Returns an error if the secret can't be written
+    fn insert_secret(&mut self, view: u64, secret: String) -> io::Result<()>;
+
+    /// Remove the secret for `view`
+    ///
+    /// # Errors
+    /// Returns an error if the secret can't be removed
+    fn remove_secret(&mut self, view: u64) -> io::Result<()>;
+
+    /// The secrets of the leaders' proposal endpoints, by view
+    fn secrets(&self) -> HashMap<u64, String>;
+
+    /// Evict the oldest views of `kind` beyond `retention.max_views`, the oldest transactions
+    /// beyond `retention.max_transactions`, and then the oldest views and transactions until
+    /// the total size is within `retention.max_bytes`. Returns the evicted transactions.
+    ///
+    /// # Errors
+    /// Returns an error if anything can't be removed
+    fn enforce_retention(
+        &mut self,
+        kind: Option<MessageKind>,
+        retention: &Retention,
+    ) -> io::Result<Vec<Vec<u8>>> {
+        let mut evicted = Vec::new();
+        if let Some(kind) = kind {
+            while self.view_count(kind) > retention.max_views {
+                let Some(view) = self.oldest_view(kind) else {
+                    break;
+                };
+                self.remove_view(kind, view)?;
+            }
+        }
+        while self.transaction_count() > retention.max_transactions {
+            let Some(index) = self.oldest_transaction() else {
+                break;
+            };
+            evicted.extend(self.remove_transaction(index)?);
+        }
+        while self.size_bytes() > retention.max_bytes {
+            let oldest_view = MessageKind::ALL
+                .into_iter()
+                .filter_map(|kind| Some((self.oldest_view(kind)?, kind)))
+                .min();
+            if let Some((view, kind)) = oldest_view {
+                self.remove_view(kind, view)?;
+            } else if let Some(index) = self.oldest_transaction() {
+                evicted.extend(self.remove_transaction(index)?);
+            } else {
+                break;
+            }
+        }
+        Ok(evicted)
+    }
+}
+
+/// Keeps everything in memory
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    /// (kind, view) -> messages, in the order they were posted
+    messages: BTreeMap<(MessageKind, u64), Vec<Vec<u8>>>,
+    /// kind -> number of views in `messages`
+    view_counts: HashMap<MessageKind, usize>,
+    /// index -> transaction
+    transactions: BTreeMap<u64, Vec<u8>>,
+    /// total size of `messages` and `transactions`
+    size_bytes: usize,
+    /// index -> commitment of the completed transactions
+    completed: BTreeMap<u64, String>,
+    /// encoded keys of the stake table
+    keys: Vec<Vec<u8>>,
+    /// view -> secret for the leader's proposal endpoint
+    secrets: HashMap<u64, String>,
+}
+
+impl MemoryStorage {
+    /// Create an empty storage
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl WebServerStorage for MemoryStorage {
+    fn append(&mut self, kind: MessageKind, view: u64, message: Vec<u8>) -> io::Result<u64> {
+        self.size_bytes += message.len();
+        let messages = self.messages.entry((kind, view)).or_default();
+        if messages.is_empty() {
+            *self.view_counts.entry(kind).or_default() += 1;
+        }
+        messages.push(message);
+        Ok(messages.len() as u64 - 1)
+    }
+
+    fn messages(&self, kind: MessageKind, view: u64, from: u64) -> Vec<Vec<u8>> {
+        self.messages
+            .get(&(kind, view))
+            .map(|messages| messages.iter().skip(from as usize).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn message_count(&self, kind: MessageKind, view: u64) -> u64 {
+        self.messages
+            .get(&(kind, view))
+            .map_or(0, |messages| messages.len() as u64)
+    }
+
+    fn view_count(&self, kind: MessageKind) -> usize {
+        self.view_counts.get(&kind).copied().unwrap_or_default()
+    }
+
+    fn oldest_view(&self, kind: MessageKind) -> Option<u64> {
+        self.messages
+            .range((kind, 0)..=(kind, u64::MAX))
+            .next()
+            .map(|((_, view), _)| *view)
+    }
+
+    fn newest_view(&self, kind: MessageKind) -> Option<u64> {
+        self.messages
+            .range((kind, 0)..=(kind, u64::MAX))
+            .next_back()
+            .map(|((_, view), _)| *view)
+    }
+
+    fn remove_view(&mut self, kind: MessageKind, view: u64) -> io::Result<()> {
+        if let Some(messages) = self.messages.remove(&(kind, view)) {
+            self.size_bytes -= messages.iter().map(Vec::len).sum::<usize>();
+            if let Some(count) = self.view_counts.get_mut(&kind) {
+                *count -= 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn insert_transaction(&mut self, index: u64, txn: Vec<u8>) -> io::Result<()> {
+        self.size_bytes += txn.len();
+        if let Some(old) = self.transactions.insert(index, txn) {
+            self.size_bytes -= old.len();
+        }
+        Ok(())
+    }
+
+    fn transaction(&self, index: u64) -> Option<Vec<u8>> {
+        self.transactions.get(&index).cloned()
+    }
+
+    fn transaction_indices(&self) -> Vec<u64> {
+        self.transactions.keys().copied().collect()
+    }
+
+    fn transaction_count(&self) -> usize {
+        self.transactions.len()
+    }
+
+    fn oldest_transaction(&self) -> Option<u64> {
+        self.transactions.keys().next().copied()
+    }
+
+    fn remove_transaction(&mut self, index: u64) -> io::Result<Option<Vec<u8>>> {
+        let txn = self.transactions.remove(&index);
+        if let Some(txn) = &txn {
+            self.size_bytes -= txn.len();
+        }
+        Ok(txn)
+    }
+
+    fn size_bytes(&self) -> usize {
+        self.size_bytes
+    }
+
+    fn insert_completed(&mut self, index: u64, commitment: String) -> io::Result<()> {
+        self.completed.insert(index, commitment);
+        Ok(())
+    }
+
+    fn remove_completed(&mut self, index: u64) -> io::Result<()> {
+        self.completed.remove(&index);
+        Ok(())
+    }
+
+    fn completed(&self) -> BTreeMap<u64, String> {
+        self.completed.clone()
+    }
+
+    fn add_key(&mut self, key: Vec<u8>) -> io::Result<()> {
+        self.keys.push(key);
+        Ok(())
+    }
+
+    fn keys(&self) -> Vec<Vec<u8>> {
+        self.keys.clone()
+    }
+
+    fn insert_secret(&mut self, view: u64, secret: String) -> io::Result<()> {
+        self.secrets.insert(view, secret);
+        Ok(())
+    }
+
+    fn remove_secret(&mut self, view: u64) -> io::Result<()> {
+        self.secrets.remove(&view);
+        Ok(())
+    }
+
+    fn secrets(&self) -> HashMap<u64, String> {
+        self.secrets.clone()
+    }
+}
+
+/// Keeps everything in memory and in a directory, from which it is loaded again on restart
+///
+/// Each message is a file `<kind>/<view>/<index>`, each transaction a file
+/// `transactions/<index>`, the commitment of each completed transaction a file
+/// `completed/<index>`, each key of the stake table a file `stake_table/<index>`, and each
+/// secret a file `secrets/<view>`.
+#[derive(Debug)]
+pub struct DiskStorage {
+    /// The directory everything is written to
+    path: PathBuf,
+    /// Everything in the directory
+    memory: MemoryStorage,
+}
+
+impl DiskStorage {
+    /// Open the storage in the directory at `path`, creating it if it doesn't exist, and load
+    /// everything in it
+    ///
+    /// # Errors
+    /// Returns an error if the directory can't be created or read
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut memory = MemoryStorage::new();
+        for kind in MessageKind::ALL {
+            for (view, view_path) in numbered_entries(&path.join(kind.name()))? {
+                for (_, message_path) in numbered_entries(&view_path)? {
+                    memory.append(kind, view, fs::read(message_path)?)?;
+                }
+            }
+        }
+        for (index, txn_path) in numbered_entries(&path.join("transactions"))? {
+            memory.insert_transaction(index, fs::read(txn_path)?)?;
+        }
+        for (index, completed_path) in numbered_entries(&path.join("completed"))? {
+            memory.insert_completed(index, fs::read_to_string(completed_path)?)?;
+        }
+        for (_, key_path) in numbered_entries(&path.join("stake_table"))? {
+            memory.add_key(fs::read(key_path)?)?;
+        }
+        for (view, secret_path) in numbered_entries(&path.join("secrets"))? {
+            memory.insert_secret(view, fs::read_to_string(secret_path)?)?;
+        }
+        Ok(Self { path, memory })
+    }
+
+    /// Directory of the messages of `kind` for `view`
+    fn view_path(&self, kind: MessageKind, view: u64) -> PathBuf {
+        self.path.join(kind.name()).join(view.to_string())
+    }
+
+    /// File of the transaction with index `index`
+    fn transaction_path(&self, index: u64) -> PathBuf {
+        self.path.join("transactions").join(index.to_string())
+    }
+
+    /// File of the record of the completed transaction with index `index`
+    fn completed_path(&self, index: u64) -> PathBuf {
+        self.path.join("completed").join(index.to_string())
+    }
+
+    /// File of the secret for `view`
+    fn secret_path(&self, view: u64) -> PathBuf {
+        self.path.join("secrets").join(view.to_string())
+    }
+}
+
+impl WebServerStorage for DiskStorage {
+    fn append(&mut self, kind: MessageKind, view: u64, message: Vec<u8>) -> io::Result<u64> {
+        let index = self.memory.message_count(kind, view);
+        write_file(
+            &self.view_path(kind, view).join(index.to_string()),
+            &message,
+        )?;
+        self.memory.append(kind, view, message)
+    }
+
+    fn messages(&self, kind: MessageKind, view: u64, from: u64) -> Vec<Vec<u8>> {
+        self.memory.messages(kind, view, from)
+    }
+
+    fn message_count(&self, kind: MessageKind, view: u64) -> u64 {
+        self.memory.message_count(kind, view)
+    }
+
+    fn view_count(&self, kind: MessageKind) -> usize {
+        self.memory.view_count(kind)
+    }
+
+    fn oldest_view(&self, kind: MessageKind) -> Option<u64> {
+        self.memory.oldest_view(kind)
+    }
+
+    fn newest_view(&self, kind: MessageKind) -> Option<u64> {
+        self.memory.newest_view(kind)
+    }
+
+    fn remove_view(&mut self, kind: MessageKind, view: u64) -> io::Result<()> {
+        remove(fs::remove_dir_all(self.view_path(kind, view)))?;
+        self.memory.remove_view(kind, view)
+    }
+
+    fn insert_transaction(&mut self, index: u64, txn: Vec<u8>) -> io::Result<()> {
+        write_file(&self.transaction_path(index), &txn)?;
+        self.memory.insert_transaction(index, txn)
+    }
+
+    fn transaction(&self, index: u64) -> Option<Vec<u8>> {
+        self.memory.transaction(index)
+    }
+
+    fn transaction_indices(&self) -> Vec<u64> {
+        self.memory.transaction_indices()
+    }
+
+    fn transaction_count(&self) -> usize {
+        self.memory.transaction_count()
+    }
+
+    fn oldest_transaction(&self) -> Option<u64> {
+        self.memory.oldest_transaction()
+    }
+
+    fn remove_transaction(&mut self, index: u64) -> io::Result<Option<Vec<u8>>> {
+        remove(fs::remove_file(self.transaction_path(index)))?;
+        self.memory.remove_transaction(index)
+    }
+
+    fn size_bytes(&self) -> usize {
+        self.memory.size_bytes()
+    }
+
+    fn insert_completed(&mut self, index: u64, commitment: String) -> io::Result<()> {
+        write_file(&self.completed_path(index), commitment.as_bytes())?;
+        self.memory.insert_completed(index, commitment)
+    }
+
+    fn remove_completed(&mut self, index: u64) -> io::Result<()> {
+        remove(fs::remove_file(self.completed_path(index)))?;
+        self.memory.remove_completed(index)
+    }
+
+    fn completed(&self) -> BTreeMap<u64, String> {
+        self.memory.completed()
+    }
+
+    fn add_key(&mut self, key: Vec<u8>) -> io::Result<()> {
+        let index = self.memory.keys.len();
+        write_file(&self.path.join("stake_table").join(index.to_string()), &key)?;
+        self.memory.add_key(key)
+    }
+
+    fn keys(&self) -> Vec<Vec<u8>> {
+        self.memory.keys()
+    }
+
+    fn insert_secret(&mut self, view: u64, secret: String) -> io::Result<()> {
+        write_file(&self.secret_path(view), secret.as_bytes())?;
+        self.memory.insert_secret(view, secret)
+    }
+
+    fn remove_secret(&mut self, view: u64) -> io::Result<()> {
+        remove(fs::remove_file(self.secret_path(view)))?;
+        self.memory.remove_secret(view)
+    }
+
+    fn secrets(&self) -> HashMap<u64, String> {
+        self.memory.secrets()
+    }
+}
+
+/// Write `contents` to the file at `path` through a temporary file, so a crash never leaves it
+/// half written
+fn write_file(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, contents)?;
+    fs::rename(tmp_path, path)
+}
+
+/// Ignore removing something that doesn't exist
+fn remove(result: io::Result<()>) -> io::Result<()> {
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// The entries of the directory at `path` named by a number, in order of the number. Other
+/// entries, such as temporary files, are skipped.
+fn numbered_entries(path: &Path) -> io::Result<Vec<(u64, PathBuf)>> {
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut numbered = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if let Some(number) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse().ok())
+        {
+            numbered.push((number, entry.path()));
+        }
+    }
+    numbered.sort();
+    Ok(numbered)
+}
+
+/// Where and how much the web server keeps
+#[derive(Clone, Debug, Default)]
+pub struct StorageConfig {
+    /// Directory to keep everything in with a [`DiskStorage`], or `None` to keep everything in a
+    /// [`MemoryStorage`]
+    pub path: Option<PathBuf>,
+    /// How much to keep
+    pub retention: Retention,
+}
+
+impl StorageConfig {
+    /// Open the configured storage
+    ///
+    /// # Errors
+    /// Returns an error if the [`DiskStorage`] can't be opened
+    pub fn open(&self) -> io::Result<Box<dyn WebServerStorage>> {
+        Ok(match &self.path {
+            Some(path) => Box::new(DiskStorage::open(path)?),
+            None => Box::new(MemoryStorage::new()),
+        })
+    }
+}